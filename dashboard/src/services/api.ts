@@ -23,13 +23,42 @@ api.interceptors.request.use(
     }
 )
 
+// Concurrent 401s share one refresh request
+let refreshing: Promise<string> | null = null
+
+async function refreshAccessToken(refreshToken: string): Promise<string> {
+    const response = await axios.post<{ token: string }>('/api/auth/refresh', {
+        refresh_token: refreshToken
+    })
+    localStorage.setItem('token', response.data.token)
+    return response.data.token
+}
+
 // Response interceptor for error handling
 api.interceptors.response.use(
     (response) => response,
-    (error) => {
+    async (error) => {
+        const request = error.config
+        const refreshToken = localStorage.getItem('refresh_token')
+        if (error.response?.status === 401 && refreshToken && request && !request._retried
+            && !request.url?.startsWith('/auth/')) {
+            // The access token expired: renew it once and replay the request
+            request._retried = true
+            try {
+                refreshing ??= refreshAccessToken(refreshToken).finally(() => {
+                    refreshing = null
+                })
+                const token = await refreshing
+                request.headers.Authorization = `Bearer ${token}`
+                return api(request)
+            } catch {
+                // Fall through to the login redirect
+            }
+        }
         if (error.response?.status === 401) {
-            // Clear token and redirect to login
+            // Clear tokens and redirect to login
             localStorage.removeItem('token')
+            localStorage.removeItem('refresh_token')
             if (window.location.pathname !== '/login') {
                 window.location.href = '/login'
             }
@@ -4,6 +4,8 @@ export interface LoginResponse {
     token: string
     token_type: string
     expires_in: number
+    refresh_token: string
+    refresh_expires_in: number
 }
 
 export interface MeResponse {
@@ -19,6 +21,10 @@ export const authApi = {
         return response.data
     },
 
+    async logout(refreshToken: string): Promise<void> {
+        await api.post('/auth/logout', { refresh_token: refreshToken })
+    },
+
     async me(): Promise<MeResponse> {
         const response = await api.get<MeResponse>('/auth/me')
         return response.data
@@ -22,6 +22,7 @@ export const useAuthStore = defineStore('auth', () => {
             const response = await authApi.login(username, password)
             token.value = response.token
             localStorage.setItem('token', response.token)
+            localStorage.setItem('refresh_token', response.refresh_token)
 
             // Fetch user info
             const userInfo = await authApi.me()
@@ -37,9 +38,15 @@ export const useAuthStore = defineStore('auth', () => {
     }
 
     function logout() {
+        const refreshToken = localStorage.getItem('refresh_token')
+        if (refreshToken) {
+            // Revoke the session server-side; local state is cleared regardless
+            authApi.logout(refreshToken).catch(() => {})
+        }
         token.value = null
         user.value = null
         localStorage.removeItem('token')
+        localStorage.removeItem('refresh_token')
     }
 
     async function fetchUser() {
@@ -5,7 +5,7 @@ import { bridgeApi } from '@/services/bridge'
 export interface BridgeStatus {
     state: 'running' | 'stopped' | 'error' | 'connecting'
     uptime_seconds: number
-    mqtt_status: 'connected' | 'disconnected' | 'connecting' | 'error'
+    mqtt_status: 'connected' | 'disconnected' | 'connecting' | 'error' | 'throttled'
     zmq_status: 'connected' | 'disconnected' | 'connecting' | 'error'
     version: string
 }
@@ -37,7 +37,7 @@ export interface MqttConfig {
 }
 
 // Updated for XPUB/XSUB pattern
-export type ZmqSocketType = 'xpub' | 'xsub' | 'pub' | 'sub'
+export type ZmqSocketType = 'xpub' | 'xsub' | 'pub' | 'sub' | 'push' | 'pull'
 
 export interface ZmqConfig {
     id?: number
@@ -0,0 +1,136 @@
+//! Administrative maintenance API handlers
+
+use crate::auth::AdminUser;
+use crate::db::get_db_path;
+use crate::error::{AppError, AppResult};
+use crate::models::{TableRowCount, WorkerThreadInfo};
+use crate::state::AppState;
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set while a vacuum is in progress
+static VACUUM_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Clears `VACUUM_RUNNING` when the vacuum finishes or fails
+struct VacuumGuard;
+
+impl VacuumGuard {
+    fn acquire() -> Option<Self> {
+        VACUUM_RUNNING
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| VacuumGuard)
+    }
+}
+
+impl Drop for VacuumGuard {
+    fn drop(&mut self) {
+        VACUUM_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VacuumResponse {
+    before_bytes: u64,
+    after_bytes: u64,
+    reclaimed_bytes: u64,
+}
+
+/// Combined size of the database files and their WALs, including the stats
+/// database if offloaded
+fn database_size(state: &AppState) -> u64 {
+    let mut paths = vec![get_db_path()];
+    paths.extend(state.config.database.stats_path.as_ref().map(PathBuf::from));
+    paths
+        .iter()
+        .flat_map(|path| {
+            let mut wal_path = path.clone().into_os_string();
+            wal_path.push("-wal");
+            [path.clone(), PathBuf::from(wal_path)]
+        })
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Vacuum the database and checkpoint the WAL
+async fn vacuum(
+    State(state): State<AppState>,
+    AdminUser(user): AdminUser,
+) -> AppResult<Json<VacuumResponse>> {
+    let _guard = VacuumGuard::acquire()
+        .ok_or_else(|| AppError::Conflict("A vacuum is already running".to_string()))?;
+
+    let before_bytes = database_size(&state);
+
+    tracing::info!("Vacuuming database at {} (requested by {})", get_db_path().display(), user.username);
+    state
+        .repo
+        .vacuum()
+        .await
+        .map_err(|e| AppError::DbError(format!("Vacuum failed: {}", e)))?;
+
+    let after_bytes = database_size(&state);
+    Ok(Json(VacuumResponse {
+        before_bytes,
+        after_bytes,
+        reclaimed_bytes: before_bytes.saturating_sub(after_bytes),
+    }))
+}
+
+/// Row counts of every database table
+async fn table_counts(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> AppResult<Json<Vec<TableRowCount>>> {
+    let counts = state
+        .repo
+        .table_row_counts()
+        .await
+        .map_err(|e| AppError::DbError(format!("Failed to count rows: {}", e)))?;
+    Ok(Json(counts))
+}
+
+/// Worker thread topology with per-thread liveness and activity
+async fn worker_threads(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> Json<Vec<WorkerThreadInfo>> {
+    Json(state.bridge.worker_threads())
+}
+
+/// Effective configuration with secrets redacted
+async fn effective_config(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> Json<serde_json::Value> {
+    Json(state.config.redacted())
+}
+
+/// Create admin routes
+pub fn admin_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vacuum", post(vacuum))
+        .route("/tables", get(table_counts))
+        .route("/threads", get(worker_threads))
+        .route("/config", get(effective_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vacuum_guard_is_exclusive() {
+        let guard = VacuumGuard::acquire().expect("first acquire");
+        assert!(VacuumGuard::acquire().is_none());
+        drop(guard);
+        assert!(VacuumGuard::acquire().is_some());
+    }
+}
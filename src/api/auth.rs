@@ -1,11 +1,12 @@
 //! Authentication API handlers
 
-use crate::auth::{encode_token, AuthUser};
+use crate::auth::{decode_token, encode_access_token, encode_token, AuthUser};
 use crate::error::{AppError, AppResult};
-use crate::models::{LoginRequest, LoginResponse, MeResponse};
+use crate::models::{AccessTokenResponse, LoginRequest, LoginResponse, MeResponse, RefreshRequest, TokenType};
 use crate::state::AppState;
 use axum::{
     extract::State,
+    http::{header::AUTHORIZATION, HeaderMap},
     routing::{get, post},
     Json, Router,
 };
@@ -19,25 +20,109 @@ async fn login(
     let user = state.repo.verify_credentials(&req.username, &req.password).await
         .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
 
-    if user.is_none() {
+    let Some(user) = user else {
         return Err(AppError::AuthError(
             "Invalid username or password".to_string(),
         ));
-    }
+    };
 
-    let token = encode_token(&req.username, &state.config)?;
+    let tokens = encode_token(&req.username, user.role, &state.config)?;
+    state
+        .repo
+        .add_refresh_token(&tokens.refresh_id, &req.username, tokens.refresh_expires_at)
+        .await
+        .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
 
     Ok(Json(LoginResponse {
-        token,
+        token: tokens.access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: state.config.jwt.access_expiration_minutes * 60,
+        refresh_token: tokens.refresh_token,
+        refresh_expires_in: state.config.jwt.expiration_hours * 3600,
+    }))
+}
+
+/// Mint a new access token from a refresh token that has not been revoked
+async fn refresh(
+    State(state): State<AppState>,
+    Json(req): Json<RefreshRequest>,
+) -> AppResult<Json<AccessTokenResponse>> {
+    let claims = decode_token(&req.refresh_token, TokenType::Refresh, &state.config)?;
+    let active = match claims.jti {
+        Some(ref id) => state
+            .repo
+            .refresh_token_active(id)
+            .await
+            .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?,
+        None => false,
+    };
+    if !active {
+        return Err(AppError::TokenError("Refresh token has been revoked".to_string()));
+    }
+
+    // Users deleted since login cannot renew their session; role changes
+    // apply from the next renewal
+    let user = state
+        .repo
+        .get_user_by_username(&claims.sub)
+        .await
+        .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
+    let Some(user) = user else {
+        return Err(AppError::TokenError("User no longer exists".to_string()));
+    };
+
+    Ok(Json(AccessTokenResponse {
+        token: encode_access_token(&claims.sub, user.role, &state.config)?,
         token_type: "Bearer".to_string(),
-        expires_in: state.config.jwt.expiration_hours * 3600,
+        expires_in: state.config.jwt.access_expiration_minutes * 60,
     }))
 }
 
+/// Revoke the bearer access token and/or the refresh token in the body, so
+/// neither authorizes further requests
+async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<RefreshRequest>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if bearer.is_none() && body.is_none() {
+        return Err(AppError::AuthError("Missing token to revoke".to_string()));
+    }
+
+    let mut revoked = false;
+    if let Some(token) = bearer {
+        let claims = decode_token(token, TokenType::Access, &state.config)?;
+        if let Some(ref jti) = claims.jti {
+            state
+                .repo
+                .revoke_access_token(jti, claims.exp)
+                .await
+                .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
+            revoked = true;
+        }
+    }
+    if let Some(Json(req)) = body {
+        let claims = decode_token(&req.refresh_token, TokenType::Refresh, &state.config)?;
+        if let Some(ref id) = claims.jti {
+            revoked |= state
+                .repo
+                .revoke_refresh_token(id)
+                .await
+                .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
+        }
+    }
+    Ok(Json(serde_json::json!({"revoked": revoked})))
+}
+
 /// Get current user info
 async fn me(AuthUser(user): AuthUser) -> Json<MeResponse> {
     Json(MeResponse {
         username: user.username,
+        role: user.role,
     })
 }
 
@@ -45,6 +130,112 @@ async fn me(AuthUser(user): AuthUser) -> Json<MeResponse> {
 pub fn auth_routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
         .route("/me", get(me))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::BridgeCore;
+    use crate::config::AppConfig;
+    use crate::db::{init_memory_db, Repository};
+    use axum::extract::FromRequestParts;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    async fn state(config: AppConfig) -> AppState {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let bridge = BridgeCore::new(repo.clone(), config.bridge.clone());
+        AppState::new(config, repo, bridge)
+    }
+
+    async fn login_default(state: &AppState) -> LoginResponse {
+        let req = LoginRequest {
+            username: "zeromqtt".to_string(),
+            password: "zeromqtt".to_string(),
+        };
+        login(State(state.clone()), Json(req)).await.unwrap().0
+    }
+
+    async fn refresh_status(state: &AppState, token: &str) -> Result<String, StatusCode> {
+        let req = RefreshRequest { refresh_token: token.to_string() };
+        match refresh(State(state.clone()), Json(req)).await {
+            Ok(Json(response)) => Ok(response.token),
+            Err(e) => Err(e.into_response().status()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_until_logout() {
+        let state = state(AppConfig::default()).await;
+        let session = login_default(&state).await;
+        assert_eq!(session.expires_in, 15 * 60);
+
+        let access = refresh_status(&state, &session.refresh_token).await.unwrap();
+        let claims = decode_token(&access, TokenType::Access, &state.config).unwrap();
+        assert_eq!(claims.sub, "zeromqtt");
+
+        let req = RefreshRequest { refresh_token: session.refresh_token.clone() };
+        let Json(revoked) = logout(State(state.clone()), HeaderMap::new(), Some(Json(req))).await.unwrap();
+        assert_eq!(revoked["revoked"], true);
+        assert_eq!(
+            refresh_status(&state, &session.refresh_token).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    async fn authenticate(state: &AppState, token: &str) -> Result<AuthUser, AppError> {
+        let (mut parts, _) = axum::http::Request::builder()
+            .extension(state.config.clone())
+            .extension(state.repo.clone())
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .body(())
+            .unwrap()
+            .into_parts();
+        AuthUser::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_logout_revokes_access_token() {
+        let state = state(AppConfig::default()).await;
+        let session = login_default(&state).await;
+        let other = login_default(&state).await;
+        assert!(authenticate(&state, &session.token).await.is_ok());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {}", session.token).parse().unwrap());
+        let Json(revoked) = logout(State(state.clone()), headers, None).await.unwrap();
+        assert_eq!(revoked["revoked"], true);
+
+        let response = authenticate(&state, &session.token).await.unwrap_err().into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        // Other sessions of the user are unaffected
+        assert!(authenticate(&state, &other.token).await.is_ok());
+
+        // Entries are pruned only once the token would have expired anyway
+        let exp = decode_token(&session.token, TokenType::Access, &state.config).unwrap().exp;
+        assert_eq!(state.repo.prune_revoked_tokens(exp).await.unwrap(), 0);
+        assert_eq!(state.repo.prune_revoked_tokens(exp + 1).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_access_token_cannot_refresh() {
+        let state = state(AppConfig::default()).await;
+        let session = login_default(&state).await;
+        assert_eq!(refresh_status(&state, &session.token).await, Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_expired_refresh_token() {
+        let mut config = AppConfig::default();
+        config.jwt.expiration_hours = -1;
+        let state = state(config).await;
+        let session = login_default(&state).await;
+        assert_eq!(
+            refresh_status(&state, &session.refresh_token).await,
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+}
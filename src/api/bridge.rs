@@ -1,14 +1,16 @@
 //! Bridge control API handlers
 
+use crate::auth::AdminUser;
+use crate::bridge::StartError;
 use crate::state::AppState;
 use axum::{
-    extract::State,
+    extract::{Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::post,
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
 struct BridgeActionResponse {
@@ -16,9 +18,20 @@ struct BridgeActionResponse {
     message: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct StartQuery {
+    /// Keep the message counters; defaults to `bridge.preserve_stats_on_restart`
+    preserve_stats: Option<bool>,
+}
+
 /// Start the bridge
-async fn start_bridge(State(state): State<AppState>) -> impl IntoResponse {
-    match state.bridge.start().await {
+async fn start_bridge(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+    Query(query): Query<StartQuery>,
+) -> impl IntoResponse {
+    let preserve_stats = query.preserve_stats.unwrap_or(state.config.bridge.preserve_stats_on_restart);
+    match state.bridge.start(preserve_stats).await {
         Ok(_) => (
             StatusCode::OK,
             Json(BridgeActionResponse {
@@ -26,6 +39,13 @@ async fn start_bridge(State(state): State<AppState>) -> impl IntoResponse {
                 message: "Bridge started successfully".to_string(),
             }),
         ),
+        Err(e) if e.downcast_ref::<StartError>().is_some() => (
+            StatusCode::CONFLICT,
+            Json(BridgeActionResponse {
+                success: false,
+                message: e.to_string(),
+            }),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(BridgeActionResponse {
@@ -37,7 +57,7 @@ async fn start_bridge(State(state): State<AppState>) -> impl IntoResponse {
 }
 
 /// Stop the bridge
-async fn stop_bridge(State(state): State<AppState>) -> impl IntoResponse {
+async fn stop_bridge(State(state): State<AppState>, AdminUser(_): AdminUser) -> impl IntoResponse {
     match state.bridge.stop().await {
         Ok(_) => (
             StatusCode::OK,
@@ -57,8 +77,13 @@ async fn stop_bridge(State(state): State<AppState>) -> impl IntoResponse {
 }
 
 /// Restart the bridge
-async fn restart_bridge(State(state): State<AppState>) -> impl IntoResponse {
-    match state.bridge.restart().await {
+async fn restart_bridge(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+    Query(query): Query<StartQuery>,
+) -> impl IntoResponse {
+    let preserve_stats = query.preserve_stats.unwrap_or(state.config.bridge.preserve_stats_on_restart);
+    match state.bridge.restart(preserve_stats).await {
         Ok(_) => (
             StatusCode::OK,
             Json(BridgeActionResponse {
@@ -66,6 +91,13 @@ async fn restart_bridge(State(state): State<AppState>) -> impl IntoResponse {
                 message: "Bridge restarted successfully".to_string(),
             }),
         ),
+        Err(e) if e.downcast_ref::<StartError>().is_some() => (
+            StatusCode::CONFLICT,
+            Json(BridgeActionResponse {
+                success: false,
+                message: e.to_string(),
+            }),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(BridgeActionResponse {
@@ -76,10 +108,30 @@ async fn restart_bridge(State(state): State<AppState>) -> impl IntoResponse {
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct DryRunRequest {
+    enabled: bool,
+}
+
+/// Switch dry-run mode: messages are still received, matched and transformed
+/// but only recorded for `/api/status/dryrun` instead of being published
+async fn set_dry_run(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+    Json(req): Json<DryRunRequest>,
+) -> Json<BridgeActionResponse> {
+    state.bridge.set_dry_run(req.enabled);
+    Json(BridgeActionResponse {
+        success: true,
+        message: if req.enabled { "Dry-run mode enabled" } else { "Dry-run mode disabled" }.to_string(),
+    })
+}
+
 /// Create bridge control routes
 pub fn bridge_routes() -> Router<AppState> {
     Router::new()
         .route("/start", post(start_bridge))
         .route("/stop", post(stop_bridge))
         .route("/restart", post(restart_bridge))
+        .route("/dryrun", post(set_dry_run))
 }
@@ -1,169 +1,800 @@
 //! Configuration API handlers - Multi-broker and Multi-ZMQ support
 
+use crate::bridge::{
+    DedupKey, MAX_BATCH_MESSAGES, OrderingKey, Schedule, TimestampSource, TopicCarrier, effective_mqtt_config, effective_zmq_config, is_broad_filter, is_publishable_topic, match_topic,
+    sort_mappings, validate_rewrites, validate_template,
+};
+use crate::auth::{AdminUser, AuthUser};
+use crate::config::{BroadSubscriptions, DuplicateMappings};
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    CreateMappingRequest, CreateMqttConfigRequest, CreateZmqConfigRequest,
-    MqttConfig, TopicMapping, ZmqConfig,
+    ConfigDiff, ConfigExport, CreateMappingRequest, CreateMqttConfigRequest, CreateZmqConfigRequest,
+    EffectiveMqttConfig, EffectiveZmqConfig, EndpointRole, EndpointType, ImportItemResult, ImportMode, MappingDirection, MappingMatch, MqttConfig, MqttProtocolVersion, PendingAction,
+    PendingChange, PendingEntity, TopicMapping, TopicSeparator, User, UserRole, ZmqConfig, ZmqRole, ZmqSocketType,
 };
+use crate::mqtt::{validate_client_id, validate_persistent_client_id, validate_tls_files};
 use crate::state::AppState;
+use crate::zeromq::{check_transport, has_separator, validate_curve_keys, validate_multicast_endpoint};
 use axum::{
-    extract::{Path, State},
-    routing::{get, put},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
     Json, Router,
 };
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// Only administrators read broker passwords and ZMQ secret keys
+fn reads_secrets(user: &User) -> bool {
+    user.role == UserRole::Admin
+}
 
 // ============ MQTT Configs (Multiple Brokers) ============
 
 /// Get all MQTT broker configurations
-async fn get_mqtt_configs(State(state): State<AppState>) -> AppResult<Json<Vec<MqttConfig>>> {
-    let configs = state
+async fn get_mqtt_configs(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> AppResult<Json<Vec<MqttConfig>>> {
+    let mut configs = state
         .repo
         .get_mqtt_configs()
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !reads_secrets(&user) {
+        configs.iter_mut().for_each(MqttConfig::redact_secrets);
+    }
     Ok(Json(configs))
 }
 
 /// Get a single MQTT broker configuration by ID
 async fn get_mqtt_config_by_id(
     State(state): State<AppState>,
+    AuthUser(user): AuthUser,
     Path(id): Path<u32>,
 ) -> AppResult<Json<MqttConfig>> {
-    let config = state
+    let mut config = state
         .repo
         .get_mqtt_config(id)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound(format!("MQTT config {} not found", id)))?;
+    if !reads_secrets(&user) {
+        config.redact_secrets();
+    }
     Ok(Json(config))
 }
 
+/// Get an MQTT broker configuration as the worker will use it
+async fn get_effective_mqtt_config(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<u32>,
+) -> AppResult<Json<EffectiveMqttConfig>> {
+    let config = state
+        .repo
+        .get_mqtt_config(id)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("MQTT config {} not found", id)))?;
+    let mut effective = effective_mqtt_config(&config);
+    if !reads_secrets(&user) {
+        effective.config.redact_secrets();
+    }
+    Ok(Json(effective))
+}
+
+/// Certificate and key files of a TLS broker must exist when it is saved
+fn validate_mqtt_tls(req: &CreateMqttConfigRequest) -> AppResult<()> {
+    validate_tls_files(
+        req.ca_cert_path.as_deref(),
+        req.client_cert_path.as_deref(),
+        req.client_key_path.as_deref(),
+    )
+    .map_err(AppError::BadRequest)
+}
+
+/// Checks of an MQTT broker configuration before it is saved
+fn validate_mqtt_config(req: &CreateMqttConfigRequest) -> AppResult<()> {
+    validate_client_id(&req.client_id).map_err(AppError::BadRequest)?;
+    // v5 sessions follow clean_start and the expiry, as `MqttConfig::resumes_session`
+    let persistent = if req.protocol_version == MqttProtocolVersion::V5 || req.topic_aliases {
+        !req.clean_start && req.session_expiry_secs > 0
+    } else {
+        !req.clean_session
+    };
+    validate_persistent_client_id(&req.client_id, !persistent).map_err(AppError::BadRequest)?;
+    validate_rewrites(&req.topic_rewrites).map_err(AppError::BadRequest)?;
+    validate_mqtt_tls(req)
+}
+
 /// Add a new MQTT broker configuration
 async fn add_mqtt_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Json(req): Json<CreateMqttConfigRequest>,
-) -> AppResult<Json<MqttConfig>> {
+) -> AppResult<Response> {
+    validate_mqtt_config(&req)?;
+    if state.config.bridge.stage_config_changes {
+        return stage_change(
+            &state,
+            PendingEntity::Mqtt,
+            PendingAction::Create,
+            None,
+            Some(serde_json::json!(req)),
+        )
+        .await;
+    }
     let config = state
         .repo
         .add_mqtt_config(&req)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
-    Ok(Json(config))
+    reload_endpoints(&state).await;
+    Ok(Json(config).into_response())
 }
 
 /// Update an existing MQTT broker configuration
 async fn update_mqtt_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
     Json(req): Json<CreateMqttConfigRequest>,
-) -> AppResult<Json<MqttConfig>> {
+) -> AppResult<Response> {
+    validate_mqtt_config(&req)?;
+    check_role_change(&state, EndpointType::Mqtt, id, req.endpoint_role).await?;
+    if state.config.bridge.stage_config_changes {
+        return stage_change(
+            &state,
+            PendingEntity::Mqtt,
+            PendingAction::Update,
+            Some(id),
+            Some(serde_json::json!(req)),
+        )
+        .await;
+    }
     let config = state
         .repo
         .update_mqtt_config(id, &req)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound(format!("MQTT config {} not found", id)))?;
-    Ok(Json(config))
+    reload_endpoints(&state).await;
+    Ok(Json(config).into_response())
 }
 
 /// Delete an MQTT broker configuration
 async fn delete_mqtt_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
-) -> AppResult<Json<serde_json::Value>> {
-    let deleted = state
-        .repo
-        .delete_mqtt_config(id)
-        .await
-        .map_err(|e| AppError::Internal(e.to_string()))?;
-
-    if deleted {
-        Ok(Json(serde_json::json!({"deleted": true, "id": id})))
-    } else {
-        Err(AppError::NotFound(format!(
-            "MQTT config with id {} not found",
-            id
-        )))
-    }
+    Query(query): Query<DeleteEndpointQuery>,
+) -> AppResult<Response> {
+    delete_endpoint(&state, EndpointType::Mqtt, id, query.force).await
 }
 
 // ============ ZeroMQ Configs (XPUB/XSUB) ============
 
 /// Get all ZeroMQ configurations
-async fn get_zmq_configs(State(state): State<AppState>) -> AppResult<Json<Vec<ZmqConfig>>> {
-    let configs = state
+async fn get_zmq_configs(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> AppResult<Json<Vec<ZmqConfig>>> {
+    let mut configs = state
         .repo
         .get_zmq_configs()
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !reads_secrets(&user) {
+        configs.iter_mut().for_each(ZmqConfig::redact_secrets);
+    }
     Ok(Json(configs))
 }
 
 /// Get a single ZMQ configuration by ID
 async fn get_zmq_config_by_id(
     State(state): State<AppState>,
+    AuthUser(user): AuthUser,
     Path(id): Path<u32>,
 ) -> AppResult<Json<ZmqConfig>> {
-    let config = state
+    let mut config = state
         .repo
         .get_zmq_config(id)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound(format!("ZMQ config {} not found", id)))?;
+    if !reads_secrets(&user) {
+        config.redact_secrets();
+    }
     Ok(Json(config))
 }
 
+/// Get a ZMQ configuration as the worker will use it
+async fn get_effective_zmq_config(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<u32>,
+) -> AppResult<Json<EffectiveZmqConfig>> {
+    let config = state
+        .repo
+        .get_zmq_config(id)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("ZMQ config {} not found", id)))?;
+    let mut effective = effective_zmq_config(&config);
+    if !reads_secrets(&user) {
+        effective.config.redact_secrets();
+    }
+    Ok(Json(effective))
+}
+
+/// Checks of a ZMQ configuration before it is saved
+fn validate_zmq_config(state: &AppState, req: &CreateZmqConfigRequest) -> AppResult<()> {
+    validate_zmq_transports(state, req)?;
+    validate_rewrites(&req.topic_rewrites).map_err(AppError::BadRequest)?;
+    validate_zmq_curve(req)?;
+    validate_zmq_role(req)?;
+    validate_zmq_rejoin(req)?;
+    validate_zmq_batch(req)
+}
+
 /// Add a new ZMQ configuration
 async fn add_zmq_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Json(req): Json<CreateZmqConfigRequest>,
-) -> AppResult<Json<ZmqConfig>> {
+) -> AppResult<Response> {
+    validate_zmq_config(&state, &req)?;
+    if state.config.bridge.stage_config_changes {
+        return stage_change(
+            &state,
+            PendingEntity::Zmq,
+            PendingAction::Create,
+            None,
+            Some(serde_json::json!(req)),
+        )
+        .await;
+    }
     let config = state
         .repo
         .add_zmq_config(&req)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
-    Ok(Json(config))
+    reload_endpoints(&state).await;
+    Ok(Json(config).into_response())
 }
 
 /// Update an existing ZMQ configuration
 async fn update_zmq_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
     Json(req): Json<CreateZmqConfigRequest>,
-) -> AppResult<Json<ZmqConfig>> {
+) -> AppResult<Response> {
+    validate_zmq_config(&state, &req)?;
+    check_role_change(&state, EndpointType::Zmq, id, req.endpoint_role).await?;
+    if state.config.bridge.stage_config_changes {
+        return stage_change(
+            &state,
+            PendingEntity::Zmq,
+            PendingAction::Update,
+            Some(id),
+            Some(serde_json::json!(req)),
+        )
+        .await;
+    }
     let config = state
         .repo
         .update_zmq_config(id, &req)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?
         .ok_or_else(|| AppError::NotFound(format!("ZMQ config {} not found", id)))?;
-    Ok(Json(config))
+    reload_endpoints(&state).await;
+    Ok(Json(config).into_response())
 }
 
 /// Delete a ZMQ configuration
 async fn delete_zmq_config(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
-) -> AppResult<Json<serde_json::Value>> {
-    let deleted = state
+    Query(query): Query<DeleteEndpointQuery>,
+) -> AppResult<Response> {
+    delete_endpoint(&state, EndpointType::Zmq, id, query.force).await
+}
+
+/// Reject bind/connect endpoints whose transport is not in `zmq.allowed_transports`,
+/// and malformed or misplaced multicast endpoints
+fn validate_zmq_transports(state: &AppState, req: &CreateZmqConfigRequest) -> AppResult<()> {
+    let allowed = &state.config.zmq.allowed_transports;
+    for endpoint in req.bind_endpoint.iter().chain(&req.connect_endpoints) {
+        check_transport(endpoint, allowed).map_err(AppError::BadRequest)?;
+        validate_multicast_endpoint(endpoint, &req.socket_type).map_err(AppError::BadRequest)?;
+    }
+    Ok(())
+}
+
+/// A connect-role publisher needs somewhere to connect to
+fn validate_zmq_role(req: &CreateZmqConfigRequest) -> AppResult<()> {
+    if req.role == ZmqRole::Connect
+        && matches!(req.socket_type, ZmqSocketType::Pub | ZmqSocketType::XPub)
+        && req.connect_endpoints.is_empty()
+    {
+        return Err(AppError::BadRequest("role 'connect' requires connect_endpoints".to_string()));
+    }
+    Ok(())
+}
+
+/// Rejoin handling watches the connections of SUB endpoints only
+fn validate_zmq_rejoin(req: &CreateZmqConfigRequest) -> AppResult<()> {
+    if (req.resubscribe_on_reconnect || req.rejoin_warmup_ms > 0) && req.socket_type != ZmqSocketType::Sub {
+        return Err(AppError::BadRequest(
+            "resubscribe_on_reconnect and rejoin_warmup_ms apply to sub endpoints only".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Only publishing endpoints send, so only they batch
+fn validate_zmq_batch(req: &CreateZmqConfigRequest) -> AppResult<()> {
+    if req.batch_max_messages > MAX_BATCH_MESSAGES {
+        return Err(AppError::BadRequest(format!(
+            "batch_max_messages must be at most {}",
+            MAX_BATCH_MESSAGES
+        )));
+    }
+    if req.batch_max_messages > 1
+        && !matches!(req.socket_type, ZmqSocketType::Pub | ZmqSocketType::XPub | ZmqSocketType::Push)
+    {
+        return Err(AppError::BadRequest(
+            "batch_max_messages applies to pub, xpub and push endpoints only".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// CURVE keys must be Z85 and form a usable set when the config is saved
+fn validate_zmq_curve(req: &CreateZmqConfigRequest) -> AppResult<()> {
+    validate_curve_keys(
+        req.curve_server_key.as_deref(),
+        req.curve_public_key.as_deref(),
+        req.curve_secret_key.as_deref(),
+        !req.connect_endpoints.is_empty(),
+    )
+    .map_err(AppError::BadRequest)
+}
+
+// ============ Endpoint Deletion ============
+
+#[derive(Debug, Deserialize)]
+struct DeleteEndpointQuery {
+    /// Also delete the mappings that reference the endpoint
+    #[serde(default)]
+    force: bool,
+}
+
+/// Refusal to delete an endpoint that `mappings` still reference
+fn endpoint_in_use(endpoint_type: &EndpointType, id: u32, mappings: &[TopicMapping]) -> AppError {
+    let kind = match endpoint_type {
+        EndpointType::Mqtt => "MQTT",
+        EndpointType::Zmq => "ZMQ",
+    };
+    let ids: Vec<String> = mappings.iter().map(|m| m.id.to_string()).collect();
+    AppError::BadRequest(format!(
+        "{} config {} is referenced by mappings {}; delete them first or pass ?force=true",
+        kind,
+        id,
+        ids.join(", ")
+    ))
+}
+
+/// 404 unless the MQTT or ZMQ config `id` exists
+async fn check_endpoint_exists(state: &AppState, endpoint_type: &EndpointType, id: u32) -> AppResult<()> {
+    let (exists, kind) = match endpoint_type {
+        EndpointType::Mqtt => (state.repo.get_mqtt_config(id).await.map(|c| c.is_some()), "MQTT"),
+        EndpointType::Zmq => (state.repo.get_zmq_config(id).await.map(|c| c.is_some()), "ZMQ"),
+    };
+    if !exists.map_err(|e| AppError::Internal(e.to_string()))? {
+        return Err(AppError::NotFound(format!("{} config with id {} not found", kind, id)));
+    }
+    Ok(())
+}
+
+/// Restart the threads of endpoints whose config changed, if the bridge runs
+async fn reload_endpoints(state: &AppState) {
+    if let Err(e) = state.bridge.reload_endpoints().await {
+        tracing::warn!("Failed to apply endpoint changes to the running bridge: {}", e);
+    }
+}
+
+/// Delete an MQTT or ZMQ config. Mappings still referencing it make this
+/// fail unless `force` is set, which deletes them in the same transaction,
+/// or only drops the endpoint from those using it as an additional target.
+async fn delete_endpoint(
+    state: &AppState,
+    endpoint_type: EndpointType,
+    id: u32,
+    force: bool,
+) -> AppResult<Response> {
+    let (entity, kind) = match endpoint_type {
+        EndpointType::Mqtt => (PendingEntity::Mqtt, "MQTT"),
+        EndpointType::Zmq => (PendingEntity::Zmq, "ZMQ"),
+    };
+    check_endpoint_exists(state, &endpoint_type, id).await?;
+    let referencing = state
         .repo
-        .delete_zmq_config(id)
+        .get_mappings_referencing(&endpoint_type, id)
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
+    if !referencing.is_empty() && !force {
+        return Err(endpoint_in_use(&endpoint_type, id, &referencing));
+    }
 
-    if deleted {
-        Ok(Json(serde_json::json!({"deleted": true, "id": id})))
-    } else {
-        Err(AppError::NotFound(format!(
-            "ZMQ config with id {} not found",
+    if state.config.bridge.stage_config_changes {
+        if !referencing.is_empty() {
+            // A staged delete would leave the mappings behind when applied
+            return Err(AppError::BadRequest(
+                "?force=true cannot be staged; stage deletes of the referencing mappings first".to_string(),
+            ));
+        }
+        return stage_change(state, entity, PendingAction::Delete, Some(id), None).await;
+    }
+
+    let (deleted_mappings, trimmed_mappings) = state
+        .repo
+        .delete_endpoint_cascade(&endpoint_type, id)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("{} config with id {} not found", kind, id)))?;
+
+    reload_endpoints(state).await;
+    if !deleted_mappings.is_empty() || !trimmed_mappings.is_empty() {
+        tracing::info!(
+            "Deleted mappings {:?} and removed additional targets from {:?} along with {} config {}",
+            deleted_mappings,
+            trimmed_mappings,
+            kind,
             id
-        )))
+        );
+        if let Err(e) = state.bridge.reload_mappings().await {
+            tracing::warn!("Failed to reload mappings after deleting {} config {}: {}", kind, id, e);
+        }
     }
+    Ok(Json(serde_json::json!({
+        "deleted": true,
+        "id": id,
+        "deleted_mappings": deleted_mappings,
+        "updated_mappings": trimmed_mappings,
+    }))
+    .into_response())
 }
 
 // ============ Topic Mappings ============
 
+/// `Warning` headers for literal ZMQ-side topics containing the delimiter of
+/// their endpoint's `topic_separator`
+fn separator_warnings(req: &CreateMappingRequest, zmq_configs: &[ZmqConfig]) -> HeaderMap {
+    let mut zmq_topics = Vec::new();
+    if req.source_endpoint_type == EndpointType::Zmq {
+        zmq_topics.push((req.source_endpoint_id, &req.source_topic));
+    }
+    if req.target_endpoint_type == EndpointType::Zmq {
+        zmq_topics.push((req.target_endpoint_id, &req.target_topic));
+    }
+    zmq_topics.extend(
+        req.additional_targets
+            .iter()
+            .filter(|t| t.endpoint_type == EndpointType::Zmq)
+            .map(|t| (t.endpoint_id, &t.target_topic)),
+    );
+    // PUSH/PULL endpoints carry no topic, so theirs cannot conflict
+    let separator = |id: u32| match zmq_configs.iter().find(|c| c.id == Some(id)) {
+        Some(c) if matches!(c.socket_type, ZmqSocketType::Push | ZmqSocketType::Pull) => None,
+        Some(c) => Some(c.topic_separator),
+        None => Some(TopicSeparator::default()),
+    };
+
+    let mut headers = HeaderMap::new();
+    for (_, topic) in zmq_topics
+        .into_iter()
+        .filter(|(id, t)| separator(*id).is_some_and(|s| has_separator(s, t)))
+    {
+        tracing::warn!("Mapping ZMQ topic '{}' contains the frame separator", topic);
+        let warning = format!("199 zeromqtt \"ZMQ topic '{}' contains the frame separator\"", topic);
+        if let Ok(value) = HeaderValue::from_str(&warning) {
+            headers.append(header::WARNING, value);
+        }
+    }
+    headers
+}
+
+/// An existing mapping other than `exclude_id` with the same endpoints, topics and direction
+fn find_duplicate_mapping<'a>(
+    mappings: &'a [TopicMapping],
+    req: &CreateMappingRequest,
+    exclude_id: Option<u32>,
+) -> Option<&'a TopicMapping> {
+    mappings.iter().find(|m| {
+        Some(m.id) != exclude_id
+            && m.source_endpoint_type == req.source_endpoint_type
+            && m.source_endpoint_id == req.source_endpoint_id
+            && m.target_endpoint_type == req.target_endpoint_type
+            && m.target_endpoint_id == req.target_endpoint_id
+            && m.source_topic == req.source_topic
+            && m.target_topic == req.target_topic
+            && m.direction == req.direction
+    })
+}
+
+/// Apply `bridge.duplicate_mappings` to a mapping about to be saved, adding a
+/// `Warning` header in warn mode
+async fn check_duplicate_mapping(
+    state: &AppState,
+    req: &CreateMappingRequest,
+    exclude_id: Option<u32>,
+    headers: &mut HeaderMap,
+) -> AppResult<()> {
+    if state.config.bridge.duplicate_mappings == DuplicateMappings::Allow {
+        return Ok(());
+    }
+    let mappings = state
+        .repo
+        .get_mappings()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    duplicate_policy(state, &mappings, req, exclude_id, headers)
+}
+
+/// `check_duplicate_mapping` against the given mappings
+fn duplicate_policy(
+    state: &AppState,
+    mappings: &[TopicMapping],
+    req: &CreateMappingRequest,
+    exclude_id: Option<u32>,
+    headers: &mut HeaderMap,
+) -> AppResult<()> {
+    let policy = state.config.bridge.duplicate_mappings;
+    if policy == DuplicateMappings::Allow {
+        return Ok(());
+    }
+    let Some(existing) = find_duplicate_mapping(mappings, req, exclude_id) else {
+        return Ok(());
+    };
+
+    let message = format!("Mapping duplicates mapping {}; both would forward every message", existing.id);
+    if policy == DuplicateMappings::Reject {
+        return Err(AppError::BadRequest(message));
+    }
+    tracing::warn!("{}", message);
+    if let Ok(value) = HeaderValue::from_str(&format!("199 zeromqtt \"{}\"", message)) {
+        headers.append(header::WARNING, value);
+    }
+    Ok(())
+}
+
+/// Why a mapping would subscribe to every topic of its MQTT broker, unless
+/// it opted in with `allow_broad_subscription`
+fn broad_subscription_message(req: &CreateMappingRequest) -> Option<String> {
+    (req.source_endpoint_type == EndpointType::Mqtt
+        && is_broad_filter(&req.source_topic)
+        && !req.allow_broad_subscription)
+        .then(|| {
+            format!(
+                "Source topic '{}' subscribes to every topic on the broker; set allow_broad_subscription to confirm",
+                req.source_topic
+            )
+        })
+}
+
+/// Apply `bridge.broad_subscriptions` to a mapping about to be saved, adding a
+/// `Warning` header in warn mode
+fn check_broad_subscription(state: &AppState, req: &CreateMappingRequest, headers: &mut HeaderMap) -> AppResult<()> {
+    let Some(message) = broad_subscription_message(req) else {
+        return Ok(());
+    };
+    if state.config.bridge.broad_subscriptions == BroadSubscriptions::Reject {
+        return Err(AppError::BadRequest(message));
+    }
+    tracing::warn!("{}", message);
+    if let Ok(value) = HeaderValue::from_str(&format!("199 zeromqtt \"{}\"", message)) {
+        headers.append(header::WARNING, value);
+    }
+    Ok(())
+}
+
+/// The direction of a mapping must agree with its endpoint types
+fn validate_mapping_direction(req: &CreateMappingRequest) -> AppResult<()> {
+    use EndpointType::{Mqtt, Zmq};
+    let types = (&req.source_endpoint_type, &req.target_endpoint_type);
+    let (consistent, requirement) = match req.direction {
+        MappingDirection::MqttToZmq => (
+            matches!(types, (Mqtt, Zmq)),
+            "mqtt_to_zmq requires an MQTT source and a ZMQ target",
+        ),
+        MappingDirection::ZmqToMqtt => (
+            matches!(types, (Zmq, Mqtt)),
+            "zmq_to_mqtt requires a ZMQ source and an MQTT target",
+        ),
+        MappingDirection::MqttToMqtt => (
+            matches!(types, (Mqtt, Mqtt)),
+            "mqtt_to_mqtt requires MQTT source and target endpoints",
+        ),
+        MappingDirection::ZmqToZmq => (
+            matches!(types, (Zmq, Zmq)),
+            "zmq_to_zmq requires ZMQ source and target endpoints",
+        ),
+        MappingDirection::Bidirectional => (
+            matches!(types, (Mqtt, Zmq) | (Zmq, Mqtt)),
+            "bidirectional requires one MQTT and one ZMQ endpoint",
+        ),
+    };
+    if consistent {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("Direction {}", requirement)))
+    }
+}
+
+/// Every endpoint a mapping forwards between must be a saved config of its type
+fn validate_mapping_endpoints(
+    req: &CreateMappingRequest,
+    mqtt_ids: &HashSet<u32>,
+    zmq_ids: &HashSet<u32>,
+) -> AppResult<()> {
+    match req.missing_endpoint(mqtt_ids, zmq_ids) {
+        Some(message) => Err(AppError::BadRequest(message)),
+        None => Ok(()),
+    }
+}
+
+/// A mapping may only read from endpoints whose `endpoint_role` allows being
+/// a source and deliver to those allowing being a sink
+fn validate_endpoint_roles(
+    req: &CreateMappingRequest,
+    mqtt_roles: &HashMap<u32, EndpointRole>,
+    zmq_roles: &HashMap<u32, EndpointRole>,
+) -> AppResult<()> {
+    match req.misused_endpoint(mqtt_roles, zmq_roles) {
+        Some(message) => Err(AppError::BadRequest(message)),
+        None => Ok(()),
+    }
+}
+
+/// A changed `endpoint_role` must still suit every mapping using the endpoint
+async fn check_role_change(
+    state: &AppState,
+    endpoint_type: EndpointType,
+    id: u32,
+    role: EndpointRole,
+) -> AppResult<()> {
+    check_endpoint_exists(state, &endpoint_type, id).await?;
+    let referencing = state
+        .repo
+        .get_mappings_referencing(&endpoint_type, id)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    // Only this endpoint is checked; the others were checked when saved
+    let changed = HashMap::from([(id, role)]);
+    let (mqtt_roles, zmq_roles) = match endpoint_type {
+        EndpointType::Mqtt => (changed, HashMap::new()),
+        EndpointType::Zmq => (HashMap::new(), changed),
+    };
+    for mapping in &referencing {
+        let req: CreateMappingRequest = import_request(mapping)?;
+        if let Some(message) = req.misused_endpoint(&mqtt_roles, &zmq_roles) {
+            return Err(AppError::BadRequest(format!("Mapping {}: {}", mapping.id, message)));
+        }
+    }
+    Ok(())
+}
+
+/// Check the endpoints of a mapping about to be saved against the saved configs
+async fn check_mapping_endpoints(
+    state: &AppState,
+    req: &CreateMappingRequest,
+    zmq_configs: &[ZmqConfig],
+) -> AppResult<()> {
+    let mqtt_configs = state
+        .repo
+        .get_mqtt_configs()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mqtt_roles: HashMap<u32, EndpointRole> =
+        mqtt_configs.iter().filter_map(|c| Some((c.id?, c.endpoint_role))).collect();
+    let zmq_roles: HashMap<u32, EndpointRole> =
+        zmq_configs.iter().filter_map(|c| Some((c.id?, c.endpoint_role))).collect();
+    let mqtt_ids = mqtt_roles.keys().copied().collect();
+    let zmq_ids = zmq_roles.keys().copied().collect();
+    validate_mapping_endpoints(req, &mqtt_ids, &zmq_ids)?;
+    validate_endpoint_roles(req, &mqtt_roles, &zmq_roles)
+}
+
+/// Refuse transform commands unless `bridge.exec_transforms` allows them
+fn validate_transform(state: &AppState, req: &CreateMappingRequest) -> AppResult<()> {
+    let Some(ref command) = req.transform_command else {
+        return Ok(());
+    };
+    if !state.config.bridge.exec_transforms {
+        return Err(AppError::BadRequest(
+            "transform_command requires bridge.exec_transforms to be enabled".to_string(),
+        ));
+    }
+    if command.trim().is_empty() {
+        return Err(AppError::BadRequest("transform_command must not be empty".to_string()));
+    }
+    Ok(())
+}
+
+/// Validate mapping fields that the database cannot check
+fn validate_mapping(req: &CreateMappingRequest) -> AppResult<()> {
+    if let Some(ref spec) = req.ordering_key
+        && OrderingKey::parse(spec).is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid ordering_key '{}': expected 'topic:N' or 'json:path'",
+            spec
+        )));
+    }
+    if let Some(ref spec) = req.timestamp_source
+        && TimestampSource::parse(spec).is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid timestamp_source '{}': expected 'json:path' or 'property:name'",
+            spec
+        )));
+    }
+    if let Some(ref spec) = req.source_topic_carrier
+        && TopicCarrier::parse(spec).is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid source_topic_carrier '{}': expected 'property:name' or 'json:field'",
+            spec
+        )));
+    }
+    if let Some(ref spec) = req.dedup_key
+        && DedupKey::parse(spec).is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "Invalid dedup_key '{}': expected 'hash' or 'json:path'",
+            spec
+        )));
+    }
+    if let Some(ref spec) = req.schedule
+        && let Err(e) = Schedule::parse(spec)
+    {
+        return Err(AppError::BadRequest(format!("Invalid schedule '{}': {}", spec, e)));
+    }
+    validate_template(&req.target_topic).map_err(AppError::BadRequest)?;
+    for target in &req.additional_targets {
+        validate_template(&target.target_topic).map_err(AppError::BadRequest)?;
+    }
+    if req.fallback_topic.as_deref().is_some_and(|t| !is_publishable_topic(t)) {
+        return Err(AppError::BadRequest(
+            "fallback_topic must be non-empty and free of wildcards".to_string(),
+        ));
+    }
+    validate_mapping_direction(req)?;
+    if req.qos > 2 {
+        return Err(AppError::BadRequest("qos must be 0, 1 or 2".to_string()));
+    }
+    if req.publish_qos.is_some_and(|q| !(0..=2).contains(&q)) {
+        return Err(AppError::BadRequest("publish_qos must be 0, 1 or 2".to_string()));
+    }
+    if req.truncate_bytes == Some(0) {
+        return Err(AppError::BadRequest("truncate_bytes must be at least 1".to_string()));
+    }
+    if req.max_payload_bytes == Some(0) {
+        return Err(AppError::BadRequest("max_payload_bytes must be at least 1".to_string()));
+    }
+    if req.sample_every_n.is_some() && req.sample_interval_ms.is_some() {
+        return Err(AppError::BadRequest(
+            "Set only one of sample_every_n and sample_interval_ms".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Get all topic mappings
 async fn get_mappings(State(state): State<AppState>) -> AppResult<Json<Vec<TopicMapping>>> {
     let mappings = state
@@ -177,8 +808,33 @@ async fn get_mappings(State(state): State<AppState>) -> AppResult<Json<Vec<Topic
 /// Add a new topic mapping
 async fn add_mapping(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Json(req): Json<CreateMappingRequest>,
-) -> AppResult<Json<TopicMapping>> {
+) -> AppResult<Response> {
+    validate_mapping(&req)?;
+    validate_transform(&state, &req)?;
+    let zmq_configs = state
+        .repo
+        .get_zmq_configs()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    check_mapping_endpoints(&state, &req, &zmq_configs).await?;
+    let mut headers = separator_warnings(&req, &zmq_configs);
+    check_broad_subscription(&state, &req, &mut headers)?;
+    check_duplicate_mapping(&state, &req, None, &mut headers).await?;
+
+    if state.config.bridge.stage_config_changes {
+        let staged = stage_change(
+            &state,
+            PendingEntity::Mapping,
+            PendingAction::Create,
+            None,
+            Some(serde_json::json!(req)),
+        )
+        .await?;
+        return Ok((headers, staged).into_response());
+    }
+
     let mapping = state
         .repo
         .add_mapping(&req)
@@ -186,17 +842,42 @@ async fn add_mapping(
         .map_err(|e| AppError::Internal(e.to_string()))?;
     
     // Reload mappings in bridge
-    let _ = state.bridge.reload_mappings().await;
+    state.bridge.request_reload();
     
-    Ok(Json(mapping))
+    Ok((headers, Json(mapping)).into_response())
 }
 
 /// Update an existing topic mapping
 async fn update_mapping(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
     Json(req): Json<CreateMappingRequest>,
-) -> AppResult<Json<TopicMapping>> {
+) -> AppResult<Response> {
+    validate_mapping(&req)?;
+    validate_transform(&state, &req)?;
+    let zmq_configs = state
+        .repo
+        .get_zmq_configs()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    check_mapping_endpoints(&state, &req, &zmq_configs).await?;
+    let mut headers = separator_warnings(&req, &zmq_configs);
+    check_broad_subscription(&state, &req, &mut headers)?;
+    check_duplicate_mapping(&state, &req, Some(id), &mut headers).await?;
+
+    if state.config.bridge.stage_config_changes {
+        let staged = stage_change(
+            &state,
+            PendingEntity::Mapping,
+            PendingAction::Update,
+            Some(id),
+            Some(serde_json::json!(req)),
+        )
+        .await?;
+        return Ok((headers, staged).into_response());
+    }
+
     let mapping = state
         .repo
         .update_mapping(id, &req)
@@ -205,16 +886,48 @@ async fn update_mapping(
         .ok_or_else(|| AppError::NotFound(format!("Mapping with id {} not found", id)))?;
     
     // Reload mappings in bridge
-    let _ = state.bridge.reload_mappings().await;
+    state.bridge.request_reload();
     
-    Ok(Json(mapping))
+    Ok((headers, Json(mapping)).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct MatchQuery {
+    topic: String,
+    endpoint_id: Option<u32>,
+    #[serde(rename = "type")]
+    endpoint_type: Option<EndpointType>,
+}
+
+/// List every mapping a topic would match, in evaluation order, with computed targets
+async fn match_mappings(
+    State(state): State<AppState>,
+    Query(query): Query<MatchQuery>,
+) -> AppResult<Json<Vec<MappingMatch>>> {
+    let mut mappings = state
+        .repo
+        .get_mappings()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    sort_mappings(&mut mappings, state.config.bridge.evaluation_strategy);
+
+    Ok(Json(match_topic(
+        &mappings,
+        query.endpoint_type.as_ref(),
+        query.endpoint_id,
+        &query.topic,
+    )))
 }
 
 /// Delete a topic mapping
 async fn delete_mapping(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
-) -> AppResult<Json<serde_json::Value>> {
+) -> AppResult<Response> {
+    if state.config.bridge.stage_config_changes {
+        return stage_change(&state, PendingEntity::Mapping, PendingAction::Delete, Some(id), None).await;
+    }
     let deleted = state
         .repo
         .delete_mapping(id)
@@ -223,8 +936,8 @@ async fn delete_mapping(
 
     if deleted {
         // Reload mappings in bridge
-        let _ = state.bridge.reload_mappings().await;
-        Ok(Json(serde_json::json!({"deleted": true, "id": id})))
+        state.bridge.request_reload();
+        Ok(Json(serde_json::json!({"deleted": true, "id": id})).into_response())
     } else {
         Err(AppError::NotFound(format!(
             "Mapping with id {} not found",
@@ -233,6 +946,385 @@ async fn delete_mapping(
     }
 }
 
+// ============ Running vs Persisted ============
+
+/// Report what a reload/restart would change compared to the running bridge
+async fn get_config_diff(State(state): State<AppState>) -> AppResult<Json<ConfigDiff>> {
+    let diff = state
+        .bridge
+        .config_diff()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Json(diff))
+}
+
+// ============ Staged Changes ============
+
+/// What `entity` is called in messages
+fn entity_name(entity: PendingEntity) -> &'static str {
+    match entity {
+        PendingEntity::Mqtt => "MQTT config",
+        PendingEntity::Zmq => "ZMQ config",
+        PendingEntity::Mapping => "Mapping",
+    }
+}
+
+/// Current value of the config `id` of `entity`, if it exists
+async fn current_config(state: &AppState, entity: PendingEntity, id: u32) -> AppResult<Option<serde_json::Value>> {
+    let current = match entity {
+        PendingEntity::Mqtt => state.repo.get_mqtt_config(id).await.map(|c| c.map(|c| serde_json::json!(c))),
+        PendingEntity::Zmq => state.repo.get_zmq_config(id).await.map(|c| c.map(|c| serde_json::json!(c))),
+        PendingEntity::Mapping => state.repo.get_mapping(id).await.map(|m| m.map(|m| serde_json::json!(m))),
+    };
+    current.map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Record a validated mutation for `/api/config/apply` instead of writing it.
+/// Updates and deletes must target a config that exists now.
+async fn stage_change(
+    state: &AppState,
+    entity: PendingEntity,
+    action: PendingAction,
+    target_id: Option<u32>,
+    body: Option<serde_json::Value>,
+) -> AppResult<Response> {
+    if let Some(id) = target_id
+        && current_config(state, entity, id).await?.is_none()
+    {
+        return Err(AppError::NotFound(format!("{} {} not found", entity_name(entity), id)));
+    }
+    let change = state
+        .repo
+        .add_pending_change(entity, action, target_id, body.as_ref())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok((StatusCode::ACCEPTED, Json(change)).into_response())
+}
+
+/// List staged changes, each with the current value of the config it
+/// replaces. Bodies carry secrets, so only administrators may read them.
+async fn get_pending_changes(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> AppResult<Json<Vec<PendingChange>>> {
+    let mut changes = state
+        .repo
+        .get_pending_changes()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    for change in &mut changes {
+        if let Some(id) = change.target_id {
+            change.current = current_config(&state, change.entity, id).await?;
+        }
+    }
+    Ok(Json(changes))
+}
+
+/// Write all staged changes at once and reload the bridge
+async fn apply_pending_changes(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let applied = state.repo.apply_pending_changes().await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => AppError::Conflict(
+            "A pending update or delete targets a config that no longer exists; nothing was applied".to_string(),
+        ),
+        e => AppError::Internal(e.to_string()),
+    })?;
+    if applied > 0 {
+        reload_endpoints(&state).await;
+        state.bridge.request_reload();
+    }
+    Ok(Json(serde_json::json!({"applied": applied})))
+}
+
+/// Drop all staged changes
+async fn discard_pending_changes(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let discarded = state
+        .repo
+        .discard_pending_changes()
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok(Json(serde_json::json!({"discarded": discarded})))
+}
+
+// ============ Bulk Import/Export ============
+
+const EXPORT_WARNING: &str =
+    "This export contains secrets (broker passwords, HMAC and CURVE secret keys); store it accordingly";
+
+/// Every configuration and mapping in one document, secrets included, for
+/// administrators
+async fn export_config(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+) -> AppResult<(HeaderMap, Json<ConfigExport>)> {
+    let export = ConfigExport {
+        warning: Some(EXPORT_WARNING.to_string()),
+        mqtt: state.repo.get_mqtt_configs().await.map_err(|e| AppError::Internal(e.to_string()))?,
+        zmq: state.repo.get_zmq_configs().await.map_err(|e| AppError::Internal(e.to_string()))?,
+        mappings: state.repo.get_mappings().await.map_err(|e| AppError::Internal(e.to_string()))?,
+    };
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&format!("199 zeromqtt \"{}\"", EXPORT_WARNING)) {
+        headers.append(header::WARNING, value);
+    }
+    Ok((headers, Json(export)))
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    mode: ImportMode,
+    /// Import the valid records and report the rest instead of importing nothing
+    #[serde(default)]
+    best_effort: bool,
+}
+
+/// The create request of an exported record, which has the same fields plus its id
+fn import_request<T: serde::Serialize, R: serde::de::DeserializeOwned>(record: &T) -> AppResult<R> {
+    serde_json::from_value(serde_json::json!(record)).map_err(|e| AppError::BadRequest(e.to_string()))
+}
+
+/// Prefix a validation error with the imported record it concerns
+fn import_error(record: String) -> impl FnOnce(AppError) -> AppError {
+    move |e| match e {
+        AppError::BadRequest(message) => AppError::BadRequest(format!("{}: {}", record, message)),
+        e => e,
+    }
+}
+
+/// Keep a validated record; a rejected one fails the import, or in
+/// best-effort mode is reported and left out
+fn admit_import<R>(
+    validated: AppResult<R>,
+    best_effort: bool,
+    rejected: impl FnOnce(String) -> ImportItemResult,
+    results: &mut Vec<ImportItemResult>,
+) -> AppResult<Option<R>> {
+    match validated {
+        Ok(req) => Ok(Some(req)),
+        Err(AppError::BadRequest(message)) if best_effort => {
+            results.push(rejected(message));
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Endpoint ids imported mappings may reference: the `admitted` endpoints and
+/// `existing` ones no imported record claims. An endpoint that was rejected
+/// leaves its id unbound rather than pointing at an unrelated existing one.
+fn importable_endpoint_ids(
+    existing: impl Iterator<Item = Option<u32>>,
+    claimed: impl Iterator<Item = Option<u32>>,
+    admitted: impl Iterator<Item = Option<u32>>,
+) -> HashSet<u32> {
+    let claimed: HashSet<u32> = claimed.flatten().collect();
+    existing
+        .flatten()
+        .filter(|id| !claimed.contains(id))
+        .chain(admitted.flatten())
+        .collect()
+}
+
+/// In merge mode an imported record must not take the id of an existing one
+fn check_import_id(id: Option<u32>, existing: &HashSet<u32>) -> AppResult<()> {
+    match id {
+        Some(id) if existing.contains(&id) => Err(AppError::BadRequest(format!(
+            "id {} is already in use; a merge does not overwrite existing records",
+            id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Stage the writes of an import for `/api/config/apply`: in replace mode
+/// deletes of every existing record first, then creates keeping the
+/// imported ids. Returns how many changes were staged.
+async fn stage_import(
+    state: &AppState,
+    mode: ImportMode,
+    mqtt: &[(Option<u32>, CreateMqttConfigRequest)],
+    zmq: &[(Option<u32>, CreateZmqConfigRequest)],
+    mappings: &[(Option<u32>, CreateMappingRequest)],
+) -> AppResult<usize> {
+    let internal = |e: sqlx::Error| AppError::Internal(e.to_string());
+    let mut changes: Vec<(PendingEntity, PendingAction, Option<u32>, Option<serde_json::Value>)> = Vec::new();
+    if mode == ImportMode::Replace {
+        let delete = |entity, id| (entity, PendingAction::Delete, Some(id), None);
+        let existing_mappings = state.repo.get_mappings().await.map_err(internal)?;
+        changes.extend(existing_mappings.iter().map(|m| delete(PendingEntity::Mapping, m.id)));
+        let existing_zmq = state.repo.get_zmq_configs().await.map_err(internal)?;
+        changes.extend(existing_zmq.iter().filter_map(|c| Some(delete(PendingEntity::Zmq, c.id?))));
+        let existing_mqtt = state.repo.get_mqtt_configs().await.map_err(internal)?;
+        changes.extend(existing_mqtt.iter().filter_map(|c| Some(delete(PendingEntity::Mqtt, c.id?))));
+    }
+    let create = |entity, id: &Option<u32>, body| (entity, PendingAction::Create, *id, Some(body));
+    changes.extend(mqtt.iter().map(|(id, req)| create(PendingEntity::Mqtt, id, serde_json::json!(req))));
+    changes.extend(zmq.iter().map(|(id, req)| create(PendingEntity::Zmq, id, serde_json::json!(req))));
+    changes.extend(mappings.iter().map(|(id, req)| create(PendingEntity::Mapping, id, serde_json::json!(req))));
+    state.repo.add_pending_changes(&changes).await.map_err(internal)
+}
+
+/// Write an exported document in one transaction and reload the mappings.
+/// Every record is validated as if it were saved on its own, including
+/// `bridge.duplicate_mappings` against existing and earlier imported
+/// mappings. A merge keeps existing records and rejects imported ids already
+/// in use. Endpoints are written first, then mappings, which must reference
+/// endpoints that exist once the import is done. With `best_effort`, invalid
+/// records and those the database rejects are left out and reported rather
+/// than failing it. With `bridge.stage_config_changes` the writes are staged
+/// instead; the database only checks them when they are applied.
+async fn import_config(
+    State(state): State<AppState>,
+    AdminUser(_): AdminUser,
+    Query(query): Query<ImportQuery>,
+    Json(import): Json<ConfigExport>,
+) -> AppResult<Response> {
+    let best_effort = query.best_effort;
+    let internal = |e: sqlx::Error| AppError::Internal(e.to_string());
+    let (existing_mqtt, existing_zmq, existing_mappings) = if query.mode == ImportMode::Merge {
+        (
+            state.repo.get_mqtt_configs().await.map_err(internal)?,
+            state.repo.get_zmq_configs().await.map_err(internal)?,
+            state.repo.get_mappings().await.map_err(internal)?,
+        )
+    } else {
+        (Vec::new(), Vec::new(), Vec::new())
+    };
+    let mqtt_in_use: HashSet<u32> = existing_mqtt.iter().filter_map(|c| c.id).collect();
+    let zmq_in_use: HashSet<u32> = existing_zmq.iter().filter_map(|c| c.id).collect();
+    let mappings_in_use: HashSet<u32> = existing_mappings.iter().map(|m| m.id).collect();
+
+    let mut rejected = Vec::new();
+    let mut mqtt = Vec::with_capacity(import.mqtt.len());
+    for config in &import.mqtt {
+        let validated = import_request(config).and_then(|req: CreateMqttConfigRequest| {
+            let context = || import_error(format!("MQTT config '{}'", config.name));
+            check_import_id(config.id, &mqtt_in_use).map_err(context())?;
+            validate_mqtt_config(&req).map_err(context())?;
+            Ok(req)
+        });
+        let failed = |e| ImportItemResult::failed(PendingEntity::Mqtt, config.id, Some(config.name.clone()), e);
+        if let Some(req) = admit_import(validated, best_effort, failed, &mut rejected)? {
+            mqtt.push((config.id, req));
+        }
+    }
+    let mut zmq = Vec::with_capacity(import.zmq.len());
+    for config in &import.zmq {
+        let validated = import_request(config).and_then(|req: CreateZmqConfigRequest| {
+            let context = || import_error(format!("ZMQ config '{}'", config.name));
+            check_import_id(config.id, &zmq_in_use).map_err(context())?;
+            validate_zmq_config(&state, &req).map_err(context())?;
+            Ok(req)
+        });
+        let failed = |e| ImportItemResult::failed(PendingEntity::Zmq, config.id, Some(config.name.clone()), e);
+        if let Some(req) = admit_import(validated, best_effort, failed, &mut rejected)? {
+            zmq.push((config.id, req));
+        }
+    }
+
+    let mut mqtt_roles: HashMap<u32, EndpointRole> = HashMap::new();
+    let mut zmq_roles: HashMap<u32, EndpointRole> = HashMap::new();
+    mqtt_roles.extend(existing_mqtt.iter().filter_map(|c| Some((c.id?, c.endpoint_role))));
+    zmq_roles.extend(existing_zmq.iter().filter_map(|c| Some((c.id?, c.endpoint_role))));
+    // A claimed id takes the imported role, or none if that record was rejected
+    for id in import.mqtt.iter().filter_map(|c| c.id) {
+        mqtt_roles.remove(&id);
+    }
+    for id in import.zmq.iter().filter_map(|c| c.id) {
+        zmq_roles.remove(&id);
+    }
+    mqtt_roles.extend(mqtt.iter().filter_map(|(id, req)| Some(((*id)?, req.endpoint_role))));
+    zmq_roles.extend(zmq.iter().filter_map(|(id, req)| Some(((*id)?, req.endpoint_role))));
+    let mqtt_ids = importable_endpoint_ids(
+        existing_mqtt.iter().map(|c| c.id),
+        import.mqtt.iter().map(|c| c.id),
+        mqtt.iter().map(|(id, _)| *id),
+    );
+    let zmq_ids = importable_endpoint_ids(
+        existing_zmq.iter().map(|c| c.id),
+        import.zmq.iter().map(|c| c.id),
+        zmq.iter().map(|(id, _)| *id),
+    );
+
+    let mut headers = HeaderMap::new();
+    // Mappings a new one may duplicate: those kept by a merge and those imported so far
+    let mut kept_mappings = existing_mappings;
+    let mut mappings = Vec::with_capacity(import.mappings.len());
+    for mapping in &import.mappings {
+        let validated = import_request(mapping).and_then(|req: CreateMappingRequest| {
+            let context = || import_error(format!("Mapping {}", mapping.id));
+            check_import_id(Some(mapping.id), &mappings_in_use).map_err(context())?;
+            validate_mapping(&req).map_err(context())?;
+            validate_transform(&state, &req).map_err(context())?;
+            // Endpoints the database rejects are only known once written
+            validate_mapping_endpoints(&req, &mqtt_ids, &zmq_ids).map_err(context())?;
+            validate_endpoint_roles(&req, &mqtt_roles, &zmq_roles).map_err(context())?;
+            check_broad_subscription(&state, &req, &mut HeaderMap::new()).map_err(context())?;
+            duplicate_policy(&state, &kept_mappings, &req, None, &mut headers).map_err(context())?;
+            Ok(req)
+        });
+        let failed = |e| ImportItemResult::failed(PendingEntity::Mapping, Some(mapping.id), None, e);
+        if let Some(req) = admit_import(validated, best_effort, failed, &mut rejected)? {
+            kept_mappings.push(mapping.clone());
+            mappings.push((Some(mapping.id), req));
+        }
+    }
+
+    let by_entity = |r: &ImportItemResult| match r.entity {
+        PendingEntity::Mqtt => 0,
+        PendingEntity::Zmq => 1,
+        PendingEntity::Mapping => 2,
+    };
+    if state.config.bridge.stage_config_changes {
+        let staged = stage_import(&state, query.mode, &mqtt, &zmq, &mappings).await?;
+        rejected.sort_by_key(by_entity);
+        let report = serde_json::json!({
+            "mode": query.mode,
+            "best_effort": best_effort,
+            "staged": staged,
+            "failed": rejected.len(),
+            "results": rejected,
+        });
+        return Ok((StatusCode::ACCEPTED, headers, Json(report)).into_response());
+    }
+
+    let mut results = state
+        .repo
+        .import_config(query.mode, best_effort, &mqtt, &zmq, &mappings)
+        .await
+        .map_err(|e| match e {
+            // E.g. two configs with the same name
+            sqlx::Error::Database(e) => AppError::Conflict(e.message().to_string()),
+            e => AppError::Internal(e.to_string()),
+        })?;
+    results.extend(rejected);
+    results.sort_by_key(by_entity);
+    reload_endpoints(&state).await;
+    if let Err(e) = state.bridge.reload_mappings().await {
+        tracing::warn!("Failed to reload mappings after import: {}", e);
+    }
+
+    let imported = |entity: PendingEntity| {
+        results.iter().filter(|r| r.entity == entity && r.error.is_none()).count()
+    };
+    let report = serde_json::json!({
+        "mode": query.mode,
+        "best_effort": best_effort,
+        "mqtt": imported(PendingEntity::Mqtt),
+        "zmq": imported(PendingEntity::Zmq),
+        "mappings": imported(PendingEntity::Mapping),
+        "failed": results.iter().filter(|r| r.error.is_some()).count(),
+        "results": results,
+    });
+    Ok((headers, Json(report)).into_response())
+}
+
 /// Create configuration routes
 pub fn config_routes() -> Router<AppState> {
     Router::new()
@@ -244,6 +1336,7 @@ pub fn config_routes() -> Router<AppState> {
                 .put(update_mqtt_config)
                 .delete(delete_mqtt_config),
         )
+        .route("/mqtt/{id}/effective", get(get_effective_mqtt_config))
         // ZeroMQ configs (XPUB/XSUB)
         .route("/zmq", get(get_zmq_configs).post(add_zmq_config))
         .route(
@@ -252,10 +1345,202 @@ pub fn config_routes() -> Router<AppState> {
                 .put(update_zmq_config)
                 .delete(delete_zmq_config),
         )
+        .route("/zmq/{id}/effective", get(get_effective_zmq_config))
         // Topic mappings
         .route("/mappings", get(get_mappings).post(add_mapping))
+        .route("/mappings/match", get(match_mappings))
         .route(
             "/mappings/{id}",
             put(update_mapping).delete(delete_mapping),
         )
+        // Running vs persisted configuration
+        .route("/diff", get(get_config_diff))
+        // Staged changes
+        .route("/pending", get(get_pending_changes))
+        .route("/apply", post(apply_pending_changes))
+        .route("/discard", post(discard_pending_changes))
+        // Bulk import/export
+        .route("/export", get(export_config))
+        .route("/import", post(import_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    fn request(target_topic: &str) -> CreateMappingRequest {
+        serde_json::from_value(serde_json::json!({
+            "source_endpoint_type": "mqtt",
+            "source_endpoint_id": 1,
+            "target_endpoint_type": "zmq",
+            "target_endpoint_id": 1,
+            "source_topic": "sensors/#",
+            "target_topic": target_topic,
+            "direction": "mqtt_to_zmq",
+            "enabled": true,
+            "description": "provisioned",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_viewers_cannot_change_config() {
+        use crate::auth::encode_access_token;
+        use crate::bridge::BridgeCore;
+        use crate::config::AppConfig;
+        use crate::db::{init_memory_db, Repository};
+        use crate::models::UserRole;
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let config = AppConfig::default();
+        let bridge = BridgeCore::new(repo.clone(), config.bridge.clone());
+        let state = AppState::new(config, repo, bridge);
+        let app = config_routes()
+            .with_state(state.clone())
+            .layer(axum::Extension(state.config.clone()))
+            .layer(axum::Extension(state.repo.clone()));
+        let send = |method: &str, role: UserRole| {
+            let token = encode_access_token("someone", role, &state.config).unwrap();
+            let body = match method {
+                "POST" => Body::from(serde_json::to_vec(&request("out/data")).unwrap()),
+                _ => Body::empty(),
+            };
+            let request = Request::builder()
+                .method(method)
+                .uri("/mappings")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body)
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = send("POST", UserRole::Viewer).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = send("GET", UserRole::Viewer).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Admins get past authorization to validation: endpoint 1 does not exist
+        let response = send("POST", UserRole::Admin).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_find_duplicate_mapping() {
+        let existing = vec![TopicMapping {
+            id: 7,
+            source_topic: "sensors/#".to_string(),
+            target_topic: "zmq/sensors".to_string(),
+            description: Some("original".to_string()),
+            ..Default::default()
+        }];
+
+        // Descriptions and other options do not make a route distinct
+        let duplicate = find_duplicate_mapping(&existing, &request("zmq/sensors"), None);
+        assert_eq!(duplicate.map(|m| m.id), Some(7));
+
+        assert!(find_duplicate_mapping(&existing, &request("zmq/other"), None).is_none());
+        // A mapping saved over itself is not a duplicate
+        assert!(find_duplicate_mapping(&existing, &request("zmq/sensors"), Some(7)).is_none());
+    }
+
+    #[test]
+    fn test_broad_subscription_needs_opt_in() {
+        let mut req = request("zmq/sensors");
+        assert!(broad_subscription_message(&req).is_none());
+
+        req.source_topic = "+/status".to_string();
+        assert!(broad_subscription_message(&req).unwrap().contains("allow_broad_subscription"));
+        req.allow_broad_subscription = true;
+        assert!(broad_subscription_message(&req).is_none());
+    }
+
+    #[test]
+    fn test_out_of_range_qos_rejected() {
+        let mut req = request("zmq/sensors");
+        assert_eq!(req.qos, 1);
+        assert!(validate_mapping(&req).is_ok());
+
+        req.qos = 3;
+        let response = validate_mapping(&req).unwrap_err().into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_direction_must_match_endpoint_types() {
+        let mut req = request("zmq/sensors");
+        assert!(validate_mapping_direction(&req).is_ok());
+
+        req.direction = MappingDirection::ZmqToMqtt;
+        assert!(validate_mapping(&req).is_err());
+        req.direction = MappingDirection::Bidirectional;
+        assert!(validate_mapping_direction(&req).is_ok());
+
+        req.target_endpoint_type = EndpointType::Mqtt;
+        let err = validate_mapping_direction(&req).unwrap_err().into_response();
+        assert_eq!(err.status(), axum::http::StatusCode::BAD_REQUEST);
+        req.direction = MappingDirection::MqttToMqtt;
+        assert!(validate_mapping_direction(&req).is_ok());
+    }
+
+    #[test]
+    fn test_endpoint_in_use_lists_mappings() {
+        let mappings = [
+            TopicMapping { id: 4, ..Default::default() },
+            TopicMapping { id: 9, ..Default::default() },
+        ];
+        let err = endpoint_in_use(&EndpointType::Zmq, 2, &mappings);
+        let expected = "ZMQ config 2 is referenced by mappings 4, 9;";
+        assert!(matches!(err, AppError::BadRequest(ref m) if m.starts_with(expected)));
+    }
+
+    #[test]
+    fn test_dangling_endpoint_rejected() {
+        let mut req = request("zmq/sensors");
+        let mqtt_ids = HashSet::from([1]);
+        let zmq_ids = HashSet::from([1]);
+        assert!(validate_mapping_endpoints(&req, &mqtt_ids, &zmq_ids).is_ok());
+
+        // The id exists, but as an MQTT config
+        req.target_endpoint_id = 2;
+        assert!(validate_mapping_endpoints(&req, &HashSet::from([1, 2]), &zmq_ids).is_err());
+
+        req.target_endpoint_id = 1;
+        req.additional_targets = serde_json::from_value(serde_json::json!([
+            {"endpoint_type": "mqtt", "endpoint_id": 9, "target_topic": "copy"}
+        ]))
+        .unwrap();
+        let err = validate_mapping_endpoints(&req, &mqtt_ids, &zmq_ids).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(ref m) if m.contains("MQTT endpoint 9")));
+    }
+
+    #[test]
+    fn test_endpoint_roles_enforced() {
+        let mut req = request("zmq/sensors");
+        let mut mqtt_roles = HashMap::from([(1, EndpointRole::SourceOnly)]);
+        let zmq_roles = HashMap::from([(1, EndpointRole::SinkOnly)]);
+        assert!(validate_endpoint_roles(&req, &mqtt_roles, &zmq_roles).is_ok());
+
+        // Each end of a bidirectional mapping is both read from and delivered to
+        req.direction = MappingDirection::Bidirectional;
+        let err = validate_endpoint_roles(&req, &mqtt_roles, &zmq_roles).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(ref m) if m.contains("source MQTT endpoint 1 is source_only")));
+
+        req.direction = MappingDirection::MqttToZmq;
+        mqtt_roles.insert(1, EndpointRole::SinkOnly);
+        let err = validate_endpoint_roles(&req, &mqtt_roles, &zmq_roles).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(ref m) if m.contains("source MQTT endpoint 1")));
+
+        mqtt_roles.insert(1, EndpointRole::Both);
+        mqtt_roles.insert(2, EndpointRole::SourceOnly);
+        req.additional_targets = serde_json::from_value(serde_json::json!([
+            {"endpoint_type": "mqtt", "endpoint_id": 2, "target_topic": "copy"}
+        ]))
+        .unwrap();
+        let err = validate_endpoint_roles(&req, &mqtt_roles, &zmq_roles).unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(ref m) if m.contains("additional target MQTT endpoint 2 is source_only")));
+    }
 }
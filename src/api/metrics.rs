@@ -2,20 +2,61 @@
 
 use axum::{
     Router,
-    response::IntoResponse,
+    extract::State,
+    response::{IntoResponse, Response},
     routing::get,
-    http::header::CONTENT_TYPE,
+    http::{HeaderMap, header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, VARY}},
 };
+use flate2::{Compression, write::GzEncoder};
+use std::io::Write;
+use crate::auth::MetricsAccess;
 use crate::state::AppState;
 use crate::telemetry::metrics;
 
-/// Get Prometheus metrics
-async fn get_metrics() -> impl IntoResponse {
-    let output = metrics().render_prometheus();
-    (
-        [(CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
-        output
-    )
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Get Prometheus metrics, gzipped if the scraper accepts it and `metrics.gzip` is set
+async fn get_metrics(State(state): State<AppState>, _access: MetricsAccess, headers: HeaderMap) -> Response {
+    let output = metrics().render_prometheus(&state.config.metrics.namespace);
+    if state.config.metrics.gzip && accepts_gzip(&headers) {
+        match gzip(output.as_bytes()) {
+            Ok(body) => {
+                return (
+                    [(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE), (CONTENT_ENCODING, "gzip"), (VARY, "accept-encoding")],
+                    body,
+                )
+                    .into_response();
+            }
+            Err(e) => tracing::warn!("Failed to compress metrics, sending them uncompressed: {}", e),
+        }
+    }
+    ([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE), (VARY, "accept-encoding")], output).into_response()
+}
+
+/// Whether `Accept-Encoding` lists gzip (or `*`) without ruling it out with `q=0`
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|coding| {
+            let mut params = coding.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let refused = params.any(|p| {
+                p.trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+        })
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
 }
 
 /// Create metrics routes
@@ -23,3 +64,24 @@ pub fn metrics_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(get_metrics))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(accept_encoding: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, accept_encoding.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        assert!(!accepts_gzip(&HeaderMap::new()));
+        assert!(accepts_gzip(&headers("gzip")));
+        assert!(accepts_gzip(&headers("deflate, GZIP;q=0.8")));
+        assert!(accepts_gzip(&headers("*")));
+        assert!(!accepts_gzip(&headers("gzip;q=0, identity")));
+        assert!(!accepts_gzip(&headers("br, deflate")));
+    }
+}
@@ -1,5 +1,6 @@
 //! API routes module
 
+pub mod admin;
 pub mod auth;
 pub mod bridge;
 pub mod config;
@@ -7,9 +8,11 @@ pub mod metrics;
 pub mod status;
 pub mod users;
 
+use crate::auth::AuthUser;
 use crate::state::AppState;
-use axum::Router;
+use axum::{middleware, Router};
 
+pub use admin::admin_routes;
 pub use auth::auth_routes;
 pub use bridge::bridge_routes;
 pub use config::config_routes;
@@ -17,14 +20,21 @@ pub use metrics::metrics_routes;
 pub use status::status_routes;
 pub use users::users_routes;
 
-/// Create all API routes
+/// Create all API routes. Everything but `/auth` requires a login token;
+/// `/metrics` checks its own access per `metrics.require_auth`, and `/admin`
+/// additionally requires an admin.
 pub fn api_routes() -> Router<AppState> {
-    Router::new()
-        .nest("/auth", auth_routes())
+    let protected = Router::new()
         .nest("/status", status_routes())
         .nest("/config", config_routes())
         .nest("/bridge", bridge_routes())
-        .nest("/metrics", metrics_routes())
         .nest("/users", users_routes())
+        .route_layer(middleware::from_extractor::<AuthUser>());
+
+    Router::new()
+        .nest("/auth", auth_routes())
+        .nest("/metrics", metrics_routes())
+        .nest("/admin", admin_routes())
+        .merge(protected)
 }
 
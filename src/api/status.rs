@@ -1,45 +1,139 @@
 //! Status API handlers
 
 use crate::error::{AppError, AppResult};
-use crate::models::{BridgeStatus, ChartData, MessageStats, TimeSeriesPoint};
+use crate::models::{
+    ActiveMapping, BridgeStatus, ChartData, DeadLetterEntry, DryRunReport, EndpointBandwidth, EndpointConnection,
+    EndpointHealth, ForwardError, MessageStats, TimeSeriesPoint,
+};
 use crate::state::AppState;
-use axum::{extract::State, routing::get, Json, Router};
+use crate::telemetry::{metrics, DEAD_LETTER_CAPACITY, RECENT_ERRORS_CAPACITY};
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use base64::Engine;
+use serde::Deserialize;
 
-/// Get bridge status
-async fn get_status(State(state): State<AppState>) -> Json<BridgeStatus> {
-    let status = state.bridge.get_status().await;
-    Json(status)
+/// Get bridge status, with a throughput summary if `bridge.status_throughput` is set
+async fn get_status(State(state): State<AppState>) -> AppResult<Json<BridgeStatus>> {
+    let mut status = state.bridge.get_status().await;
+    if state.config.bridge.status_throughput {
+        let stats = message_stats(&state).await?;
+        status.total_messages = Some(stats.total_messages());
+        status.messages_per_second = Some(stats.messages_per_second);
+    }
+    Ok(Json(status))
+}
+
+/// Get the mappings loaded in the running bridge
+async fn get_active_mappings(State(state): State<AppState>) -> Json<Vec<ActiveMapping>> {
+    Json(state.bridge.active_mappings().await)
+}
+
+/// Payload bytes received and sent per endpoint since startup
+async fn get_bandwidth() -> Json<Vec<EndpointBandwidth>> {
+    Json(metrics().endpoint_bandwidth())
+}
+
+/// Latest liveness check of each endpoint of the running bridge
+async fn get_health() -> Json<Vec<EndpointHealth>> {
+    Json(metrics().endpoint_health())
+}
+
+/// Connection state of each endpoint, as reported by its worker thread
+async fn get_endpoints(State(state): State<AppState>) -> Json<Vec<EndpointConnection>> {
+    Json(state.bridge.endpoint_connections())
+}
+
+#[derive(Debug, Deserialize)]
+struct CountQuery {
+    count: Option<usize>,
+}
+
+/// Most recent forwarding errors, newest first; 20 unless `count` asks for
+/// more, up to the errors kept
+async fn get_recent_errors(Query(query): Query<CountQuery>) -> Json<Vec<ForwardError>> {
+    let count = query.count.unwrap_or(20).min(RECENT_ERRORS_CAPACITY);
+    Json(metrics().recent_errors(count))
+}
+
+/// Messages no mapping matched or whose target endpoint was missing, and
+/// those stored in the `dead_letters` table because a mapping could not
+/// transform them, newest first; the newest `DEAD_LETTER_CAPACITY` unless
+/// `count` asks for fewer or more
+async fn get_dead_letters(
+    State(state): State<AppState>,
+    Query(query): Query<CountQuery>,
+) -> AppResult<Json<Vec<DeadLetterEntry>>> {
+    let count = query.count.unwrap_or(DEAD_LETTER_CAPACITY);
+    let encode = |payload: &[u8]| base64::engine::general_purpose::STANDARD.encode(payload);
+    let stored = state
+        .repo
+        .get_dead_letters(count.min(u32::MAX as usize) as u32)
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut entries: Vec<DeadLetterEntry> = metrics()
+        .dead_letters(count)
+        .into_iter()
+        .map(|m| DeadLetterEntry {
+            timestamp: m.timestamp,
+            source: m.source,
+            source_id: m.source_id,
+            mapping_id: None,
+            topic: m.topic,
+            payload: encode(&m.payload),
+            reason: m.reason,
+        })
+        .chain(stored.into_iter().map(|d| DeadLetterEntry {
+            timestamp: d.created_at,
+            source: d.source,
+            source_id: d.source_id,
+            mapping_id: Some(d.mapping_id),
+            topic: d.topic,
+            payload: encode(&d.payload),
+            reason: d.reason,
+        }))
+        .collect();
+    // Stable, so entries of the same second keep their order within each store
+    entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+    entries.truncate(count);
+    Ok(Json(entries))
+}
+
+/// What dry-run mode would have published since it was last enabled
+async fn get_dry_run(State(state): State<AppState>) -> Json<DryRunReport> {
+    Json(state.bridge.dry_run_report())
 }
 
 /// Get message statistics
 async fn get_stats(State(state): State<AppState>) -> AppResult<Json<MessageStats>> {
+    Ok(Json(message_stats(&state).await?))
+}
+
+/// Stored message counters with the rates derived from them
+async fn message_stats(state: &AppState) -> AppResult<MessageStats> {
     let mut stats = state
         .repo
         .get_stats()
         .await
         .map_err(|e| AppError::Internal(e.to_string()))?;
 
-    // Calculate runtime values
-    let start_time = state
-        .repo
-        .get_start_time()
-        .await
-        .unwrap_or(chrono::Utc::now().timestamp());
-    let elapsed = (chrono::Utc::now().timestamp() - start_time) as f64;
-    
-    let total_messages = stats.mqtt_received + stats.mqtt_sent + stats.zmq_received + stats.zmq_sent;
-    
-    if elapsed > 0.0 && total_messages > 0 {
-        stats.messages_per_second = total_messages as f64 / elapsed;
+    // Live values from the running bridge
+    stats.oversize_dropped = metrics().oversize_dropped();
+    stats.overflow_dropped = metrics().forward_overflow_dropped();
+    stats.queue_depth = state.bridge.queue_depth();
+    stats.messages_per_second = metrics().messages_per_second();
+
+    if stats.messages_per_second > 0.0 {
         // Realistic latency based on message rate (simple estimate)
         stats.avg_latency_ms = 1.0 / (stats.messages_per_second + 1.0) * 100.0;
         stats.avg_latency_ms = stats.avg_latency_ms.clamp(0.1, 10.0);
     } else {
-        stats.messages_per_second = 0.0;
         stats.avg_latency_ms = 0.0;
     }
 
-    Ok(Json(stats))
+    Ok(stats)
 }
 
 /// Get throughput chart data
@@ -96,5 +190,49 @@ pub fn status_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(get_status))
         .route("/stats", get(get_stats))
+        .route("/mappings", get(get_active_mappings))
+        .route("/bandwidth", get(get_bandwidth))
+        .route("/health", get(get_health))
+        .route("/endpoints", get(get_endpoints))
+        .route("/errors", get(get_recent_errors))
+        .route("/deadletter", get(get_dead_letters))
+        .route("/dryrun", get(get_dry_run))
         .route("/chart", get(get_chart_data))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::BridgeCore;
+    use crate::config::AppConfig;
+    use crate::db::{init_memory_db, Repository};
+
+    #[tokio::test]
+    async fn test_status_throughput_matches_stats() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let mut config = AppConfig::default();
+        let bridge = BridgeCore::new(repo.clone(), config.bridge.clone());
+        let state = AppState::new(config.clone(), repo.clone(), bridge.clone());
+        let Json(status) = get_status(State(state)).await.unwrap();
+        assert_eq!(status.total_messages, None);
+
+        config.bridge.status_throughput = true;
+        let state = AppState::new(config, repo, bridge);
+        state.repo.increment_stats(4, 3, 2, 1, 0).await.unwrap();
+
+        // The rate covers complete seconds only, so a pair taken within one
+        // second agrees even while other tests record messages
+        let (status, stats) = loop {
+            let second = metrics().uptime_seconds() as u64;
+            let Json(status) = get_status(State(state.clone())).await.unwrap();
+            let Json(stats) = get_stats(State(state.clone())).await.unwrap();
+            if metrics().uptime_seconds() as u64 == second {
+                break (status, stats);
+            }
+        };
+        assert_eq!(status.total_messages, Some(10));
+        assert_eq!(status.total_messages, Some(stats.total_messages()));
+        assert_eq!(status.messages_per_second, Some(stats.messages_per_second));
+        assert_eq!(stats.queue_depth, 0, "nothing queued while the bridge is stopped");
+    }
+}
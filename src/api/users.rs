@@ -1,8 +1,9 @@
 //! User management API handlers
 
+use crate::auth::{AdminUser, AuthUser};
 use crate::error::{AppError, AppResult};
 use crate::models::{
-    ChangePasswordRequest, CreateUserRequest, UpdateUserRequest, UserResponse,
+    ChangePasswordRequest, CreateUserRequest, UpdateUserRequest, UserResponse, UserRole,
 };
 use crate::state::AppState;
 use axum::{
@@ -43,6 +44,7 @@ async fn get_user(
 /// Create a new user
 async fn create_user(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Json(req): Json<CreateUserRequest>,
 ) -> AppResult<Json<UserResponse>> {
     // Validate request
@@ -81,6 +83,7 @@ async fn create_user(
 /// Update an existing user
 async fn update_user(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
     Json(req): Json<UpdateUserRequest>,
 ) -> AppResult<Json<UserResponse>> {
@@ -105,6 +108,20 @@ async fn update_user(
         )));
     }
 
+    // The default user stays an admin so configuration is never locked
+    if req.role == Some(UserRole::Viewer) {
+        let user = state
+            .repo
+            .get_user_by_id(id)
+            .await
+            .map_err(|e| AppError::DbError(format!("Failed to get user: {}", e)))?;
+        if user.is_some_and(|u| u.is_default) {
+            return Err(AppError::BadRequest(
+                "Cannot make the default user a viewer".to_string(),
+            ));
+        }
+    }
+
     let user = state
         .repo
         .update_user(id, &req)
@@ -117,12 +134,27 @@ async fn update_user(
     }
 }
 
-/// Change user password
+/// Change user password: any user may change their own, only admins others'.
+/// Viewers must confirm their current password.
 async fn change_password(
     State(state): State<AppState>,
+    AuthUser(caller): AuthUser,
     Path(id): Path<u32>,
     Json(req): Json<ChangePasswordRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
+    let target = state
+        .repo
+        .get_user_by_id(id)
+        .await
+        .map_err(|e| AppError::DbError(format!("Failed to get user: {}", e)))?;
+    let own = target.is_some_and(|u| u.username == caller.username);
+    if !own && caller.role != UserRole::Admin {
+        return Err(AppError::Forbidden("Administrator access required".to_string()));
+    }
+    if own && caller.role != UserRole::Admin && req.current_password.is_none() {
+        return Err(AppError::BadRequest("Current password is required".to_string()));
+    }
+
     // Validate new password
     if req.new_password.len() < 6 {
         return Err(AppError::BadRequest(
@@ -148,6 +180,7 @@ async fn change_password(
 /// Delete a user (cannot delete default user)
 async fn delete_user(
     State(state): State<AppState>,
+    AdminUser(_): AdminUser,
     Path(id): Path<u32>,
 ) -> AppResult<Json<serde_json::Value>> {
     // Check if user is default
@@ -1,42 +1,155 @@
 //! JWT token handling
+//!
+//! Login issues a short-lived access token for API requests and a refresh
+//! token that mints new access tokens until `jwt.expiration_hours` pass or
+//! it is revoked at logout. The `typ` claim keeps one from passing as the other.
+//! Logout also revokes the access token by its `jti` until it expires.
 
 use crate::config::AppConfig;
+use crate::db::Repository;
 use crate::error::{AppError, AppResult};
-use crate::models::Claims;
+use crate::models::{Claims, TokenType, UserRole};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
 
-/// Encode a JWT token for the given username
-pub fn encode_token(username: &str, config: &AppConfig) -> AppResult<String> {
+/// How often revoked access tokens past their expiry are forgotten
+const REVOCATION_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Tokens issued at login
+#[derive(Debug)]
+pub struct IssuedTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// `jti` of the refresh token, stored so it can be revoked
+    pub refresh_id: String,
+    /// Expiration timestamp of the refresh token
+    pub refresh_expires_at: i64,
+}
+
+/// Encode an access token and a refresh token for the given user
+pub fn encode_token(username: &str, role: UserRole, config: &AppConfig) -> AppResult<IssuedTokens> {
+    let now = Utc::now();
+    let refresh_id = token_id();
+    let refresh_expires_at = (now + Duration::hours(config.jwt.expiration_hours)).timestamp();
+
+    let refresh = Claims {
+        sub: username.to_string(),
+        iat: now.timestamp(),
+        exp: refresh_expires_at,
+        typ: TokenType::Refresh,
+        jti: Some(refresh_id.clone()),
+        role,
+    };
+
+    Ok(IssuedTokens {
+        access_token: encode_access_token(username, role, config)?,
+        refresh_token: sign(&refresh, config)?,
+        refresh_id,
+        refresh_expires_at,
+    })
+}
+
+/// Encode an access token valid for `jwt.access_expiration_minutes`
+pub fn encode_access_token(username: &str, role: UserRole, config: &AppConfig) -> AppResult<String> {
     let now = Utc::now();
-    let expiration = now + Duration::hours(config.jwt.expiration_hours);
+    let expiration = now + Duration::minutes(config.jwt.access_expiration_minutes);
 
     let claims = Claims {
         sub: username.to_string(),
         iat: now.timestamp(),
         exp: expiration.timestamp(),
+        typ: TokenType::Access,
+        jti: Some(token_id()),
+        role,
     };
+    sign(&claims, config)
+}
 
+fn token_id() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+fn sign(claims: &Claims, config: &AppConfig) -> AppResult<String> {
     encode(
         &Header::default(),
-        &claims,
+        claims,
         &EncodingKey::from_secret(config.jwt.secret.as_bytes()),
     )
     .map_err(|e| AppError::TokenError(format!("Failed to encode token: {}", e)))
 }
 
-/// Decode and validate a JWT token
-pub fn decode_token(token: &str, config: &AppConfig) -> AppResult<Claims> {
-    decode::<Claims>(
+/// Decode and validate a JWT token, which must be of the `expected` type
+pub fn decode_token(token: &str, expected: TokenType, config: &AppConfig) -> AppResult<Claims> {
+    let claims = decode::<Claims>(
         token,
         &DecodingKey::from_secret(config.jwt.secret.as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
-    .map_err(|e| AppError::TokenError(format!("Invalid token: {}", e)))
+    .map_err(|e| AppError::TokenError(format!("Invalid token: {}", e)))?;
+
+    if claims.typ != expected {
+        return Err(AppError::TokenError(match expected {
+            TokenType::Access => "Refresh tokens cannot authorize requests".to_string(),
+            TokenType::Refresh => "Expected a refresh token".to_string(),
+        }));
+    }
+    Ok(claims)
+}
+
+/// Prune expired entries of the access token revocation list every hour
+pub fn spawn_revocation_prune_task(repo: Repository) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REVOCATION_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repo.prune_revoked_tokens(Utc::now().timestamp()).await {
+                Ok(0) => {}
+                Ok(pruned) => info!("Pruned {} expired revoked tokens", pruned),
+                Err(e) => warn!("Revoked token pruning failed: {}", e),
+            }
+        }
+    })
 }
 
 /// Validate user credentials against default config
 pub fn validate_credentials(username: &str, password: &str, config: &AppConfig) -> bool {
     username == config.credentials.username && password == config.credentials.password
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_types_are_not_interchangeable() {
+        let config = AppConfig::default();
+        let tokens = encode_token("admin", UserRole::Admin, &config).unwrap();
+
+        let access = decode_token(&tokens.access_token, TokenType::Access, &config).unwrap();
+        assert_eq!(access.sub, "admin");
+        assert_eq!(access.role, UserRole::Admin);
+        assert!(access.jti.is_some());
+        let refresh = decode_token(&tokens.refresh_token, TokenType::Refresh, &config).unwrap();
+        assert_eq!(refresh.jti.as_deref(), Some(tokens.refresh_id.as_str()));
+        assert_eq!(refresh.exp, tokens.refresh_expires_at);
+        assert!(refresh.exp > access.exp);
+
+        assert!(decode_token(&tokens.access_token, TokenType::Refresh, &config).is_err());
+        assert!(decode_token(&tokens.refresh_token, TokenType::Access, &config).is_err());
+    }
+
+    #[test]
+    fn test_expired_tokens_rejected() {
+        let mut config = AppConfig::default();
+        // Past the default validation leeway of a minute
+        config.jwt.access_expiration_minutes = -5;
+        config.jwt.expiration_hours = -1;
+        let tokens = encode_token("admin", UserRole::Admin, &config).unwrap();
+
+        assert!(decode_token(&tokens.access_token, TokenType::Access, &config).is_err());
+        assert!(decode_token(&tokens.refresh_token, TokenType::Refresh, &config).is_err());
+    }
+}
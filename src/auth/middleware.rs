@@ -2,8 +2,9 @@
 
 use crate::auth::jwt::decode_token;
 use crate::config::AppConfig;
+use crate::db::Repository;
 use crate::error::AppError;
-use crate::models::User;
+use crate::models::{TokenType, User, UserRole};
 use axum::{
     extract::FromRequestParts,
     http::{header::AUTHORIZATION, request::Parts},
@@ -40,10 +41,132 @@ where
             .ok_or_else(|| AppError::Internal("Config not found in request".to_string()))?;
 
         // Decode and validate token
-        let claims = decode_token(token, config)?;
+        let claims = decode_token(token, TokenType::Access, config)?;
+
+        // Tokens issued before ids were added cannot be revoked and simply expire
+        if let Some(ref jti) = claims.jti {
+            let repo = parts
+                .extensions
+                .get::<Repository>()
+                .ok_or_else(|| AppError::Internal("Repository not found in request".to_string()))?;
+            let revoked = repo
+                .access_token_revoked(jti)
+                .await
+                .map_err(|e| AppError::DbError(format!("Database error: {}", e)))?;
+            if revoked {
+                return Err(AppError::TokenError("Token has been revoked".to_string()));
+            }
+        }
 
         Ok(AuthUser(User {
             username: claims.sub,
+            role: claims.role,
         }))
     }
 }
+
+/// Authenticated user with the `admin` role, required to change
+/// configuration, control the bridge and manage users
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub User);
+
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthUser(user) = AuthUser::from_request_parts(parts, state).await?;
+
+        if user.role != UserRole::Admin {
+            return Err(AppError::Forbidden("Administrator access required".to_string()));
+        }
+
+        Ok(AdminUser(user))
+    }
+}
+
+/// Access to the metrics endpoint: open unless `metrics.require_auth` is set,
+/// then a login token or the configured scrape token
+#[derive(Debug, Clone)]
+pub struct MetricsAccess;
+
+impl<S> FromRequestParts<S> for MetricsAccess
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = parts
+            .extensions
+            .get::<Arc<AppConfig>>()
+            .cloned()
+            .ok_or_else(|| AppError::Internal("Config not found in request".to_string()))?;
+        if !config.metrics.require_auth {
+            return Ok(MetricsAccess);
+        }
+
+        // Scrapers authenticate with a static token, without logging in
+        let bearer = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if let (Some(token), Some(expected)) = (bearer, config.metrics.scrape_token.as_deref())
+            && !expected.is_empty()
+            && constant_time_eq(token.as_bytes(), expected.as_bytes())
+        {
+            return Ok(MetricsAccess);
+        }
+
+        AuthUser::from_request_parts(parts, state).await?;
+        Ok(MetricsAccess)
+    }
+}
+
+/// Compare secrets without leaking the position of the first mismatch
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::encode_token;
+    use crate::db::init_memory_db;
+
+    async fn metrics_access(config: &AppConfig, bearer: Option<&str>) -> Result<MetricsAccess, AppError> {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let mut request = axum::http::Request::builder()
+            .extension(Arc::new(config.clone()))
+            .extension(repo);
+        if let Some(token) = bearer {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        MetricsAccess::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_metrics_open_by_default() {
+        let config = AppConfig::default();
+        assert!(metrics_access(&config, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_metrics_require_auth() {
+        let mut config = AppConfig::default();
+        config.metrics.require_auth = true;
+        config.metrics.scrape_token = Some("scrape-secret".to_string());
+
+        assert!(metrics_access(&config, None).await.is_err());
+        assert!(metrics_access(&config, Some("wrong")).await.is_err());
+        assert!(metrics_access(&config, Some("scrape-secret")).await.is_ok());
+
+        let login = encode_token("admin", UserRole::Viewer, &config).unwrap();
+        assert!(metrics_access(&config, Some(&login.access_token)).await.is_ok());
+        assert!(metrics_access(&config, Some(&login.refresh_token)).await.is_err());
+    }
+}
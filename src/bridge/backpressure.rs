@@ -0,0 +1,318 @@
+//! Slow-consumer detection on the forwarding channel
+//!
+//! Endpoint workers hand messages to the forwarding task through a bounded
+//! channel. When forwarding cannot keep up, the channel fills and latency
+//! grows silently until producers block. The detector samples the channel
+//! depth and flags a slow consumer once it stays above a threshold for a
+//! sustained period, clearing the flag when the depth recovers.
+//!
+//! Producers send through a [`ForwardSender`], which applies
+//! `bridge.forward_overflow` once the channel is full: wait for room, or
+//! drop the newest or oldest message so the endpoint keeps receiving. Both
+//! halves keep a depth gauge current for `/api/status/stats`.
+
+use crate::config::{BridgeConfig, ForwardOverflow};
+use crate::telemetry::metrics;
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+use tracing::{info, warn};
+
+/// Interval between samples of the forwarding channel depth
+pub const SLOW_CONSUMER_SAMPLE_MS: u64 = 250;
+
+/// Messages the forwarding channel holds before `bridge.forward_overflow` applies
+pub const FORWARD_CHANNEL_CAPACITY: usize = 1000;
+
+/// Messages queued in the channel `tx` sends into
+pub fn channel_depth<T>(tx: &mpsc::Sender<T>) -> usize {
+    tx.max_capacity() - tx.capacity()
+}
+
+/// A bounded forwarding channel applying `overflow` when it is full and
+/// storing its depth in `depth` on every send and receive
+pub fn forward_channel<T>(
+    capacity: usize,
+    overflow: ForwardOverflow,
+    dead_letter_drops: bool,
+    depth: Arc<AtomicU32>,
+) -> (ForwardSender<T>, ForwardReceiver<T>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    // Senders share the receiver so `drop_oldest` can evict from the front
+    let rx = Arc::new(Mutex::new(rx));
+    let sender = ForwardSender {
+        tx,
+        rx: rx.clone(),
+        overflow,
+        dead_letter_drops,
+        depth: depth.clone(),
+    };
+    (sender, ForwardReceiver { rx, depth })
+}
+
+/// Sending half of a [`forward_channel`]
+pub struct ForwardSender<T> {
+    tx: mpsc::Sender<T>,
+    rx: Arc<Mutex<mpsc::Receiver<T>>>,
+    overflow: ForwardOverflow,
+    dead_letter_drops: bool,
+    depth: Arc<AtomicU32>,
+}
+
+impl<T> Clone for ForwardSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+            overflow: self.overflow,
+            dead_letter_drops: self.dead_letter_drops,
+            depth: self.depth.clone(),
+        }
+    }
+}
+
+impl<T> ForwardSender<T> {
+    /// Queue `msg`, returning the messages dropped to make room: `msg` itself
+    /// with `drop_newest`, the oldest queued ones with `drop_oldest`. Only
+    /// `block` waits. Fails once the receiver is gone.
+    pub async fn send(&self, msg: T) -> Result<Vec<T>, SendError<T>> {
+        let mut dropped = Vec::new();
+        let mut msg = match self.overflow {
+            ForwardOverflow::Block => {
+                self.tx.send(msg).await?;
+                self.update_depth();
+                return Ok(dropped);
+            }
+            ForwardOverflow::DropNewest | ForwardOverflow::DropOldest => msg,
+        };
+        loop {
+            msg = match self.tx.try_send(msg) {
+                Ok(()) => {
+                    self.update_depth();
+                    return Ok(dropped);
+                }
+                Err(TrySendError::Closed(msg)) => return Err(SendError(msg)),
+                Err(TrySendError::Full(msg)) => msg,
+            };
+            if self.overflow == ForwardOverflow::DropNewest {
+                dropped.push(msg);
+                return Ok(dropped);
+            }
+            // Another producer may take the freed slot first, so retry until
+            // this message is queued; an empty channel has room on the retry
+            match self.rx.lock().try_recv() {
+                Ok(oldest) => dropped.push(oldest),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => return Err(SendError(msg)),
+            }
+        }
+    }
+
+    /// Messages queued and not yet received
+    pub fn depth(&self) -> usize {
+        channel_depth(&self.tx)
+    }
+
+    fn update_depth(&self) {
+        self.depth.store(self.depth() as u32, Ordering::Relaxed);
+    }
+
+    /// Whether dropped messages should be dead-lettered rather than only counted
+    pub fn dead_letter_drops(&self) -> bool {
+        self.dead_letter_drops
+    }
+
+    /// A sender that does not keep the channel open
+    pub fn downgrade(&self) -> mpsc::WeakSender<T> {
+        self.tx.downgrade()
+    }
+}
+
+/// Receiving half of a [`forward_channel`]
+pub struct ForwardReceiver<T> {
+    rx: Arc<Mutex<mpsc::Receiver<T>>>,
+    depth: Arc<AtomicU32>,
+}
+
+impl<T> ForwardReceiver<T> {
+    /// The next message; `None` once every sender is gone and the channel is drained
+    pub async fn recv(&mut self) -> Option<T> {
+        let msg = std::future::poll_fn(|cx| self.rx.lock().poll_recv(cx)).await;
+        self.depth.store(self.rx.lock().len() as u32, Ordering::Relaxed);
+        msg
+    }
+}
+
+impl<T> Drop for ForwardReceiver<T> {
+    fn drop(&mut self) {
+        // Senders hold the receiver too; close it so they fail instead of blocking
+        self.rx.lock().close();
+    }
+}
+
+/// Tracks how long the forwarding channel has been above its threshold
+#[derive(Debug)]
+pub struct SlowConsumerDetector {
+    threshold: usize,
+    duration: Duration,
+    above_since: Option<Instant>,
+    flag: Arc<AtomicBool>,
+}
+
+impl SlowConsumerDetector {
+    /// Flag `flag` once the depth stays above `threshold` for `duration`
+    pub fn new(threshold: usize, duration: Duration, flag: Arc<AtomicBool>) -> Self {
+        Self {
+            threshold,
+            duration,
+            above_since: None,
+            flag,
+        }
+    }
+
+    pub fn from_settings(settings: &BridgeConfig, flag: Arc<AtomicBool>) -> Self {
+        Self::new(
+            settings.slow_consumer_depth,
+            Duration::from_secs(settings.slow_consumer_secs),
+            flag,
+        )
+    }
+
+    /// Record the channel depth at `now`, updating the flag and gauge
+    pub fn sample(&mut self, depth: usize, now: Instant) {
+        if depth <= self.threshold {
+            self.above_since = None;
+            if self.flag.swap(false, Ordering::Relaxed) {
+                metrics().set_slow_consumer(false);
+                info!("Forwarding caught up: channel depth {} is back at or below {}", depth, self.threshold);
+            }
+            return;
+        }
+
+        let since = *self.above_since.get_or_insert(now);
+        if now.duration_since(since) >= self.duration && !self.flag.swap(true, Ordering::Relaxed) {
+            metrics().set_slow_consumer(true);
+            warn!(
+                "Slow consumer: forwarding channel depth {} has stayed above {} for {}s",
+                depth,
+                self.threshold,
+                self.duration.as_secs()
+            );
+        }
+    }
+
+    pub fn is_slow(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_flag_set_and_cleared() {
+        let (tx, mut rx) = mpsc::channel::<u32>(10);
+        let mut detector = SlowConsumerDetector::new(8, Duration::from_secs(5), Arc::new(AtomicBool::new(false)));
+        let start = Instant::now();
+
+        // Drive the channel near capacity with nothing consuming
+        for i in 0..9 {
+            tx.send(i).await.unwrap();
+        }
+        assert_eq!(channel_depth(&tx), 9);
+
+        detector.sample(channel_depth(&tx), start);
+        detector.sample(channel_depth(&tx), start + Duration::from_secs(4));
+        assert!(!detector.is_slow());
+        detector.sample(channel_depth(&tx), start + Duration::from_secs(5));
+        assert!(detector.is_slow());
+
+        // Draining recovers the depth and clears the flag
+        while rx.try_recv().is_ok() {}
+        detector.sample(channel_depth(&tx), start + Duration::from_secs(6));
+        assert!(!detector.is_slow());
+    }
+
+    /// Fill a capacity-2 channel with nothing consuming, then send 3 and 4
+    async fn overflow(overflow: ForwardOverflow) -> (Vec<u32>, Vec<u32>) {
+        let (tx, mut rx) = forward_channel::<u32>(2, overflow, false, Arc::default());
+        let mut dropped = Vec::new();
+        for i in 1..=4 {
+            dropped.extend(tx.send(i).await.unwrap());
+        }
+        assert_eq!(tx.depth(), 2);
+        let mut kept = Vec::new();
+        while tx.depth() > 0 {
+            kept.push(rx.recv().await.unwrap());
+        }
+        (kept, dropped)
+    }
+
+    #[tokio::test]
+    async fn test_overflow_drop_policies() {
+        assert_eq!(overflow(ForwardOverflow::DropNewest).await, (vec![1, 2], vec![3, 4]));
+        assert_eq!(overflow(ForwardOverflow::DropOldest).await, (vec![3, 4], vec![1, 2]));
+    }
+
+    #[tokio::test]
+    async fn test_overflow_block_waits_for_room() {
+        let (tx, mut rx) = forward_channel::<u32>(1, ForwardOverflow::Block, false, Arc::default());
+        assert!(tx.send(1).await.unwrap().is_empty());
+        let blocked = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.send(2).await.map_err(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        // The slow consumer catches up and the producer resumes, losing nothing
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(blocked.await.unwrap(), Ok(Vec::new()));
+        assert_eq!(rx.recv().await, Some(2));
+
+        // Dropping the receiver fails a producer that would otherwise wait forever
+        tx.send(3).await.unwrap();
+        let blocked = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.send(4).await.is_err() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(rx);
+        assert!(blocked.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_depth_gauge_rises_while_consumer_paused() {
+        let depth = Arc::new(AtomicU32::new(0));
+        let (tx, mut rx) = forward_channel::<u32>(10, ForwardOverflow::Block, false, depth.clone());
+        for i in 0..3 {
+            tx.send(i).await.unwrap();
+        }
+        assert_eq!(depth.load(Ordering::Relaxed), 3);
+
+        // The consumer resumes and the gauge follows it down
+        assert_eq!(rx.recv().await, Some(0));
+        assert_eq!(depth.load(Ordering::Relaxed), 2);
+        rx.recv().await;
+        rx.recv().await;
+        assert_eq!(depth.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_brief_spike_not_flagged() {
+        let mut detector = SlowConsumerDetector::new(8, Duration::from_secs(5), Arc::new(AtomicBool::new(false)));
+        let start = Instant::now();
+        detector.sample(9, start);
+        detector.sample(3, start + Duration::from_secs(3));
+        // The timer restarts after the depth dipped
+        detector.sample(9, start + Duration::from_secs(4));
+        detector.sample(9, start + Duration::from_secs(8));
+        assert!(!detector.is_slow());
+        detector.sample(9, start + Duration::from_secs(9));
+        assert!(detector.is_slow());
+    }
+}
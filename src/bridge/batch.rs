@@ -0,0 +1,113 @@
+//! Batching of messages sent by publishing ZMQ endpoints
+//!
+//! Handing messages to libzmq one at a time wakes the socket's I/O thread for
+//! each of them. A batching endpoint holds messages until `batch_max_messages`
+//! have arrived or the oldest has waited `batch_max_delay_ms`, then encodes
+//! them all and sends them back to back so the I/O thread writes them out in
+//! one burst. Each message remains a ZMQ message of its own, so subscribers
+//! see no difference.
+
+use crate::models::ZmqConfig;
+use std::time::{Duration, Instant};
+
+/// Largest accepted `batch_max_messages`
+pub const MAX_BATCH_MESSAGES: u32 = 10_000;
+
+/// Messages held by a batching endpoint
+#[derive(Debug)]
+pub struct Batch<T> {
+    max_messages: usize,
+    max_delay: Duration,
+    pending: Vec<T>,
+    /// When the oldest pending message was added
+    oldest: Option<Instant>,
+}
+
+impl<T> Batch<T> {
+    pub fn new(max_messages: usize, max_delay: Duration) -> Self {
+        Self {
+            max_messages,
+            max_delay,
+            pending: Vec::new(),
+            oldest: None,
+        }
+    }
+
+    /// Batching as configured for an endpoint; `None` unless
+    /// `batch_max_messages` is above 1
+    pub fn from_config(config: &ZmqConfig) -> Option<Self> {
+        (config.batch_max_messages > 1).then(|| {
+            Self::new(
+                config.batch_max_messages.min(MAX_BATCH_MESSAGES) as usize,
+                Duration::from_millis(config.batch_max_delay_ms as u64),
+            )
+        })
+    }
+
+    pub fn push(&mut self, item: T, now: Instant) {
+        self.oldest.get_or_insert(now);
+        self.pending.push(item);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Whether the batch is full or its oldest message has waited long enough
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.pending.len() >= self.max_messages
+            || self.oldest.is_some_and(|oldest| now.duration_since(oldest) >= self.max_delay)
+    }
+
+    /// Time until the oldest message is due; `None` while the batch is empty
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.oldest
+            .map(|oldest| self.max_delay.saturating_sub(now.duration_since(oldest)))
+    }
+
+    /// Every pending message, oldest first, leaving the batch empty
+    pub fn take(&mut self) -> Vec<T> {
+        self.oldest = None;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_due_when_full_or_delayed() {
+        let start = Instant::now();
+        let mut batch = Batch::new(3, Duration::from_millis(5));
+        assert!(!batch.is_due(start));
+        assert_eq!(batch.time_left(start), None);
+
+        batch.push(1, start);
+        batch.push(2, start + Duration::from_millis(1));
+        assert!(!batch.is_due(start + Duration::from_millis(2)));
+        assert_eq!(batch.time_left(start + Duration::from_millis(2)), Some(Duration::from_millis(3)));
+        // The delay runs from the oldest message
+        assert!(batch.is_due(start + Duration::from_millis(5)));
+
+        batch.push(3, start + Duration::from_millis(2));
+        assert!(batch.is_due(start + Duration::from_millis(2)));
+        assert_eq!(batch.take(), vec![1, 2, 3]);
+        assert!(batch.is_empty());
+        assert!(!batch.is_due(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_batching_needs_more_than_one_message() {
+        let mut config = ZmqConfig::default();
+        assert!(Batch::<()>::from_config(&config).is_none());
+        config.batch_max_messages = 1;
+        assert!(Batch::<()>::from_config(&config).is_none());
+        config.batch_max_messages = 64;
+        assert!(Batch::<()>::from_config(&config).is_some());
+        // Rows saved before the bound was enforced are capped
+        config.batch_max_messages = u32::MAX;
+        let batch = Batch::<()>::from_config(&config).unwrap();
+        assert_eq!(batch.max_messages, MAX_BATCH_MESSAGES as usize);
+    }
+}
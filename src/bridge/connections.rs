@@ -0,0 +1,114 @@
+//! Live connection state of the endpoints of the running bridge
+//!
+//! Endpoint threads report connects, drops and reconnects here, so the status
+//! API reflects the brokers and sockets themselves rather than only whether
+//! the bridge was started.
+
+use crate::models::{ConnectionStatus, EndpointConnection, EndpointType};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// Connection status per endpoint. MQTT and ZMQ ids come from separate
+/// tables, so endpoints are keyed by type as well as id.
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    endpoints: RwLock<HashMap<(EndpointType, u32), EndpointConnection>>,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the status of an endpoint; reporting the current status again
+    /// keeps the time it was entered
+    pub fn set(&self, endpoint_type: EndpointType, endpoint_id: u32, name: &str, status: ConnectionStatus) {
+        // Threads report on every poll, so most reports change nothing
+        let key = (endpoint_type, endpoint_id);
+        if self.endpoints.read().get(&key).is_some_and(|c| c.status == status) {
+            return;
+        }
+        let mut endpoints = self.endpoints.write();
+        let entry = endpoints
+            .entry(key.clone())
+            .or_insert_with(|| EndpointConnection {
+                endpoint_type: key.0,
+                endpoint_id,
+                name: name.to_string(),
+                status: status.clone(),
+                since: chrono::Utc::now().timestamp(),
+            });
+        if entry.status != status {
+            entry.status = status;
+            entry.since = chrono::Utc::now().timestamp();
+        }
+    }
+
+    /// Forget an endpoint that is no longer running
+    pub fn remove(&self, endpoint_type: &EndpointType, endpoint_id: u32) {
+        self.endpoints.write().remove(&(endpoint_type.clone(), endpoint_id));
+    }
+
+    /// Forget every endpoint, e.g. once the bridge stops
+    pub fn clear(&self) {
+        self.endpoints.write().clear();
+    }
+
+    /// Every reported endpoint, MQTT brokers first, each type by id
+    pub fn snapshot(&self) -> Vec<EndpointConnection> {
+        let mut endpoints: Vec<_> = self.endpoints.read().values().cloned().collect();
+        endpoints.sort_by_key(|c| (c.endpoint_type == EndpointType::Zmq, c.endpoint_id));
+        endpoints
+    }
+
+    /// Combined status of the endpoints of one type: connected if any of them
+    /// is, otherwise the most hopeful of their statuses
+    pub fn summary(&self, endpoint_type: &EndpointType) -> ConnectionStatus {
+        let rank = |status: &ConnectionStatus| match status {
+            ConnectionStatus::Connected => 4,
+            ConnectionStatus::Throttled => 3,
+            ConnectionStatus::Connecting => 2,
+            ConnectionStatus::Error => 1,
+            ConnectionStatus::Disconnected => 0,
+        };
+        self.endpoints
+            .read()
+            .values()
+            .filter(|c| c.endpoint_type == *endpoint_type)
+            .map(|c| &c.status)
+            .max_by_key(|s| rank(s))
+            .cloned()
+            .unwrap_or(ConnectionStatus::Disconnected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_is_connected_if_any_endpoint_is() {
+        let tracker = ConnectionTracker::new();
+        assert_eq!(tracker.summary(&EndpointType::Mqtt), ConnectionStatus::Disconnected);
+
+        tracker.set(EndpointType::Mqtt, 1, "primary", ConnectionStatus::Error);
+        tracker.set(EndpointType::Mqtt, 2, "backup", ConnectionStatus::Connecting);
+        // Same id, other table
+        tracker.set(EndpointType::Zmq, 1, "pub", ConnectionStatus::Connected);
+        assert_eq!(tracker.summary(&EndpointType::Mqtt), ConnectionStatus::Connecting);
+        assert_eq!(tracker.summary(&EndpointType::Zmq), ConnectionStatus::Connected);
+
+        tracker.set(EndpointType::Mqtt, 2, "backup", ConnectionStatus::Connected);
+        assert_eq!(tracker.summary(&EndpointType::Mqtt), ConnectionStatus::Connected);
+
+        let snapshot = tracker.snapshot();
+        let order: Vec<_> = snapshot.iter().map(|c| (c.endpoint_type.clone(), c.endpoint_id)).collect();
+        assert_eq!(
+            order,
+            vec![(EndpointType::Mqtt, 1), (EndpointType::Mqtt, 2), (EndpointType::Zmq, 1)]
+        );
+
+        tracker.clear();
+        assert!(tracker.snapshot().is_empty());
+    }
+}
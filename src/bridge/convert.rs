@@ -0,0 +1,90 @@
+//! Payload representation conversions between JSON and MessagePack
+//!
+//! Bridges often connect a JSON-speaking MQTT side with a ZMQ side using
+//! MessagePack. A mapping's `payload_transform` re-encodes each payload
+//! through a generic value, so structure is preserved in either direction.
+//! `truncate_bytes` keeps only the head of a payload for consumers that
+//! ignore the rest; unlike the re-encodings it cannot be reversed.
+
+use crate::models::PayloadTransform;
+
+/// Why a payload could not be converted
+#[derive(Debug, thiserror::Error)]
+pub enum ConvertError {
+    #[error("payload is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("payload is not valid MessagePack: {0}")]
+    MsgpackDecode(#[from] rmp_serde::decode::Error),
+    #[error("failed to encode MessagePack: {0}")]
+    MsgpackEncode(#[from] rmp_serde::encode::Error),
+}
+
+/// `payload` re-encoded as `transform` describes. MessagePack values without
+/// a JSON equivalent (binary data, non-string map keys) are rejected.
+pub fn convert_payload(transform: PayloadTransform, payload: &[u8]) -> Result<Vec<u8>, ConvertError> {
+    match transform {
+        PayloadTransform::JsonToMsgpack => {
+            let value: serde_json::Value = serde_json::from_slice(payload)?;
+            Ok(rmp_serde::to_vec(&value)?)
+        }
+        PayloadTransform::MsgpackToJson => {
+            let value: serde_json::Value = rmp_serde::from_slice(payload)?;
+            Ok(serde_json::to_vec(&value)?)
+        }
+    }
+}
+
+/// The first `max_bytes` bytes of `payload`, or None if it is no longer than that
+pub fn truncate_payload(payload: &[u8], max_bytes: u32) -> Option<&[u8]> {
+    payload.get(..max_bytes as usize).filter(|head| head.len() < payload.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_msgpack_round_trip() {
+        let value = json!({
+            "sensor": "t1",
+            "reading": { "celsius": 21.5, "raw": -4, "ok": true },
+            "tags": ["a", "b"],
+            "note": null,
+            "count": 18446744073709551615u64,
+        });
+        let packed = convert_payload(PayloadTransform::JsonToMsgpack, value.to_string().as_bytes()).unwrap();
+        assert_eq!(rmp_serde::from_slice::<serde_json::Value>(&packed).unwrap(), value);
+
+        let json = convert_payload(PayloadTransform::MsgpackToJson, &packed).unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_scalar_payloads() {
+        let packed = convert_payload(PayloadTransform::JsonToMsgpack, b"42").unwrap();
+        assert_eq!(packed, vec![42]);
+        assert_eq!(convert_payload(PayloadTransform::MsgpackToJson, &packed).unwrap(), b"42");
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(matches!(
+            convert_payload(PayloadTransform::JsonToMsgpack, b"{not json"),
+            Err(ConvertError::Json(_))
+        ));
+        // 0xc1 is never used in MessagePack
+        assert!(convert_payload(PayloadTransform::MsgpackToJson, &[0xc1]).is_err());
+        // A map keyed by an integer has no JSON equivalent
+        assert!(convert_payload(PayloadTransform::MsgpackToJson, &[0x81, 0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    fn test_truncate_payload() {
+        assert_eq!(truncate_payload(b"header:body", 6), Some(&b"header"[..]));
+        // Payloads within the limit pass through untouched
+        assert_eq!(truncate_payload(b"header", 6), None);
+        assert_eq!(truncate_payload(b"hdr", 6), None);
+        assert_eq!(truncate_payload(b"", 6), None);
+    }
+}
@@ -1,13 +1,20 @@
 //! Bridge core - orchestrates MQTT and ZeroMQ message forwarding
 //! Now supports multiple MQTT brokers and XPUB/XSUB proxy pattern
 
+use crate::config::{BridgeConfig, ZmqSettings};
 use crate::db::Repository;
-use crate::models::{BridgeState, BridgeStatus, ConnectionStatus, TopicMapping};
-use crate::bridge::BridgeWorker;
+use crate::models::{
+    ActiveMapping, BridgeState, BridgeStatus, ConfigDiff, DryRunReport, EndpointConnection, EndpointType, MqttConfig,
+    WorkerThreadInfo, ZmqConfig,
+};
+use crate::bridge::{
+    diff_by_id, sort_mappings, BridgeWorker, CompiledMappings, ConnectionTracker, Debouncer, DryRun,
+};
+use crate::telemetry::metrics;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use parking_lot::Mutex;
-use tracing::info;
+use tracing::{info, warn};
 
 /// Bridge state container
 #[derive(Clone)]
@@ -15,24 +22,55 @@ pub struct BridgeCore {
     state: Arc<RwLock<BridgeState>>,
     repo: Repository,
     /// Shared mappings cache - updated on add/update/delete, used by worker
-    mappings_cache: Arc<RwLock<Vec<TopicMapping>>>,
+    mappings_cache: Arc<RwLock<CompiledMappings>>,
     worker: Arc<Mutex<BridgeWorker>>,
+    /// Endpoint configs the running worker threads were started with
+    running_mqtt: Arc<RwLock<Vec<MqttConfig>>>,
+    running_zmq: Arc<RwLock<Vec<ZmqConfig>>>,
+    /// Error from the last failed start, cleared on a successful start or stop
+    last_error: Arc<RwLock<Option<String>>>,
+    /// Coalesces bursts of reload requests
+    reload_debouncer: Debouncer,
+    /// Shared with the worker, so it can be switched while running
+    dry_run: Arc<DryRun>,
+    /// Connection state the worker threads report
+    connections: Arc<ConnectionTracker>,
+    config: BridgeConfig,
+    zmq_settings: ZmqSettings,
 }
 
 impl BridgeCore {
     /// Create a new bridge core
-    pub fn new(repo: Repository) -> Self {
+    pub fn new(repo: Repository, config: BridgeConfig) -> Self {
+        let worker = BridgeWorker::new();
+        let dry_run = worker.dry_run();
+        dry_run.set_enabled(config.dry_run);
+        let connections = worker.connections();
         Self {
             state: Arc::new(RwLock::new(BridgeState::Stopped)),
             repo,
-            mappings_cache: Arc::new(RwLock::new(vec![])),
-            worker: Arc::new(Mutex::new(BridgeWorker::new())),
+            mappings_cache: Arc::new(RwLock::new(CompiledMappings::default())),
+            worker: Arc::new(Mutex::new(worker)),
+            running_mqtt: Arc::new(RwLock::new(vec![])),
+            running_zmq: Arc::new(RwLock::new(vec![])),
+            last_error: Arc::new(RwLock::new(None)),
+            reload_debouncer: Debouncer::new(std::time::Duration::from_millis(config.reload_debounce_ms)),
+            dry_run,
+            connections,
+            config,
+            zmq_settings: ZmqSettings::default(),
         }
     }
 
-    /// Get current bridge status
+    /// Use `settings` for the ZMQ context of subsequent starts
+    pub fn with_zmq_settings(mut self, settings: ZmqSettings) -> Self {
+        self.zmq_settings = settings;
+        self
+    }
+
+    /// Get current bridge status; a running bridge in dry-run mode reports `DryRun`
     pub async fn get_status(&self) -> BridgeStatus {
-        let state = self.state.read().await.clone();
+        let mut state = self.state.read().await.clone();
         let start_time = self.repo.get_start_time().await.unwrap_or(0);
         let now = chrono::Utc::now().timestamp();
         let uptime = if start_time > 0 && state == BridgeState::Running {
@@ -41,13 +79,12 @@ impl BridgeCore {
             0
         };
 
-        // Determine connection statuses based on state
-        let (mqtt_status, zmq_status) = match state {
-            BridgeState::Running => (ConnectionStatus::Connected, ConnectionStatus::Connected),
-            BridgeState::Connecting => (ConnectionStatus::Connecting, ConnectionStatus::Connecting),
-            BridgeState::Error => (ConnectionStatus::Error, ConnectionStatus::Error),
-            BridgeState::Stopped => (ConnectionStatus::Disconnected, ConnectionStatus::Disconnected),
-        };
+        // Connected as long as any endpoint of the protocol is
+        let mqtt_status = self.connections.summary(&EndpointType::Mqtt);
+        let zmq_status = self.connections.summary(&EndpointType::Zmq);
+        if state == BridgeState::Running && self.dry_run.is_enabled() {
+            state = BridgeState::DryRun;
+        }
 
         BridgeStatus {
             state,
@@ -55,11 +92,18 @@ impl BridgeCore {
             mqtt_status,
             zmq_status,
             version: env!("CARGO_PKG_VERSION").to_string(),
+            last_error: self.last_error.read().await.clone(),
+            degraded: self.repo.write_health().is_degraded(),
+            degraded_reason: self.repo.write_health().degraded_reason(),
+            slow_consumer: self.worker.lock().is_slow_consumer(),
+            total_messages: None,
+            messages_per_second: None,
         }
     }
 
-    /// Start the bridge
-    pub async fn start(&self) -> Result<(), anyhow::Error> {
+    /// Start the bridge, zeroing the message counters and start time unless
+    /// `preserve_stats` is set
+    pub async fn start(&self, preserve_stats: bool) -> Result<(), anyhow::Error> {
         {
             let current_state = self.state.read().await;
             if *current_state == BridgeState::Running {
@@ -71,16 +115,43 @@ impl BridgeCore {
         info!("Starting bridge...");
         *self.state.write().await = BridgeState::Connecting;
 
+        match self.start_worker(preserve_stats).await {
+            Ok(()) => {
+                *self.last_error.write().await = None;
+                *self.state.write().await = BridgeState::Running;
+                info!("Bridge started successfully");
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Bridge failed to start: {}", e);
+                self.running_mqtt.write().await.clear();
+                self.running_zmq.write().await.clear();
+                *self.last_error.write().await = Some(e.to_string());
+                *self.state.write().await = BridgeState::Error;
+                Err(e)
+            }
+        }
+    }
+
+    /// Load configuration and start the worker threads
+    async fn start_worker(&self, preserve_stats: bool) -> Result<(), anyhow::Error> {
         // Load configurations - now supporting multiple configs
         let mqtt_configs = self.repo.get_mqtt_configs().await?;
         let zmq_configs = self.repo.get_zmq_configs().await?;
-        let mappings = self.repo.get_mappings().await?;
+        let mut mappings = self.repo.get_mappings().await?;
+        sort_mappings(&mut mappings, self.config.evaluation_strategy);
 
         // Initialize mappings cache
-        *self.mappings_cache.write().await = mappings;
+        *self.mappings_cache.write().await = mappings.into();
+
+        // Reset stats and record start time, unless a restart carries them over
+        if !preserve_stats {
+            let _ = self.repo.reset_stats().await;
+        }
 
-        // Reset stats and record start time
-        let _ = self.repo.reset_stats().await;
+        // Remember what the worker runs with, for diffing against the database
+        *self.running_mqtt.write().await = mqtt_configs.iter().filter(|c| c.enabled).cloned().collect();
+        *self.running_zmq.write().await = zmq_configs.iter().filter(|c| c.enabled).cloned().collect();
 
         // Start the worker with shared mappings cache
         {
@@ -89,13 +160,12 @@ impl BridgeCore {
                 mqtt_configs, 
                 zmq_configs, 
                 self.mappings_cache.clone(), 
-                self.repo.clone()
+                self.repo.clone(),
+                self.config.clone(),
+                &self.zmq_settings,
             )?;
         }
 
-        *self.state.write().await = BridgeState::Running;
-        info!("Bridge started successfully");
-
         Ok(())
     }
 
@@ -108,30 +178,225 @@ impl BridgeCore {
             worker.stop();
         }
 
+        self.running_mqtt.write().await.clear();
+        self.running_zmq.write().await.clear();
+        *self.last_error.write().await = None;
+
         *self.state.write().await = BridgeState::Stopped;
+        // Write the counters collected since the last flush
+        if let Err(e) = self.repo.flush_stats().await {
+            warn!("Failed to write message stats: {}", e);
+        }
         info!("Bridge stopped");
         Ok(())
     }
 
-    /// Restart the bridge
-    pub async fn restart(&self) -> Result<(), anyhow::Error> {
+    /// Restart the bridge, keeping the message counters if `preserve_stats` is set
+    pub async fn restart(&self, preserve_stats: bool) -> Result<(), anyhow::Error> {
         self.stop().await?;
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        self.start().await
+        self.start(preserve_stats).await
+    }
+
+    /// Reload mappings once changes settle for `reload_debounce_ms`
+    pub fn request_reload(&self) {
+        let bridge = self.clone();
+        self.reload_debouncer.trigger(move || async move {
+            if let Err(e) = bridge.reload_mappings().await {
+                warn!("Failed to reload mappings: {}", e);
+            }
+        });
+    }
+
+    /// Apply MQTT and ZMQ config changes from the database to the running
+    /// bridge: threads of removed or disabled endpoints stop, changed ones
+    /// restart with their new settings and added ones start, while the other
+    /// endpoints keep running. Publishes queued for a restarting endpoint are
+    /// delivered by its new thread.
+    pub async fn reload_endpoints(&self) -> Result<(), anyhow::Error> {
+        if *self.state.read().await != BridgeState::Running {
+            return Ok(());
+        }
+
+        let mqtt_configs: Vec<MqttConfig> = self
+            .repo
+            .get_mqtt_configs()
+            .await?
+            .into_iter()
+            .filter(|c| c.enabled)
+            .collect();
+        let zmq_configs: Vec<ZmqConfig> = self
+            .repo
+            .get_zmq_configs()
+            .await?
+            .into_iter()
+            .filter(|c| c.enabled)
+            .collect();
+
+        let mut running_mqtt = self.running_mqtt.write().await;
+        let mut running_zmq = self.running_zmq.write().await;
+        let mqtt = diff_by_id(running_mqtt.as_slice(), mqtt_configs.as_slice(), |c| c.id.unwrap_or(0));
+        let zmq = diff_by_id(running_zmq.as_slice(), zmq_configs.as_slice(), |c| c.id.unwrap_or(0));
+        if mqtt.is_empty() && zmq.is_empty() {
+            return Ok(());
+        }
+
+        // Stopped threads are joined once every lock is released
+        let mut stopping = Vec::new();
+        {
+            let mut worker = self.worker.lock();
+            for id in &mqtt.removed {
+                stopping.extend(worker.remove_endpoint(&EndpointType::Mqtt, *id));
+            }
+            for id in &zmq.removed {
+                stopping.extend(worker.remove_endpoint(&EndpointType::Zmq, *id));
+            }
+            for config in &mqtt_configs {
+                let id = config.id.unwrap_or(0);
+                if mqtt.changed.contains(&id) {
+                    stopping.extend(worker.stop_endpoint(&EndpointType::Mqtt, id));
+                }
+                if mqtt.changed.contains(&id) || mqtt.added.contains(&id) {
+                    worker.start_mqtt_endpoint(config);
+                }
+            }
+            for config in &zmq_configs {
+                let id = config.id.unwrap_or(0);
+                if zmq.changed.contains(&id) {
+                    stopping.extend(worker.stop_endpoint(&EndpointType::Zmq, id));
+                }
+                if zmq.changed.contains(&id) || zmq.added.contains(&id) {
+                    worker.start_zmq_endpoint(config);
+                }
+            }
+        }
+
+        info!(
+            "Endpoints reloaded: MQTT added {:?}, changed {:?}, removed {:?}; ZMQ added {:?}, changed {:?}, removed {:?}",
+            mqtt.added, mqtt.changed, mqtt.removed, zmq.added, zmq.changed, zmq.removed
+        );
+        *running_mqtt = mqtt_configs;
+        *running_zmq = zmq_configs;
+        drop(running_mqtt);
+        drop(running_zmq);
+
+        tokio::task::spawn_blocking(move || {
+            for thread in stopping {
+                thread.join();
+            }
+        })
+        .await?;
+        Ok(())
     }
 
     /// Reload topic mappings from database into cache and update subscriptions
     pub async fn reload_mappings(&self) -> Result<(), anyhow::Error> {
-        let mappings = self.repo.get_mappings().await?;
-        *self.mappings_cache.write().await = mappings.clone();
+        let mut mappings = self.repo.get_mappings().await?;
+        sort_mappings(&mut mappings, self.config.evaluation_strategy);
+        *self.mappings_cache.write().await = mappings.clone().into();
+
+        // Deleted mappings stop exporting their counters and lose their sampling state
+        let mapping_ids: Vec<u32> = mappings.iter().map(|m| m.id).collect();
+        metrics().retain_mapping_counters(&mapping_ids);
         
-        // Update MQTT subscriptions dynamically
-        {
-            let worker = self.worker.lock();
+        // Update MQTT subscriptions dynamically, and reconnect brokers whose
+        // mappings now need another protocol version
+        let running_mqtt = self.running_mqtt.read().await;
+        let stopping = {
+            let mut worker = self.worker.lock();
+            worker.retain_samples(&mapping_ids);
             worker.update_subscriptions(&mappings);
+            worker.restart_for_protocol(&running_mqtt, &mappings)
+        };
+        drop(running_mqtt);
+        if !stopping.is_empty() {
+            tokio::task::spawn_blocking(move || {
+                for thread in stopping {
+                    thread.join();
+                }
+            })
+            .await?;
         }
-        
+
         info!("Topic mappings reloaded into cache");
         Ok(())
     }
+
+    /// Mappings loaded in the running bridge, with their publish QoS and schedule state
+    pub async fn active_mappings(&self) -> Vec<ActiveMapping> {
+        let qos_report = self.worker.lock().qos_report();
+        let now = chrono::Utc::now();
+        let mappings = self.mappings_cache.read().await;
+        mappings
+            .iter()
+            .map(|mapping| ActiveMapping {
+                publish_qos_status: qos_report.get(mapping.id),
+                schedule_active: mappings.schedule_active(mapping, now),
+                mapping: mapping.clone(),
+            })
+            .collect()
+    }
+
+    /// Switch dry-run mode, also while running; enabling it starts a new report
+    pub fn set_dry_run(&self, enabled: bool) {
+        self.dry_run.set_enabled(enabled);
+        info!("Dry-run mode {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    /// What dry-run mode held back since it was last enabled
+    pub fn dry_run_report(&self) -> DryRunReport {
+        self.dry_run.report()
+    }
+
+    /// Connection state of each endpoint of the running bridge
+    pub fn endpoint_connections(&self) -> Vec<EndpointConnection> {
+        self.connections.snapshot()
+    }
+
+    /// Endpoint threads of the running worker
+    pub fn worker_threads(&self) -> Vec<WorkerThreadInfo> {
+        self.worker.lock().threads()
+    }
+
+    /// Messages waiting in the forwarding channel of the running worker
+    pub fn queue_depth(&self) -> u32 {
+        self.worker.lock().queue_depth()
+    }
+
+    /// Compare the running configuration against what is persisted in the database
+    pub async fn config_diff(&self) -> Result<ConfigDiff, anyhow::Error> {
+        let bridge_running = *self.state.read().await == BridgeState::Running;
+
+        let persisted_mqtt: Vec<MqttConfig> = self
+            .repo
+            .get_mqtt_configs()
+            .await?
+            .into_iter()
+            .filter(|c| c.enabled)
+            .collect();
+        let persisted_zmq: Vec<ZmqConfig> = self
+            .repo
+            .get_zmq_configs()
+            .await?
+            .into_iter()
+            .filter(|c| c.enabled)
+            .collect();
+        let persisted_mappings = self.repo.get_mappings().await?;
+
+        let running_mqtt = self.running_mqtt.read().await.clone();
+        let running_zmq = self.running_zmq.read().await.clone();
+        let running_mappings = self.mappings_cache.read().await.to_vec();
+
+        let mqtt = diff_by_id(running_mqtt.as_slice(), persisted_mqtt.as_slice(), |c| c.id.unwrap_or(0));
+        let zmq = diff_by_id(running_zmq.as_slice(), persisted_zmq.as_slice(), |c| c.id.unwrap_or(0));
+        let mappings = diff_by_id(running_mappings.as_slice(), persisted_mappings.as_slice(), |m| m.id);
+
+        Ok(ConfigDiff {
+            bridge_running,
+            in_sync: mqtt.is_empty() && zmq.is_empty() && mappings.is_empty(),
+            mqtt,
+            zmq,
+            mappings,
+        })
+    }
 }
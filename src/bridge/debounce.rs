@@ -0,0 +1,77 @@
+//! Coalescing of rapid repeated triggers into a single action
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Runs an action once no further trigger arrived within `window`
+#[derive(Clone)]
+pub struct Debouncer {
+    window: Duration,
+    generation: Arc<AtomicU64>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Schedule `action`; each call restarts the quiet period and supersedes earlier ones
+    pub fn trigger<F, Fut>(&self, action: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let scheduled = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if self.window.is_zero() {
+            tokio::spawn(action());
+            return;
+        }
+
+        let generation = self.generation.clone();
+        let window = self.window;
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            if generation.load(Ordering::SeqCst) == scheduled {
+                action().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_rapid_triggers_coalesce() {
+        let debouncer = Debouncer::new(Duration::from_millis(50));
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..10 {
+            let runs = runs.clone();
+            debouncer.trigger(move || async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // A later trigger after the quiet period runs again
+        let counter = runs.clone();
+        debouncer.trigger(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,196 @@
+//! Message deduplication that survives restarts
+//!
+//! A retained or republished message reaches the bridge again after a
+//! reconnect or restart. Mappings with a `dedup_key` record the key of each
+//! message they deliver in the database and drop later messages with the
+//! same key until `bridge.dedup_ttl_secs` has passed. A message that is
+//! filtered or fails delivery leaves no key, so a retry still gets through.
+//! This costs database access per message, so it only applies to mappings
+//! that opt in.
+
+use crate::config::BridgeConfig;
+use crate::db::Repository;
+use crate::models::TopicMapping;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Interval between prunes of expired and excess keys
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What identifies a message for deduplication
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupKey {
+    /// SHA-256 of topic and payload, e.g. `hash`
+    Hash,
+    /// Dotted path to an id in a JSON payload, e.g. `json:msg.id` or `json:$.msg.id`
+    JsonPath(String),
+}
+
+impl DedupKey {
+    /// Parse a dedup key spec as stored on a mapping
+    pub fn parse(spec: &str) -> Option<Self> {
+        if spec == "hash" {
+            return Some(DedupKey::Hash);
+        }
+        let path = spec.strip_prefix("json:")?;
+        let path = path.strip_prefix("$.").unwrap_or(path);
+        (!path.is_empty()).then(|| DedupKey::JsonPath(path.to_string()))
+    }
+
+    /// The key of a message; None if the payload has no id at the path
+    pub fn extract(&self, topic: &str, payload: &[u8]) -> Option<String> {
+        match self {
+            DedupKey::Hash => {
+                let mut hasher = Sha256::new();
+                hasher.update(topic.as_bytes());
+                // Keeps topic "a" + payload "bc" apart from topic "ab" + payload "c"
+                hasher.update([0]);
+                hasher.update(payload);
+                Some(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+            }
+            DedupKey::JsonPath(path) => {
+                let value: serde_json::Value = serde_json::from_slice(payload).ok()?;
+                match value.pointer(&format!("/{}", path.replace('.', "/")))? {
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    serde_json::Value::Null => None,
+                    other => Some(other.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Outcome of checking a message against the keys a mapping delivered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Seen {
+    /// The mapping has no dedup key or the message has none
+    Untracked,
+    /// Not delivered within the TTL; record the key once it is
+    First(String),
+    /// Delivered within the TTL
+    Repeat,
+}
+
+/// Whether `mapping` delivered this message within `ttl` of `now` (Unix
+/// seconds). Nothing is recorded; call `record_seen` once it is delivered.
+/// Database failures count as not seen.
+pub async fn first_seen(
+    repo: &Repository,
+    mapping: &TopicMapping,
+    ttl: Duration,
+    topic: &str,
+    payload: &[u8],
+    now: i64,
+) -> Seen {
+    let Some(key) = mapping.dedup_key.as_deref().and_then(DedupKey::parse) else {
+        return Seen::Untracked;
+    };
+    let Some(key) = key.extract(topic, payload) else {
+        return Seen::Untracked;
+    };
+    let cutoff = now - ttl.as_secs() as i64;
+    match repo.seen_since(mapping.id, &key, cutoff).await {
+        Ok(true) => Seen::Repeat,
+        Ok(false) => Seen::First(key),
+        Err(e) => {
+            warn!("Mapping {} could not check its dedup key, forwarding: {}", mapping.id, e);
+            Seen::First(key)
+        }
+    }
+}
+
+/// Record that `mapping` delivered the message with `key` at `now`
+pub async fn record_seen(repo: &Repository, mapping: &TopicMapping, key: &str, now: i64) {
+    if let Err(e) = repo.mark_seen(mapping.id, key, now, now).await {
+        warn!("Mapping {} could not record its dedup key: {}", mapping.id, e);
+    }
+}
+
+/// Prune keys older than `bridge.dedup_ttl_secs` and beyond `bridge.dedup_max_keys`
+pub fn spawn_dedup_prune_task(repo: Repository, settings: &BridgeConfig) -> JoinHandle<()> {
+    let ttl = settings.dedup_ttl_secs as i64;
+    let max_keys = settings.dedup_max_keys;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match repo.prune_seen_keys(chrono::Utc::now().timestamp() - ttl, max_keys).await {
+                Ok(0) => {}
+                Ok(pruned) => info!("Pruned {} dedup keys", pruned),
+                Err(e) => warn!("Dedup key pruning failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_memory_db;
+
+    #[test]
+    fn test_parse_and_extract() {
+        assert_eq!(DedupKey::parse("hash"), Some(DedupKey::Hash));
+        assert_eq!(DedupKey::parse("json:$.msg.id"), Some(DedupKey::JsonPath("msg.id".to_string())));
+        assert_eq!(DedupKey::parse("json:"), None);
+        assert_eq!(DedupKey::parse("topic:1"), None);
+
+        let key = DedupKey::JsonPath("msg.id".to_string());
+        assert_eq!(key.extract("t", br#"{"msg":{"id":42}}"#), Some("42".to_string()));
+        assert_eq!(key.extract("t", br#"{"msg":{}}"#), None);
+
+        let hash = DedupKey::Hash;
+        assert_eq!(hash.extract("a", b"bc"), hash.extract("a", b"bc"));
+        assert_ne!(hash.extract("a", b"bc"), hash.extract("ab", b"c"));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_survives_restart() {
+        let path = std::env::temp_dir().join(format!("zeromqtt-dedup-{}.db", std::process::id()));
+        let url = format!("sqlite:{}?mode=rwc", path.display());
+        let mapping = TopicMapping { id: 1, dedup_key: Some("json:id".to_string()), ..Default::default() };
+        let ttl = Duration::from_secs(60);
+        let now = 1_700_000_000;
+
+        {
+            let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+            crate::db::run_migrations(&pool).await.unwrap();
+            let repo = Repository::new(pool.clone());
+            let first = first_seen(&repo, &mapping, ttl, "t", br#"{"id":"a"}"#, now).await;
+            assert_eq!(first, Seen::First("a".to_string()));
+            // Not delivered yet, so a retry is not a repeat
+            assert_eq!(first_seen(&repo, &mapping, ttl, "t", br#"{"id":"a"}"#, now).await, first);
+            record_seen(&repo, &mapping, "a", now).await;
+            assert_eq!(first_seen(&repo, &mapping, ttl, "t", br#"{"id":"a"}"#, now + 1).await, Seen::Repeat);
+            pool.close().await;
+        }
+
+        // A new process sees the keys of the old one
+        let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+        let repo = Repository::new(pool.clone());
+        assert_eq!(first_seen(&repo, &mapping, ttl, "t", br#"{"id":"a"}"#, now + 30).await, Seen::Repeat);
+        assert_eq!(first_seen(&repo, &mapping, ttl, "t", br#"{"id":"b"}"#, now + 30).await, Seen::First("b".to_string()));
+        assert_eq!(first_seen(&repo, &mapping, ttl, "t", b"{}", now + 30).await, Seen::Untracked);
+        // Other mappings keep their own keys
+        let other = TopicMapping { id: 2, ..mapping.clone() };
+        assert_eq!(first_seen(&repo, &other, ttl, "t", br#"{"id":"a"}"#, now + 30).await, Seen::First("a".to_string()));
+        // Once the TTL has passed the key counts as new again
+        assert_eq!(first_seen(&repo, &mapping, ttl, "t", br#"{"id":"a"}"#, now + 61).await, Seen::First("a".to_string()));
+        pool.close().await;
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_prune_seen_keys() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        for (i, seen_at) in [100, 200, 300, 400].into_iter().enumerate() {
+            repo.mark_seen(1, &i.to_string(), seen_at, 0).await.unwrap();
+        }
+        // One expired, then the oldest of the rest beyond two
+        assert_eq!(repo.prune_seen_keys(150, 2).await.unwrap(), 2);
+        assert!(repo.mark_seen(1, "1", 500, 0).await.unwrap());
+        assert!(!repo.mark_seen(1, "3", 500, 0).await.unwrap());
+    }
+}
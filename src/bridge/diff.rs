@@ -0,0 +1,43 @@
+//! Comparison of the running bridge configuration against the database
+
+use crate::models::EntityDiff;
+
+/// Compare running and persisted records keyed by id
+pub fn diff_by_id<T: PartialEq>(running: &[T], persisted: &[T], id: impl Fn(&T) -> u32) -> EntityDiff {
+    let mut diff = EntityDiff::default();
+
+    for item in persisted {
+        match running.iter().find(|r| id(r) == id(item)) {
+            None => diff.added.push(id(item)),
+            Some(r) if r != item => diff.changed.push(id(item)),
+            Some(_) => {}
+        }
+    }
+
+    for item in running {
+        if !persisted.iter().any(|p| id(p) == id(item)) {
+            diff.removed.push(id(item));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_by_id() {
+        let running: Vec<(u32, &str)> = vec![(1, "a"), (2, "b"), (3, "c")];
+        let persisted: Vec<(u32, &str)> = vec![(1, "a"), (2, "B"), (4, "d")];
+
+        let diff = diff_by_id(&running, &persisted, |(id, _)| *id);
+        assert_eq!(diff.added, vec![4]);
+        assert_eq!(diff.removed, vec![3]);
+        assert_eq!(diff.changed, vec![2]);
+        assert!(!diff.is_empty());
+
+        assert!(diff_by_id(&running, &running, |(id, _)| *id).is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+//! Dry-run mode
+//!
+//! The bridge keeps receiving and runs every message through matching and
+//! transforms, but records each publish it would make instead of making it,
+//! so new mappings can be checked against live traffic.
+
+use crate::models::{DryRunReport, EndpointType, WouldForward, WouldForwardTarget};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Held back messages kept as samples in `DryRunReport`
+pub const DRY_RUN_SAMPLES: usize = 20;
+
+#[derive(Debug, Default)]
+struct Recorded {
+    since: Option<i64>,
+    would_forward: u64,
+    targets: Vec<WouldForwardTarget>,
+    /// Oldest first
+    samples: VecDeque<WouldForward>,
+}
+
+/// Dry-run switch and what it held back, shared by the bridge and its worker
+#[derive(Debug, Default)]
+pub struct DryRun {
+    enabled: AtomicBool,
+    recorded: Mutex<Recorded>,
+}
+
+impl DryRun {
+    pub fn new(enabled: bool) -> Self {
+        let dry_run = Self::default();
+        dry_run.set_enabled(enabled);
+        dry_run
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Switch dry-run mode; enabling it starts a new report
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut recorded = self.recorded.lock();
+        if enabled && !self.enabled.swap(true, Ordering::Relaxed) {
+            *recorded = Recorded {
+                since: Some(chrono::Utc::now().timestamp()),
+                ..Default::default()
+            };
+        } else if !enabled {
+            self.enabled.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Record a message that would have been published to a target
+    pub fn record(
+        &self,
+        mapping_id: u32,
+        endpoint_type: &EndpointType,
+        endpoint_id: u32,
+        source_topic: &str,
+        target_topic: &str,
+        payload_bytes: usize,
+    ) {
+        let mut recorded = self.recorded.lock();
+        recorded.would_forward += 1;
+        match recorded
+            .targets
+            .iter_mut()
+            .find(|t| t.endpoint_type == *endpoint_type && t.endpoint_id == endpoint_id)
+        {
+            Some(target) => target.messages += 1,
+            None => recorded.targets.push(WouldForwardTarget {
+                endpoint_type: endpoint_type.clone(),
+                endpoint_id,
+                messages: 1,
+            }),
+        }
+        if recorded.samples.len() == DRY_RUN_SAMPLES {
+            recorded.samples.pop_front();
+        }
+        recorded.samples.push_back(WouldForward {
+            timestamp: chrono::Utc::now().timestamp(),
+            mapping_id,
+            endpoint_type: endpoint_type.clone(),
+            endpoint_id,
+            source_topic: source_topic.to_string(),
+            target_topic: target_topic.to_string(),
+            payload_bytes,
+        });
+    }
+
+    /// Counts and samples since dry-run mode was last enabled; they stay
+    /// readable after it is disabled
+    pub fn report(&self) -> DryRunReport {
+        let recorded = self.recorded.lock();
+        DryRunReport {
+            enabled: self.is_enabled(),
+            since: recorded.since,
+            would_forward: recorded.would_forward,
+            targets: recorded.targets.clone(),
+            samples: recorded.samples.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabling_starts_a_new_report() {
+        let dry_run = DryRun::new(true);
+        for _ in 0..DRY_RUN_SAMPLES + 5 {
+            dry_run.record(1, &EndpointType::Zmq, 2, "in", "out", 3);
+        }
+        dry_run.record(1, &EndpointType::Mqtt, 2, "in", "last", 3);
+
+        let report = dry_run.report();
+        assert_eq!(report.would_forward, DRY_RUN_SAMPLES as u64 + 6);
+        assert_eq!(report.targets.len(), 2);
+        assert_eq!(report.samples.len(), DRY_RUN_SAMPLES);
+        assert_eq!(report.samples[0].target_topic, "last");
+
+        // Re-enabling while enabled keeps the report; disabling keeps it readable
+        dry_run.set_enabled(true);
+        dry_run.set_enabled(false);
+        let report = dry_run.report();
+        assert!(!report.enabled);
+        assert_eq!(report.would_forward, DRY_RUN_SAMPLES as u64 + 6);
+
+        dry_run.set_enabled(true);
+        assert_eq!(dry_run.report().would_forward, 0);
+    }
+}
@@ -0,0 +1,79 @@
+//! Endpoint configs as reported with the values the workers apply
+
+use crate::models::{EffectiveMqttConfig, EffectiveZmqConfig, MqttConfig, ZmqConfig};
+
+/// QoS used for MQTT subscriptions and publishes
+pub const MQTT_QOS: i32 = 1;
+/// Initial delay before an automatic MQTT reconnect
+pub const MQTT_RECONNECT_MIN_SECS: u64 = 1;
+/// Upper bound of the MQTT reconnect back-off
+pub const MQTT_RECONNECT_MAX_SECS: u64 = 30;
+
+/// An MQTT config as the worker applies it. The worker uses the stored
+/// config as is, so this only drops the values it ignores.
+fn resolve_mqtt_config(config: &MqttConfig) -> MqttConfig {
+    let mut resolved = config.clone();
+    // As `will_message` and `birth_message` treat them
+    resolved.will_topic = config.will_topic.clone().filter(|t| !t.is_empty());
+    resolved.birth_topic = config.birth_topic.clone().filter(|t| !t.is_empty());
+    resolved
+}
+
+/// Effective MQTT settings, including values the worker fixes internally.
+/// `client_id` is reported as stored; its placeholders are expanded on every
+/// start and an empty one leaves the id to the broker.
+pub fn effective_mqtt_config(config: &MqttConfig) -> EffectiveMqttConfig {
+    let config = resolve_mqtt_config(config);
+    let scheme = if config.use_tls { "ssl" } else { "tcp" };
+    EffectiveMqttConfig {
+        server_uri: format!("{}://{}:{}", scheme, config.broker_url, config.port),
+        qos: MQTT_QOS,
+        reconnect_min_seconds: MQTT_RECONNECT_MIN_SECS,
+        reconnect_max_seconds: MQTT_RECONNECT_MAX_SECS,
+        config,
+    }
+}
+
+/// Effective ZMQ settings, including values the worker fixes internally
+pub fn effective_zmq_config(config: &ZmqConfig) -> EffectiveZmqConfig {
+    EffectiveZmqConfig {
+        signing_enabled: config.hmac_key.is_some(),
+        curve_enabled: config.curve_server_key.is_some(),
+        config: config.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_mqtt_config() {
+        let config = MqttConfig {
+            id: Some(3),
+            broker_url: "broker.local".to_string(),
+            client_id: "".to_string(),
+            will_topic: Some("".to_string()),
+            ..Default::default()
+        };
+        let effective = effective_mqtt_config(&config);
+        assert_eq!(effective.server_uri, "tcp://broker.local:1883");
+        // Reported as the worker uses it, not replaced with a default
+        assert_eq!(effective.config.client_id, "");
+        assert_eq!(effective.config.will_topic, None);
+        assert_eq!(effective.qos, MQTT_QOS);
+    }
+
+    #[test]
+    fn test_effective_zmq_config() {
+        let config = ZmqConfig {
+            connect_endpoints: vec!["tcp://a:1".to_string()],
+            hmac_key: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let effective = effective_zmq_config(&config);
+        assert_eq!(effective.config.connect_endpoints, vec!["tcp://a:1".to_string()]);
+        assert!(effective.signing_enabled);
+        assert!(!effective.curve_enabled);
+    }
+}
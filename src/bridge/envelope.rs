@@ -0,0 +1,80 @@
+//! Carrying the source ZMQ topic when publishing to a fixed MQTT topic
+//!
+//! A mapping may keep the original ZMQ topic as metadata: an MQTT v5 user
+//! property (`property:name`) or a field of a JSON envelope around the
+//! payload (`json:field`) for brokers spoken to over MQTT 3.
+
+use serde_json::{Map, Value};
+
+/// Envelope field holding the original payload
+pub const ENVELOPE_PAYLOAD_FIELD: &str = "payload";
+
+/// Where a mapping puts the source topic
+#[derive(Debug, Clone, PartialEq)]
+pub enum TopicCarrier {
+    /// MQTT v5 user property, e.g. `property:zmq-topic`
+    Property(String),
+    /// Field of a JSON envelope, e.g. `json:zmq_topic`
+    JsonField(String),
+}
+
+impl TopicCarrier {
+    /// Parse a topic carrier spec as stored on a mapping
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(name) = spec.strip_prefix("property:") {
+            return (!name.is_empty()).then(|| TopicCarrier::Property(name.to_string()));
+        }
+        let field = spec.strip_prefix("json:")?;
+        (!field.is_empty() && field != ENVELOPE_PAYLOAD_FIELD).then(|| TopicCarrier::JsonField(field.to_string()))
+    }
+}
+
+/// Wrap a payload as `{"<field>": topic, "payload": ...}`. JSON payloads are
+/// embedded as values when `embed_json` allows it, anything else as a string.
+pub fn wrap_envelope(field: &str, topic: &str, payload: &[u8], embed_json: bool) -> Vec<u8> {
+    let payload = embed_json
+        .then(|| serde_json::from_slice::<Value>(payload).ok())
+        .flatten()
+        .unwrap_or_else(|| Value::String(String::from_utf8_lossy(payload).into_owned()));
+
+    let mut envelope = Map::new();
+    envelope.insert(field.to_string(), Value::String(topic.to_string()));
+    envelope.insert(ENVELOPE_PAYLOAD_FIELD.to_string(), payload);
+    serde_json::to_vec(&envelope).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_carrier() {
+        assert_eq!(
+            TopicCarrier::parse("property:zmq-topic"),
+            Some(TopicCarrier::Property("zmq-topic".to_string()))
+        );
+        assert_eq!(
+            TopicCarrier::parse("json:zmq_topic"),
+            Some(TopicCarrier::JsonField("zmq_topic".to_string()))
+        );
+        assert_eq!(TopicCarrier::parse("json:payload"), None);
+        assert_eq!(TopicCarrier::parse("property:"), None);
+        assert_eq!(TopicCarrier::parse("topic:1"), None);
+    }
+
+    #[test]
+    fn test_wrap_envelope() {
+        let wrapped = wrap_envelope("zmq_topic", "sensors/a", br#"{"t":21.5}"#, true);
+        let value: Value = serde_json::from_slice(&wrapped).unwrap();
+        assert_eq!(value["zmq_topic"], "sensors/a");
+        assert_eq!(value["payload"]["t"], 21.5);
+
+        let wrapped = wrap_envelope("zmq_topic", "sensors/a", b"21.5 C", true);
+        let value: Value = serde_json::from_slice(&wrapped).unwrap();
+        assert_eq!(value["payload"], "21.5 C");
+
+        let wrapped = wrap_envelope("zmq_topic", "sensors/a", b"[1]", false);
+        let value: Value = serde_json::from_slice(&wrapped).unwrap();
+        assert_eq!(value["payload"], "[1]");
+    }
+}
@@ -0,0 +1,227 @@
+//! Payload transforms through external commands
+//!
+//! A mapping may pipe payloads through a long-running sidecar process: each
+//! payload is written to its stdin as one line, and the line it writes back
+//! replaces the payload. Processes are pooled per command so none is spawned
+//! per message, and one that writes more lines than it was sent is replaced
+//! so its extra output is never taken for the next payload's. Running
+//! arbitrary programs is opt-in via `bridge.exec_transforms`.
+
+use crate::config::BridgeConfig;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::Semaphore;
+
+/// Why a payload could not be transformed
+#[derive(Debug, thiserror::Error)]
+pub enum ExecError {
+    #[error("empty transform command")]
+    EmptyCommand,
+    #[error("failed to start '{0}': {1}")]
+    Spawn(String, std::io::Error),
+    #[error("payload contains a newline")]
+    MultiLinePayload,
+    #[error("transform timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("transform exited with {0}")]
+    Exited(ExitStatus),
+    #[error("transform I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A running transform process
+struct Sidecar {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Sidecar {
+    /// Start `command`: a program path followed by whitespace-separated
+    /// arguments, run without a shell
+    fn spawn(command: &str) -> Result<Self, ExecError> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or(ExecError::EmptyCommand)?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ExecError::Spawn(command.to_string(), e))?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(ExecError::Spawn(command.to_string(), std::io::ErrorKind::BrokenPipe.into()));
+        };
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout),
+        })
+    }
+
+    /// Whether the process has written nothing beyond its last answer and is
+    /// still running. Anything else would be read as the next answer.
+    fn in_sync(&mut self) -> bool {
+        let mut cx = Context::from_waker(Waker::noop());
+        matches!(Pin::new(&mut self.stdout).poll_fill_buf(&mut cx), Poll::Pending)
+    }
+
+    /// Send one payload line and read the transformed line back
+    async fn exchange(&mut self, payload: &[u8]) -> Result<Vec<u8>, ExecError> {
+        let mut line = Vec::new();
+        let read = async {
+            self.stdin.write_all(payload).await?;
+            self.stdin.write_all(b"\n").await?;
+            self.stdin.flush().await?;
+            self.stdout.read_until(b'\n', &mut line).await
+        };
+        match read.await {
+            Ok(0) => Err(ExecError::Exited(self.child.wait().await?)),
+            Ok(_) => {
+                if line.last() == Some(&b'\n') {
+                    line.pop();
+                }
+                Ok(line)
+            }
+            // A process that already exited reports its status rather than a broken pipe
+            Err(e) => match self.child.try_wait() {
+                Ok(Some(status)) => Err(ExecError::Exited(status)),
+                _ => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// Idle processes of one command and the permits bounding how many run
+struct Pool {
+    idle: Mutex<Vec<Sidecar>>,
+    permits: Semaphore,
+}
+
+/// Pools of transform processes keyed by command
+pub struct ExecTransformer {
+    timeout: Duration,
+    pool_size: usize,
+    pools: Mutex<HashMap<String, Arc<Pool>>>,
+}
+
+impl ExecTransformer {
+    pub fn new(timeout: Duration, pool_size: usize) -> Self {
+        Self {
+            timeout,
+            pool_size: pool_size.max(1),
+            pools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A transformer if `bridge.exec_transforms` is enabled
+    pub fn from_settings(settings: &BridgeConfig) -> Option<Self> {
+        settings
+            .exec_transforms
+            .then(|| Self::new(Duration::from_millis(settings.exec_timeout_ms), settings.exec_pool_size))
+    }
+
+    /// Pipe `payload` through `command`. At most `pool_size` payloads per
+    /// command are in flight; a process that times out or fails is killed
+    /// and replaced on the next call.
+    pub async fn transform(&self, command: &str, payload: &[u8]) -> Result<Vec<u8>, ExecError> {
+        if payload.contains(&b'\n') {
+            return Err(ExecError::MultiLinePayload);
+        }
+        let pool = self
+            .pools
+            .lock()
+            .entry(command.to_string())
+            .or_insert_with(|| {
+                Arc::new(Pool {
+                    idle: Mutex::new(Vec::new()),
+                    permits: Semaphore::new(self.pool_size),
+                })
+            })
+            .clone();
+
+        let _permit = pool
+            .permits
+            .acquire()
+            .await
+            .map_err(|_| std::io::Error::other("transform pool closed"))?;
+        let idle = pool.idle.lock().pop();
+        let mut sidecar = match idle {
+            Some(mut sidecar) => {
+                if sidecar.in_sync() {
+                    sidecar
+                } else {
+                    tracing::warn!("Transform '{}' wrote unexpected output or exited; restarting it", command);
+                    Sidecar::spawn(command)?
+                }
+            }
+            None => Sidecar::spawn(command)?,
+        };
+
+        match tokio::time::timeout(self.timeout, sidecar.exchange(payload)).await {
+            Ok(Ok(output)) => {
+                if sidecar.in_sync() {
+                    pool.idle.lock().push(sidecar);
+                } else {
+                    tracing::warn!("Transform '{}' wrote more than one line per payload; restarting it", command);
+                }
+                Ok(output)
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(ExecError::Timeout(self.timeout)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_echo_and_reuse() {
+        let exec = ExecTransformer::new(Duration::from_secs(5), 1);
+        assert_eq!(exec.transform("cat", b"hello").await.unwrap(), b"hello");
+        // The single pooled process serves the next payload too
+        assert_eq!(exec.transform("cat", b"again").await.unwrap(), b"again");
+        assert_eq!(exec.pools.lock()["cat"].idle.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transform_command_with_arguments() {
+        let exec = ExecTransformer::new(Duration::from_secs(5), 2);
+        let output = exec.transform("sed -u s/celsius/kelvin/", br#"{"unit":"celsius"}"#).await.unwrap();
+        assert_eq!(output, br#"{"unit":"kelvin"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_failures() {
+        let exec = ExecTransformer::new(Duration::from_millis(200), 1);
+        assert!(matches!(exec.transform("sleep 5", b"x").await, Err(ExecError::Timeout(_))));
+        assert!(exec.transform("false", b"x").await.is_err());
+        assert!(matches!(
+            exec.transform("/nonexistent/transform", b"x").await,
+            Err(ExecError::Spawn(..))
+        ));
+        assert!(matches!(exec.transform("cat", b"a\nb").await, Err(ExecError::MultiLinePayload)));
+        // Timed-out processes are not returned to the pool
+        assert!(exec.pools.lock()["sleep 5"].idle.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extra_output_restarts_sidecar() {
+        let exec = ExecTransformer::new(Duration::from_secs(5), 1);
+        // Answers every line twice, so its second line must not answer the next payload
+        let command = "sed -u p";
+        assert_eq!(exec.transform(command, b"first").await.unwrap(), b"first");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(exec.transform(command, b"second").await.unwrap(), b"second");
+    }
+}
@@ -0,0 +1,94 @@
+//! Periodic endpoint liveness checks
+//!
+//! Message counters only move while traffic flows, so an idle endpoint whose
+//! broker or peer went away looks the same as a quiet healthy one. Each
+//! endpoint thread runs a check at a fixed interval instead and records the
+//! outcome, independent of message flow.
+
+use crate::config::BridgeConfig;
+use crate::models::EndpointType;
+use crate::telemetry::metrics;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Schedules the liveness checks of one endpoint
+#[derive(Debug)]
+pub struct HealthCheck {
+    endpoint_type: EndpointType,
+    endpoint_id: u32,
+    interval: Duration,
+    next: Option<Instant>,
+    healthy: Option<bool>,
+}
+
+impl HealthCheck {
+    /// Check `endpoint_id` every `interval`, starting with the first poll
+    pub fn new(endpoint_type: EndpointType, endpoint_id: u32, interval: Duration) -> Self {
+        Self {
+            endpoint_type,
+            endpoint_id,
+            interval,
+            next: None,
+            healthy: None,
+        }
+    }
+
+    pub fn from_settings(settings: &BridgeConfig, endpoint_type: EndpointType, endpoint_id: u32) -> Self {
+        Self::new(
+            endpoint_type,
+            endpoint_id,
+            Duration::from_secs(settings.health_check_interval_secs.max(1)),
+        )
+    }
+
+    /// Run `check` if a check is due at `now` and record its outcome.
+    /// Returns the outcome, or None if no check was due.
+    pub fn poll(&mut self, now: Instant, check: impl FnOnce() -> bool) -> Option<bool> {
+        if self.next.is_some_and(|next| now < next) {
+            return None;
+        }
+        self.next = Some(now + self.interval);
+
+        let healthy = check();
+        metrics().record_endpoint_health(&self.endpoint_type, self.endpoint_id, healthy);
+        if self.healthy.replace(healthy) != Some(healthy) {
+            if healthy {
+                info!("{:?} endpoint {} is healthy", self.endpoint_type, self.endpoint_id);
+            } else {
+                warn!("{:?} endpoint {} failed its health check", self.endpoint_type, self.endpoint_id);
+            }
+        }
+        Some(healthy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(endpoint_id: u32) -> Option<bool> {
+        metrics()
+            .endpoint_health()
+            .into_iter()
+            .find(|h| h.endpoint_type == EndpointType::Zmq && h.endpoint_id == endpoint_id)
+            .map(|h| h.healthy)
+    }
+
+    #[test]
+    fn test_checker_updates_status() {
+        // An id no other test records health for
+        let id = 90_001;
+        let mut check = HealthCheck::new(EndpointType::Zmq, id, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert_eq!(check.poll(start, || false), Some(false));
+        assert_eq!(recorded(id), Some(false));
+
+        // Not due yet: the check does not run and the status stands
+        assert_eq!(check.poll(start + Duration::from_secs(5), || panic!("not due")), None);
+        assert_eq!(recorded(id), Some(false));
+
+        assert_eq!(check.poll(start + Duration::from_secs(10), || true), Some(true));
+        assert_eq!(recorded(id), Some(true));
+    }
+}
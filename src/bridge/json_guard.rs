@@ -0,0 +1,102 @@
+//! Size and nesting limits checked before a payload is parsed as JSON
+//!
+//! The depth scan is a single pass over the bytes that tracks brackets
+//! outside of strings, so oversized or deeply nested input is rejected
+//! without building a value.
+
+use crate::config::BridgeConfig;
+
+/// Why a payload was refused
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum JsonLimitError {
+    #[error("payload of {0} bytes exceeds the JSON size limit")]
+    TooLarge(usize),
+    #[error("JSON nesting exceeds depth {0}")]
+    TooDeep(usize),
+}
+
+/// Limits for payloads parsed as JSON; 0 disables a limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JsonLimits {
+    pub max_depth: usize,
+    pub max_bytes: usize,
+}
+
+impl JsonLimits {
+    pub fn from_settings(settings: &BridgeConfig) -> Self {
+        Self {
+            max_depth: settings.json_max_depth,
+            max_bytes: settings.json_max_bytes,
+        }
+    }
+
+    /// Check a payload before it is handed to a JSON parser
+    pub fn check(&self, payload: &[u8]) -> Result<(), JsonLimitError> {
+        if self.max_bytes > 0 && payload.len() > self.max_bytes {
+            return Err(JsonLimitError::TooLarge(payload.len()));
+        }
+        if self.max_depth == 0 {
+            return Ok(());
+        }
+
+        let mut depth = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+        for &b in payload {
+            if in_string {
+                match b {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match b {
+                b'"' => in_string = true,
+                b'{' | b'[' => {
+                    depth += 1;
+                    if depth > self.max_depth {
+                        return Err(JsonLimitError::TooDeep(self.max_depth));
+                    }
+                }
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: JsonLimits = JsonLimits { max_depth: 4, max_bytes: 64 };
+
+    #[test]
+    fn test_within_limits() {
+        assert_eq!(LIMITS.check(br#"{"a":{"b":[1,{"c":2}]}}"#), Ok(()));
+    }
+
+    #[test]
+    fn test_deeply_nested_rejected() {
+        let nested = format!("{}{}", "[".repeat(5), "]".repeat(5));
+        assert_eq!(LIMITS.check(nested.as_bytes()), Err(JsonLimitError::TooDeep(4)));
+
+        let unlimited = JsonLimits { max_depth: 0, max_bytes: 0 };
+        let deep = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+        assert_eq!(unlimited.check(deep.as_bytes()), Ok(()));
+    }
+
+    #[test]
+    fn test_brackets_in_strings_ignored() {
+        assert_eq!(LIMITS.check(br#"{"s":"[[[[[[\"{{{{"}"#), Ok(()));
+    }
+
+    #[test]
+    fn test_oversized_rejected() {
+        let big = format!(r#"{{"data":"{}"}}"#, "x".repeat(100));
+        assert_eq!(LIMITS.check(big.as_bytes()), Err(JsonLimitError::TooLarge(big.len())));
+    }
+}
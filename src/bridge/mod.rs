@@ -1,9 +1,55 @@
 //! Bridge module
 
+pub mod backpressure;
+pub mod batch;
+pub mod connections;
+pub mod convert;
 pub mod core;
+pub mod debounce;
+pub mod dedup;
+pub mod diff;
+pub mod dryrun;
+pub mod effective;
+pub mod envelope;
+pub mod exec;
+pub mod health;
+pub mod json_guard;
+pub mod ordering;
+pub mod qos;
+pub mod reconnect;
+pub mod rewrite;
+pub mod sampling;
+pub mod schedule;
+pub mod template;
+pub mod threads;
+pub mod timestamp;
 pub mod topic_mapper;
+pub mod wal;
 pub mod worker;
 
+pub use backpressure::*;
+pub use batch::*;
+pub use connections::*;
+pub use convert::*;
 pub use core::*;
+pub use debounce::*;
+pub use dedup::*;
+pub use diff::*;
+pub use dryrun::*;
+pub use effective::*;
+pub use envelope::*;
+pub use exec::*;
+pub use health::*;
+pub use json_guard::*;
+pub use ordering::*;
+pub use qos::*;
+pub use reconnect::*;
+pub use rewrite::*;
+pub use sampling::*;
+pub use schedule::*;
+pub use template::*;
+pub use threads::*;
+pub use timestamp::*;
 pub use topic_mapper::*;
+pub use wal::*;
 pub use worker::*;
@@ -0,0 +1,200 @@
+//! Ordering keys and sharded, order-preserving forwarding queues
+//!
+//! Messages that share an ordering key are always handled by the same shard,
+//! so they stay strictly ordered, while different keys are forwarded concurrently.
+
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Where a mapping takes its ordering key from
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderingKey {
+    /// N-th wildcard capture of the source pattern (1-based), e.g. `topic:1`
+    TopicCapture(usize),
+    /// Dotted path into a JSON payload, e.g. `json:device.id` or `json:$.device.id`
+    JsonPath(String),
+}
+
+impl OrderingKey {
+    /// Parse an ordering key spec as stored on a mapping
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(index) = spec.strip_prefix("topic:") {
+            return index
+                .parse::<usize>()
+                .ok()
+                .filter(|i| *i > 0)
+                .map(OrderingKey::TopicCapture);
+        }
+        if let Some(path) = spec.strip_prefix("json:") {
+            let path = path.strip_prefix("$.").unwrap_or(path);
+            if !path.is_empty() {
+                return Some(OrderingKey::JsonPath(path.to_string()));
+            }
+        }
+        None
+    }
+
+    /// Extract the key for a message matched by `pattern`
+    pub fn extract(&self, pattern: &str, topic: &str, payload: &[u8]) -> Option<String> {
+        match self {
+            OrderingKey::TopicCapture(index) => {
+                wildcard_captures(pattern, topic).into_iter().nth(index - 1)
+            }
+            OrderingKey::JsonPath(path) => {
+                let value: serde_json::Value = serde_json::from_slice(payload).ok()?;
+                let pointer = format!("/{}", path.replace('.', "/"));
+                match value.pointer(&pointer)? {
+                    serde_json::Value::String(s) => Some(s.clone()),
+                    serde_json::Value::Null => None,
+                    other => Some(other.to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Collect the topic segments matched by `+` and `#` wildcards in `pattern`
+pub fn wildcard_captures(pattern: &str, topic: &str) -> Vec<String> {
+    let topic_parts: Vec<&str> = topic.split('/').collect();
+    let mut captures = Vec::new();
+
+    for (i, p) in pattern.split('/').enumerate() {
+        if p == "+" {
+            if let Some(t) = topic_parts.get(i) {
+                captures.push(t.to_string());
+            }
+        } else if p == "#" {
+            if i < topic_parts.len() {
+                captures.push(topic_parts[i..].join("/"));
+            }
+            break;
+        }
+    }
+
+    captures
+}
+
+/// Pick the shard for a key
+pub fn shard_for(key: &str, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % shards.max(1) as u64) as usize
+}
+
+/// A pool of FIFO queues, each drained by its own task
+pub struct ShardedQueue<T> {
+    senders: Vec<mpsc::Sender<T>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> ShardedQueue<T> {
+    /// Spawn `shards` consumer tasks that run `handler` for every item in order
+    pub fn new<F, Fut>(shards: usize, capacity: usize, handler: F) -> Self
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let mut senders = Vec::with_capacity(shards);
+        let mut handles = Vec::with_capacity(shards);
+
+        for _ in 0..shards.max(1) {
+            let (tx, mut rx) = mpsc::channel::<T>(capacity);
+            let handler = handler.clone();
+            handles.push(tokio::spawn(async move {
+                while let Some(item) = rx.recv().await {
+                    handler(item).await;
+                }
+            }));
+            senders.push(tx);
+        }
+
+        Self { senders, handles }
+    }
+
+    /// Number of shards
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// Queue an item on the shard owning `key`
+    pub async fn dispatch(&self, key: &str, item: T) {
+        let shard = shard_for(key, self.senders.len());
+        let _ = self.senders[shard].send(item).await;
+    }
+
+    /// Stop accepting items and wait for every shard to drain
+    pub async fn close(self) {
+        drop(self.senders);
+        for handle in self.handles {
+            let _ = handle.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    #[test]
+    fn test_parse_ordering_key() {
+        assert_eq!(OrderingKey::parse("topic:1"), Some(OrderingKey::TopicCapture(1)));
+        assert_eq!(
+            OrderingKey::parse("json:$.device.id"),
+            Some(OrderingKey::JsonPath("device.id".to_string()))
+        );
+        assert_eq!(OrderingKey::parse("topic:0"), None);
+        assert_eq!(OrderingKey::parse("payload"), None);
+    }
+
+    #[test]
+    fn test_extract_ordering_key() {
+        let key = OrderingKey::TopicCapture(1);
+        assert_eq!(key.extract("sensors/+/temp", "sensors/d42/temp", b""), Some("d42".to_string()));
+
+        let key = OrderingKey::JsonPath("device.id".to_string());
+        assert_eq!(
+            key.extract("#", "any", br#"{"device":{"id":"d7"}}"#),
+            Some("d7".to_string())
+        );
+        assert_eq!(key.extract("#", "any", b"not json"), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_per_key_ordering_under_concurrency() {
+        let seen: Arc<Mutex<Vec<(String, u32)>>> = Arc::new(Mutex::new(Vec::new()));
+        let seen_handler = seen.clone();
+
+        let queue = ShardedQueue::new(4, 16, move |(key, seq): (String, u32)| {
+            let seen = seen_handler.clone();
+            async move {
+                let jitter = rand::random::<u64>() % 3;
+                tokio::time::sleep(std::time::Duration::from_millis(jitter)).await;
+                seen.lock().push((key, seq));
+            }
+        });
+
+        for seq in 0..200u32 {
+            let key = format!("device-{}", seq % 7);
+            queue.dispatch(&key, (key.clone(), seq)).await;
+        }
+        queue.close().await;
+
+        let seen = seen.lock();
+        assert_eq!(seen.len(), 200);
+        for device in 0..7 {
+            let key = format!("device-{}", device);
+            let order: Vec<u32> = seen.iter().filter(|(k, _)| *k == key).map(|(_, s)| *s).collect();
+            assert!(order.windows(2).all(|w| w[0] < w[1]), "{} out of order: {:?}", key, order);
+        }
+    }
+}
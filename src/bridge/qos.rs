@@ -0,0 +1,91 @@
+//! Requested vs effective MQTT publish QoS per mapping
+//!
+//! A v5 broker advertises its highest supported QoS in CONNACK and may reject
+//! publishes above it; v3 brokers accept any QoS. Mismatches are recorded so
+//! brokers that downgrade can be spotted from the API and metrics.
+
+use crate::models::QosStatus;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+/// Per-mapping publish QoS outcomes, shared between MQTT threads and the API
+#[derive(Debug, Default)]
+pub struct QosReport {
+    entries: Mutex<HashMap<u32, QosStatus>>,
+}
+
+impl QosReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the QoS a publish for `mapping_id` was delivered at
+    pub fn record(&self, mapping_id: u32, requested: i32, effective: i32) {
+        let mut entries = self.entries.lock();
+        let status = entries.entry(mapping_id).or_default();
+        status.requested_qos = requested;
+        status.effective_qos = effective;
+        if effective < requested {
+            status.downgrades += 1;
+        }
+    }
+
+    pub fn get(&self, mapping_id: u32) -> Option<QosStatus> {
+        self.entries.lock().get(&mapping_id).cloned()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+/// QoS to publish at, given the broker's advertised maximum
+pub fn effective_qos(requested: i32, broker_max: Option<i32>) -> i32 {
+    requested.min(broker_max.unwrap_or(2)).max(0)
+}
+
+/// QoS a publish was actually delivered at, judged from its delivery token
+///
+/// A completed token means the broker acknowledged the QoS it was sent with.
+/// A "QoS not supported" rejection yields 0: the publish is retried at QoS 0.
+pub fn delivered_qos(sent: i32, result: &Result<(), paho_mqtt::Error>) -> Option<i32> {
+    match result {
+        Ok(()) => Some(sent),
+        Err(paho_mqtt::Error::ReasonCode(paho_mqtt::ReasonCode::QosNotSupported)) => Some(0),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_qos() {
+        assert_eq!(effective_qos(2, None), 2);
+        assert_eq!(effective_qos(2, Some(1)), 1);
+        assert_eq!(effective_qos(1, Some(0)), 0);
+        assert_eq!(effective_qos(0, Some(1)), 0);
+    }
+
+    #[test]
+    fn test_delivered_qos_from_token() {
+        assert_eq!(delivered_qos(1, &Ok(())), Some(1));
+        let rejected = Err(paho_mqtt::Error::ReasonCode(paho_mqtt::ReasonCode::QosNotSupported));
+        assert_eq!(delivered_qos(2, &rejected), Some(0));
+        assert_eq!(delivered_qos(1, &Err(paho_mqtt::Error::General("timeout"))), None);
+    }
+
+    #[test]
+    fn test_report_counts_downgrades() {
+        let report = QosReport::new();
+        report.record(7, 2, 2);
+        report.record(7, 2, 1);
+        report.record(7, 2, 1);
+        assert_eq!(
+            report.get(7),
+            Some(QosStatus { requested_qos: 2, effective_qos: 1, downgrades: 2 })
+        );
+        assert_eq!(report.get(8), None);
+    }
+}
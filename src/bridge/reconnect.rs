@@ -0,0 +1,163 @@
+//! Reconnect storm limiting for MQTT brokers
+//!
+//! Reconnect attempts are counted per endpoint and across all endpoints
+//! within a sliding window. Once either cap is hit the endpoint is held off
+//! for a backoff that doubles on every consecutive throttle.
+
+use crate::config::BridgeConfig;
+use crate::telemetry::metrics;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Upper bound for the doubling throttle backoff
+const MAX_THROTTLE: Duration = Duration::from_secs(600);
+
+/// Outcome of asking to reconnect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReconnectDecision {
+    Proceed,
+    /// Hold off for this long before asking again
+    Throttled(Duration),
+}
+
+#[derive(Debug, Default)]
+struct EndpointAttempts {
+    attempts: VecDeque<Instant>,
+    throttled_until: Option<Instant>,
+    strikes: u32,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    global: VecDeque<Instant>,
+    endpoints: HashMap<u32, EndpointAttempts>,
+}
+
+/// Caps reconnect attempts per endpoint and globally, shared by MQTT threads
+#[derive(Debug)]
+pub struct ReconnectLimiter {
+    window: Duration,
+    max_per_endpoint: usize,
+    max_global: usize,
+    throttle: Duration,
+    state: Mutex<LimiterState>,
+}
+
+impl ReconnectLimiter {
+    pub fn new(window: Duration, max_per_endpoint: usize, max_global: usize, throttle: Duration) -> Self {
+        Self {
+            window,
+            max_per_endpoint,
+            max_global,
+            throttle,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    pub fn from_settings(settings: &BridgeConfig) -> Self {
+        Self::new(
+            Duration::from_secs(settings.reconnect_window_secs),
+            settings.reconnect_max_per_endpoint,
+            settings.reconnect_max_global,
+            Duration::from_secs(settings.reconnect_throttle_secs),
+        )
+    }
+
+    /// Ask to make a reconnect attempt for `endpoint_id`; a cap of 0 is unlimited
+    pub fn attempt(&self, endpoint_id: u32, now: Instant) -> ReconnectDecision {
+        let mut state = self.state.lock();
+        let LimiterState { global, endpoints } = &mut *state;
+        let endpoint = endpoints.entry(endpoint_id).or_default();
+
+        if let Some(until) = endpoint.throttled_until.filter(|until| *until > now) {
+            return ReconnectDecision::Throttled(until - now);
+        }
+
+        prune(global, now, self.window);
+        prune(&mut endpoint.attempts, now, self.window);
+
+        let over_endpoint = self.max_per_endpoint > 0 && endpoint.attempts.len() >= self.max_per_endpoint;
+        let over_global = self.max_global > 0 && global.len() >= self.max_global;
+        if over_endpoint || over_global {
+            endpoint.strikes += 1;
+            let backoff = self
+                .throttle
+                .saturating_mul(1 << (endpoint.strikes - 1).min(16))
+                .min(MAX_THROTTLE);
+            endpoint.throttled_until = Some(now + backoff);
+            metrics().record_reconnect_throttled();
+            return ReconnectDecision::Throttled(backoff);
+        }
+
+        endpoint.throttled_until = None;
+        endpoint.attempts.push_back(now);
+        global.push_back(now);
+        ReconnectDecision::Proceed
+    }
+
+    /// Reset the throttle backoff once an endpoint is connected again
+    pub fn record_connected(&self, endpoint_id: u32) {
+        if let Some(endpoint) = self.state.lock().endpoints.get_mut(&endpoint_id) {
+            endpoint.strikes = 0;
+            endpoint.throttled_until = None;
+        }
+    }
+}
+
+fn prune(attempts: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while attempts.front().is_some_and(|t| now.duration_since(*t) >= window) {
+        attempts.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_endpoint: usize, global: usize) -> ReconnectLimiter {
+        ReconnectLimiter::new(Duration::from_secs(60), per_endpoint, global, Duration::from_secs(10))
+    }
+
+    #[test]
+    fn test_endpoint_cap_throttles_with_doubling_backoff() {
+        let limiter = limiter(2, 0);
+        let start = Instant::now();
+
+        assert_eq!(limiter.attempt(1, start), ReconnectDecision::Proceed);
+        assert_eq!(limiter.attempt(1, start), ReconnectDecision::Proceed);
+        assert_eq!(limiter.attempt(1, start), ReconnectDecision::Throttled(Duration::from_secs(10)));
+        assert_eq!(
+            limiter.attempt(1, start + Duration::from_secs(4)),
+            ReconnectDecision::Throttled(Duration::from_secs(6))
+        );
+
+        // Still within the window after the first backoff: throttled twice as long
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.attempt(1, later), ReconnectDecision::Throttled(Duration::from_secs(20)));
+
+        // Other endpoints are unaffected
+        assert_eq!(limiter.attempt(2, later), ReconnectDecision::Proceed);
+    }
+
+    #[test]
+    fn test_window_expiry_and_reset() {
+        let limiter = limiter(1, 0);
+        let start = Instant::now();
+
+        assert_eq!(limiter.attempt(1, start), ReconnectDecision::Proceed);
+        assert!(matches!(limiter.attempt(1, start), ReconnectDecision::Throttled(_)));
+        limiter.record_connected(1);
+        assert_eq!(limiter.attempt(1, start + Duration::from_secs(60)), ReconnectDecision::Proceed);
+    }
+
+    #[test]
+    fn test_global_cap() {
+        let limiter = limiter(0, 2);
+        let now = Instant::now();
+
+        assert_eq!(limiter.attempt(1, now), ReconnectDecision::Proceed);
+        assert_eq!(limiter.attempt(2, now), ReconnectDecision::Proceed);
+        assert!(matches!(limiter.attempt(3, now), ReconnectDecision::Throttled(_)));
+    }
+}
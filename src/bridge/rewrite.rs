@@ -0,0 +1,185 @@
+//! Endpoint-level rewriting of incoming topics
+//!
+//! Brokers often publish the same logical topic under their own prefixes.
+//! Rewrite rules normalize a received topic before any mapping is matched,
+//! so mappings are written once against the canonical form.
+
+use crate::bridge::topic_mapper::is_valid_filter;
+use crate::models::TopicRewrite;
+
+/// Topic produced by the first rule that applies, or `None` to keep the topic
+pub fn rewrite_topic(rules: &[TopicRewrite], topic: &str) -> Option<String> {
+    rules
+        .iter()
+        .find_map(|rule| match rule {
+            TopicRewrite::Prefix { from, to } => topic.strip_prefix(from.as_str()).map(|rest| format!("{}{}", to, rest)),
+            TopicRewrite::Pattern { from, to } => capture_wildcards(from, topic).map(|captures| fill_wildcards(to, captures)),
+        })
+        .filter(|t| !t.is_empty())
+}
+
+/// Levels captured by the wildcards of a pattern rule
+#[derive(Debug, Default)]
+struct Captures {
+    /// One level per `+`, in order
+    single: Vec<String>,
+    /// Remaining levels matched by a trailing `#`
+    rest: Option<String>,
+}
+
+/// Levels matched by the wildcards of `filter`, if `topic` matches it
+fn capture_wildcards(filter: &str, topic: &str) -> Option<Captures> {
+    let mut captures = Captures::default();
+    let mut levels = topic.split('/');
+    for part in filter.split('/') {
+        match part {
+            "#" => {
+                captures.rest = Some(levels.collect::<Vec<_>>().join("/"));
+                return Some(captures);
+            }
+            "+" => captures.single.push(levels.next()?.to_string()),
+            literal => {
+                if levels.next()? != literal {
+                    return None;
+                }
+            }
+        }
+    }
+    levels.next().is_none().then_some(captures)
+}
+
+/// Substitute captured levels for the wildcards of `template`: each `+` takes
+/// the next `+` capture and `#` the `#` capture. An empty `#` capture drops
+/// its level rather than leaving a trailing `/`
+fn fill_wildcards(template: &str, captures: Captures) -> String {
+    let mut single = captures.single.into_iter();
+    template
+        .split('/')
+        .filter_map(|part| match part {
+            "+" => Some(single.next().unwrap_or_default()),
+            "#" => captures.rest.clone().filter(|r| !r.is_empty()),
+            literal => Some(literal.to_string()),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Check rules before they are saved
+pub fn validate_rewrites(rules: &[TopicRewrite]) -> Result<(), String> {
+    for rule in rules {
+        match rule {
+            TopicRewrite::Prefix { from, to } => {
+                if from.is_empty() {
+                    return Err("Invalid topic rewrite: prefix 'from' must not be empty".to_string());
+                }
+                // Rewritten topics are published, where wildcards are not allowed
+                if [from, to].iter().any(|p| p.contains(['+', '#'])) {
+                    return Err(format!(
+                        "Invalid topic rewrite '{}' -> '{}': prefixes must not contain '+' or '#'",
+                        from, to
+                    ));
+                }
+            }
+            TopicRewrite::Pattern { from, to } => {
+                if !is_valid_filter(from) {
+                    return Err(format!("Invalid topic rewrite pattern '{}'", from));
+                }
+                let count = |s: &str, wildcard: &str| s.split('/').filter(|l| *l == wildcard).count();
+                if !is_valid_filter(to) || count(to, "+") > count(from, "+") || count(to, "#") > count(from, "#") {
+                    return Err(format!(
+                        "Invalid topic rewrite '{}' -> '{}': 'to' may only use wildcards captured by 'from'",
+                        from, to
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Filters to subscribe so the broker delivers every topic that rewrites into
+/// one of `filters`: the filters themselves, each with a rewritten prefix
+/// restored, and the `from` filter of every pattern rule
+pub fn upstream_filters(rules: &[TopicRewrite], filters: &[String]) -> Vec<String> {
+    let mut upstream: Vec<String> = filters.to_vec();
+    for rule in rules {
+        match rule {
+            TopicRewrite::Prefix { from, to } => upstream.extend(
+                filters
+                    .iter()
+                    .filter_map(|f| f.strip_prefix(to.as_str()))
+                    .map(|rest| format!("{}{}", from, rest))
+                    .filter(|f| is_valid_filter(f)),
+            ),
+            TopicRewrite::Pattern { from, .. } => upstream.push(from.clone()),
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    upstream.retain(|f| seen.insert(f.clone()));
+    upstream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(from: &str, to: &str) -> TopicRewrite {
+        TopicRewrite::Prefix { from: from.to_string(), to: to.to_string() }
+    }
+
+    fn pattern(from: &str, to: &str) -> TopicRewrite {
+        TopicRewrite::Pattern { from: from.to_string(), to: to.to_string() }
+    }
+
+    #[test]
+    fn test_prefix_rewrite() {
+        let rules = vec![prefix("acme/", ""), prefix("legacy/", "sensors/")];
+        assert_eq!(rewrite_topic(&rules, "acme/sensors/t1").as_deref(), Some("sensors/t1"));
+        assert_eq!(rewrite_topic(&rules, "legacy/t1").as_deref(), Some("sensors/t1"));
+        assert_eq!(rewrite_topic(&rules, "sensors/t1"), None);
+        // A rewrite to an empty topic is not applied
+        assert_eq!(rewrite_topic(&rules, "acme/"), None);
+    }
+
+    #[test]
+    fn test_pattern_rewrite() {
+        let rules = vec![pattern("+/site/+/#", "sites/+/+/#")];
+        assert_eq!(rewrite_topic(&rules, "eu/site/berlin/temp/1").as_deref(), Some("sites/eu/berlin/temp/1"));
+        assert_eq!(rewrite_topic(&rules, "eu/site/berlin").as_deref(), Some("sites/eu/berlin"));
+        assert_eq!(rewrite_topic(&rules, "eu/other/berlin"), None);
+
+        // Dropping a captured level
+        let rules = vec![pattern("+/sensors/#", "sensors/#")];
+        assert_eq!(rewrite_topic(&rules, "broker-a/sensors/t1").as_deref(), Some("sensors/t1"));
+        assert_eq!(rewrite_topic(&[pattern("a/+", "b/+")], "a/x/y"), None);
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = vec![prefix("a/", "first/"), prefix("a/b/", "second/")];
+        assert_eq!(rewrite_topic(&rules, "a/b/c").as_deref(), Some("first/b/c"));
+    }
+
+    #[test]
+    fn test_validate_rewrites() {
+        assert!(validate_rewrites(&[prefix("acme/", ""), pattern("+/x/#", "x/+/#")]).is_ok());
+        assert!(validate_rewrites(&[prefix("", "x/")]).is_err());
+        assert!(validate_rewrites(&[prefix("acme/", "+/")]).is_err());
+        assert!(validate_rewrites(&[prefix("acme/#", "x/")]).is_err());
+        assert!(validate_rewrites(&[pattern("a/#/b", "b")]).is_err());
+        assert!(validate_rewrites(&[pattern("a/+", "b/+/+")]).is_err());
+        assert!(validate_rewrites(&[pattern("a/#", "b/+")]).is_err());
+        assert!(validate_rewrites(&[pattern("a/+", "b/#")]).is_err());
+    }
+
+    #[test]
+    fn test_upstream_filters() {
+        let rules = vec![prefix("acme/", ""), pattern("+/legacy/#", "#")];
+        let filters = vec!["sensors/#".to_string(), "acme/x".to_string()];
+        assert_eq!(
+            upstream_filters(&rules, &filters),
+            vec!["sensors/#", "acme/x", "acme/sensors/#", "acme/acme/x", "+/legacy/#"]
+        );
+        assert_eq!(upstream_filters(&[], &filters), filters);
+    }
+}
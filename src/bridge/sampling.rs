@@ -0,0 +1,122 @@
+//! Deterministic per-mapping downsampling
+//!
+//! Unlike rate limiting, sampling forwards a predictable subset: every Nth
+//! message, or the first message in each interval.
+
+use crate::models::TopicMapping;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct SampleState {
+    /// `sample_every_n` and `sample_interval_ms` the state was counted with
+    settings: (Option<u32>, Option<u64>),
+    seen: u64,
+    window_start: Option<Instant>,
+}
+
+/// Sampling counters and timers keyed by mapping id
+#[derive(Debug, Default)]
+pub struct Sampler {
+    states: Mutex<HashMap<u32, SampleState>>,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a mapping samples at all
+    pub fn is_sampled(mapping: &TopicMapping) -> bool {
+        mapping.sample_every_n.is_some_and(|n| n > 1)
+            || mapping.sample_interval_ms.is_some_and(|ms| ms > 0)
+    }
+
+    /// Decide whether the next message on `mapping` is forwarded
+    pub fn admit(&self, mapping: &TopicMapping, now: Instant) -> bool {
+        if !Self::is_sampled(mapping) {
+            return true;
+        }
+
+        let mut states = self.states.lock();
+        let state = states.entry(mapping.id).or_default();
+        // Changed settings start counting over
+        let settings = (mapping.sample_every_n, mapping.sample_interval_ms);
+        if state.settings != settings {
+            *state = SampleState { settings, ..Default::default() };
+        }
+
+        if let Some(n) = mapping.sample_every_n.filter(|n| *n > 1) {
+            let admit = state.seen % n as u64 == 0;
+            state.seen += 1;
+            return admit;
+        }
+
+        let interval = Duration::from_millis(mapping.sample_interval_ms.unwrap_or(0));
+        match state.window_start {
+            Some(start) if now.duration_since(start) < interval => false,
+            _ => {
+                state.window_start = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Drop the state of mappings not in `mapping_ids`, e.g. deleted ones
+    pub fn retain_mappings(&self, mapping_ids: &[u32]) {
+        self.states.lock().retain(|id, _| mapping_ids.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_every_n() {
+        let sampler = Sampler::new();
+        let mapping = TopicMapping { id: 1, sample_every_n: Some(3), ..Default::default() };
+        let now = Instant::now();
+
+        let admitted: Vec<bool> = (0..7).map(|_| sampler.admit(&mapping, now)).collect();
+        assert_eq!(admitted, vec![true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn test_sample_interval() {
+        let sampler = Sampler::new();
+        let mapping = TopicMapping { id: 1, sample_interval_ms: Some(100), ..Default::default() };
+        let start = Instant::now();
+
+        assert!(sampler.admit(&mapping, start));
+        assert!(!sampler.admit(&mapping, start + Duration::from_millis(50)));
+        assert!(!sampler.admit(&mapping, start + Duration::from_millis(99)));
+        assert!(sampler.admit(&mapping, start + Duration::from_millis(100)));
+        assert!(!sampler.admit(&mapping, start + Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn test_state_restarts_for_new_settings_and_ids() {
+        let sampler = Sampler::new();
+        let mapping = TopicMapping { id: 1, sample_every_n: Some(3), ..Default::default() };
+        let now = Instant::now();
+        assert!(sampler.admit(&mapping, now));
+        assert!(!sampler.admit(&mapping, now));
+
+        let every_other = TopicMapping { sample_every_n: Some(2), ..mapping.clone() };
+        let admitted: Vec<bool> = (0..3).map(|_| sampler.admit(&every_other, now)).collect();
+        assert_eq!(admitted, vec![true, false, true]);
+
+        // A deleted mapping's id reused by a new one starts with a fresh count
+        sampler.retain_mappings(&[2]);
+        assert!(sampler.admit(&every_other, now));
+    }
+
+    #[test]
+    fn test_unsampled_mapping_always_admits() {
+        let sampler = Sampler::new();
+        let mapping = TopicMapping { id: 1, sample_every_n: Some(1), ..Default::default() };
+        assert!((0..5).all(|_| sampler.admit(&mapping, Instant::now())));
+    }
+}
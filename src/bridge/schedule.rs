@@ -0,0 +1,194 @@
+//! Time windows during which a mapping forwards
+//!
+//! A schedule is one or more `;`-separated windows, each an optional day list
+//! and a time range, followed by an optional UTC offset:
+//! `Mon-Fri 09:00-17:00; Sat 10:00-12:00 +01:00`. A range whose end is not
+//! after its start runs past midnight into the next day. Without an offset
+//! times are UTC.
+
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc, Weekday};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// One window: active days (Monday first) and a range in minutes of the day
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    days: [bool; 7],
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    fn contains(&self, weekday: usize, minute: u32) -> bool {
+        if self.start < self.end {
+            return self.days[weekday] && (self.start..self.end).contains(&minute);
+        }
+        // Overnight: the part after midnight belongs to the previous day
+        let previous = (weekday + 6) % 7;
+        (self.days[weekday] && minute >= self.start) || (self.days[previous] && minute < self.end)
+    }
+}
+
+/// A parsed mapping schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    windows: Vec<Window>,
+    offset: FixedOffset,
+}
+
+impl Schedule {
+    /// Parse a schedule spec as stored on a mapping
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut spec = spec.trim();
+        let mut offset = FixedOffset::east_opt(0).expect("zero offset");
+        if let Some((rest, last)) = spec.rsplit_once(char::is_whitespace)
+            && let Some(parsed) = parse_offset(last)
+        {
+            offset = parsed?;
+            spec = rest;
+        }
+
+        let windows = spec
+            .split(';')
+            .map(parse_window)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { windows, offset })
+    }
+
+    /// Whether forwarding is allowed at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.offset);
+        let weekday = local.weekday().num_days_from_monday() as usize;
+        let minute = local.hour() * 60 + local.minute();
+        self.windows.iter().any(|w| w.contains(weekday, minute))
+    }
+}
+
+/// Parse `UTC`, `Z`, `+HH:MM`, `-HH:MM` or `UTC+HH:MM`; None if not an offset token
+fn parse_offset(token: &str) -> Option<Result<FixedOffset, String>> {
+    let upper = token.to_ascii_uppercase();
+    if upper == "UTC" || upper == "Z" {
+        return Some(Ok(FixedOffset::east_opt(0).expect("zero offset")));
+    }
+    let signed = upper.strip_prefix("UTC").unwrap_or(&upper);
+    let sign = match signed.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let invalid = || format!("invalid UTC offset '{}'", token);
+    let Some((hours, minutes)) = signed[1..].split_once(':') else {
+        return Some(Err(invalid()));
+    };
+    let seconds = match (hours.parse::<i32>(), minutes.parse::<i32>()) {
+        (Ok(h), Ok(m)) if h <= 14 && m < 60 => sign * (h * 3600 + m * 60),
+        _ => return Some(Err(invalid())),
+    };
+    Some(FixedOffset::east_opt(seconds).ok_or_else(invalid))
+}
+
+fn parse_window(spec: &str) -> Result<Window, String> {
+    let spec = spec.trim();
+    let (days, range) = match spec.rsplit_once(char::is_whitespace) {
+        Some((days, range)) => (parse_days(days.trim())?, range),
+        None => ([true; 7], spec),
+    };
+    let (start, end) = range
+        .split_once('-')
+        .ok_or_else(|| format!("invalid time range '{}': expected HH:MM-HH:MM", range))?;
+    let start = parse_time(start)?;
+    let end = parse_time(end)?;
+    if start == end || start == MINUTES_PER_DAY {
+        return Err(format!("time range '{}' is empty", range));
+    }
+    Ok(Window { days, start, end: end % MINUTES_PER_DAY })
+}
+
+/// `HH:MM` as minutes of the day; `24:00` is allowed as a range end
+fn parse_time(value: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid time '{}': expected HH:MM", value);
+    let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 || hours * 60 + minutes > MINUTES_PER_DAY {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
+
+/// Comma-separated days or wrapping day ranges, e.g. `Mon-Fri,Sun`
+fn parse_days(spec: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    for part in spec.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let first = parse_day(first)?;
+        let last = parse_day(last)?;
+        let mut day = first;
+        loop {
+            days[day] = true;
+            if day == last {
+                break;
+            }
+            day = (day + 1) % 7;
+        }
+    }
+    Ok(days)
+}
+
+fn parse_day(name: &str) -> Result<usize, String> {
+    name.trim()
+        .parse::<Weekday>()
+        .map(|day| day.num_days_from_monday() as usize)
+        .map_err(|_| format!("invalid day '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// 2024-01-01 was a Monday
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_business_hours() {
+        let schedule = Schedule::parse("Mon-Fri 09:00-17:00").unwrap();
+        assert!(schedule.is_active(at(1, 9, 0)));
+        assert!(schedule.is_active(at(5, 16, 59)));
+        assert!(!schedule.is_active(at(1, 17, 0)));
+        assert!(!schedule.is_active(at(1, 8, 59)));
+        assert!(!schedule.is_active(at(6, 12, 0)));
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let schedule = Schedule::parse("Sat 22:00-02:00").unwrap();
+        assert!(schedule.is_active(at(6, 23, 0)));
+        assert!(schedule.is_active(at(7, 1, 30)));
+        assert!(!schedule.is_active(at(7, 2, 0)));
+        assert!(!schedule.is_active(at(7, 23, 0)));
+    }
+
+    #[test]
+    fn test_offset_and_multiple_windows() {
+        let schedule = Schedule::parse("Mon 08:00-09:00; Tue-Wed 20:00-24:00 +02:00").unwrap();
+        // 06:30 UTC is 08:30 at +02:00
+        assert!(schedule.is_active(at(1, 6, 30)));
+        assert!(!schedule.is_active(at(1, 8, 30)));
+        assert!(schedule.is_active(at(3, 21, 59)));
+        assert!(!schedule.is_active(at(3, 22, 0)));
+
+        let every_day = Schedule::parse("00:00-12:00 UTC").unwrap();
+        assert!(every_day.is_active(at(7, 11, 0)));
+        assert!(!every_day.is_active(at(7, 12, 0)));
+    }
+
+    #[test]
+    fn test_invalid_specs() {
+        for spec in ["", "Mon", "Funday 09:00-10:00", "09:00-09:00", "25:00-26:00", "09:00-10:00 +99:00"] {
+            assert!(Schedule::parse(spec).is_err(), "{spec}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Target topic templates resolved per message
+//!
+//! A target topic may contain `${json:path}` and `${topic:N}` placeholders,
+//! e.g. `devices/${json:device}/data`. Placeholders use the same specs as
+//! mapping ordering keys.
+
+use crate::bridge::ordering::OrderingKey;
+
+/// Whether a target topic contains any placeholders
+pub fn is_template(target: &str) -> bool {
+    target.contains("${")
+}
+
+/// Check that every placeholder in a template is a valid spec
+pub fn validate_template(target: &str) -> Result<(), String> {
+    let mut rest = target;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unterminated placeholder in '{}'", target))?;
+        let spec = &after[..end];
+        if OrderingKey::parse(spec).is_none() {
+            return Err(format!("Invalid placeholder '${{{}}}'", spec));
+        }
+        rest = &after[end + 1..];
+    }
+    Ok(())
+}
+
+/// Substitute placeholders in `target` for a message matched by `pattern`
+///
+/// Missing values, and values containing `+` or `#` that would turn the
+/// topic into a filter, are replaced by `fallback`; without a fallback the
+/// message should be dropped and `None` is returned.
+pub fn render_template(
+    target: &str,
+    pattern: &str,
+    topic: &str,
+    payload: &[u8],
+    fallback: Option<&str>,
+) -> Option<String> {
+    let mut result = String::with_capacity(target.len());
+    let mut rest = target;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}')?;
+        let value = OrderingKey::parse(&after[..end])
+            .and_then(|key| key.extract(pattern, topic, payload))
+            .filter(|v| !v.is_empty() && !v.contains(['+', '#']));
+        result.push_str(&value.or_else(|| fallback.map(str::to_string))?);
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_substitution() {
+        let payload = br#"{"device":"d42","site":{"id":7}}"#;
+        assert_eq!(
+            render_template("devices/${json:device}/data", "#", "raw", payload, None),
+            Some("devices/d42/data".to_string())
+        );
+        assert_eq!(
+            render_template("sites/${json:site.id}/${topic:1}", "in/+", "in/temp", payload, None),
+            Some("sites/7/temp".to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_field_fallback() {
+        let payload = br#"{"other":1}"#;
+        assert_eq!(
+            render_template("devices/${json:device}/data", "#", "raw", payload, Some("unknown")),
+            Some("devices/unknown/data".to_string())
+        );
+        assert_eq!(render_template("devices/${json:device}/data", "#", "raw", payload, None), None);
+        assert_eq!(render_template("devices/${json:device}", "#", "raw", b"not json", None), None);
+    }
+
+    #[test]
+    fn test_wildcard_values_rejected() {
+        let payload = br#"{"device":"d/#"}"#;
+        assert_eq!(render_template("devices/${json:device}", "#", "raw", payload, None), None);
+        assert_eq!(
+            render_template("devices/${json:device}", "#", "raw", payload, Some("unknown")),
+            Some("devices/unknown".to_string())
+        );
+        assert_eq!(render_template("rooms/${topic:1}", "in/#", "in/+", b"", None), None);
+    }
+
+    #[test]
+    fn test_validate_template() {
+        assert!(validate_template("devices/${json:device}/data").is_ok());
+        assert!(validate_template("plain/topic").is_ok());
+        assert!(validate_template("devices/${device}").is_err());
+        assert!(validate_template("devices/${json:device").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Per-thread metadata for runtime diagnostics
+//!
+//! Each endpoint thread keeps its handle alongside counters it updates
+//! itself, so a stuck or dead thread can be spotted without a restart.
+
+use crate::models::{EndpointType, WorkerThreadInfo};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+
+/// Counters updated by a running endpoint thread
+#[derive(Debug, Default)]
+pub struct ThreadActivity {
+    received: AtomicU64,
+    sent: AtomicU64,
+    /// Unix seconds of the last message in either direction; 0 if none yet
+    last_activity: AtomicI64,
+    /// MQTT client id after placeholder expansion
+    client_id: Mutex<Option<String>>,
+    /// Reconnects are being held off by the storm limiter
+    throttled: AtomicBool,
+    /// Set to end this thread alone, e.g. to restart its endpoint
+    stop: AtomicBool,
+}
+
+impl ThreadActivity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a message received from the endpoint
+    pub fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// Record a message sent to the endpoint
+    pub fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// Record the client id the thread connected with
+    pub fn set_client_id(&self, client_id: &str) {
+        *self.client_id.lock() = Some(client_id.to_string());
+    }
+
+    /// Mark whether reconnects are currently throttled
+    pub fn set_throttled(&self, throttled: bool) {
+        self.throttled.store(throttled, Ordering::Relaxed);
+    }
+
+    pub fn is_throttled(&self) -> bool {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Whether the thread was asked to exit on its own
+    pub fn stop_requested(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    fn touch(&self) {
+        self.last_activity.store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
+    }
+}
+
+/// An endpoint thread with the metadata needed to report on it
+pub struct WorkerThread {
+    endpoint_type: EndpointType,
+    endpoint_id: u32,
+    name: String,
+    started_at: i64,
+    activity: Arc<ThreadActivity>,
+    handle: JoinHandle<()>,
+}
+
+impl WorkerThread {
+    /// Spawn `run` on a new thread, handing it the activity counters to update
+    pub fn spawn<F>(endpoint_type: EndpointType, endpoint_id: u32, name: &str, run: F) -> Self
+    where
+        F: FnOnce(Arc<ThreadActivity>) + Send + 'static,
+    {
+        let activity = Arc::new(ThreadActivity::new());
+        let thread_activity = activity.clone();
+        let handle = thread::spawn(move || run(thread_activity));
+
+        Self {
+            endpoint_type,
+            endpoint_id,
+            name: name.to_string(),
+            started_at: chrono::Utc::now().timestamp(),
+            activity,
+            handle,
+        }
+    }
+
+    /// Snapshot of the thread's state
+    pub fn info(&self) -> WorkerThreadInfo {
+        let last_activity = self.activity.last_activity.load(Ordering::Relaxed);
+        WorkerThreadInfo {
+            endpoint_type: self.endpoint_type.clone(),
+            endpoint_id: self.endpoint_id,
+            name: self.name.clone(),
+            alive: !self.handle.is_finished(),
+            started_at: self.started_at,
+            messages_received: self.activity.received.load(Ordering::Relaxed),
+            messages_sent: self.activity.sent.load(Ordering::Relaxed),
+            last_activity: (last_activity > 0).then_some(last_activity),
+            client_id: self.activity.client_id.lock().clone(),
+            throttled: self.activity.is_throttled(),
+        }
+    }
+
+    pub fn endpoint_id(&self) -> u32 {
+        self.endpoint_id
+    }
+
+    /// Ask the thread to exit without waiting for it
+    pub fn request_stop(&self) {
+        self.activity.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Wait for the thread to exit
+    pub fn join(self) {
+        let _ = self.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_thread_info_reflects_activity() {
+        let thread = WorkerThread::spawn(EndpointType::Zmq, 3, "pub", |activity| {
+            activity.record_received();
+            activity.record_received();
+            activity.record_sent();
+        });
+
+        while !thread.handle.is_finished() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let info = thread.info();
+        assert_eq!((info.endpoint_id, info.name.as_str()), (3, "pub"));
+        assert!(!info.alive);
+        assert_eq!((info.messages_received, info.messages_sent), (2, 1));
+        assert!(info.last_activity.is_some());
+        thread.join();
+    }
+}
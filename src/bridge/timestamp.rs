@@ -0,0 +1,112 @@
+//! Producer timestamps for end-to-end latency
+//!
+//! A mapping may name where its messages carry the time they were produced:
+//! a JSON payload field (`json:path`) or an MQTT v5 user property
+//! (`property:name`). Values may be unix seconds or milliseconds (integer or
+//! fractional) or RFC 3339 strings.
+
+use crate::bridge::ordering::OrderingKey;
+
+/// Integers below this are taken as seconds, above as milliseconds (year 5138)
+const SECONDS_THRESHOLD: f64 = 1e11;
+
+/// Where a mapping reads the producer timestamp from
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampSource {
+    /// Dotted path into a JSON payload, e.g. `json:meta.ts`
+    JsonPath(String),
+    /// MQTT v5 user property, e.g. `property:sent-at`
+    Property(String),
+}
+
+impl TimestampSource {
+    /// Parse a timestamp source spec as stored on a mapping
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some(name) = spec.strip_prefix("property:") {
+            return (!name.is_empty()).then(|| TimestampSource::Property(name.to_string()));
+        }
+        match OrderingKey::parse(spec)? {
+            OrderingKey::JsonPath(path) => Some(TimestampSource::JsonPath(path)),
+            OrderingKey::TopicCapture(_) => None,
+        }
+    }
+
+    /// Producer timestamp of a message in unix milliseconds
+    pub fn extract_ms(&self, payload: &[u8], user_properties: &[(String, String)]) -> Option<i64> {
+        let raw = match self {
+            TimestampSource::JsonPath(path) => {
+                OrderingKey::JsonPath(path.clone()).extract("", "", payload)?
+            }
+            TimestampSource::Property(name) => user_properties
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())?,
+        };
+        parse_timestamp_ms(raw.trim())
+    }
+}
+
+/// Parse unix seconds/milliseconds or an RFC 3339 time into unix milliseconds
+fn parse_timestamp_ms(value: &str) -> Option<i64> {
+    if let Ok(number) = value.parse::<f64>() {
+        if !number.is_finite() || number <= 0.0 {
+            return None;
+        }
+        let ms = if number < SECONDS_THRESHOLD { number * 1000.0 } else { number };
+        return Some(ms as i64);
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.timestamp_millis())
+}
+
+/// Latency from a producer timestamp to `now_ms`; None for clocks running ahead
+pub fn e2e_latency_ms(produced_ms: i64, now_ms: i64) -> Option<f64> {
+    (now_ms >= produced_ms).then(|| (now_ms - produced_ms) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            TimestampSource::parse("json:$.meta.ts"),
+            Some(TimestampSource::JsonPath("meta.ts".to_string()))
+        );
+        assert_eq!(
+            TimestampSource::parse("property:sent-at"),
+            Some(TimestampSource::Property("sent-at".to_string()))
+        );
+        assert_eq!(TimestampSource::parse("topic:1"), None);
+        assert_eq!(TimestampSource::parse("property:"), None);
+    }
+
+    #[test]
+    fn test_extract_from_json() {
+        let source = TimestampSource::parse("json:meta.ts").unwrap();
+        assert_eq!(source.extract_ms(br#"{"meta":{"ts":1700000000123}}"#, &[]), Some(1_700_000_000_123));
+        assert_eq!(source.extract_ms(br#"{"meta":{"ts":1700000000.5}}"#, &[]), Some(1_700_000_000_500));
+        assert_eq!(
+            source.extract_ms(br#"{"meta":{"ts":"2023-11-14T22:13:20.250Z"}}"#, &[]),
+            Some(1_700_000_000_250)
+        );
+        assert_eq!(source.extract_ms(br#"{"meta":{}}"#, &[]), None);
+        assert_eq!(source.extract_ms(b"not json", &[]), None);
+    }
+
+    #[test]
+    fn test_extract_from_property() {
+        let source = TimestampSource::parse("property:sent-at").unwrap();
+        let props = vec![("sent-at".to_string(), "1700000000".to_string())];
+        assert_eq!(source.extract_ms(b"", &props), Some(1_700_000_000_000));
+        assert_eq!(source.extract_ms(b"", &[]), None);
+    }
+
+    #[test]
+    fn test_e2e_latency() {
+        assert_eq!(e2e_latency_ms(1_000, 1_250), Some(250.0));
+        assert_eq!(e2e_latency_ms(2_000, 1_000), None);
+    }
+}
@@ -1,20 +1,147 @@
 //! Topic mapping and wildcard matching
 
+use crate::bridge::schedule::Schedule;
+use crate::config::EvaluationStrategy;
 use crate::models::{MappingDirection, TopicMapping};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+/// One topic level of a `TopicIndex`
+#[derive(Debug, Clone, Default)]
+struct IndexNode {
+    /// Children reached by a literal level
+    literal: HashMap<String, IndexNode>,
+    /// Child reached by any single level (`+`)
+    single: Option<Box<IndexNode>>,
+    /// Patterns ending in `#` here, matching the remaining levels
+    multi: Vec<usize>,
+    /// Patterns ending exactly here
+    terminal: Vec<usize>,
+}
+
+impl IndexNode {
+    fn collect(&self, levels: &[&str], out: &mut Vec<usize>) {
+        out.extend_from_slice(&self.multi);
+        match levels.split_first() {
+            None => out.extend_from_slice(&self.terminal),
+            Some((level, rest)) => {
+                if let Some(child) = self.literal.get(*level) {
+                    child.collect(rest, out);
+                }
+                if let Some(ref child) = self.single {
+                    child.collect(rest, out);
+                }
+            }
+        }
+    }
+}
+
+/// Trie of topic patterns by level, so finding the patterns matching a topic
+/// walks its levels instead of testing every pattern
+#[derive(Debug, Clone, Default)]
+pub struct TopicIndex {
+    root: IndexNode,
+}
+
+impl TopicIndex {
+    /// Index `patterns` by their position
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut root = IndexNode::default();
+        for (position, pattern) in patterns.into_iter().enumerate() {
+            let mut node = &mut root;
+            let mut multi = false;
+            for level in pattern.split('/') {
+                match level {
+                    // Matches the rest of the topic, like `matches_topic_pattern`
+                    "#" => {
+                        multi = true;
+                        break;
+                    }
+                    "+" => node = &mut **node.single.get_or_insert_with(Box::default),
+                    _ => node = node.literal.entry(level.to_string()).or_default(),
+                }
+            }
+            if multi {
+                node.multi.push(position);
+            } else {
+                node.terminal.push(position);
+            }
+        }
+        Self { root }
+    }
+
+    /// Positions of the patterns matching `topic`, ascending
+    pub fn matches(&self, topic: &str) -> Vec<usize> {
+        let levels: Vec<&str> = topic.split('/').collect();
+        let mut positions = Vec::new();
+        self.root.collect(&levels, &mut positions);
+        positions.sort_unstable();
+        positions
+    }
+}
+
+/// Mappings in evaluation order, precompiled with an index of their source
+/// topics and their parsed schedules. Derefs to the mappings for everything
+/// but matching and schedules.
+#[derive(Debug, Clone, Default)]
+pub struct CompiledMappings {
+    mappings: Vec<TopicMapping>,
+    index: TopicIndex,
+    /// Schedules by mapping id; specs that no longer parse are left out
+    schedules: HashMap<u32, Schedule>,
+}
+
+impl CompiledMappings {
+    pub fn new(mappings: Vec<TopicMapping>) -> Self {
+        let index = TopicIndex::new(mappings.iter().map(|m| m.source_topic.as_str()));
+        let schedules = mappings
+            .iter()
+            .filter_map(|m| Some((m.id, Schedule::parse(m.schedule.as_deref()?).ok()?)))
+            .collect();
+        Self { mappings, index, schedules }
+    }
+
+    /// Whether the schedule of `mapping` allows forwarding at `now`;
+    /// unscheduled mappings and specs that no longer parse are always active
+    pub fn schedule_active(&self, mapping: &TopicMapping, now: DateTime<Utc>) -> bool {
+        self.schedules.get(&mapping.id).is_none_or(|schedule| schedule.is_active(now))
+    }
+
+    /// Mappings whose source topic matches `topic`, in evaluation order
+    pub fn matching(&self, topic: &str) -> impl Iterator<Item = &TopicMapping> {
+        self.index.matches(topic).into_iter().map(|position| &self.mappings[position])
+    }
+}
+
+impl From<Vec<TopicMapping>> for CompiledMappings {
+    fn from(mappings: Vec<TopicMapping>) -> Self {
+        Self::new(mappings)
+    }
+}
+
+impl Deref for CompiledMappings {
+    type Target = [TopicMapping];
+
+    fn deref(&self) -> &[TopicMapping] {
+        &self.mappings
+    }
+}
 
 /// Topic mapper for MQTT/ZeroMQ topic conversion
 pub struct TopicMapper {
-    mappings: Vec<TopicMapping>,
+    mappings: CompiledMappings,
 }
 
 impl TopicMapper {
     pub fn new(mappings: Vec<TopicMapping>) -> Self {
-        Self { mappings }
+        Self { mappings: mappings.into() }
     }
 
     /// Update mappings
     pub fn update_mappings(&mut self, mappings: Vec<TopicMapping>) {
-        self.mappings = mappings;
+        self.mappings = mappings.into();
     }
 
     /// Get all enabled MQTT source topics (for subscription)
@@ -32,7 +159,7 @@ impl TopicMapper {
 
     /// Match a source topic and return the target topic for MQTT → ZMQ
     pub fn map_mqtt_to_zmq(&self, source_topic: &str) -> Option<String> {
-        for mapping in &self.mappings {
+        for mapping in self.mappings.matching(source_topic) {
             if !mapping.enabled {
                 continue;
             }
@@ -42,20 +169,18 @@ impl TopicMapper {
                 continue;
             }
 
-            if matches_topic_pattern(&mapping.source_topic, source_topic) {
-                return Some(apply_topic_mapping(
-                    &mapping.source_topic,
-                    &mapping.target_topic,
-                    source_topic,
-                ));
-            }
+            return Some(apply_topic_mapping(
+                &mapping.source_topic,
+                &mapping.target_topic,
+                source_topic,
+            ));
         }
         None
     }
 
     /// Match a source topic and return the target topic for ZMQ → MQTT
     pub fn map_zmq_to_mqtt(&self, source_topic: &str) -> Option<String> {
-        for mapping in &self.mappings {
+        for mapping in self.mappings.matching(source_topic) {
             if !mapping.enabled {
                 continue;
             }
@@ -65,19 +190,69 @@ impl TopicMapper {
                 continue;
             }
 
-            // For ZMQ→MQTT, we match against source_topic pattern
-            if matches_topic_pattern(&mapping.source_topic, source_topic) {
-                return Some(apply_topic_mapping(
-                    &mapping.source_topic,
-                    &mapping.target_topic,
-                    source_topic,
-                ));
-            }
+            return Some(apply_topic_mapping(
+                &mapping.source_topic,
+                &mapping.target_topic,
+                source_topic,
+            ));
         }
         None
     }
 }
 
+/// Specificity of a topic pattern; larger tuples are more specific.
+/// Fewer wildcards win, then a longer literal prefix, then more levels.
+pub fn pattern_specificity(pattern: &str) -> (Reverse<usize>, usize, usize) {
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let wildcards = parts.iter().filter(|p| **p == "+" || **p == "#").count();
+    let literal_prefix = parts.iter().take_while(|p| **p != "+" && **p != "#").count();
+    (Reverse(wildcards), literal_prefix, parts.len())
+}
+
+/// Sort mappings into evaluation order (stable, ties keep id order)
+pub fn sort_mappings(mappings: &mut [TopicMapping], strategy: EvaluationStrategy) {
+    match strategy {
+        EvaluationStrategy::DbOrder => mappings.sort_by_key(|m| m.id),
+        EvaluationStrategy::Priority => mappings.sort_by_key(|m| (Reverse(m.priority), m.id)),
+        EvaluationStrategy::MostSpecific => {
+            mappings.sort_by_key(|m| (Reverse(pattern_specificity(&m.source_topic)), m.id))
+        }
+    }
+}
+
+/// Whether a computed topic can be published to: non-empty, without wildcards or NUL
+pub fn is_publishable_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && !topic.contains('\0')
+        && topic.split('/').all(|level| level != "+" && level != "#")
+}
+
+/// Whether a subscription filter is well formed: non-empty, without NUL, with
+/// `+` and `#` only as whole levels and `#` only as the last level
+pub fn is_valid_filter(filter: &str) -> bool {
+    if filter.is_empty() || filter.contains('\0') || filter.len() > u16::MAX as usize {
+        return false;
+    }
+    let levels: Vec<&str> = filter.split('/').collect();
+    levels.iter().enumerate().all(|(i, level)| match *level {
+        "+" => true,
+        "#" => i == levels.len() - 1,
+        _ => !level.contains(['+', '#']),
+    })
+}
+
+/// Whether a subscription filter spans a broker's whole namespace: its
+/// first level is a wildcard, as in `#` or `+/status`
+pub fn is_broad_filter(filter: &str) -> bool {
+    matches!(filter.split('/').next(), Some("#" | "+"))
+}
+
+/// Whether a topic has more than `max` `/`-separated levels, counted
+/// without splitting the topic
+pub fn exceeds_topic_levels(topic: &str, max: usize) -> bool {
+    topic.bytes().filter(|&b| b == b'/').take(max).count() >= max
+}
+
 /// Check if a topic matches a pattern with MQTT wildcards
 /// + matches single level
 /// # matches multiple levels (only at end)
@@ -173,6 +348,34 @@ mod tests {
         assert!(!matches_topic_pattern("sensors/temperature", "sensors/humidity"));
     }
 
+    #[test]
+    fn test_publishable_topic() {
+        assert!(is_publishable_topic("sensors/room1"));
+        assert!(is_publishable_topic("a//b"));
+        assert!(!is_publishable_topic(""));
+        assert!(!is_publishable_topic("sensors/+"));
+        assert!(!is_publishable_topic("#"));
+    }
+
+    #[test]
+    fn test_topic_level_boundary() {
+        assert!(!exceeds_topic_levels("a/b/c", 3));
+        assert!(exceeds_topic_levels("a/b/c/d", 3));
+        assert!(!exceeds_topic_levels("a", 1));
+        assert!(exceeds_topic_levels("a/", 1));
+        assert!(exceeds_topic_levels(&"x/".repeat(100_000), 8));
+    }
+
+    #[test]
+    fn test_valid_filter() {
+        assert!(is_valid_filter("sensors/+/temp"));
+        assert!(is_valid_filter("#"));
+        assert!(is_valid_filter("$SYS/broker/#"));
+        assert!(!is_valid_filter("sensors/#/temp"));
+        assert!(!is_valid_filter("sensors/room+"));
+        assert!(!is_valid_filter(""));
+    }
+
     #[test]
     fn test_single_level_wildcard() {
         assert!(matches_topic_pattern("sensors/+/temperature", "sensors/room1/temperature"));
@@ -186,4 +389,129 @@ mod tests {
         assert!(matches_topic_pattern("sensors/#", "sensors"));
         assert!(matches_topic_pattern("#", "anything/goes/here"));
     }
+
+    fn mapping(id: u32, source_topic: &str, priority: i32) -> TopicMapping {
+        TopicMapping {
+            id,
+            source_topic: source_topic.to_string(),
+            priority,
+            ..Default::default()
+        }
+    }
+
+    fn ids(mappings: &[TopicMapping]) -> Vec<u32> {
+        mappings.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn test_most_specific_ordering() {
+        let mut mappings = vec![
+            mapping(1, "#", 0),
+            mapping(2, "sensors/#", 0),
+            mapping(3, "sensors/+/temp", 0),
+            mapping(4, "sensors/room1/temp", 0),
+            mapping(5, "+/room1/temp", 0),
+        ];
+        sort_mappings(&mut mappings, EvaluationStrategy::MostSpecific);
+        assert_eq!(ids(&mappings), vec![4, 3, 2, 5, 1]);
+    }
+
+    #[test]
+    fn test_index_agrees_with_pattern_matching() {
+        let patterns = [
+            "#", "sensors/#", "sensors/+/temp", "sensors/room1/temp", "+/room1/temp", "sensors", "sensors/+",
+            "a/+/+/d", "a//c", "+", "a/+", "$SYS/#", "a/#/c",
+        ];
+        let topics = [
+            "sensors", "sensors/room1", "sensors/room1/temp", "sensors/room2/temp", "other/room1/temp", "a/b/c/d",
+            "a//c", "a/", "a", "", "$SYS/broker/uptime", "a/x", "a/x/c", "sensors/room1/temp/extra",
+        ];
+        let index = TopicIndex::new(patterns);
+        for topic in topics {
+            let expected: Vec<usize> = (0..patterns.len())
+                .filter(|&i| matches_topic_pattern(patterns[i], topic))
+                .collect();
+            assert_eq!(index.matches(topic), expected, "topic {:?}", topic);
+        }
+    }
+
+    #[test]
+    fn test_compiled_mappings_keep_evaluation_order() {
+        let compiled = CompiledMappings::new(vec![
+            mapping(7, "sensors/room1/temp", 0),
+            mapping(3, "sensors/#", 0),
+            mapping(5, "nothing/here", 0),
+            mapping(1, "+/room1/+", 0),
+        ]);
+        let matched: Vec<u32> = compiled.matching("sensors/room1/temp").map(|m| m.id).collect();
+        assert_eq!(matched, vec![7, 3, 1]);
+        assert_eq!(compiled.len(), 4);
+    }
+
+    /// Matching throughput of 500 mappings, scanned and indexed. Run with
+    /// `cargo test --release bench_mapping_match -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_mapping_match() {
+        use std::time::Instant;
+
+        let mappings: Vec<TopicMapping> = (0..500u32)
+            .map(|i| match i % 4 {
+                0 => mapping(i, &format!("site{}/sensors/+/temp", i), 0),
+                1 => mapping(i, &format!("site{}/alarms/#", i), 0),
+                2 => mapping(i, &format!("site{}/meters/{}/power", i % 50, i), 0),
+                _ => mapping(i, &format!("+/devices/{}/state", i), 0),
+            })
+            .collect();
+        let topics: Vec<String> = (0..1000u32)
+            .map(|i| match i % 3 {
+                0 => format!("site{}/sensors/room{}/temp", i % 500, i),
+                1 => format!("site{}/alarms/fire/{}", i % 500, i),
+                _ => format!("site{}/devices/{}/state", i % 7, i % 500),
+            })
+            .collect();
+        let compiled = CompiledMappings::new(mappings.clone());
+        let rounds = 100;
+
+        let start = Instant::now();
+        let mut scanned = 0;
+        for _ in 0..rounds {
+            for topic in &topics {
+                scanned += mappings.iter().filter(|m| matches_topic_pattern(&m.source_topic, topic)).count();
+            }
+        }
+        let scan = start.elapsed();
+
+        let start = Instant::now();
+        let mut indexed = 0;
+        for _ in 0..rounds {
+            for topic in &topics {
+                indexed += compiled.matching(topic).count();
+            }
+        }
+        let index = start.elapsed();
+
+        assert_eq!(scanned, indexed);
+        let messages = (rounds * topics.len()) as f64;
+        println!("scan:  {:>12.0} msg/s", messages / scan.as_secs_f64());
+        println!("index: {:>12.0} msg/s", messages / index.as_secs_f64());
+    }
+
+    #[test]
+    fn test_broad_filter() {
+        assert!(is_broad_filter("#"));
+        assert!(is_broad_filter("+/status"));
+        assert!(!is_broad_filter("sensors/#"));
+        assert!(!is_broad_filter("sensors/+/temp"));
+    }
+
+    #[test]
+    fn test_priority_and_db_ordering() {
+        let mut mappings = vec![mapping(3, "a", 1), mapping(1, "b", 0), mapping(2, "c", 5)];
+        sort_mappings(&mut mappings, EvaluationStrategy::Priority);
+        assert_eq!(ids(&mappings), vec![2, 3, 1]);
+
+        sort_mappings(&mut mappings, EvaluationStrategy::DbOrder);
+        assert_eq!(ids(&mappings), vec![1, 2, 3]);
+    }
 }
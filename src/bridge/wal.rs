@@ -0,0 +1,514 @@
+//! Write-ahead log for messages of durable mappings
+//!
+//! Messages are appended to the active segment (and synced) before they are
+//! forwarded, and an ack record is written once every target endpoint thread
+//! published them. On startup the segments are replayed and unacked messages
+//! are forwarded again, so delivery is at least once across crashes.
+//!
+//! Records are `A <id> <len> <message>` or `D <id>`; a torn record at the end
+//! of a segment ends that segment. Compaction copies the still-pending
+//! messages into the active segment and deletes every older one.
+
+use crate::bridge::worker::{ForwardMessage, MessageSource};
+use crate::config::BridgeConfig;
+use crate::telemetry::metrics;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tracing::warn;
+
+const APPEND: u8 = b'A';
+const ACK: u8 = b'D';
+const SEGMENT_EXTENSION: &str = "wal";
+
+/// Open log state, created on first use
+struct LogState {
+    active: File,
+    active_segment: u64,
+    active_len: u64,
+    next_id: u64,
+    /// Unacked messages by entry id
+    pending: BTreeMap<u64, ForwardMessage>,
+}
+
+/// On-disk queue of messages not yet handed to their targets
+pub struct WriteAheadLog {
+    dir: PathBuf,
+    segment_bytes: u64,
+    state: Mutex<Option<LogState>>,
+}
+
+impl WriteAheadLog {
+    pub fn new(dir: impl Into<PathBuf>, segment_bytes: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            segment_bytes: segment_bytes.max(1),
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Log in `bridge.wal_dir`, by default `~/.zeromqtt/wal`
+    pub fn from_settings(settings: &BridgeConfig) -> Option<Self> {
+        let dir = match settings.wal_dir {
+            Some(ref dir) => PathBuf::from(dir),
+            None => dirs::home_dir()?.join(".zeromqtt").join("wal"),
+        };
+        Some(Self::new(dir, settings.wal_segment_bytes))
+    }
+
+    /// Load existing segments and return the messages left unacked by the
+    /// previous run, oldest first. Does nothing if no log was written yet.
+    pub fn recover(&self) -> io::Result<Vec<(u64, ForwardMessage)>> {
+        let mut state = self.state.lock();
+        if state.is_none() {
+            if segment_ids(&self.dir)?.is_empty() {
+                return Ok(Vec::new());
+            }
+            *state = Some(self.open()?);
+        }
+        let pending = state.as_ref().map(|s| s.pending.clone()).unwrap_or_default();
+        Ok(pending.into_iter().collect())
+    }
+
+    /// Persist a message before it is forwarded, returning its entry id
+    pub fn append(&self, msg: &ForwardMessage) -> io::Result<u64> {
+        let mut guard = self.state.lock();
+        let state = match *guard {
+            Some(ref mut state) => state,
+            None => guard.insert(self.open()?),
+        };
+
+        let id = state.next_id;
+        let body = encode_message(msg);
+        let mut record = Vec::with_capacity(13 + body.len());
+        record.push(APPEND);
+        record.extend_from_slice(&id.to_le_bytes());
+        record.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        record.extend_from_slice(&body);
+        state.active.write_all(&record)?;
+        state.active.sync_data()?;
+
+        state.next_id += 1;
+        state.active_len += record.len() as u64;
+        state.pending.insert(id, msg.clone());
+        metrics().set_wal_pending(state.pending.len());
+        if state.active_len >= self.segment_bytes {
+            self.rotate(state)?;
+        }
+        Ok(id)
+    }
+
+    /// Mark a message as delivered. Not synced: losing an ack only replays
+    /// the message again.
+    pub fn ack(&self, id: u64) -> io::Result<()> {
+        let mut guard = self.state.lock();
+        let Some(ref mut state) = *guard else {
+            return Ok(());
+        };
+        if state.pending.remove(&id).is_none() {
+            return Ok(());
+        }
+        let mut record = [0u8; 9];
+        record[0] = ACK;
+        record[1..].copy_from_slice(&id.to_le_bytes());
+        state.active.write_all(&record)?;
+        state.active_len += record.len() as u64;
+        metrics().set_wal_pending(state.pending.len());
+        Ok(())
+    }
+
+    /// Number of messages appended but not yet acked
+    pub fn pending_count(&self) -> usize {
+        self.state.lock().as_ref().map_or(0, |s| s.pending.len())
+    }
+
+    /// Copy pending messages into a fresh segment and delete all older ones
+    pub fn compact(&self) -> io::Result<()> {
+        let mut guard = self.state.lock();
+        let Some(ref mut state) = *guard else {
+            return Ok(());
+        };
+        let obsolete: Vec<u64> = segment_ids(&self.dir)?
+            .into_iter()
+            .filter(|&id| id != state.active_segment)
+            .collect();
+        if obsolete.is_empty() && state.pending.is_empty() && state.active_len > 0 {
+            // Everything in the active segment is acked: start over
+            self.rotate(state)?;
+            fs::remove_file(segment_path(&self.dir, state.active_segment - 1))?;
+            return Ok(());
+        }
+        if obsolete.is_empty() {
+            return Ok(());
+        }
+
+        self.rotate(state)?;
+        let mut records = Vec::new();
+        for (id, msg) in &state.pending {
+            let body = encode_message(msg);
+            records.push(APPEND);
+            records.extend_from_slice(&id.to_le_bytes());
+            records.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            records.extend_from_slice(&body);
+        }
+        state.active.write_all(&records)?;
+        state.active.sync_data()?;
+        state.active_len = records.len() as u64;
+
+        for segment in segment_ids(&self.dir)?.into_iter().filter(|&id| id != state.active_segment) {
+            fs::remove_file(segment_path(&self.dir, segment))?;
+        }
+        Ok(())
+    }
+
+    /// Replay every segment and start a new active one after them
+    fn open(&self) -> io::Result<LogState> {
+        fs::create_dir_all(&self.dir)?;
+        let segments = segment_ids(&self.dir)?;
+        let mut pending = BTreeMap::new();
+        let mut next_id = 0;
+        for &segment in &segments {
+            let data = fs::read(segment_path(&self.dir, segment))?;
+            replay_segment(&data, &mut pending, &mut next_id);
+        }
+
+        let active_segment = segments.last().map_or(0, |last| last + 1);
+        metrics().set_wal_pending(pending.len());
+        Ok(LogState {
+            active: create_segment(&self.dir, active_segment)?,
+            active_segment,
+            active_len: 0,
+            next_id,
+            pending,
+        })
+    }
+
+    fn rotate(&self, state: &mut LogState) -> io::Result<()> {
+        state.active_segment += 1;
+        state.active = create_segment(&self.dir, state.active_segment)?;
+        state.active_len = 0;
+        Ok(())
+    }
+}
+
+/// Delivery state of one logged message, shared by the commands carrying it
+/// to its targets. The entry is acked when the last reference is dropped,
+/// provided every endpoint published its copy; a failed publish or a command
+/// dropped unsent leaves it for the next replay.
+pub struct DeliveryReceipt {
+    wal: Arc<WriteAheadLog>,
+    id: u64,
+    /// Commands handed to an endpoint and not yet published
+    outstanding: AtomicUsize,
+    failed: AtomicBool,
+}
+
+impl DeliveryReceipt {
+    pub fn new(wal: Arc<WriteAheadLog>, id: u64) -> Arc<Self> {
+        Arc::new(Self {
+            wal,
+            id,
+            outstanding: AtomicUsize::new(0),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Count one more command carrying the message
+    pub fn handed(&self) {
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// An endpoint published its copy
+    pub fn published(&self) {
+        self.outstanding.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// A target did not get the message: keep the entry
+    pub fn fail(&self) {
+        self.failed.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for DeliveryReceipt {
+    fn drop(&mut self) {
+        if self.failed.load(Ordering::SeqCst) || self.outstanding.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        if let Err(e) = self.wal.ack(self.id) {
+            warn!("Failed to ack write-ahead log entry {}: {}", self.id, e);
+        }
+    }
+}
+
+fn segment_path(dir: &Path, segment: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", segment, SEGMENT_EXTENSION))
+}
+
+fn create_segment(dir: &Path, segment: u64) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(segment_path(dir, segment))
+}
+
+/// Ids of the segments in `dir`, ascending; empty if it does not exist
+fn segment_ids(dir: &Path) -> io::Result<Vec<u64>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut ids: Vec<u64> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != SEGMENT_EXTENSION {
+                return None;
+            }
+            path.file_stem()?.to_str()?.parse().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Apply the records of one segment, stopping at a torn or unknown record
+fn replay_segment(data: &[u8], pending: &mut BTreeMap<u64, ForwardMessage>, next_id: &mut u64) {
+    let mut reader = Reader(data);
+    while let Some(kind) = reader.u8() {
+        let Some(id) = reader.u64() else { return };
+        match kind {
+            APPEND => {
+                let Some(body) = reader.u32().and_then(|len| reader.bytes(len as usize)) else {
+                    return;
+                };
+                let Some(msg) = decode_message(body) else { return };
+                pending.insert(id, msg);
+            }
+            ACK => {
+                pending.remove(&id);
+            }
+            _ => return,
+        }
+        *next_id = (*next_id).max(id + 1);
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn encode_message(msg: &ForwardMessage) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + msg.topic.len() + msg.payload.len());
+    out.push(match msg.source {
+        MessageSource::Mqtt => 0,
+        MessageSource::Zmq => 1,
+    });
+    out.extend_from_slice(&msg.source_id.to_le_bytes());
+    put_bytes(&mut out, msg.topic.as_bytes());
+    put_bytes(&mut out, &msg.payload);
+    match msg.identity {
+        Some(ref identity) => {
+            out.push(1);
+            put_bytes(&mut out, identity);
+        }
+        None => out.push(0),
+    }
+    out.extend_from_slice(&(msg.user_properties.len() as u32).to_le_bytes());
+    for (key, value) in &msg.user_properties {
+        put_bytes(&mut out, key.as_bytes());
+        put_bytes(&mut out, value.as_bytes());
+    }
+    out.extend_from_slice(&(msg.frames.len() as u32).to_le_bytes());
+    for frame in &msg.frames {
+        put_bytes(&mut out, frame);
+    }
+    out
+}
+
+fn decode_message(data: &[u8]) -> Option<ForwardMessage> {
+    let mut reader = Reader(data);
+    let source = match reader.u8()? {
+        0 => MessageSource::Mqtt,
+        1 => MessageSource::Zmq,
+        _ => return None,
+    };
+    let source_id = reader.u32()?;
+    let topic = reader.string()?;
+    let payload = reader.sized()?.to_vec();
+    let identity = match reader.u8()? {
+        0 => None,
+        _ => Some(reader.sized()?.to_vec()),
+    };
+    let count = reader.u32()?;
+    let mut user_properties = Vec::new();
+    for _ in 0..count {
+        user_properties.push((reader.string()?, reader.string()?));
+    }
+    // Entries logged before multipart frames were carried end here
+    let mut frames = Vec::new();
+    for _ in 0..reader.u32().unwrap_or(0) {
+        frames.push(reader.sized()?.to_vec());
+    }
+    Some(ForwardMessage {
+        source,
+        source_id,
+        topic,
+        payload,
+        identity,
+        user_properties,
+        frames,
+    })
+}
+
+/// Cursor over little-endian fields; `None` once the data runs out
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    fn sized(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.sized()?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("zeromqtt-wal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn message(topic: &str) -> ForwardMessage {
+        ForwardMessage {
+            source: MessageSource::Zmq,
+            source_id: 3,
+            topic: topic.to_string(),
+            payload: b"42".to_vec(),
+            identity: Some(b"peer".to_vec()),
+            user_properties: vec![("k".to_string(), "v".to_string())],
+            frames: vec![b"body".to_vec()],
+        }
+    }
+
+    #[test]
+    fn test_replays_unacked_messages() {
+        let dir = temp_dir("replay");
+        let wal = WriteAheadLog::new(&dir, 1024 * 1024);
+        assert!(wal.recover().unwrap().is_empty());
+        assert!(!dir.exists(), "nothing is written before the first append");
+
+        let first = wal.append(&message("a")).unwrap();
+        let second = wal.append(&message("b")).unwrap();
+        wal.ack(first).unwrap();
+        drop(wal);
+
+        let reopened = WriteAheadLog::new(&dir, 1024 * 1024);
+        let pending = reopened.recover().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, second);
+        assert_eq!(pending[0].1.topic, "b");
+        assert_eq!(pending[0].1.identity.as_deref(), Some(&b"peer"[..]));
+        assert_eq!(pending[0].1.user_properties, message("b").user_properties);
+        assert_eq!(pending[0].1.frames, message("b").frames);
+
+        // New entries never reuse an id from the previous run
+        assert!(reopened.append(&message("c")).unwrap() > second);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_receipt_acks_once_every_copy_is_published() {
+        let dir = temp_dir("receipt");
+        let wal = Arc::new(WriteAheadLog::new(&dir, 1024 * 1024));
+
+        let id = wal.append(&message("a")).unwrap();
+        let receipt = DeliveryReceipt::new(wal.clone(), id);
+        receipt.handed();
+        receipt.handed();
+        let in_flight = receipt.clone();
+        receipt.published();
+        drop(receipt);
+        assert_eq!(wal.pending_count(), 1, "one copy is still queued");
+        in_flight.published();
+        drop(in_flight);
+        assert_eq!(wal.pending_count(), 0);
+
+        // Never published, or published by one target and failed by another
+        let unsent = DeliveryReceipt::new(wal.clone(), wal.append(&message("b")).unwrap());
+        unsent.handed();
+        drop(unsent);
+        let failed = DeliveryReceipt::new(wal.clone(), wal.append(&message("c")).unwrap());
+        failed.handed();
+        failed.published();
+        failed.fail();
+        drop(failed);
+        assert_eq!(wal.pending_count(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_torn_record_is_ignored() {
+        let dir = temp_dir("torn");
+        let wal = WriteAheadLog::new(&dir, 1024 * 1024);
+        wal.append(&message("a")).unwrap();
+        drop(wal);
+
+        let segment = segment_path(&dir, segment_ids(&dir).unwrap()[0]);
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[APPEND, 9, 0, 0]).unwrap();
+
+        let pending = WriteAheadLog::new(&dir, 1024 * 1024).recover().unwrap();
+        assert_eq!(pending.len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rotation_and_compaction() {
+        let dir = temp_dir("compact");
+        let wal = WriteAheadLog::new(&dir, 64);
+        let ids: Vec<u64> = (0..10).map(|i| wal.append(&message(&format!("t/{}", i))).unwrap()).collect();
+        assert!(segment_ids(&dir).unwrap().len() > 2);
+
+        for &id in &ids[..9] {
+            wal.ack(id).unwrap();
+        }
+        wal.compact().unwrap();
+        assert_eq!(segment_ids(&dir).unwrap().len(), 1);
+        assert_eq!(wal.pending_count(), 1);
+
+        let pending = WriteAheadLog::new(&dir, 64).recover().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.topic, "t/9");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,13 +1,52 @@
 //! Bridge worker - handles message forwarding with XPUB/XSUB proxy and multi-broker support
 
+use crate::bridge::connections::ConnectionTracker;
+use crate::bridge::convert::{convert_payload, truncate_payload};
+use crate::bridge::dedup::{Seen, first_seen, record_seen};
+use crate::bridge::dryrun::DryRun;
+use crate::bridge::effective::{MQTT_QOS, MQTT_RECONNECT_MAX_SECS, MQTT_RECONNECT_MIN_SECS};
+use crate::bridge::envelope::{TopicCarrier, wrap_envelope};
+use crate::bridge::exec::ExecTransformer;
+use crate::bridge::health::HealthCheck;
+use crate::bridge::json_guard::JsonLimits;
+use crate::bridge::backpressure::{
+    FORWARD_CHANNEL_CAPACITY, ForwardReceiver, ForwardSender, SLOW_CONSUMER_SAMPLE_MS, SlowConsumerDetector, channel_depth, forward_channel,
+};
+use crate::bridge::batch::Batch;
+use crate::bridge::ordering::{OrderingKey, ShardedQueue};
+use crate::bridge::qos::{QosReport, delivered_qos, effective_qos};
+use crate::bridge::reconnect::{ReconnectDecision, ReconnectLimiter};
+use crate::bridge::rewrite::{rewrite_topic, upstream_filters};
+use crate::bridge::sampling::Sampler;
+use crate::bridge::template::{is_template, render_template};
+use crate::bridge::threads::{ThreadActivity, WorkerThread};
+use crate::bridge::timestamp::{TimestampSource, e2e_latency_ms};
+use crate::bridge::topic_mapper::{
+    CompiledMappings, exceeds_topic_levels, is_broad_filter, is_publishable_topic, is_valid_filter,
+};
+use crate::bridge::wal::{DeliveryReceipt, WriteAheadLog};
+use crate::config::{BridgeConfig, BroadSubscriptions, ExtraFrames, SeparatorConflict, ZmqSettings};
 use crate::db::Repository;
-use crate::models::{MqttConfig, ZmqConfig, TopicMapping, ZmqSocketType, EndpointType};
+use crate::models::{
+    ConnectionStatus, DeadLetterMessage, EndpointType, MappingMatch, MatchedTarget, MqttConfig, MqttProtocolVersion, PayloadTransform, RewriteStage,
+    TopicMapping, TopicRewrite, TopicSeparator, TransformErrorPolicy, WorkerThreadInfo, ZmqConfig, ZmqRole, ZmqSocketType,
+};
+use crate::mqtt::{
+    AliasUse, TopicAliases, birth_message, broker_alias_maximum, connect_options, encode_identity, expand_client_id,
+    identity_from_user_properties, ssl_options, string_properties, user_properties,
+};
 use crate::telemetry::metrics;
+use crate::zeromq::{
+    Liveness, SocketMonitor, apply_multicast_options, bind_socket, connect_socket, decode_message,
+    encode_message, has_separator, is_multicast, sign_frames, split_identity_frames, verify_frames, with_identity_frames,
+};
+use parking_lot::{Mutex, RwLock};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread::{self, JoinHandle};
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use tokio::sync::{Notify, mpsc};
 use tracing::{debug, error, info, warn};
 
 /// Message to be forwarded
@@ -17,9 +56,22 @@ pub struct ForwardMessage {
     pub source_id: u32,
     pub topic: String,
     pub payload: Vec<u8>,
+    /// Routing identity frame of a multipart ZMQ message
+    pub identity: Option<Vec<u8>>,
+    /// MQTT v5 user properties of the received message
+    pub user_properties: Vec<(String, String)>,
+    /// Frames of a multipart ZMQ message after the payload frame
+    pub frames: Vec<Vec<u8>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+/// Reasons the worker refuses to start
+#[derive(Debug, thiserror::Error)]
+pub enum StartError {
+    #[error("no enabled endpoints configured")]
+    NoEnabledEndpoints,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MessageSource {
     Mqtt,
     Zmq,
@@ -28,11 +80,30 @@ pub enum MessageSource {
 /// Bridge worker that runs MQTT and ZMQ clients in dedicated threads
 pub struct BridgeWorker {
     running: Arc<AtomicBool>,
-    mqtt_threads: Vec<JoinHandle<()>>,
-    zmq_threads: Vec<JoinHandle<()>>,
-    forward_tx: Option<mpsc::Sender<ForwardMessage>>,
-    /// MQTT command channels for dynamic subscription updates
-    mqtt_cmd_txs: std::collections::HashMap<u32, std::sync::mpsc::Sender<MqttCommand>>,
+    mqtt_threads: Vec<WorkerThread>,
+    zmq_threads: Vec<WorkerThread>,
+    /// What the endpoint threads of the current run share
+    run: Option<RunState>,
+    /// Topic rewrite rules per MQTT broker, applied to subscription updates
+    mqtt_rewrites: HashMap<u32, Vec<TopicRewrite>>,
+    /// `max_subscriptions` of the MQTT brokers that set one
+    mqtt_max_subscriptions: HashMap<u32, u32>,
+    /// Whether each MQTT thread was started speaking MQTT v5
+    mqtt_v5: HashMap<u32, bool>,
+    /// `bridge.broad_subscriptions` of the current run
+    broad_subscriptions: BroadSubscriptions,
+    /// Publish QoS outcomes per mapping for the current run
+    qos_report: Arc<QosReport>,
+    /// Forwarding has stayed behind its input; see `SlowConsumerDetector`
+    slow_consumer: Arc<AtomicBool>,
+    /// Messages waiting in the forwarding channel, kept by its two halves
+    queue_depth: Arc<AtomicU32>,
+    /// Dry-run switch, kept across runs
+    dry_run: Arc<DryRun>,
+    /// Connection state reported by the endpoint threads
+    connections: Arc<ConnectionTracker>,
+    /// Wakes the forwarding task of the current run to exit
+    forward_shutdown: Option<Arc<Notify>>,
 }
 
 impl BridgeWorker {
@@ -41,203 +112,203 @@ impl BridgeWorker {
             running: Arc::new(AtomicBool::new(false)),
             mqtt_threads: vec![],
             zmq_threads: vec![],
-            forward_tx: None,
-            mqtt_cmd_txs: std::collections::HashMap::new(),
+            run: None,
+            mqtt_rewrites: HashMap::new(),
+            mqtt_max_subscriptions: HashMap::new(),
+            mqtt_v5: HashMap::new(),
+            broad_subscriptions: BroadSubscriptions::default(),
+            qos_report: Arc::new(QosReport::new()),
+            slow_consumer: Arc::new(AtomicBool::new(false)),
+            queue_depth: Arc::new(AtomicU32::new(0)),
+            dry_run: Arc::new(DryRun::default()),
+            connections: Arc::new(ConnectionTracker::new()),
+            forward_shutdown: None,
         }
     }
 
+    /// Dry-run switch and report, which the worker consults on every message
+    pub fn dry_run(&self) -> Arc<DryRun> {
+        self.dry_run.clone()
+    }
+
+    /// Connection state of the endpoints, kept up to date by their threads
+    pub fn connections(&self) -> Arc<ConnectionTracker> {
+        self.connections.clone()
+    }
+
+    /// Whether forwarding is persistently falling behind its input
+    pub fn is_slow_consumer(&self) -> bool {
+        self.slow_consumer.load(Ordering::Relaxed)
+    }
+
+    /// Messages waiting in the forwarding channel; 0 while stopped
+    pub fn queue_depth(&self) -> u32 {
+        self.queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Publish QoS outcomes recorded by the MQTT threads
+    pub fn qos_report(&self) -> Arc<QosReport> {
+        self.qos_report.clone()
+    }
+
+    /// Snapshot of every endpoint thread, MQTT first
+    pub fn threads(&self) -> Vec<WorkerThreadInfo> {
+        self.mqtt_threads
+            .iter()
+            .chain(&self.zmq_threads)
+            .map(WorkerThread::info)
+            .collect()
+    }
+
     /// Start the bridge worker with extended multi-config support
     pub fn start_extended(
         &mut self,
         mqtt_configs: Vec<MqttConfig>,
         zmq_configs: Vec<ZmqConfig>,
-        mappings_cache: Arc<tokio::sync::RwLock<Vec<TopicMapping>>>,
+        mappings_cache: Arc<tokio::sync::RwLock<CompiledMappings>>,
         repo: Repository,
+        settings: BridgeConfig,
+        zmq_settings: &ZmqSettings,
     ) -> Result<(), anyhow::Error> {
         if self.running.load(Ordering::SeqCst) {
             return Ok(());
         }
 
+        // Zero worker threads would report Running while nothing is connected
+        if !mqtt_configs.iter().any(|c| c.enabled) && !zmq_configs.iter().any(|c| c.enabled) {
+            return Err(StartError::NoEnabledEndpoints.into());
+        }
+
         self.running.store(true, Ordering::SeqCst);
 
         // Create channels for message forwarding
-        let (forward_tx, mut forward_rx) = mpsc::channel::<ForwardMessage>(1000);
-        
-        // Command channels for each endpoint
-        let mut mqtt_cmd_txs: std::collections::HashMap<u32, std::sync::mpsc::Sender<MqttCommand>> = std::collections::HashMap::new();
-        let mut zmq_cmd_txs: std::collections::HashMap<u32, std::sync::mpsc::Sender<ZmqCommand>> = std::collections::HashMap::new();
+        let (forward_tx, forward_rx) = forward_channel::<ForwardMessage>(
+            FORWARD_CHANNEL_CAPACITY,
+            settings.forward_overflow,
+            settings.dead_letter_overflow,
+            self.queue_depth.clone(),
+        );
+
+        self.qos_report.clear();
+        self.broad_subscriptions = settings.broad_subscriptions;
+
+        // Endpoint tables start empty and are filled as each endpoint starts
+        let ctx = ForwardContext {
+            mqtt_cmd_txs: Arc::default(),
+            zmq_cmd_txs: Arc::default(),
+            mappings_cache: mappings_cache.clone(),
+            repo: repo.clone(),
+            sampler: Arc::new(Sampler::new()),
+            separator_conflict: settings.separator_conflict,
+            json_limits: JsonLimits::from_settings(&settings),
+            max_topic_levels: settings.max_topic_levels,
+            max_payload_bytes: settings.max_payload_bytes,
+            topic_rewrites: Arc::default(),
+            egress_rewrites: Arc::default(),
+            dry_run: self.dry_run.clone(),
+            exec: ExecTransformer::from_settings(&settings).map(Arc::new),
+            wal: WriteAheadLog::from_settings(&settings).map(Arc::new),
+            zmq_separators: Arc::default(),
+            extra_frames: settings.zmq_extra_frames,
+            dedup_ttl: Duration::from_secs(settings.dedup_ttl_secs),
+            user_property_frames: Arc::default(),
+        };
+
+        // One context for all ZMQ threads: its I/O threads serve every socket
+        let zmq_context = zmq::Context::new();
+        if let Err(e) = zmq_context.set_io_threads(zmq_settings.io_threads.max(1)) {
+            warn!("Failed to set ZMQ I/O threads to {}: {}", zmq_settings.io_threads, e);
+        }
 
-        self.forward_tx = Some(forward_tx.clone());
+        self.run = Some(RunState {
+            forward_tx: forward_tx.clone(),
+            mappings_cache: mappings_cache.clone(),
+            subscribe_limits: SubscribeLimits::from_settings(&settings),
+            reconnect_limiter: Arc::new(ReconnectLimiter::from_settings(&settings)),
+            settings: settings.clone(),
+            zmq_context,
+            forward: ctx.clone(),
+            mqtt_cmd_rxs: HashMap::new(),
+            zmq_cmd_rxs: HashMap::new(),
+        });
 
         // Start MQTT threads for each enabled broker
         for config in mqtt_configs.iter().filter(|c| c.enabled) {
-            let (mqtt_cmd_tx, mqtt_cmd_rx) = std::sync::mpsc::channel::<MqttCommand>();
-            let config_id = config.id.unwrap_or(0);
-            mqtt_cmd_txs.insert(config_id, mqtt_cmd_tx);
-            
-            // Get initial topics from mappings cache
-            // New topics can be subscribed dynamically via MqttCommand::Subscribe
-            let subscribe_topics: Vec<String> = {
-                if let Ok(guard) = mappings_cache.try_read() {
-                    guard.iter()
-                        .filter(|m| m.enabled && m.source_endpoint_type == EndpointType::Mqtt && m.source_endpoint_id == config_id)
-                        .map(|m| m.source_topic.clone())
-                        .collect()
-                } else {
-                    vec![]
-                }
-            };
-
-            let running_mqtt = self.running.clone();
-            let forward_tx_mqtt = forward_tx.clone();
-            let config_clone = config.clone();
-
-            let mqtt_thread = thread::spawn(move || {
-                run_mqtt_worker(
-                    running_mqtt,
-                    config_clone,
-                    subscribe_topics,
-                    forward_tx_mqtt,
-                    mqtt_cmd_rx,
-                );
-            });
-
-            self.mqtt_threads.push(mqtt_thread);
+            self.start_mqtt_endpoint(config);
         }
 
         // Start ZMQ threads for each enabled config (XPUB/XSUB pattern)
         for config in zmq_configs.iter().filter(|c| c.enabled) {
-            let (zmq_cmd_tx, zmq_cmd_rx) = std::sync::mpsc::channel::<ZmqCommand>();
-            let config_id = config.id.unwrap_or(0);
-            zmq_cmd_txs.insert(config_id, zmq_cmd_tx);
-
-            let running_zmq = self.running.clone();
-            let forward_tx_zmq = forward_tx.clone();
-            let config_clone = config.clone();
-
-            let zmq_thread = thread::spawn(move || {
-                run_zmq_worker(
-                    running_zmq,
-                    config_clone,
-                    forward_tx_zmq,
-                    zmq_cmd_rx,
-                );
-            });
-
-            self.zmq_threads.push(zmq_thread);
+            self.start_zmq_endpoint(config);
         }
 
-        // Store MQTT command channels for dynamic subscription updates
-        self.mqtt_cmd_txs = mqtt_cmd_txs.clone();
-
         // Start forwarding task
-        let running_fwd = self.running.clone();
-        let repo_fwd = repo.clone();
-        let mappings_cache_fwd = mappings_cache.clone();
-
-        tokio::spawn(async move {
-            while running_fwd.load(Ordering::SeqCst) {
-                tokio::select! {
-                    Some(msg) = forward_rx.recv() => {
-                        let forward_start = Instant::now();
-                        info!("Received message from {:?} id={}: topic={}", msg.source, msg.source_id, msg.topic);
-                        
-                        // Track received stats (both DB and telemetry)
-                        match msg.source {
-                            MessageSource::Mqtt => {
-                                metrics().record_mqtt_received();
-                                let _ = repo_fwd.increment_stats(1, 0, 0, 0, 0).await;
-                            }
-                            MessageSource::Zmq => {
-                                metrics().record_zmq_received();
-                                let _ = repo_fwd.increment_stats(0, 0, 1, 0, 0).await;
-                            }
-                        }
-                        
-                        // Read mappings from shared cache (fast, in-memory)
-                        let mappings = mappings_cache_fwd.read().await;
-                        
-                        let mut matched = false;
-                        // Find matching mappings
-                        for mapping in mappings.iter().filter(|m| m.enabled) {
-                            // Check if source matches
-                            let source_matches = match msg.source {
-                                MessageSource::Mqtt => {
-                                    mapping.source_endpoint_type == EndpointType::Mqtt
-                                        && mapping.source_endpoint_id == msg.source_id
-                                        && matches_topic_pattern(&mapping.source_topic, &msg.topic)
-                                }
-                                MessageSource::Zmq => {
-                                    mapping.source_endpoint_type == EndpointType::Zmq
-                                        && mapping.source_endpoint_id == msg.source_id
-                                        && matches_topic_pattern(&mapping.source_topic, &msg.topic)
-                                }
-                            };
-
-                            if source_matches {
-                                matched = true;
-                                let target_topic = apply_mapping(&mapping.source_topic, &mapping.target_topic, &msg.topic);
-                                
-                                match mapping.target_endpoint_type {
-                                    EndpointType::Mqtt => {
-                                        if let Some(tx) = mqtt_cmd_txs.get(&mapping.target_endpoint_id) {
-                                            info!("Forwarding to MQTT endpoint {}: {}", mapping.target_endpoint_id, target_topic);
-                                            let _ = tx.send(MqttCommand::Publish(target_topic, msg.payload.clone()));
-                                            metrics().record_mqtt_sent();
-                                            let _ = repo_fwd.increment_stats(0, 1, 0, 0, 0).await;
-                                        } else {
-                                            metrics().record_error();
-                                            warn!("MQTT endpoint {} not found!", mapping.target_endpoint_id);
-                                        }
-                                    }
-                                    EndpointType::Zmq => {
-                                        if let Some(tx) = zmq_cmd_txs.get(&mapping.target_endpoint_id) {
-                                            info!("Forwarding to ZMQ endpoint {}: {}", mapping.target_endpoint_id, target_topic);
-                                            let _ = tx.send(ZmqCommand::Publish(target_topic, msg.payload.clone()));
-                                            metrics().record_zmq_sent();
-                                            let _ = repo_fwd.increment_stats(0, 0, 0, 1, 0).await;
-                                        } else {
-                                            metrics().record_error();
-                                            warn!("ZMQ endpoint {} not found!", mapping.target_endpoint_id);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        
-                        if !matched {
-                            debug!("No matching mapping found for topic: {}", msg.topic);
-                        } else {
-                            // Record forwarding latency
-                            let latency_ms = forward_start.elapsed().as_secs_f64() * 1000.0;
-                            metrics().record_latency(latency_ms);
-                        }
-                    }
-                    else => {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        let forward_shards = settings.forward_shards;
+
+        if let Some(wal) = ctx.wal.clone() {
+            let running_compact = self.running.clone();
+            let interval = std::time::Duration::from_secs(settings.wal_compact_interval_secs.max(1));
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.tick().await;
+                while running_compact.load(Ordering::SeqCst) {
+                    ticker.tick().await;
+                    let wal = wal.clone();
+                    match tokio::task::spawn_blocking(move || wal.compact()).await {
+                        Ok(Err(e)) => warn!("Write-ahead log compaction failed: {}", e),
+                        Err(e) => warn!("Write-ahead log compaction panicked: {}", e),
+                        Ok(Ok(())) => {}
                     }
                 }
+            });
+        }
+
+        // Sample the forwarding channel depth; a weak sender does not keep the channel open
+        self.slow_consumer.store(false, Ordering::Relaxed);
+        metrics().set_slow_consumer(false);
+        let mut detector = SlowConsumerDetector::from_settings(&settings, self.slow_consumer.clone());
+        let depth_tx = forward_tx.downgrade();
+        let running_depth = self.running.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_millis(SLOW_CONSUMER_SAMPLE_MS));
+            while running_depth.load(Ordering::SeqCst) {
+                ticker.tick().await;
+                let Some(tx) = depth_tx.upgrade() else { break };
+                detector.sample(channel_depth(&tx), Instant::now());
             }
         });
 
+        let shutdown = Arc::new(Notify::new());
+        self.forward_shutdown = Some(shutdown.clone());
+        tokio::spawn(run_forwarding(ctx, forward_rx, forward_shards, shutdown));
+
         info!("Bridge worker started with {} MQTT brokers and {} ZMQ endpoints", 
               mqtt_configs.iter().filter(|c| c.enabled).count(),
               zmq_configs.iter().filter(|c| c.enabled).count());
         Ok(())
     }
 
+    /// Forget the sampling state of mappings not in `mapping_ids`
+    pub fn retain_samples(&self, mapping_ids: &[u32]) {
+        if let Some(ref run) = self.run {
+            run.forward.sampler.retain_mappings(mapping_ids);
+        }
+    }
+
     /// Update MQTT subscriptions dynamically based on new mappings
     pub fn update_subscriptions(&self, mappings: &[TopicMapping]) {
-        for (config_id, tx) in &self.mqtt_cmd_txs {
+        let Some(ref run) = self.run else { return };
+        for (config_id, tx) in run.forward.mqtt_cmd_txs.read().iter() {
             // Get topics for this MQTT broker from the mappings
-            let topics: Vec<String> = mappings
-                .iter()
-                .filter(|m| m.enabled && m.source_endpoint_type == EndpointType::Mqtt && m.source_endpoint_id == *config_id)
-                .map(|m| m.source_topic.clone())
-                .collect();
-            
+            let rules = self.mqtt_rewrites.get(config_id).map(Vec::as_slice).unwrap_or_default();
+            let (topics, qos) = cap_subscriptions(
+                &format!("broker {}", config_id),
+                broker_subscriptions(mappings, *config_id, rules, self.broad_subscriptions),
+                self.mqtt_max_subscriptions.get(config_id).copied(),
+            );
+
             if !topics.is_empty() {
-                if let Err(e) = tx.send(MqttCommand::Subscribe(topics.clone())) {
+                if let Err(e) = tx.send(MqttCommand::Subscribe(topics.clone(), qos)) {
                     error!("Failed to send subscribe command: {}", e);
                 } else {
                     info!("Sent subscribe command for topics: {:?}", topics);
@@ -246,19 +317,227 @@ impl BridgeWorker {
         }
     }
 
+    /// Start the thread of an MQTT broker, e.g. one added while the bridge
+    /// runs. A broker that had a thread before picks up the commands queued
+    /// for it in the meantime.
+    pub fn start_mqtt_endpoint(&mut self, config: &MqttConfig) {
+        let Some(ref mut run) = self.run else { return };
+        let config_id = config.id.unwrap_or(0);
+
+        let cmd_rx = run
+            .mqtt_cmd_rxs
+            .entry(config_id)
+            .or_insert_with(|| {
+                let (tx, rx) = std::sync::mpsc::channel::<MqttCommand>();
+                run.forward.mqtt_cmd_txs.write().insert(config_id, tx);
+                Arc::new(Mutex::new(rx))
+            })
+            .clone();
+        run.forward.route_endpoint(
+            (MessageSource::Mqtt, config_id),
+            &config.topic_rewrites,
+            config.rewrite_stage,
+        );
+        let mut frames = run.forward.user_property_frames.write();
+        if config.protocol_version == MqttProtocolVersion::V5 {
+            frames.insert(config_id);
+        } else {
+            frames.remove(&config_id);
+        }
+        drop(frames);
+
+        self.mqtt_rewrites.insert(config_id, ingress_rules(config).to_vec());
+        match config.max_subscriptions {
+            Some(max) => self.mqtt_max_subscriptions.insert(config_id, max),
+            None => self.mqtt_max_subscriptions.remove(&config_id),
+        };
+
+        // Get initial topics from mappings cache
+        // New topics can be subscribed dynamically via MqttCommand::Subscribe
+        let mappings_cache = &run.mappings_cache;
+        let (subscribe_topics, subscribe_qos) = match mappings_cache.try_read() {
+            Ok(guard) => cap_subscriptions(
+                &config.name,
+                broker_subscriptions(&guard, config_id, ingress_rules(config), run.settings.broad_subscriptions),
+                config.max_subscriptions,
+            ),
+            Err(_) => (vec![], HashMap::new()),
+        };
+
+        let mqtt_v5 = mappings_cache.try_read().map_or(
+            config.protocol_version == MqttProtocolVersion::V5 || config.topic_aliases,
+            |guard| needs_mqtt_v5(config, &guard),
+        );
+        self.mqtt_v5.insert(config_id, mqtt_v5);
+
+        let running_mqtt = self.running.clone();
+        let forward_tx_mqtt = run.forward_tx.clone();
+        let config_clone = config.clone();
+        let limits = run.subscribe_limits.clone();
+        let qos_report = self.qos_report.clone();
+        let limiter = run.reconnect_limiter.clone();
+        let health = HealthCheck::from_settings(&run.settings, EndpointType::Mqtt, config_id);
+        let connections = self.connections.clone();
+
+        let mqtt_thread = WorkerThread::spawn(EndpointType::Mqtt, config_id, &config.name, move |activity| {
+            run_mqtt_worker(
+                running_mqtt,
+                config_clone,
+                subscribe_topics,
+                subscribe_qos,
+                forward_tx_mqtt,
+                cmd_rx,
+                limits,
+                mqtt_v5,
+                qos_report,
+                activity,
+                limiter,
+                health,
+                connections,
+            );
+        });
+
+        self.mqtt_threads.push(mqtt_thread);
+    }
+
+    /// Start the thread of a ZMQ endpoint, e.g. one added while the bridge
+    /// runs. An endpoint that had a thread before picks up the commands queued
+    /// for it in the meantime.
+    pub fn start_zmq_endpoint(&mut self, config: &ZmqConfig) {
+        let Some(ref mut run) = self.run else { return };
+        let config_id = config.id.unwrap_or(0);
+
+        let cmd_rx = run
+            .zmq_cmd_rxs
+            .entry(config_id)
+            .or_insert_with(|| {
+                let (tx, rx) = std::sync::mpsc::channel::<ZmqCommand>();
+                run.forward.zmq_cmd_txs.write().insert(config_id, tx);
+                Arc::new(Mutex::new(rx))
+            })
+            .clone();
+        run.forward.route_endpoint(
+            (MessageSource::Zmq, config_id),
+            &config.topic_rewrites,
+            config.rewrite_stage,
+        );
+        run.forward.zmq_separators.write().insert(
+            config_id,
+            (config.socket_type != ZmqSocketType::Push).then_some(config.topic_separator),
+        );
+
+        let running_zmq = self.running.clone();
+        let forward_tx_zmq = run.forward_tx.clone();
+        let config_clone = config.clone();
+        let context = run.zmq_context.clone();
+        let health = HealthCheck::from_settings(&run.settings, EndpointType::Zmq, config_id);
+        let connections = self.connections.clone();
+
+        let zmq_thread = WorkerThread::spawn(EndpointType::Zmq, config_id, &config.name, move |activity| {
+            run_zmq_worker(
+                context,
+                running_zmq,
+                config_clone,
+                forward_tx_zmq,
+                cmd_rx,
+                activity,
+                health,
+                connections,
+            );
+        });
+
+        self.zmq_threads.push(zmq_thread);
+    }
+
+    /// Ask the thread of one endpoint to exit, leaving the others running,
+    /// and return it for the caller to join without holding the worker.
+    /// Messages for the endpoint keep queuing until it is started again or
+    /// removed; a restarted thread waits for the old one to let go of them.
+    pub fn stop_endpoint(&mut self, endpoint_type: &EndpointType, endpoint_id: u32) -> Option<WorkerThread> {
+        let threads = match endpoint_type {
+            EndpointType::Mqtt => &mut self.mqtt_threads,
+            EndpointType::Zmq => &mut self.zmq_threads,
+        };
+        let index = threads.iter().position(|t| t.endpoint_id() == endpoint_id)?;
+        let thread = threads.remove(index);
+        thread.request_stop();
+        Some(thread)
+    }
+
+    /// Restart the MQTT threads whose mappings now need a different protocol
+    /// than they were started with, e.g. after a mapping started carrying
+    /// user properties. Returns the stopped threads for the caller to join.
+    pub fn restart_for_protocol(&mut self, configs: &[MqttConfig], mappings: &[TopicMapping]) -> Vec<WorkerThread> {
+        let mut stopped = Vec::new();
+        for config in configs {
+            let id = config.id.unwrap_or(0);
+            let Some(&running_v5) = self.mqtt_v5.get(&id) else { continue };
+            if running_v5 != needs_mqtt_v5(config, mappings) {
+                info!("[MQTT:{}] Reconnecting to switch MQTT protocol version", config.name);
+                stopped.extend(self.stop_endpoint(&EndpointType::Mqtt, id));
+                self.start_mqtt_endpoint(config);
+            }
+        }
+        stopped
+    }
+
+    /// Stop an endpoint that is no longer configured; messages for it are
+    /// dead-lettered from then on. Returns its thread as `stop_endpoint` does.
+    pub fn remove_endpoint(&mut self, endpoint_type: &EndpointType, endpoint_id: u32) -> Option<WorkerThread> {
+        let thread = self.stop_endpoint(endpoint_type, endpoint_id);
+        if let Some(ref mut run) = self.run {
+            match endpoint_type {
+                EndpointType::Mqtt => {
+                    run.mqtt_cmd_rxs.remove(&endpoint_id);
+                    run.forward.mqtt_cmd_txs.write().remove(&endpoint_id);
+                    run.forward.user_property_frames.write().remove(&endpoint_id);
+                    run.forward.route_endpoint((MessageSource::Mqtt, endpoint_id), &[], RewriteStage::default());
+                }
+                EndpointType::Zmq => {
+                    run.zmq_cmd_rxs.remove(&endpoint_id);
+                    run.forward.zmq_cmd_txs.write().remove(&endpoint_id);
+                    run.forward.zmq_separators.write().remove(&endpoint_id);
+                    run.forward.route_endpoint((MessageSource::Zmq, endpoint_id), &[], RewriteStage::default());
+                }
+            }
+        }
+        if *endpoint_type == EndpointType::Mqtt {
+            self.mqtt_rewrites.remove(&endpoint_id);
+            self.mqtt_max_subscriptions.remove(&endpoint_id);
+            self.mqtt_v5.remove(&endpoint_id);
+        }
+        self.connections.remove(endpoint_type, endpoint_id);
+        thread
+    }
+
     /// Stop the bridge worker
     pub fn stop(&mut self) {
         self.running.store(false, Ordering::SeqCst);
+        // A stored permit covers a task that is not waiting right now
+        if let Some(shutdown) = self.forward_shutdown.take() {
+            shutdown.notify_one();
+        }
+        let had_threads = !self.mqtt_threads.is_empty() || !self.zmq_threads.is_empty();
         
         // Wait for threads to finish
-        for handle in self.mqtt_threads.drain(..) {
-            let _ = handle.join();
+        for thread in self.mqtt_threads.drain(..) {
+            thread.join();
         }
-        for handle in self.zmq_threads.drain(..) {
-            let _ = handle.join();
+        for thread in self.zmq_threads.drain(..) {
+            thread.join();
+        }
+        // Every socket is closed now, so terminating the context cannot block
+        self.run = None;
+        self.mqtt_v5.clear();
+
+        self.slow_consumer.store(false, Ordering::Relaxed);
+        metrics().set_slow_consumer(false);
+        self.queue_depth.store(0, Ordering::Relaxed);
+        // Results of stopped endpoints would otherwise linger as current
+        if had_threads {
+            metrics().clear_endpoint_health();
+            self.connections.clear();
         }
-        
-        self.forward_tx = None;
         info!("Bridge worker stopped");
     }
 
@@ -279,43 +558,1191 @@ impl Drop for BridgeWorker {
     }
 }
 
+/// Forward messages from the endpoint threads until `shutdown` is notified or
+/// every sender is gone, parked on the channel while there is no traffic
+async fn run_forwarding(
+    ctx: ForwardContext,
+    mut forward_rx: ForwardReceiver<ForwardMessage>,
+    forward_shards: usize,
+    shutdown: Arc<Notify>,
+) {
+    replay_wal(&ctx).await;
+
+    // With several shards, messages sharing an ordering key stay on one
+    // shard (strictly ordered) while different keys forward concurrently
+    let shards = (forward_shards > 1).then(|| {
+        let shard_ctx = ctx.clone();
+        ShardedQueue::new(forward_shards, 1000, move |msg: ForwardMessage| {
+            let ctx = shard_ctx.clone();
+            async move { forward_message(&ctx, msg).await }
+        })
+    });
+
+    loop {
+        let msg = tokio::select! {
+            // Checked first so a stop is not held up by a busy channel
+            biased;
+            _ = shutdown.notified() => break,
+            msg = forward_rx.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+        };
+        let msg = rewrite_incoming(&ctx, msg);
+        match shards {
+            Some(ref shards) => {
+                let key = ordering_key_for(&ctx, &msg).await;
+                shards.dispatch(&key, msg).await;
+            }
+            None => forward_message(&ctx, msg).await,
+        }
+    }
+    debug!("Forwarding task stopped");
+}
+
+/// Hand a received message to the forwarding task, counting (and optionally
+/// dead-lettering) whatever the channel's overflow policy dropped for it
+async fn queue_forward(
+    forward_tx: &ForwardSender<ForwardMessage>,
+    msg: ForwardMessage,
+) -> Result<(), mpsc::error::SendError<ForwardMessage>> {
+    for dropped in forward_tx.send(msg).await? {
+        metrics().record_error();
+        metrics().record_forward_overflow();
+        debug!("Forwarding channel full, dropped message on topic {}", dropped.topic);
+        if forward_tx.dead_letter_drops() {
+            dead_letter(&dropped, "forwarding channel full".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// What the endpoint threads of a run are started with, kept so single
+/// endpoints can be restarted while the others keep running
+struct RunState {
+    forward_tx: ForwardSender<ForwardMessage>,
+    mappings_cache: Arc<tokio::sync::RwLock<CompiledMappings>>,
+    settings: BridgeConfig,
+    subscribe_limits: SubscribeLimits,
+    reconnect_limiter: Arc<ReconnectLimiter>,
+    /// Context shared by the ZMQ threads of the run
+    zmq_context: zmq::Context,
+    /// Shares its endpoint tables with the forwarding task
+    forward: ForwardContext,
+    /// Command receivers, handed to each thread of an endpoint in turn so
+    /// commands queued while it restarts are not lost
+    mqtt_cmd_rxs: HashMap<u32, Arc<Mutex<std::sync::mpsc::Receiver<MqttCommand>>>>,
+    zmq_cmd_rxs: HashMap<u32, Arc<Mutex<std::sync::mpsc::Receiver<ZmqCommand>>>>,
+}
+
+/// Throttling applied to MQTT subscribe calls
+#[derive(Clone)]
+struct SubscribeLimits {
+    batch_size: usize,
+    batch_delay: std::time::Duration,
+    permits: Option<Arc<tokio::sync::Semaphore>>,
+    /// Re-issue subscriptions after a reconnect that lost the session
+    resubscribe_on_reconnect: bool,
+}
+
+impl SubscribeLimits {
+    fn from_settings(settings: &BridgeConfig) -> Self {
+        Self {
+            batch_size: settings.subscribe_batch_size,
+            batch_delay: std::time::Duration::from_millis(settings.subscribe_batch_delay_ms),
+            // Shared by all MQTT threads so a reload cannot flood every broker at once
+            permits: (settings.subscribe_concurrency > 0)
+                .then(|| Arc::new(tokio::sync::Semaphore::new(settings.subscribe_concurrency))),
+            resubscribe_on_reconnect: settings.resubscribe_on_reconnect,
+        }
+    }
+}
+
+/// Whether subscriptions must be re-issued after a reconnect: clean sessions
+/// always start empty, persistent ones only when the broker dropped the session
+fn should_resubscribe(limits: &SubscribeLimits, clean_session: bool, session_present: bool) -> bool {
+    limits.resubscribe_on_reconnect && (clean_session || !session_present)
+}
+
+/// Filters to subscribe on MQTT broker `config_id` and the QoS of each: the
+/// highest `qos` among enabled mappings sharing the source topic. Filters
+/// added for topic rewrites take the highest QoS of the broker's mappings.
+fn broker_subscriptions(
+    mappings: &[TopicMapping],
+    config_id: u32,
+    rules: &[TopicRewrite],
+    broad: BroadSubscriptions,
+) -> (Vec<String>, HashMap<String, i32>) {
+    let mut topics = Vec::new();
+    let mut qos: HashMap<String, i32> = HashMap::new();
+    for m in mappings
+        .iter()
+        .filter(|m| m.enabled && m.source_endpoint_type == EndpointType::Mqtt && m.source_endpoint_id == config_id)
+    {
+        if is_broad_filter(&m.source_topic) && !m.allow_broad_subscription {
+            if broad == BroadSubscriptions::Reject {
+                warn!(
+                    "Mapping {} not subscribed: '{}' matches every topic and allow_broad_subscription is not set",
+                    m.id, m.source_topic
+                );
+                continue;
+            }
+            warn!("Mapping {} subscribes to every topic on broker {} with '{}'", m.id, config_id, m.source_topic);
+        }
+        topics.push(m.source_topic.clone());
+        let level = qos.entry(m.source_topic.clone()).or_insert(0);
+        *level = (*level).max(m.qos as i32);
+    }
+
+    let highest = qos.values().copied().max().unwrap_or(MQTT_QOS);
+    let filters = upstream_filters(rules, &topics);
+    for filter in &filters {
+        qos.entry(filter.clone()).or_insert(highest);
+    }
+    (filters, qos)
+}
+
+/// Keep the first `max` filters of a broker's subscribe set, so a runaway
+/// mapping set cannot flood the broker with subscriptions; unset keeps all
+fn cap_subscriptions(
+    name: &str,
+    (mut topics, mut qos): (Vec<String>, HashMap<String, i32>),
+    max: Option<u32>,
+) -> (Vec<String>, HashMap<String, i32>) {
+    let Some(max) = max.map(|max| max as usize).filter(|&max| topics.len() > max) else {
+        return (topics, qos);
+    };
+    let dropped = topics.split_off(max);
+    for topic in &dropped {
+        qos.remove(topic);
+    }
+    metrics().record_subscriptions_truncated(dropped.len());
+    warn!(
+        "[MQTT:{}] {} topic filters exceed max_subscriptions {}; not subscribing to {:?}",
+        name,
+        topics.len() + dropped.len(),
+        max,
+        dropped
+    );
+    (topics, qos)
+}
+
+/// Split a topic list into subscribe batches; 0 disables chunking
+fn subscribe_batches(topics: &[String], batch_size: usize) -> Vec<&[String]> {
+    if batch_size == 0 || topics.len() <= batch_size {
+        return vec![topics];
+    }
+    topics.chunks(batch_size).collect()
+}
+
+/// Split subscription filters into well-formed and invalid ones
+fn split_valid_filters(topics: &[String]) -> (Vec<String>, Vec<String>) {
+    topics.iter().cloned().partition(|t| is_valid_filter(t))
+}
+
+/// Subscribe to `topics` at their QoS in `qos` (default 1) in batches,
+/// honoring the shared concurrency limit.
+/// Invalid filters are skipped and a batch the broker fails is retried one
+/// topic at a time, so one bad filter cannot cost the others their
+/// subscription. Returns the filters that could not be subscribed.
+async fn subscribe_batched(
+    client: &paho_mqtt::AsyncClient,
+    name: &str,
+    topics: &[String],
+    qos: &HashMap<String, i32>,
+    limits: &SubscribeLimits,
+) -> Vec<String> {
+    let (valid, mut failed) = split_valid_filters(topics);
+    for topic in &failed {
+        warn!("[MQTT:{}] Skipping invalid topic filter '{}'", name, topic);
+        metrics().record_subscribe_failure();
+    }
+
+    let batches = subscribe_batches(&valid, limits.batch_size);
+    let last = batches.len().saturating_sub(1);
+
+    for (i, batch) in batches.into_iter().enumerate() {
+        let _permit = match limits.permits {
+            Some(ref permits) => permits.acquire().await.ok(),
+            None => None,
+        };
+
+        let qos_of = |topic: &String| qos.get(topic).copied().unwrap_or(MQTT_QOS);
+        let levels: Vec<i32> = batch.iter().map(qos_of).collect();
+        let topics_ref: Vec<&str> = batch.iter().map(|s| s.as_str()).collect();
+        match client.subscribe_many(&topics_ref, &levels).await {
+            Ok(rsp) => {
+                // Return codes of 0x80 and above refuse a single filter
+                let codes = rsp.subscribe_many_response().unwrap_or_default();
+                for (topic, code) in batch.iter().zip(codes).filter(|(_, code)| *code >= 0x80) {
+                    warn!("[MQTT:{}] Broker refused topic filter '{}' (code {:#04x})", name, topic, code);
+                    metrics().record_subscribe_failure();
+                    failed.push(topic.clone());
+                }
+            }
+            Err(e) => {
+                warn!("[MQTT:{}] Subscribing a batch of {} topics failed ({}); retrying individually", name, batch.len(), e);
+                for topic in batch {
+                    if let Err(e) = client.subscribe(topic, qos_of(topic)).await {
+                        warn!("[MQTT:{}] Failed to subscribe to '{}': {}", name, topic, e);
+                        metrics().record_subscribe_failure();
+                        failed.push(topic.clone());
+                    }
+                }
+            }
+        }
+
+        if i < last {
+            debug!("[MQTT:{}] Subscribed batch {} of {} topics", name, i + 1, batch.len());
+            tokio::time::sleep(limits.batch_delay).await;
+        }
+    }
+
+    failed
+}
+
+/// Handles needed to route a message to its target endpoints
+#[derive(Clone)]
+struct ForwardContext {
+    mqtt_cmd_txs: Arc<RwLock<HashMap<u32, std::sync::mpsc::Sender<MqttCommand>>>>,
+    zmq_cmd_txs: Arc<RwLock<HashMap<u32, std::sync::mpsc::Sender<ZmqCommand>>>>,
+    mappings_cache: Arc<tokio::sync::RwLock<CompiledMappings>>,
+    repo: Repository,
+    sampler: Arc<Sampler>,
+    separator_conflict: SeparatorConflict,
+    json_limits: JsonLimits,
+    /// Messages with deeper topics are dropped before mapping evaluation
+    max_topic_levels: Option<usize>,
+    /// Payload size limit of mappings without their own `max_payload_bytes`
+    max_payload_bytes: Option<usize>,
+    /// Rewrite rules applied to topics received from each endpoint
+    topic_rewrites: Arc<RwLock<HashMap<(MessageSource, u32), Vec<TopicRewrite>>>>,
+    /// Rewrite rules applied to target topics of messages sent to each endpoint
+    egress_rewrites: Arc<RwLock<HashMap<(MessageSource, u32), Vec<TopicRewrite>>>>,
+    /// Transform processes; `None` unless `bridge.exec_transforms` is set
+    exec: Option<Arc<ExecTransformer>>,
+    /// Log of messages matched by durable mappings; `None` without a home directory
+    wal: Option<Arc<WriteAheadLog>>,
+    /// Topic/payload layout of each ZMQ endpoint; `None` for PUSH endpoints,
+    /// which send no topic
+    zmq_separators: Arc<RwLock<HashMap<u32, Option<TopicSeparator>>>>,
+    /// Handling of extra multipart frames for targets that cannot carry them
+    extra_frames: ExtraFrames,
+    /// MQTT brokers configured for v5, whose user properties travel to ZMQ
+    /// targets as a trailing frame
+    user_property_frames: Arc<RwLock<HashSet<u32>>>,
+    /// How long mappings with a `dedup_key` drop repeats of a key
+    dedup_ttl: Duration,
+    /// Records publishes instead of making them while enabled
+    dry_run: Arc<DryRun>,
+}
+
+impl ForwardContext {
+    /// Enter an endpoint's rewrite rules into the ingress and egress tables
+    fn route_endpoint(&self, endpoint: (MessageSource, u32), rules: &[TopicRewrite], stage: RewriteStage) {
+        for (table, applies) in [(&self.topic_rewrites, stage.ingress()), (&self.egress_rewrites, stage.egress())] {
+            let mut table = table.write();
+            if applies && !rules.is_empty() {
+                table.insert(endpoint, rules.to_vec());
+            } else {
+                table.remove(&endpoint);
+            }
+        }
+    }
+}
+
+/// Rewrite rules of an MQTT broker that apply to received topics, which its
+/// subscriptions must also cover
+fn ingress_rules(config: &MqttConfig) -> &[TopicRewrite] {
+    match config.rewrite_stage.ingress() {
+        true => &config.topic_rewrites,
+        false => &[],
+    }
+}
+
+/// Ingress stage: normalize a received topic with its source endpoint's rewrite rules
+fn rewrite_incoming(ctx: &ForwardContext, mut msg: ForwardMessage) -> ForwardMessage {
+    if let Some(rules) = ctx.topic_rewrites.read().get(&(msg.source, msg.source_id))
+        && let Some(topic) = rewrite_topic(rules, &msg.topic)
+    {
+        debug!("Rewrote topic {} from {:?} id={} to {}", msg.topic, msg.source, msg.source_id, topic);
+        msg.topic = topic;
+    }
+    msg
+}
+
+/// Egress stage: rewrite a resolved target topic with its target endpoint's rules
+fn rewrite_outgoing(ctx: &ForwardContext, endpoint_type: &EndpointType, endpoint_id: u32, topic: String) -> String {
+    let target = match endpoint_type {
+        EndpointType::Mqtt => MessageSource::Mqtt,
+        EndpointType::Zmq => MessageSource::Zmq,
+    };
+    let rewritten = ctx.egress_rewrites.read().get(&(target, endpoint_id)).and_then(|rules| rewrite_topic(rules, &topic));
+    match rewritten {
+        Some(rewritten) => {
+            debug!("Rewrote target topic {} for {:?} id={} to {}", topic, target, endpoint_id, rewritten);
+            rewritten
+        }
+        None => topic,
+    }
+}
+
+/// Routing identity to carry across a mapping, if it opts in
+fn message_identity(mapping: &TopicMapping, msg: &ForwardMessage) -> Option<Vec<u8>> {
+    let name = mapping.identity_property.as_deref()?;
+    match msg.source {
+        MessageSource::Zmq => msg.identity.clone(),
+        MessageSource::Mqtt => identity_from_user_properties(&msg.user_properties, name),
+    }
+}
+
+/// Whether a mapping sends MQTT v5 user properties
+fn uses_user_properties(mapping: &TopicMapping) -> bool {
+    mapping.identity_property.is_some()
+        || matches!(
+            mapping.source_topic_carrier.as_deref().and_then(TopicCarrier::parse),
+            Some(TopicCarrier::Property(_))
+        )
+}
+
+/// User properties of a message from a v5-configured broker as a JSON object
+/// frame; `None` if there are none to carry
+fn user_property_frame(ctx: &ForwardContext, msg: &ForwardMessage) -> Option<Vec<u8>> {
+    if msg.source != MessageSource::Mqtt
+        || msg.user_properties.is_empty()
+        || !ctx.user_property_frames.read().contains(&msg.source_id)
+    {
+        return None;
+    }
+    let properties: serde_json::Map<String, serde_json::Value> = msg
+        .user_properties
+        .iter()
+        .map(|(k, v)| (k.clone(), serde_json::Value::String(v.clone())))
+        .collect();
+    serde_json::to_vec(&properties).ok()
+}
+
+/// Payload and user properties of an MQTT publish for a forwarded message
+fn mqtt_publish_content(
+    ctx: &ForwardContext,
+    mapping: &TopicMapping,
+    msg: &ForwardMessage,
+) -> (Vec<u8>, Vec<(String, String)>) {
+    let mut properties = Vec::new();
+    if let Some(name) = mapping.identity_property.clone()
+        && let Some(identity) = message_identity(mapping, msg)
+    {
+        properties.push((name, encode_identity(&identity)));
+    }
+
+    let carrier = match msg.source {
+        MessageSource::Zmq => mapping.source_topic_carrier.as_deref().and_then(TopicCarrier::parse),
+        MessageSource::Mqtt => None,
+    };
+    let payload = match carrier {
+        Some(TopicCarrier::Property(name)) => {
+            properties.push((name, msg.topic.clone()));
+            msg.payload.clone()
+        }
+        Some(TopicCarrier::JsonField(field)) => {
+            let embed_json = ctx.json_limits.check(&msg.payload).is_ok();
+            wrap_envelope(&field, &msg.topic, &msg.payload, embed_json)
+        }
+        None => msg.payload.clone(),
+    };
+    (payload, properties)
+}
+
+/// Primary target followed by any fan-out targets of a mapping
+fn mapping_targets(mapping: &TopicMapping) -> impl Iterator<Item = (&EndpointType, u32, &str)> {
+    std::iter::once((
+        &mapping.target_endpoint_type,
+        mapping.target_endpoint_id,
+        mapping.target_topic.as_str(),
+    ))
+    .chain(
+        mapping
+            .additional_targets
+            .iter()
+            .map(|t| (&t.endpoint_type, t.endpoint_id, t.target_topic.as_str())),
+    )
+}
+
+/// Resolve wildcards and `${...}` placeholders in a target topic
+fn resolve_target_topic(mapping: &TopicMapping, target: &str, msg: &ForwardMessage) -> Option<String> {
+    let target_topic = apply_mapping(&mapping.source_topic, target, &msg.topic);
+    if !is_template(&target_topic) {
+        return Some(target_topic);
+    }
+    render_template(
+        &target_topic,
+        &mapping.source_topic,
+        &msg.topic,
+        &msg.payload,
+        mapping.target_fallback.as_deref(),
+    )
+}
+
+/// Latency since the producer timestamp of a message, if the mapping reads one
+fn producer_latency_ms(mapping: &TopicMapping, msg: &ForwardMessage) -> Option<f64> {
+    let source = TimestampSource::parse(mapping.timestamp_source.as_deref()?)?;
+    let produced = source.extract_ms(&msg.payload, &msg.user_properties)?;
+    e2e_latency_ms(produced, chrono::Utc::now().timestamp_millis())
+}
+
+/// Replace an empty or wildcard target topic with the mapping's fallback, if any
+fn guard_target_topic(mapping: &TopicMapping, target_topic: String) -> Option<String> {
+    if is_publishable_topic(&target_topic) {
+        return Some(target_topic);
+    }
+    mapping.fallback_topic.clone().filter(|t| is_publishable_topic(t))
+}
+
+/// The receipt a command of a durable mapping carries, counted as handed
+fn hand_receipt(mapping: &TopicMapping, receipt: Option<&Arc<DeliveryReceipt>>) -> Option<Arc<DeliveryReceipt>> {
+    let receipt = receipt.filter(|_| mapping.durable)?;
+    receipt.handed();
+    Some(receipt.clone())
+}
+
+/// Hand a message to one target endpoint, counting the delivery. False if
+/// the endpoint is not running.
+async fn deliver(
+    ctx: &ForwardContext,
+    mapping: &TopicMapping,
+    endpoint_type: &EndpointType,
+    endpoint_id: u32,
+    target_topic: String,
+    msg: &ForwardMessage,
+    receipt: Option<&Arc<DeliveryReceipt>>,
+) -> bool {
+    match endpoint_type {
+        EndpointType::Mqtt => {
+            // Cloned out, as the lock must not be held across the stats update
+            let tx = ctx.mqtt_cmd_txs.read().get(&endpoint_id).cloned();
+            if let Some(tx) = tx {
+                info!("Forwarding to MQTT endpoint {}: {}", endpoint_id, target_topic);
+                let joined;
+                let msg = match ctx.extra_frames {
+                    ExtraFrames::Concatenate if !msg.frames.is_empty() => {
+                        let mut payload = msg.payload.clone();
+                        payload.extend(msg.frames.concat());
+                        joined = ForwardMessage { payload, frames: Vec::new(), ..msg.clone() };
+                        &joined
+                    }
+                    _ => msg,
+                };
+                let (payload, user_properties) = mqtt_publish_content(ctx, mapping, msg);
+                let len = payload.len();
+                let sent = tx
+                    .send(MqttCommand::Publish(MqttPublish {
+                        mapping_id: mapping.id,
+                        topic: target_topic,
+                        payload,
+                        qos: mapping.publish_qos.unwrap_or(mapping.qos as i32),
+                        retain: mapping.retain,
+                        user_properties,
+                        receipt: hand_receipt(mapping, receipt),
+                    }))
+                    .is_ok();
+                if sent {
+                    metrics().record_bytes_sent(endpoint_type, endpoint_id, len);
+                    metrics().record_mqtt_sent();
+                    metrics().record_mapping_forwarded(mapping.id);
+                    let _ = ctx.repo.increment_stats(0, 1, 0, 0, 0).await;
+                }
+                sent
+            } else {
+                metrics().record_forward_error(endpoint_type, endpoint_id, &target_topic, "endpoint not found".to_string());
+                warn!("MQTT endpoint {} not found!", endpoint_id);
+                dead_letter(msg, format!("MQTT endpoint {} not found", endpoint_id));
+                false
+            }
+        }
+        EndpointType::Zmq => {
+            let separator = ctx
+                .zmq_separators
+                .read()
+                .get(&endpoint_id)
+                .copied()
+                .unwrap_or(Some(TopicSeparator::default()));
+            if separator.is_some_and(|s| has_separator(s, &target_topic)) {
+                if ctx.separator_conflict == SeparatorConflict::Reject {
+                    metrics().record_separator_conflict();
+                    warn!("Mapping {} dropped message: ZMQ topic '{}' contains the frame separator", mapping.id, target_topic);
+                    return true;
+                }
+                warn!("Mapping {} forwarding ZMQ topic '{}' containing the frame separator", mapping.id, target_topic);
+            }
+            let tx = ctx.zmq_cmd_txs.read().get(&endpoint_id).cloned();
+            if let Some(tx) = tx {
+                info!("Forwarding to ZMQ endpoint {}: {}", endpoint_id, target_topic);
+                let identity = message_identity(mapping, msg);
+                // Multipart and PUSH endpoints carry every frame; others follow `bridge.zmq_extra_frames`
+                let mut payload: Vec<Vec<u8>> = match (separator, ctx.extra_frames) {
+                    (None | Some(TopicSeparator::Multipart), _) | (_, ExtraFrames::Concatenate) => {
+                        std::iter::once(msg.payload.clone()).chain(msg.frames.iter().cloned()).collect()
+                    }
+                    (_, ExtraFrames::Drop) => vec![msg.payload.clone()],
+                };
+                if matches!(separator, None | Some(TopicSeparator::Multipart))
+                    && let Some(frame) = user_property_frame(ctx, msg)
+                {
+                    payload.push(frame);
+                }
+                let len: usize = payload.iter().map(Vec::len).sum();
+                let receipt = hand_receipt(mapping, receipt);
+                let sent = tx.send(ZmqCommand::Publish(target_topic, payload, identity, Instant::now(), receipt)).is_ok();
+                if sent {
+                    metrics().record_bytes_sent(endpoint_type, endpoint_id, len);
+                    metrics().record_zmq_sent();
+                    metrics().record_mapping_forwarded(mapping.id);
+                    let _ = ctx.repo.increment_stats(0, 0, 0, 1, 0).await;
+                }
+                sent
+            } else {
+                metrics().record_forward_error(endpoint_type, endpoint_id, &target_topic, "endpoint not found".to_string());
+                warn!("ZMQ endpoint {} not found!", endpoint_id);
+                dead_letter(msg, format!("ZMQ endpoint {} not found", endpoint_id));
+                false
+            }
+        }
+    }
+}
+
+/// Largest payload `mapping` forwards: the lower of its own limit and
+/// `bridge.max_payload_bytes`
+fn payload_limit(ctx: &ForwardContext, mapping: &TopicMapping) -> Option<usize> {
+    match (mapping.max_payload_bytes.map(|n| n as usize), ctx.max_payload_bytes) {
+        (Some(own), Some(bridge)) => Some(own.min(bridge)),
+        (own, bridge) => own.or(bridge),
+    }
+}
+
+/// Count and dead-letter a message dropped for its payload size
+fn drop_oversize(msg: &ForwardMessage, len: usize, limit: usize) {
+    // Publishing it could overrun a ZMQ high-water mark or the broker's packet size
+    metrics().record_error();
+    metrics().record_oversize_drop();
+    dead_letter(msg, format!("payload of {} bytes exceeds max_payload_bytes {}", len, limit));
+}
+
+/// Keep `msg` for `/api/status/deadletter`
+fn dead_letter(msg: &ForwardMessage, reason: String) {
+    let source = match msg.source {
+        MessageSource::Mqtt => EndpointType::Mqtt,
+        MessageSource::Zmq => EndpointType::Zmq,
+    };
+    metrics().push_dead_letter(DeadLetterMessage {
+        timestamp: chrono::Utc::now().timestamp(),
+        source,
+        source_id: msg.source_id,
+        topic: msg.topic.clone(),
+        payload: msg.payload.clone(),
+        reason,
+    });
+}
+
+/// Every enabled mapping matching `topic`, in evaluation order, with the targets
+/// each would compute. Optionally restricted to one source endpoint type and id.
+pub fn match_topic(
+    mappings: &[TopicMapping],
+    endpoint_type: Option<&EndpointType>,
+    endpoint_id: Option<u32>,
+    topic: &str,
+) -> Vec<MappingMatch> {
+    let mut stopped = false;
+    mappings
+        .iter()
+        .filter(|m| {
+            m.enabled
+                && endpoint_type.is_none_or(|t| *t == m.source_endpoint_type)
+                && endpoint_id.is_none_or(|id| id == m.source_endpoint_id)
+                && matches_topic_pattern(&m.source_topic, topic)
+        })
+        .map(|mapping| {
+            let msg = ForwardMessage {
+                source: match mapping.source_endpoint_type {
+                    EndpointType::Mqtt => MessageSource::Mqtt,
+                    EndpointType::Zmq => MessageSource::Zmq,
+                },
+                source_id: mapping.source_endpoint_id,
+                topic: topic.to_string(),
+                payload: Vec::new(),
+                identity: None,
+                user_properties: Vec::new(),
+                frames: Vec::new(),
+            };
+            let targets = mapping_targets(mapping)
+                .map(|(endpoint_type, endpoint_id, target)| MatchedTarget {
+                    endpoint_type: endpoint_type.clone(),
+                    endpoint_id,
+                    target_topic: resolve_target_topic(mapping, target, &msg)
+                        .and_then(|t| guard_target_topic(mapping, t)),
+                })
+                .collect();
+            let shadowed = stopped;
+            stopped |= mapping.stop_on_match;
+            MappingMatch {
+                mapping_id: mapping.id,
+                source_topic: mapping.source_topic.clone(),
+                targets,
+                stop_on_match: mapping.stop_on_match,
+                shadowed,
+            }
+        })
+        .collect()
+}
+
+/// Check whether a mapping's source endpoint sent an incoming message; its
+/// topic is matched by `CompiledMappings::matching`
+fn source_matches(mapping: &TopicMapping, msg: &ForwardMessage) -> bool {
+    let endpoint_type = match msg.source {
+        MessageSource::Mqtt => EndpointType::Mqtt,
+        MessageSource::Zmq => EndpointType::Zmq,
+    };
+    mapping.source_endpoint_type == endpoint_type && mapping.source_endpoint_id == msg.source_id
+}
+
+/// Whether handling a message on this mapping parses its payload as JSON
+fn parses_json(mapping: &TopicMapping) -> bool {
+    let is_json_spec = |spec: Option<&str>| spec.is_some_and(|s| s.starts_with("json:"));
+    is_json_spec(mapping.ordering_key.as_deref())
+        || is_json_spec(mapping.timestamp_source.as_deref())
+        || is_json_spec(mapping.dedup_key.as_deref())
+        || mapping_targets(mapping).any(|(_, _, target)| target.contains("${json:"))
+}
+
+/// Resolve the ordering key for a message, defaulting to its topic
+async fn ordering_key_for(ctx: &ForwardContext, msg: &ForwardMessage) -> String {
+    let json_allowed = ctx.json_limits.check(&msg.payload).is_ok();
+    let mappings = ctx.mappings_cache.read().await;
+    mappings
+        .matching(&msg.topic)
+        .filter(|m| m.enabled && source_matches(m, msg))
+        .filter(|m| json_allowed || !parses_json(m))
+        .find_map(|m| {
+            let key = OrderingKey::parse(m.ordering_key.as_deref()?)?;
+            key.extract(&m.source_topic, &msg.topic, &msg.payload)
+        })
+        .unwrap_or_else(|| msg.topic.clone())
+}
+
+/// Payload of a message piped through a mapping's transform command, or why
+/// the transform failed: transforms are disabled, the command failed or its
+/// output exceeds the JSON limits
+async fn transform_payload(
+    ctx: &ForwardContext,
+    mapping: &TopicMapping,
+    command: &str,
+    msg: &ForwardMessage,
+) -> Result<Vec<u8>, String> {
+    let Some(ref exec) = ctx.exec else {
+        metrics().record_exec_transform_failure();
+        return Err("bridge.exec_transforms is disabled".to_string());
+    };
+    let payload = match exec.transform(command, &msg.payload).await {
+        Ok(payload) => payload,
+        Err(e) => {
+            metrics().record_exec_transform_failure();
+            return Err(e.to_string());
+        }
+    };
+    if parses_json(mapping)
+        && let Err(e) = ctx.json_limits.check(&payload)
+    {
+        metrics().record_json_limit_exceeded();
+        return Err(format!("transformed payload: {}", e));
+    }
+    Ok(payload)
+}
+
+/// Apply a mapping's transform error policy to `received`, whose transform
+/// step failed for `reason`. True if it is forwarded without the step.
+async fn handle_transform_error(
+    ctx: &ForwardContext,
+    mapping: &TopicMapping,
+    received: &ForwardMessage,
+    reason: &str,
+) -> bool {
+    match mapping.transform_error_policy {
+        TransformErrorPolicy::Drop => {
+            warn!("Mapping {} dropped message on {}: {}", mapping.id, received.topic, reason);
+            false
+        }
+        TransformErrorPolicy::PassThrough => {
+            warn!("Mapping {} forwarding message on {} untransformed: {}", mapping.id, received.topic, reason);
+            true
+        }
+        TransformErrorPolicy::DeadLetter => {
+            warn!("Mapping {} dead-lettered message on {}: {}", mapping.id, received.topic, reason);
+            let source = match received.source {
+                MessageSource::Mqtt => EndpointType::Mqtt,
+                MessageSource::Zmq => EndpointType::Zmq,
+            };
+            let stored = ctx
+                .repo
+                .add_dead_letter(mapping.id, (&source, received.source_id), &received.topic, &received.payload, reason)
+                .await;
+            match stored {
+                Ok(()) => metrics().record_dead_letter(),
+                Err(e) => error!("Failed to store dead letter of mapping {}: {}", mapping.id, e),
+            }
+            false
+        }
+    }
+}
+
+/// Mapping stage: `transform_command`, then `payload_transform`, then
+/// `truncate_bytes`. `None` if the transform error policy drops the message.
+async fn transform_for_mapping<'a>(
+    ctx: &ForwardContext,
+    mapping: &TopicMapping,
+    received: &'a ForwardMessage,
+) -> Option<Cow<'a, ForwardMessage>> {
+    let mut msg = Cow::Borrowed(received);
+    if let Some(command) = mapping.transform_command.as_deref() {
+        // The command may act on what it is given, so a dry run skips it
+        if ctx.dry_run.is_enabled() {
+            debug!("Dry run: mapping {} skips its transform command", mapping.id);
+        } else {
+            match transform_payload(ctx, mapping, command, received).await {
+                Ok(payload) => msg.to_mut().payload = payload,
+                Err(reason) => {
+                    if !handle_transform_error(ctx, mapping, received, &reason).await {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(transform) = mapping.payload_transform {
+        // JSON is parsed only within the limits, as everywhere else
+        let converted = if transform == PayloadTransform::JsonToMsgpack
+            && let Err(e) = ctx.json_limits.check(&msg.payload)
+        {
+            metrics().record_json_limit_exceeded();
+            Err(e.to_string())
+        } else {
+            convert_payload(transform, &msg.payload).map_err(|e| {
+                metrics().record_payload_conversion_failure();
+                e.to_string()
+            })
+        };
+        match converted {
+            Ok(payload) => msg.to_mut().payload = payload,
+            Err(reason) => {
+                if !handle_transform_error(ctx, mapping, received, &reason).await {
+                    return None;
+                }
+            }
+        }
+    }
+
+    if let Some(head) = mapping.truncate_bytes.and_then(|n| truncate_payload(&msg.payload, n)) {
+        metrics().record_payload_truncation();
+        let head = head.to_vec();
+        msg.to_mut().payload = head;
+    }
+    Some(msg)
+}
+
+/// Forward messages a previous run logged but did not deliver
+async fn replay_wal(ctx: &ForwardContext) {
+    let Some(ref wal) = ctx.wal else { return };
+    let pending = match wal.recover() {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!("Failed to read the write-ahead log: {}", e);
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+    info!("Replaying {} undelivered messages from the write-ahead log", pending.len());
+    for (id, msg) in pending {
+        let receipt = DeliveryReceipt::new(wal.clone(), id);
+        if !route_message(ctx, msg, Some(&receipt)).await {
+            receipt.fail();
+        }
+    }
+}
+
+/// Log a message before routing if an enabled durable mapping matches it,
+/// returning the receipt of its log entry
+async fn log_durable(ctx: &ForwardContext, msg: &ForwardMessage) -> Option<Arc<DeliveryReceipt>> {
+    // Nothing is delivered in a dry run, so there is nothing to replay
+    if ctx.dry_run.is_enabled() {
+        return None;
+    }
+    let wal = ctx.wal.as_ref()?;
+    let durable = ctx
+        .mappings_cache
+        .read()
+        .await
+        .matching(&msg.topic)
+        .any(|m| m.enabled && m.durable && source_matches(m, msg));
+    if !durable {
+        return None;
+    }
+    // The append syncs the segment, which must not stall the runtime
+    let (log, logged) = (wal.clone(), msg.clone());
+    match tokio::task::spawn_blocking(move || log.append(&logged)).await {
+        Ok(Ok(id)) => Some(DeliveryReceipt::new(wal.clone(), id)),
+        Ok(Err(e)) => {
+            metrics().record_error();
+            error!("Failed to log message on {} to the write-ahead log: {}", msg.topic, e);
+            None
+        }
+        Err(e) => {
+            metrics().record_error();
+            error!("Write-ahead log append for {} panicked: {}", msg.topic, e);
+            None
+        }
+    }
+}
+
+/// Route one message, holding it in the write-ahead log until every target
+/// endpoint published it when a durable mapping matches. An entry whose
+/// delivery failed stays in the log and is forwarded again on the next replay.
+async fn forward_message(ctx: &ForwardContext, msg: ForwardMessage) {
+    let receipt = log_durable(ctx, &msg).await;
+    if !route_message(ctx, msg, receipt.as_ref()).await
+        && let Some(ref receipt) = receipt
+    {
+        receipt.fail();
+    }
+}
+
+/// Route one message to every matching mapping target. Its topic already went
+/// through the ingress stage; each matching mapping then applies its own
+/// transforms, and each target endpoint its egress rewrites.
+///
+/// Returns false if a target of a durable mapping could not be handed the
+/// message; messages dropped by a filter or policy count as settled. The
+/// commands of durable mappings carry `receipt`, the message's log entry.
+async fn route_message(ctx: &ForwardContext, msg: ForwardMessage, receipt: Option<&Arc<DeliveryReceipt>>) -> bool {
+    let forward_start = Instant::now();
+    info!("Received message from {:?} id={}: topic={}", msg.source, msg.source_id, msg.topic);
+
+    // Track received stats (both DB and telemetry)
+    match msg.source {
+        MessageSource::Mqtt => {
+            metrics().record_mqtt_received();
+            metrics().record_bytes_received(&EndpointType::Mqtt, msg.source_id, msg.payload.len());
+            let _ = ctx.repo.increment_stats(1, 0, 0, 0, 0).await;
+        }
+        MessageSource::Zmq => {
+            metrics().record_zmq_received();
+            metrics().record_bytes_received(&EndpointType::Zmq, msg.source_id, msg.payload.len());
+            let _ = ctx.repo.increment_stats(0, 0, 1, 0, 0).await;
+        }
+    }
+
+    // Pathological topics are dropped before the matcher splits them
+    if let Some(max) = ctx.max_topic_levels
+        && exceeds_topic_levels(&msg.topic, max)
+    {
+        metrics().record_topic_too_deep();
+        warn!("Dropped message with more than {} topic levels from {:?} id={}", max, msg.source, msg.source_id);
+        return true;
+    }
+
+    // The bridge-wide limit applies to the payload as received, before any mapping
+    if let Some(limit) = ctx.max_payload_bytes
+        && msg.payload.len() > limit
+    {
+        warn!(
+            "Dropped message on {} from {:?} id={}: payload of {} bytes exceeds {}",
+            msg.topic, msg.source, msg.source_id, msg.payload.len(), limit
+        );
+        drop_oversize(&msg, msg.payload.len(), limit);
+        return true;
+    }
+
+    // Read mappings from shared cache (fast, in-memory)
+    let mappings = ctx.mappings_cache.read().await;
+
+    let mut matched = false;
+    // Matched only by mappings outside their schedule, which is not a dead letter
+    let mut scheduled_out = false;
+    let mut delivered = true;
+    // A message too large for several mappings is counted and dead-lettered once
+    let mut oversize_dropped = false;
+    let mut e2e_recorded = false;
+    // Find matching mappings
+    for mapping in mappings.matching(&msg.topic).filter(|m| m.enabled) {
+        if !source_matches(mapping, &msg) {
+            continue;
+        }
+        if !mappings.schedule_active(mapping, chrono::Utc::now()) {
+            metrics().record_schedule_skip();
+            scheduled_out = true;
+            continue;
+        }
+
+        matched = true;
+        if let Some(limit) = mapping.max_payload_bytes.map(|n| n as usize)
+            && msg.payload.len() > limit
+        {
+            warn!(
+                "Mapping {} dropped message on {}: payload of {} bytes exceeds {}",
+                mapping.id, msg.topic, msg.payload.len(), limit
+            );
+            if !std::mem::replace(&mut oversize_dropped, true) {
+                drop_oversize(&msg, msg.payload.len(), limit);
+            }
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        }
+        if parses_json(mapping)
+            && let Err(e) = ctx.json_limits.check(&msg.payload)
+        {
+            metrics().record_json_limit_exceeded();
+            warn!("Mapping {} dropped message on {}: {}", mapping.id, msg.topic, e);
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        }
+        if !ctx.sampler.admit(mapping, Instant::now()) {
+            metrics().record_sampled_drop();
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        }
+        let now = chrono::Utc::now().timestamp();
+        let seen = first_seen(&ctx.repo, mapping, ctx.dedup_ttl, &msg.topic, &msg.payload, now).await;
+        if seen == Seen::Repeat {
+            metrics().record_duplicate_drop();
+            debug!("Mapping {} dropped a repeat of a message on {}", mapping.id, msg.topic);
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        }
+
+        let Some(msg) = transform_for_mapping(ctx, mapping, &msg).await else {
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        };
+        // Transforms may grow the payload past the limit again
+        if let Some(limit) = payload_limit(ctx, mapping)
+            && msg.payload.len() > limit
+        {
+            warn!(
+                "Mapping {} dropped message on {}: transformed payload of {} bytes exceeds {}",
+                mapping.id, msg.topic, msg.payload.len(), limit
+            );
+            if !std::mem::replace(&mut oversize_dropped, true) {
+                drop_oversize(&msg, msg.payload.len(), limit);
+            }
+            if mapping.stop_on_match {
+                break;
+            }
+            continue;
+        }
+
+        // A key is recorded only once every target was handed the message
+        let mut handed_all = true;
+        let mut handed_any = false;
+        for (endpoint_type, endpoint_id, target) in mapping_targets(mapping) {
+            let Some(target_topic) = resolve_target_topic(mapping, target, &msg) else {
+                debug!("Mapping {} dropped message on {}: unresolved target topic", mapping.id, msg.topic);
+                continue;
+            };
+            let target_topic = rewrite_outgoing(ctx, endpoint_type, endpoint_id, target_topic);
+            let Some(target_topic) = guard_target_topic(mapping, target_topic) else {
+                warn!("Mapping {} dropped message on {}: empty or invalid target topic", mapping.id, msg.topic);
+                metrics().record_empty_target_drop();
+                continue;
+            };
+            if ctx.dry_run.is_enabled() {
+                debug!("Dry run: mapping {} would forward {} to {}", mapping.id, msg.topic, target_topic);
+                ctx.dry_run.record(mapping.id, endpoint_type, endpoint_id, &msg.topic, &target_topic, msg.payload.len());
+                continue;
+            }
+            let handed = deliver(ctx, mapping, endpoint_type, endpoint_id, target_topic, &msg, receipt).await;
+            delivered &= handed || !mapping.durable;
+            handed_all &= handed;
+            handed_any |= handed;
+        }
+        if let Seen::First(ref key) = seen
+            && handed_any
+            && handed_all
+            && !ctx.dry_run.is_enabled()
+        {
+            record_seen(&ctx.repo, mapping, key, now).await;
+        }
+
+        if !e2e_recorded && let Some(latency) = producer_latency_ms(mapping, &msg) {
+            metrics().record_e2e_latency(latency);
+            e2e_recorded = true;
+        }
+
+        if mapping.stop_on_match {
+            break;
+        }
+    }
+
+    if !matched && scheduled_out {
+        debug!("Only mappings outside their schedule match topic: {}", msg.topic);
+    } else if !matched {
+        debug!("No matching mapping found for topic: {}", msg.topic);
+        dead_letter(&msg, "no matching mapping".to_string());
+    } else {
+        // Record forwarding latency
+        let latency_ms = forward_start.elapsed().as_secs_f64() * 1000.0;
+        metrics().record_latency(latency_ms);
+    }
+    delivered
+}
+
+/// A forwarded message to publish on an MQTT broker
+struct MqttPublish {
+    mapping_id: u32,
+    topic: String,
+    payload: Vec<u8>,
+    /// Requested QoS
+    qos: i32,
+    retain: bool,
+    /// User properties, sent when the broker connection is MQTT v5
+    user_properties: Vec<(String, String)>,
+    /// Write-ahead log entry, for durable mappings
+    receipt: Option<Arc<DeliveryReceipt>>,
+}
+
 // Commands for MQTT thread
 enum MqttCommand {
-    Publish(String, Vec<u8>),
-    Subscribe(Vec<String>),
+    Publish(MqttPublish),
+    /// Every filter for the broker and the QoS of each
+    Subscribe(Vec<String>, HashMap<String, i32>),
 }
 
 // Commands for ZMQ thread
 enum ZmqCommand {
-    Publish(String, Vec<u8>),
+    /// Topic, payload frames, an optional routing identity frame, when the
+    /// message was queued and its write-ahead log entry
+    Publish(String, Vec<Vec<u8>>, Option<Vec<u8>>, Instant, Option<Arc<DeliveryReceipt>>),
+}
+
+/// Client options for `server_uri`, speaking MQTT v5 if `v5` is set and
+/// 3.1.1 otherwise
+fn mqtt_create_options(server_uri: &str, client_id: &str, v5: bool) -> paho_mqtt::CreateOptions {
+    paho_mqtt::CreateOptionsBuilder::new()
+        .server_uri(server_uri)
+        .client_id(client_id)
+        .mqtt_version(if v5 { paho_mqtt::MQTT_VERSION_5 } else { paho_mqtt::MQTT_VERSION_3_1_1 })
+        .finalize()
+}
+
+/// Whether a broker must speak MQTT v5. User properties and topic aliases
+/// need it, so brokers that use them switch protocol even when not
+/// configured for v5.
+fn needs_mqtt_v5(config: &MqttConfig, mappings: &[TopicMapping]) -> bool {
+    let config_id = config.id.unwrap_or(0);
+    config.protocol_version == MqttProtocolVersion::V5
+        || config.topic_aliases
+        || mappings.iter().any(|m| {
+            m.enabled
+                && uses_user_properties(m)
+                && ((m.source_endpoint_type == EndpointType::Mqtt && m.source_endpoint_id == config_id)
+                    || mapping_targets(m).any(|(t, id, _)| *t == EndpointType::Mqtt && id == config_id))
+        })
+}
+
+/// Run `fut` unless the bridge stops or the thread is asked to exit first,
+/// so a broker that never answers does not hold up a stop
+async fn until_stopped<F: std::future::Future>(fut: F, running: &AtomicBool, activity: &ThreadActivity) -> Option<F::Output> {
+    let stopped = async {
+        while running.load(Ordering::SeqCst) && !activity.stop_requested() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    };
+    tokio::select! {
+        output = fut => Some(output),
+        _ = stopped => None,
+    }
+}
+
+/// Whether a failed connect means the broker does not speak MQTT v5
+fn rejects_v5(err: &paho_mqtt::Error) -> bool {
+    matches!(
+        err,
+        paho_mqtt::Error::ConnectReturn(paho_mqtt::ConnectReturnCode::UnacceptableProtocolVersion)
+            | paho_mqtt::Error::ReasonCode(paho_mqtt::ReasonCode::UnsupportedProtocolVersion)
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_mqtt_worker(
     running: Arc<AtomicBool>,
     config: MqttConfig,
-    subscribe_topics: Vec<String>,
-    forward_tx: mpsc::Sender<ForwardMessage>,
-    cmd_rx: std::sync::mpsc::Receiver<MqttCommand>,
+    mut subscribe_topics: Vec<String>,
+    mut subscribe_qos: HashMap<String, i32>,
+    forward_tx: ForwardSender<ForwardMessage>,
+    cmd_rx: Arc<Mutex<std::sync::mpsc::Receiver<MqttCommand>>>,
+    limits: SubscribeLimits,
+    mut mqtt_v5: bool,
+    qos_report: Arc<QosReport>,
+    activity: Arc<ThreadActivity>,
+    limiter: Arc<ReconnectLimiter>,
+    mut health: HealthCheck,
+    connections: Arc<ConnectionTracker>,
 ) {
-    use paho_mqtt::{AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
-    use std::time::Duration;
+    use paho_mqtt::{AsyncClient, MessageBuilder};
 
     let config_id = config.id.unwrap_or(0);
+    // Held for the thread's lifetime; a restarted endpoint's next thread takes it over
+    let cmd_rx = cmd_rx.lock();
+    let report = |status: ConnectionStatus| connections.set(EndpointType::Mqtt, config_id, &config.name, status);
+    report(ConnectionStatus::Connecting);
     let server_uri = if config.use_tls {
         format!("ssl://{}:{}", config.broker_url, config.port)
     } else {
         format!("tcp://{}:{}", config.broker_url, config.port)
     };
 
-    let create_opts = CreateOptionsBuilder::new()
-        .server_uri(&server_uri)
-        .client_id(&config.client_id)
-        .finalize();
+    let client_id = expand_client_id(&config.client_id, &config.name);
+    info!("[MQTT:{}] Using client id {}", config.name, client_id);
+    activity.set_client_id(&client_id);
+
+    let new_client = |v5: bool| {
+        let client = AsyncClient::new(mqtt_create_options(&server_uri, &client_id, v5))?;
+        // Re-announce the bridge after reconnects; the initial birth
+        // message is published explicitly once subscriptions are in place
+        if let Some(birth) = birth_message(&config) {
+            let reconnected = Arc::new(AtomicBool::new(false));
+            client.set_connected_callback(move |cli| {
+                if reconnected.swap(true, Ordering::SeqCst) {
+                    let _ = cli.publish(birth.clone());
+                }
+            });
+        }
+        Ok::<_, paho_mqtt::Error>(client)
+    };
+
+    let ssl = match config.use_tls.then(|| ssl_options(&config)).transpose() {
+        Ok(ssl) => ssl,
+        Err(e) => {
+            error!("[MQTT:{}] Invalid TLS configuration: {}", config.name, e);
+            report(ConnectionStatus::Error);
+            return;
+        }
+    };
 
-    let mut client = match AsyncClient::new(create_opts) {
+    let mut client = match new_client(mqtt_v5) {
         Ok(c) => c,
         Err(e) => {
             error!("[MQTT:{}] Failed to create client: {}", config.name, e);
+            report(ConnectionStatus::Error);
             return;
         }
     };
@@ -326,79 +1753,270 @@ fn run_mqtt_worker(
         Ok(rt) => rt,
         Err(e) => {
             error!("[MQTT:{}] Failed to create tokio runtime: {}", config.name, e);
+            report(ConnectionStatus::Error);
             return;
         }
     };
 
     rt.block_on(async {
-        let mut conn_opts = ConnectOptionsBuilder::new();
-        conn_opts
-            .keep_alive_interval(Duration::from_secs(config.keep_alive_seconds as u64))
-            .clean_session(config.clean_session)
-            .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
-
-        if let Some(ref username) = config.username {
-            conn_opts.user_name(username);
-        }
-        if let Some(ref password) = config.password {
-            conn_opts.password(password);
-        }
-
-        let conn_opts = conn_opts.finalize();
-
-        if let Err(e) = client.connect(conn_opts).await {
-            error!("[MQTT:{}] Failed to connect: {}", config.name, e);
+        // Opened before connecting: a restored persistent session delivers its
+        // queued messages in a burst right after CONNACK
+        let stream_capacity = if config.resumes_session(mqtt_v5) { 10_000 } else { 100 };
+        let mut stream = client.get_stream(stream_capacity);
+
+        // v5 brokers advertise the highest QoS they accept and how many topic
+        // aliases they take in CONNACK
+        let alias_maximum = |rsp: &paho_mqtt::ServerResponse| {
+            if config.topic_aliases { broker_alias_maximum(rsp.properties()) } else { 0 }
+        };
+        let mut aliases = TopicAliases::default();
+        // A broker that is down at startup is retried like a lost connection
+        let mut retry_delay = Duration::from_secs(MQTT_RECONNECT_MIN_SECS);
+        let mut first_attempt = true;
+        let connected = loop {
+            if !std::mem::take(&mut first_attempt) {
+                match limiter.attempt(config_id, Instant::now()) {
+                    ReconnectDecision::Proceed => activity.set_throttled(false),
+                    ReconnectDecision::Throttled(wait) => {
+                        warn!("[MQTT:{}] Reconnects throttled for {}s", config.name, wait.as_secs());
+                        activity.set_throttled(true);
+                        report(ConnectionStatus::Throttled);
+                        if until_stopped(tokio::time::sleep(wait), &running, &activity).await.is_none() {
+                            break None;
+                        }
+                        continue;
+                    }
+                }
+                report(ConnectionStatus::Connecting);
+            }
+            let options = match connect_options(&config, mqtt_v5, ssl.as_ref()) {
+                Ok(options) => options,
+                Err(e) => {
+                    error!("[MQTT:{}] Invalid connect options: {}", config.name, e);
+                    report(ConnectionStatus::Error);
+                    return;
+                }
+            };
+            let mut connected = until_stopped(client.connect(options), &running, &activity).await;
+            if let Some(Err(ref e)) = connected
+                && mqtt_v5
+                && rejects_v5(e)
+            {
+                warn!("[MQTT:{}] Broker rejected MQTT v5 ({}), falling back to 3.1.1", config.name, e);
+                mqtt_v5 = false;
+                client = match new_client(false) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        error!("[MQTT:{}] Failed to create client: {}", config.name, e);
+                        report(ConnectionStatus::Error);
+                        return;
+                    }
+                };
+                stream = client.get_stream(stream_capacity);
+                let options = match connect_options(&config, false, ssl.as_ref()) {
+                    Ok(options) => options,
+                    Err(e) => {
+                        error!("[MQTT:{}] Invalid connect options: {}", config.name, e);
+                        report(ConnectionStatus::Error);
+                        return;
+                    }
+                };
+                connected = until_stopped(client.connect(options), &running, &activity).await;
+            }
+            match connected {
+                None => break None,
+                Some(Ok(rsp)) => break Some(rsp),
+                Some(Err(e)) => {
+                    warn!("[MQTT:{}] Failed to connect, retrying in {}s: {}", config.name, retry_delay.as_secs(), e);
+                    report(ConnectionStatus::Disconnected);
+                    if until_stopped(tokio::time::sleep(retry_delay), &running, &activity).await.is_none() {
+                        break None;
+                    }
+                    retry_delay = (retry_delay * 2).min(Duration::from_secs(MQTT_RECONNECT_MAX_SECS));
+                }
+            }
+        };
+        let Some(rsp) = connected else {
+            info!("[MQTT:{}] Stopped while connecting", config.name);
+            report(ConnectionStatus::Disconnected);
             return;
-        }
+        };
+        limiter.record_connected(config_id);
+        activity.set_throttled(false);
+        aliases.reset(alias_maximum(&rsp));
+        let broker_max_qos = rsp.properties().get_int(paho_mqtt::PropertyCode::MaximumQos);
+        let session_present = rsp.connect_response().is_some_and(|c| c.session_present);
 
         info!("[MQTT:{}] Connected to {}:{}", config.name, config.broker_url, config.port);
+        report(ConnectionStatus::Connected);
+        if session_present {
+            info!("[MQTT:{}] Resumed persistent session {}", config.name, client_id);
+        }
 
-        // Subscribe to topics
-        if !subscribe_topics.is_empty() {
-            let qos: Vec<i32> = subscribe_topics.iter().map(|_| 1).collect();
-            let topics_ref: Vec<&str> = subscribe_topics.iter().map(|s| s.as_str()).collect();
-            if let Err(e) = client.subscribe_many(&topics_ref, &qos).await {
-                error!("[MQTT:{}] Failed to subscribe: {}", config.name, e);
-            } else {
+        // A resumed session already holds its filters, as after a reconnect
+        let resumed = config.resumes_session(mqtt_v5) && session_present;
+        if !resumed && !subscribe_topics.is_empty() {
+            let failed = subscribe_batched(&client, &config.name, &subscribe_topics, &subscribe_qos, &limits).await;
+            if failed.is_empty() {
                 info!("[MQTT:{}] Subscribed to {:?}", config.name, subscribe_topics);
+            } else {
+                error!("[MQTT:{}] Failed to subscribe to {:?}", config.name, failed);
             }
         }
 
-        let stream = client.get_stream(100);
+        if let Some(birth) = birth_message(&config) {
+            if let Err(e) = client.publish(birth).await {
+                error!("[MQTT:{}] Failed to publish birth message: {}", config.name, e);
+            }
+        }
 
-        while running.load(Ordering::SeqCst) {
+        // Reconnects are driven here rather than by paho so the storm limiter can hold them off
+        let mut retry_at: Option<Instant> = None;
+        retry_delay = Duration::from_secs(MQTT_RECONNECT_MIN_SECS);
+
+        while running.load(Ordering::SeqCst) && !activity.stop_requested() {
             tokio::select! {
                 msg_opt = async { stream.recv().await.ok().flatten() } => {
                     if let Some(msg) = msg_opt {
+                        activity.record_received();
                         let fwd_msg = ForwardMessage {
                             source: MessageSource::Mqtt,
                             source_id: config_id,
                             topic: msg.topic().to_string(),
                             payload: msg.payload().to_vec(),
+                            identity: None,
+                            user_properties: user_properties(msg.properties()),
+                            frames: Vec::new(),
                         };
-                        if let Err(e) = forward_tx.send(fwd_msg).await {
+                        if let Err(e) = queue_forward(&forward_tx, fwd_msg).await {
                             error!("[MQTT:{}] Failed to forward: {}", config.name, e);
                         }
                     }
                 }
                 _ = tokio::time::sleep(Duration::from_millis(10)) => {
+                    let now = Instant::now();
+                    health.poll(now, || client.is_connected());
+                    if !client.is_connected() && retry_at.is_none_or(|at| now >= at) {
+                        match limiter.attempt(config_id, now) {
+                            ReconnectDecision::Proceed => {
+                                activity.set_throttled(false);
+                                report(ConnectionStatus::Connecting);
+                                match until_stopped(client.reconnect(), &running, &activity).await {
+                                    // The loop ends on its next check
+                                    None => {}
+                                    Some(Ok(rsp)) => {
+                                        info!("[MQTT:{}] Reconnected", config.name);
+                                        report(ConnectionStatus::Connected);
+                                        limiter.record_connected(config_id);
+                                        retry_at = None;
+                                        retry_delay = Duration::from_secs(MQTT_RECONNECT_MIN_SECS);
+                                        // Aliases do not outlive the connection they were assigned on
+                                        aliases.reset(alias_maximum(&rsp));
+
+                                        let session_present = rsp.connect_response().is_some_and(|c| c.session_present);
+                                        if should_resubscribe(&limits, !config.resumes_session(mqtt_v5), session_present)
+                                            && !subscribe_topics.is_empty()
+                                        {
+                                            let failed = subscribe_batched(&client, &config.name, &subscribe_topics, &subscribe_qos, &limits).await;
+                                            info!(
+                                                "[MQTT:{}] Resubscribed to {} of {} topics",
+                                                config.name,
+                                                subscribe_topics.len() - failed.len(),
+                                                subscribe_topics.len()
+                                            );
+                                        }
+                                    }
+                                    Some(Err(e)) => {
+                                        warn!("[MQTT:{}] Reconnect failed: {}", config.name, e);
+                                        report(ConnectionStatus::Disconnected);
+                                        retry_at = Some(now + retry_delay);
+                                        retry_delay = (retry_delay * 2).min(Duration::from_secs(MQTT_RECONNECT_MAX_SECS));
+                                    }
+                                }
+                            }
+                            ReconnectDecision::Throttled(wait) => {
+                                warn!("[MQTT:{}] Reconnects throttled for {}s", config.name, wait.as_secs());
+                                activity.set_throttled(true);
+                                report(ConnectionStatus::Throttled);
+                                retry_at = Some(now + wait);
+                            }
+                        }
+                    }
+
                     while let Ok(cmd) = cmd_rx.try_recv() {
                         match cmd {
-                            MqttCommand::Publish(topic, payload) => {
-                                let msg = Message::new(&topic, payload, 1);
-                                if let Err(e) = client.publish(msg).await {
-                                    error!("[MQTT:{}] Failed to publish: {}", config.name, e);
+                            MqttCommand::Publish(publish) => {
+                                let alias = aliases.alias(&publish.topic);
+                                let build = |qos: i32| {
+                                    let message = MessageBuilder::new()
+                                        .payload(publish.payload.clone())
+                                        .qos(qos)
+                                        .retained(publish.retain);
+                                    if mqtt_v5 && (alias.is_some() || !publish.user_properties.is_empty()) {
+                                        let mut props = string_properties(&publish.user_properties);
+                                        if let Some(alias) = alias {
+                                            alias.apply(&mut props);
+                                        }
+                                        message
+                                            .topic(alias.map_or(publish.topic.as_str(), |a| a.wire_topic(&publish.topic)))
+                                            .properties(props)
+                                            .finalize()
+                                    } else {
+                                        message.topic(&publish.topic).finalize()
+                                    }
+                                };
+
+                                let sent = effective_qos(publish.qos, broker_max_qos);
+                                let mut result = client.publish(build(sent)).await;
+                                let mut delivered = delivered_qos(sent, &result);
+                                if delivered == Some(0) && sent > 0 {
+                                    // Broker rejected the QoS; fall back to fire-and-forget
+                                    result = client.publish(build(0)).await;
+                                    delivered = delivered_qos(0, &result);
+                                }
+
+                                match (delivered, result) {
+                                    (Some(effective), _) => {
+                                        activity.record_sent();
+                                        if let Some(ref receipt) = publish.receipt {
+                                            receipt.published();
+                                        }
+                                        if effective < publish.qos {
+                                            metrics().record_qos_downgrade();
+                                            debug!(
+                                                "[MQTT:{}] Mapping {} published at QoS {} instead of {}",
+                                                config.name, publish.mapping_id, effective, publish.qos
+                                            );
+                                        }
+                                        qos_report.record(publish.mapping_id, publish.qos, effective);
+                                    }
+                                    (None, Err(e)) => {
+                                        // The broker never learned an alias assigned by a failed publish
+                                        if matches!(alias, Some(AliasUse::Assign(_))) {
+                                            aliases.forget(&publish.topic);
+                                        }
+                                        metrics().record_forward_error(
+                                            &EndpointType::Mqtt,
+                                            config_id,
+                                            &publish.topic,
+                                            format!("publish failed: {}", e),
+                                        );
+                                        error!("[MQTT:{}] Failed to publish: {}", config.name, e)
+                                    }
+                                    (None, Ok(())) => {}
                                 }
                             }
-                            MqttCommand::Subscribe(topics) => {
+                            MqttCommand::Subscribe(topics, qos) => {
                                 if !topics.is_empty() {
-                                    let qos: Vec<i32> = topics.iter().map(|_| 1).collect();
-                                    let topics_ref: Vec<&str> = topics.iter().map(|s| s.as_str()).collect();
-                                    if let Err(e) = client.subscribe_many(&topics_ref, &qos).await {
-                                        error!("[MQTT:{}] Failed to subscribe: {}", config.name, e);
-                                    } else {
+                                    let failed = subscribe_batched(&client, &config.name, &topics, &qos, &limits).await;
+                                    if failed.is_empty() {
                                         info!("[MQTT:{}] Dynamically subscribed to {:?}", config.name, topics);
+                                    } else {
+                                        error!("[MQTT:{}] Failed to subscribe to {:?}", config.name, failed);
                                     }
+                                    // The full set for this broker, restored after reconnects
+                                    subscribe_topics = topics;
+                                    subscribe_qos = qos;
                                 }
                             }
                         }
@@ -409,19 +2027,27 @@ fn run_mqtt_worker(
 
         let _ = client.disconnect(None).await;
         info!("[MQTT:{}] Disconnected", config.name);
+        report(ConnectionStatus::Disconnected);
     });
 }
 
 fn run_zmq_worker(
+    context: zmq::Context,
     running: Arc<AtomicBool>,
     config: ZmqConfig,
-    forward_tx: mpsc::Sender<ForwardMessage>,
-    cmd_rx: std::sync::mpsc::Receiver<ZmqCommand>,
+    forward_tx: ForwardSender<ForwardMessage>,
+    cmd_rx: Arc<Mutex<std::sync::mpsc::Receiver<ZmqCommand>>>,
+    activity: Arc<ThreadActivity>,
+    mut health: HealthCheck,
+    connections: Arc<ConnectionTracker>,
 ) {
-    use zmq::{Context, SocketType};
+    use zmq::SocketType;
 
     let config_id = config.id.unwrap_or(0);
-    let context = Context::new();
+    // Held for the thread's lifetime; a restarted endpoint's next thread takes it over
+    let cmd_rx = cmd_rx.lock();
+    let report = |status: ConnectionStatus| connections.set(EndpointType::Zmq, config_id, &config.name, status);
+    report(ConnectionStatus::Connecting);
 
     // Create socket based on type
     let socket_type = match config.socket_type {
@@ -429,26 +2055,65 @@ fn run_zmq_worker(
         ZmqSocketType::XSub => SocketType::XSUB,
         ZmqSocketType::Pub => SocketType::PUB,
         ZmqSocketType::Sub => SocketType::SUB,
+        ZmqSocketType::Push => SocketType::PUSH,
+        ZmqSocketType::Pull => SocketType::PULL,
     };
 
     let socket = match context.socket(socket_type) {
         Ok(s) => s,
         Err(e) => {
             error!("[ZMQ:{}] Failed to create socket: {}", config.name, e);
+            report(ConnectionStatus::Error);
             return;
         }
     };
 
     let _ = socket.set_sndhwm(config.high_water_mark as i32);
     let _ = socket.set_rcvhwm(config.high_water_mark as i32);
+    // libzmq retries refused and dropped connections by itself, at this interval
+    let _ = socket.set_reconnect_ivl(config.reconnect_interval_ms.max(1) as i32);
+    if let Err(e) = apply_multicast_options(&socket, &config) {
+        warn!("[ZMQ:{}] Failed to set multicast options: {}", config.name, e);
+    }
+
+    // Health comes from monitor events, so attach before binding or connecting
+    let publisher_connects =
+        matches!(config.socket_type, ZmqSocketType::XPub | ZmqSocketType::Pub) && config.role == ZmqRole::Connect;
+    // PGM has no listeners or connections to report, so it is not waited for
+    let bind = config
+        .bind_endpoint
+        .as_deref()
+        .filter(|e| !is_multicast(e) && config.socket_type != ZmqSocketType::Sub && !publisher_connects);
+    let connects: Vec<String> = match config.socket_type {
+        ZmqSocketType::XPub | ZmqSocketType::Pub if !publisher_connects => Vec::new(),
+        _ => config.connect_endpoints.iter().filter(|e| !is_multicast(e)).cloned().collect(),
+    };
+    let mut monitor = match SocketMonitor::attach(&context, &socket, Liveness::new(bind, &connects)) {
+        Ok(monitor) => Some(monitor),
+        Err(e) => {
+            warn!("[ZMQ:{}] Failed to monitor socket, health checks will always pass: {}", config.name, e);
+            None
+        }
+    };
 
     // Bind or connect based on socket type
     match config.socket_type {
+        ZmqSocketType::XPub | ZmqSocketType::Pub if publisher_connects => {
+            // Attach to a peer that owns the bind, e.g. a proxy's XSUB
+            for endpoint in &config.connect_endpoints {
+                if let Err(e) = connect_socket(&socket, &config, endpoint) {
+                    warn!("[ZMQ:{}] Failed to connect to {}: {}", config.name, endpoint, e);
+                } else {
+                    info!("[ZMQ:{}] {:?} connected to {}", config.name, config.socket_type, endpoint);
+                }
+            }
+        }
         ZmqSocketType::XPub | ZmqSocketType::XSub => {
             // Bind for proxy sockets
             if let Some(ref endpoint) = config.bind_endpoint {
-                if let Err(e) = socket.bind(endpoint) {
+                if let Err(e) = bind_socket(&socket, &config, endpoint) {
                     error!("[ZMQ:{}] Failed to bind: {}", config.name, e);
+                    report(ConnectionStatus::Error);
                     return;
                 }
                 info!("[ZMQ:{}] Bound to {}", config.name, endpoint);
@@ -460,7 +2125,7 @@ fn run_zmq_worker(
                 
                 // Also connect to external publishers
                 for endpoint in &config.connect_endpoints {
-                    if let Err(e) = socket.connect(endpoint) {
+                    if let Err(e) = connect_socket(&socket, &config, endpoint) {
                         warn!("[ZMQ:{}] Failed to connect to {}: {}", config.name, endpoint, e);
                     } else {
                         info!("[ZMQ:{}] Connected to {}", config.name, endpoint);
@@ -471,8 +2136,9 @@ fn run_zmq_worker(
         ZmqSocketType::Pub => {
             // Bind for publishing
             if let Some(ref endpoint) = config.bind_endpoint {
-                if let Err(e) = socket.bind(endpoint) {
+                if let Err(e) = bind_socket(&socket, &config, endpoint) {
                     error!("[ZMQ:{}] Failed to bind: {}", config.name, e);
+                    report(ConnectionStatus::Error);
                     return;
                 }
                 info!("[ZMQ:{}] PUB bound to {}", config.name, endpoint);
@@ -482,13 +2148,31 @@ fn run_zmq_worker(
             // Connect to publishers
             let _ = socket.set_subscribe(b"");
             for endpoint in &config.connect_endpoints {
-                if let Err(e) = socket.connect(endpoint) {
+                if let Err(e) = connect_socket(&socket, &config, endpoint) {
                     warn!("[ZMQ:{}] Failed to connect to {}: {}", config.name, endpoint, e);
                 } else {
                     info!("[ZMQ:{}] SUB connected to {}", config.name, endpoint);
                 }
             }
         }
+        ZmqSocketType::Push | ZmqSocketType::Pull => {
+            // Either side may bind; PUSH/PULL has no subscription filter
+            if let Some(ref endpoint) = config.bind_endpoint {
+                if let Err(e) = bind_socket(&socket, &config, endpoint) {
+                    error!("[ZMQ:{}] Failed to bind: {}", config.name, e);
+                    report(ConnectionStatus::Error);
+                    return;
+                }
+                info!("[ZMQ:{}] Bound to {}", config.name, endpoint);
+            }
+            for endpoint in &config.connect_endpoints {
+                if let Err(e) = connect_socket(&socket, &config, endpoint) {
+                    warn!("[ZMQ:{}] Failed to connect to {}: {}", config.name, endpoint, e);
+                } else {
+                    info!("[ZMQ:{}] Connected to {}", config.name, endpoint);
+                }
+            }
+        }
     }
 
     let _ = socket.set_rcvtimeo(100); // 100ms timeout
@@ -499,39 +2183,117 @@ fn run_zmq_worker(
         Ok(rt) => rt,
         Err(e) => {
             error!("[ZMQ:{}] Failed to create tokio runtime: {}", config.name, e);
+            report(ConnectionStatus::Error);
             return;
         }
     };
 
-    while running.load(Ordering::SeqCst) {
-        // Receive from socket (for XSUB, SUB types)
-        if matches!(config.socket_type, ZmqSocketType::XSub | ZmqSocketType::Sub) {
-            match socket.recv_bytes(0) {
-                Ok(data) => {
-                    info!("[ZMQ:{}] Received {} bytes", config.name, data.len());
-                    
-                    // Parse topic and payload (format: "topic payload")
-                    if let Some(sep_pos) = data.iter().position(|&b| b == b' ') {
-                        let topic = String::from_utf8_lossy(&data[..sep_pos]).to_string();
-                        let payload = data[sep_pos + 1..].to_vec();
+    let publishes = matches!(config.socket_type, ZmqSocketType::XPub | ZmqSocketType::Pub | ZmqSocketType::Push);
+    let mut batch = Batch::from_config(&config).filter(|_| publishes);
+    let send_batch = |batch: &mut Batch<ZmqCommand>| send_zmq_burst(&socket, &config, &activity, batch.take());
+
+    // End of the warmup after a SUB reconnect, and messages received during it
+    let mut rejoin_until: Option<Instant> = None;
+    let mut rejoin_received = 0u64;
+
+    while running.load(Ordering::SeqCst) && !activity.stop_requested() {
+        let now = Instant::now();
+        // Peers come and go as libzmq reconnects, which the monitor follows
+        let healthy = monitor.as_mut().is_none_or(SocketMonitor::healthy);
+        health.poll(now, || healthy);
+        report(if healthy { ConnectionStatus::Connected } else { ConnectionStatus::Connecting });
+
+        // A SUB reconnects by itself, but a restarted publisher drops what it
+        // sends before the subscription reaches it
+        if config.socket_type == ZmqSocketType::Sub
+            && let Some(ref mut monitor) = monitor
+        {
+            for endpoint in monitor.rejoined() {
+                metrics().record_zmq_rejoin();
+                if config.resubscribe_on_reconnect {
+                    // libzmq only sends subscriptions it does not hold yet
+                    let _ = socket.set_unsubscribe(b"");
+                    let _ = socket.set_subscribe(b"");
+                }
+                if config.rejoin_warmup_ms > 0 {
+                    info!(
+                        "[ZMQ:{}] Rejoining {}; messages published in the next {} ms may be missed",
+                        config.name, endpoint, config.rejoin_warmup_ms
+                    );
+                    rejoin_until = Some(Instant::now() + Duration::from_millis(config.rejoin_warmup_ms as u64));
+                    rejoin_received = 0;
+                } else {
+                    info!("[ZMQ:{}] Reconnected to {}", config.name, endpoint);
+                }
+            }
+        }
+        if rejoin_until.is_some_and(|until| Instant::now() >= until) {
+            info!("[ZMQ:{}] Rejoined, {} messages received during warmup", config.name, rejoin_received);
+            rejoin_until = None;
+        }
 
-                        info!("[ZMQ:{}] Parsed message: topic={}, payload_len={}", config.name, topic, payload.len());
+        // Receive from socket (for XSUB, SUB, PULL types)
+        if matches!(config.socket_type, ZmqSocketType::XSub | ZmqSocketType::Sub | ZmqSocketType::Pull) {
+            match socket.recv_multipart(0) {
+                Ok(frames) => {
+                    activity.record_received();
+                    if rejoin_until.is_some() {
+                        rejoin_received += 1;
+                    }
+                    let pull = config.socket_type == ZmqSocketType::Pull;
+                    let (identity, frames) = if pull {
+                        (None, frames)
+                    } else {
+                        split_identity_frames(frames)
+                    };
+                    info!("[ZMQ:{}] Received {} bytes", config.name, frames.iter().map(Vec::len).sum::<usize>());
+
+                    // Verify and strip the HMAC tag when signing is enabled
+                    let frames = match config.hmac_key {
+                        Some(ref key) => match verify_frames(key.as_bytes(), frames) {
+                            Some(frames) => frames,
+                            None => {
+                                metrics().record_hmac_failure();
+                                warn!("[ZMQ:{}] Dropping frame with invalid HMAC", config.name);
+                                continue;
+                            }
+                        },
+                        None => frames,
+                    };
+                    
+                    // Parse topic and payload in the endpoint's layout. PULL messages
+                    // carry no topic: every frame is payload under an empty topic
+                    let decoded = if pull {
+                        (!frames.is_empty()).then(|| (String::new(), frames))
+                    } else {
+                        decode_message(config.topic_separator, frames)
+                    };
+                    if let Some((topic, payload_frames)) = decoded {
+                        let mut payload_frames = payload_frames.into_iter();
+                        let payload = payload_frames.next().unwrap_or_default();
+                        let frames: Vec<Vec<u8>> = payload_frames.collect();
+                        info!(
+                            "[ZMQ:{}] Parsed message: topic={}, payload_len={}, extra_frames={}",
+                            config.name, topic, payload.len(), frames.len()
+                        );
 
                         let fwd_msg = ForwardMessage {
                             source: MessageSource::Zmq,
                             source_id: config_id,
                             topic,
                             payload,
+                            identity,
+                            user_properties: Vec::new(),
+                            frames,
                         };
 
                         rt.block_on(async {
-                            if let Err(e) = forward_tx.send(fwd_msg).await {
+                            if let Err(e) = queue_forward(&forward_tx, fwd_msg).await {
                                 error!("[ZMQ:{}] Failed to forward: {}", config.name, e);
                             }
                         });
                     } else {
-                        // No space separator - treat entire message as topic or use alternative parsing
-                        warn!("[ZMQ:{}] Message has no space separator, raw: {:?}", config.name, String::from_utf8_lossy(&data));
+                        warn!("[ZMQ:{}] Dropping message not framed as {:?}", config.name, config.topic_separator);
                     }
                 }
                 Err(zmq::Error::EAGAIN) => {
@@ -544,32 +2306,114 @@ fn run_zmq_worker(
                 }
             }
         } else {
-            // For XPUB/PUB sockets, just sleep a bit to prevent busy loop
-            std::thread::sleep(std::time::Duration::from_millis(10));
+            // For XPUB/PUB sockets, just sleep a bit to prevent busy loop,
+            // waking early when a batch is due
+            let idle = Duration::from_millis(10);
+            let idle = batch.as_ref().and_then(|b| b.time_left(Instant::now())).map_or(idle, |left| left.min(idle));
+            std::thread::sleep(idle);
         }
 
-        // Check for commands (for all socket types that can publish: XPUB, PUB)
-        if matches!(config.socket_type, ZmqSocketType::XPub | ZmqSocketType::Pub) {
+        // Check for commands (for all socket types that can publish: XPUB, PUB, PUSH)
+        if publishes {
             while let Ok(cmd) = cmd_rx.try_recv() {
-                match cmd {
-                    ZmqCommand::Publish(topic, payload) => {
-                        let mut message = topic.as_bytes().to_vec();
-                        message.push(b' ');
-                        message.extend_from_slice(&payload);
-                        
-                        info!("[ZMQ:{}] Publishing to topic: {} ({} bytes)", config.name, topic, payload.len());
-                        
-                        match socket.send(&message, 0) {
-                            Ok(_) => debug!("[ZMQ:{}] Message sent successfully", config.name),
-                            Err(e) => error!("[ZMQ:{}] Failed to send: {}", config.name, e),
+                match batch {
+                    Some(ref mut batch) => {
+                        batch.push(cmd, Instant::now());
+                        if batch.is_due(Instant::now()) {
+                            send_batch(batch);
                         }
                     }
+                    None => send_zmq_command(&socket, &config, &activity, cmd),
                 }
             }
+            if let Some(ref mut batch) = batch
+                && batch.is_due(Instant::now())
+            {
+                send_batch(batch);
+            }
         }
     }
 
+    // Messages already taken from the queue would be lost with the socket
+    if let Some(ref mut batch) = batch {
+        send_batch(batch);
+    }
+
     info!("[ZMQ:{}] Worker stopped", config.name);
+    report(ConnectionStatus::Disconnected);
+}
+
+/// A queued message encoded for its socket, ready to send
+struct EncodedPublish {
+    topic: String,
+    frames: Vec<Vec<u8>>,
+    flags: i32,
+    queued: Instant,
+    receipt: Option<Arc<DeliveryReceipt>>,
+}
+
+/// Build the frames of a queued message as the endpoint sends them
+fn encode_zmq_command(config: &ZmqConfig, cmd: ZmqCommand) -> EncodedPublish {
+    let ZmqCommand::Publish(topic, payload, identity, queued, receipt) = cmd;
+    let push = config.socket_type == ZmqSocketType::Push;
+    let mut frames = if push {
+        payload
+    } else {
+        encode_message(config.topic_separator, &topic, &payload)
+    };
+    if let Some(ref key) = config.hmac_key {
+        frames = sign_frames(key.as_bytes(), frames);
+    }
+
+    // PUSH blocks without a connected peer; drop like PUB does instead
+    let (frames, flags) = if push {
+        (frames, zmq::DONTWAIT)
+    } else {
+        (with_identity_frames(identity, frames), 0)
+    };
+    EncodedPublish { topic, frames, flags, queued, receipt }
+}
+
+/// Send a queued message on a publishing socket
+fn send_zmq_command(socket: &zmq::Socket, config: &ZmqConfig, activity: &ThreadActivity, cmd: ZmqCommand) {
+    let encoded = encode_zmq_command(config, cmd);
+    let len: usize = encoded.frames.iter().map(Vec::len).sum();
+    info!("[ZMQ:{}] Publishing to topic: {} ({} bytes)", config.name, encoded.topic, len);
+    send_encoded(socket, config, activity, encoded);
+}
+
+/// Send a batch as one burst: every message is encoded first, then all are
+/// handed to libzmq back to back
+fn send_zmq_burst(socket: &zmq::Socket, config: &ZmqConfig, activity: &ThreadActivity, cmds: Vec<ZmqCommand>) {
+    let burst: Vec<EncodedPublish> = cmds.into_iter().map(|cmd| encode_zmq_command(config, cmd)).collect();
+    info!("[ZMQ:{}] Publishing a burst of {} messages", config.name, burst.len());
+    for encoded in burst {
+        send_encoded(socket, config, activity, encoded);
+    }
+}
+
+fn send_encoded(socket: &zmq::Socket, config: &ZmqConfig, activity: &ThreadActivity, encoded: EncodedPublish) {
+    let EncodedPublish { topic, frames, flags, queued, receipt } = encoded;
+    // Time in the endpoint's queue, including any wait for its batch to fill
+    metrics().record_zmq_queue_time(queued.elapsed().as_secs_f64() * 1000.0);
+    match socket.send_multipart(frames, flags) {
+        Ok(_) => {
+            activity.record_sent();
+            if let Some(receipt) = receipt {
+                receipt.published();
+            }
+            debug!("[ZMQ:{}] Message sent on {}", config.name, topic);
+        }
+        Err(e) => {
+            metrics().record_forward_error(
+                &EndpointType::Zmq,
+                config.id.unwrap_or(0),
+                &topic,
+                format!("send failed: {}", e),
+            );
+            error!("[ZMQ:{}] Failed to send: {}", config.name, e)
+        }
+    }
 }
 
 /// Check if topic matches pattern with MQTT wildcards
@@ -629,3 +2473,1038 @@ fn apply_mapping(pattern: &str, target: &str, source: &str) -> String {
         result.join("/")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::dedup::DedupKey;
+    use crate::config::ForwardOverflow;
+    use crate::mqtt::identity_properties;
+    use crate::zeromq::{split_identity, with_identity};
+
+    /// Context routing `mappings` to the given endpoint queues, with every
+    /// other setting at its default and an unconnected database
+    fn test_context(
+        mappings: Vec<TopicMapping>,
+        mqtt_txs: HashMap<u32, std::sync::mpsc::Sender<MqttCommand>>,
+        zmq_txs: HashMap<u32, std::sync::mpsc::Sender<ZmqCommand>>,
+    ) -> ForwardContext {
+        ForwardContext {
+            mqtt_cmd_txs: Arc::new(RwLock::new(mqtt_txs)),
+            zmq_cmd_txs: Arc::new(RwLock::new(zmq_txs)),
+            mappings_cache: Arc::new(tokio::sync::RwLock::new(mappings.into())),
+            repo: Repository::new(sqlx::SqlitePool::connect_lazy("sqlite::memory:").unwrap()),
+            sampler: Arc::new(Sampler::new()),
+            separator_conflict: SeparatorConflict::Reject,
+            json_limits: JsonLimits::from_settings(&BridgeConfig::default()),
+            max_topic_levels: None,
+            max_payload_bytes: None,
+            topic_rewrites: Arc::default(),
+            egress_rewrites: Arc::default(),
+            exec: None,
+            wal: None,
+            zmq_separators: Arc::default(),
+            extra_frames: ExtraFrames::Drop,
+            user_property_frames: Arc::default(),
+            dedup_ttl: Duration::from_secs(3600),
+            dry_run: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_batches() {
+        let topics: Vec<String> = (0..5).map(|i| format!("t/{}", i)).collect();
+
+        assert_eq!(subscribe_batches(&topics, 0).len(), 1);
+        assert_eq!(subscribe_batches(&topics, 10).len(), 1);
+
+        let batches = subscribe_batches(&topics, 2);
+        assert_eq!(batches.iter().map(|b| b.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_start_without_enabled_endpoints() {
+        let pool = sqlx::SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        let mut worker = BridgeWorker::new();
+
+        let err = worker
+            .start_extended(
+                vec![MqttConfig { enabled: false, ..Default::default() }],
+                vec![ZmqConfig { enabled: false, ..Default::default() }],
+                Arc::new(tokio::sync::RwLock::new(CompiledMappings::default())),
+                Repository::new(pool),
+                BridgeConfig::default(),
+                &ZmqSettings::default(),
+            )
+            .unwrap_err();
+
+        assert!(matches!(err.downcast_ref::<StartError>(), Some(StartError::NoEnabledEndpoints)));
+        assert!(!worker.is_running());
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_reaches_all_targets() {
+        use crate::models::MappingTarget;
+
+        let (mqtt_tx, mqtt_rx) = std::sync::mpsc::channel();
+        let (zmq1_tx, zmq1_rx) = std::sync::mpsc::channel();
+        let (zmq2_tx, zmq2_rx) = std::sync::mpsc::channel();
+
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "sensors/+".to_string(),
+            target_topic: "zmq/out".to_string(),
+            target_endpoint_id: 1,
+            additional_targets: vec![
+                MappingTarget {
+                    endpoint_type: EndpointType::Zmq,
+                    endpoint_id: 2,
+                    target_topic: "copy/out".to_string(),
+                },
+                MappingTarget {
+                    endpoint_type: EndpointType::Mqtt,
+                    endpoint_id: 7,
+                    target_topic: "mirror/out".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let ctx = test_context(
+            vec![mapping],
+            HashMap::from([(7, mqtt_tx)]),
+            HashMap::from([(1, zmq1_tx), (2, zmq2_tx)]),
+        );
+
+        forward_message(&ctx, ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "sensors/t1".to_string(),
+            payload: b"21.5".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        })
+        .await;
+
+        let Ok(ZmqCommand::Publish(topic, payload, _, _, _)) = zmq1_rx.try_recv() else { panic!("zmq 1") };
+        assert_eq!((topic.as_str(), payload), ("zmq/out", vec![b"21.5".to_vec()]));
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq2_rx.try_recv() else { panic!("zmq 2") };
+        assert_eq!(topic, "copy/out");
+        let Ok(MqttCommand::Publish(publish)) = mqtt_rx.try_recv() else { panic!("mqtt") };
+        assert_eq!(publish.topic, "mirror/out");
+        assert_eq!(publish.mapping_id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_v5_user_properties_travel_as_zmq_frame() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "sensors/+".to_string(),
+            target_topic: "zmq/out".to_string(),
+            target_endpoint_type: EndpointType::Zmq,
+            target_endpoint_id: 1,
+            ..Default::default()
+        };
+        let ctx = ForwardContext {
+            zmq_separators: Arc::new(RwLock::new(HashMap::from([(1, Some(TopicSeparator::Multipart))]))),
+            user_property_frames: Arc::new(RwLock::new(HashSet::from([1]))),
+            ..test_context(
+                vec![
+                    mapping.clone(),
+                    TopicMapping { id: 2, source_endpoint_id: 2, ..mapping },
+                ],
+                HashMap::new(),
+                HashMap::from([(1, zmq_tx)]),
+            )
+        };
+        let msg = |source_id: u32| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id,
+            topic: "sensors/t1".to_string(),
+            payload: b"21.5".to_vec(),
+            identity: None,
+            user_properties: vec![("unit".to_string(), "C".to_string())],
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg(1)).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("v5 broker") };
+        assert_eq!(payload, vec![b"21.5".to_vec(), br#"{"unit":"C"}"#.to_vec()]);
+
+        // Brokers not configured for v5 keep their properties out of ZMQ
+        forward_message(&ctx, msg(2)).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("v3 broker") };
+        assert_eq!(payload, vec![b"21.5".to_vec()]);
+    }
+
+    #[test]
+    fn test_create_options_follow_protocol_version() {
+        for (v5, version) in [(true, paho_mqtt::MQTT_VERSION_5), (false, paho_mqtt::MQTT_VERSION_3_1_1)] {
+            let options = mqtt_create_options("tcp://localhost:1883", "zeromqtt-test-version", v5);
+            let client = paho_mqtt::AsyncClient::new(options).unwrap();
+            assert_eq!(client.mqtt_version(), version);
+        }
+    }
+
+    #[test]
+    fn test_empty_target_topic_paths() {
+        let msg = ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "sensors/t1".to_string(),
+            payload: b"{}".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+        let mapping = TopicMapping { source_topic: "sensors/+".to_string(), ..Default::default() };
+
+        // An empty target and a target with more wildcards than source levels
+        let empty = resolve_target_topic(&mapping, "", &msg).unwrap();
+        assert_eq!(empty, "");
+        assert_eq!(guard_target_topic(&mapping, empty.clone()), None);
+        let leftover = resolve_target_topic(&mapping, "+/+/+", &msg).unwrap();
+        assert_eq!(leftover, "sensors/t1/+");
+        assert_eq!(guard_target_topic(&mapping, leftover.clone()), None);
+
+        let with_fallback = TopicMapping { fallback_topic: Some("unrouted".to_string()), ..mapping.clone() };
+        assert_eq!(guard_target_topic(&with_fallback, empty), Some("unrouted".to_string()));
+        assert_eq!(guard_target_topic(&with_fallback, leftover), Some("unrouted".to_string()));
+        assert_eq!(guard_target_topic(&with_fallback, "a/b".to_string()), Some("a/b".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_separator_conflict_rejected() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "#".to_string(),
+            target_topic: "#".to_string(),
+            target_endpoint_id: 1,
+            ..Default::default()
+        };
+        let ctx = test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]));
+        let msg = |topic: &str| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: b"1".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg("a b/c")).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        forward_message(&ctx, msg("a_b/c")).await;
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "a_b/c");
+    }
+
+    #[test]
+    fn test_match_topic_in_order() {
+        let mappings = vec![
+            TopicMapping {
+                id: 1,
+                source_topic: "sensors/#".to_string(),
+                target_topic: "all".to_string(),
+                stop_on_match: true,
+                ..Default::default()
+            },
+            TopicMapping { id: 2, source_topic: "sensors/+".to_string(), target_topic: "one".to_string(), ..Default::default() },
+            TopicMapping { id: 3, source_topic: "other".to_string(), ..Default::default() },
+            TopicMapping { id: 4, source_topic: "sensors/t1".to_string(), enabled: false, ..Default::default() },
+        ];
+
+        let matches = match_topic(&mappings, None, None, "sensors/t1");
+        let summary: Vec<(u32, bool)> = matches.iter().map(|m| (m.mapping_id, m.shadowed)).collect();
+        assert_eq!(summary, vec![(1, false), (2, true)]);
+        assert_eq!(matches[1].targets[0].target_topic.as_deref(), Some("one"));
+
+        assert!(match_topic(&mappings, Some(&EndpointType::Zmq), None, "sensors/t1").is_empty());
+        assert!(match_topic(&mappings, None, Some(9), "sensors/t1").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_json_limits_drop_before_parse() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "in".to_string(),
+            target_topic: "out/${json:device}".to_string(),
+            target_endpoint_id: 1,
+            ..Default::default()
+        };
+        let ctx = ForwardContext {
+            json_limits: JsonLimits { max_depth: 3, max_bytes: 256 },
+            ..test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = |payload: String| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "in".to_string(),
+            payload: payload.into_bytes(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        let nested = format!(r#"{{"device":"d1","x":{}{}}}"#, "[".repeat(10), "]".repeat(10));
+        forward_message(&ctx, msg(nested)).await;
+        let oversized = format!(r#"{{"device":"d1","x":"{}"}}"#, "x".repeat(300));
+        forward_message(&ctx, msg(oversized)).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        forward_message(&ctx, msg(r#"{"device":"d1"}"#.to_string())).await;
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/d1");
+    }
+
+    #[tokio::test]
+    async fn test_topic_rewrites_apply_before_matching() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "sensors/+".to_string(),
+            target_topic: "out/data".to_string(),
+            target_endpoint_id: 1,
+            ..Default::default()
+        };
+        let rules = vec![TopicRewrite::Prefix { from: "site-a/".to_string(), to: String::new() }];
+        let ctx = ForwardContext {
+            topic_rewrites: Arc::new(RwLock::new(HashMap::from([((MessageSource::Mqtt, 1), rules)]))),
+            ..test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = |source_id: u32| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id,
+            topic: "site-a/sensors/t1".to_string(),
+            payload: b"1".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // Rules belong to their endpoint only
+        let other = rewrite_incoming(&ctx, msg(2));
+        assert_eq!(other.topic, "site-a/sensors/t1");
+
+        forward_message(&ctx, rewrite_incoming(&ctx, msg(1))).await;
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/data");
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_pipeline_order() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "sensors/+".to_string(),
+            target_topic: "out/+".to_string(),
+            target_endpoint_id: 1,
+            truncate_bytes: Some(4),
+            ..Default::default()
+        };
+        let ingress = vec![TopicRewrite::Prefix { from: "site-a/".to_string(), to: String::new() }];
+        let egress = vec![TopicRewrite::Prefix { from: "out/".to_string(), to: "plant/out/".to_string() }];
+        let ctx = ForwardContext {
+            topic_rewrites: Arc::new(RwLock::new(HashMap::from([((MessageSource::Mqtt, 1), ingress)]))),
+            egress_rewrites: Arc::new(RwLock::new(HashMap::from([((MessageSource::Zmq, 1), egress)]))),
+            ..test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "site-a/sensors/t1".to_string(),
+            payload: b"HDR1 and a long tail".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // Ingress rewrite, then mapping and truncation, then egress rewrite
+        forward_message(&ctx, rewrite_incoming(&ctx, msg)).await;
+        let Ok(ZmqCommand::Publish(topic, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "plant/out/t1");
+        assert_eq!(payload, vec![b"HDR1".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_records_without_publishing() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "sensors/+".to_string(),
+            target_topic: "out/+".to_string(),
+            target_endpoint_id: 1,
+            ..Default::default()
+        };
+        let dry_run = Arc::new(DryRun::new(true));
+        let ctx = ForwardContext {
+            dry_run: dry_run.clone(),
+            ..test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = || ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "sensors/t1".to_string(),
+            payload: b"21.5".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg()).await;
+        forward_message(&ctx, msg()).await;
+        assert!(zmq_rx.try_recv().is_err());
+        let report = dry_run.report();
+        assert_eq!(report.would_forward, 2);
+        assert_eq!(report.targets[0].messages, 2);
+        assert_eq!(report.samples[0].target_topic, "out/t1");
+        assert_eq!(report.samples[0].payload_bytes, 4);
+
+        dry_run.set_enabled(false);
+        forward_message(&ctx, msg()).await;
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/t1");
+        assert_eq!(dry_run.report().would_forward, 2);
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_idles_and_stops_promptly() {
+        use std::sync::atomic::AtomicUsize;
+
+        let ctx = test_context(Vec::new(), HashMap::new(), HashMap::new());
+        // The sender stays open, as the endpoint threads keep theirs while running
+        let (_forward_tx, forward_rx) = forward_channel::<ForwardMessage>(10, ForwardOverflow::Block, false, Arc::default());
+        let shutdown = Arc::new(Notify::new());
+
+        // Every wakeup of the task is a poll of its future
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        let mut forwarding = Box::pin(run_forwarding(ctx, forward_rx, 1, shutdown.clone()));
+        let task = tokio::spawn(std::future::poll_fn(move |cx| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            forwarding.as_mut().poll(cx)
+        }));
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!task.is_finished());
+        assert!(polls.load(Ordering::Relaxed) <= 2, "woke {} times without traffic", polls.load(Ordering::Relaxed));
+
+        let stopping = Instant::now();
+        shutdown.notify_one();
+        tokio::time::timeout(Duration::from_secs(1), task).await.expect("stops").unwrap();
+        assert!(stopping.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_unrouted_messages_are_dead_lettered() {
+        // Endpoint 9 is not running
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "dlq-test/routed".to_string(),
+            target_topic: "out".to_string(),
+            target_endpoint_id: 9,
+            ..Default::default()
+        };
+        let ctx = test_context(vec![mapping], HashMap::new(), HashMap::new());
+        let msg = |topic: &str| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: b"lost".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg("dlq-test/unmatched")).await;
+        forward_message(&ctx, msg("dlq-test/routed")).await;
+
+        // The buffer is global, so look only at this test's topics
+        let kept: Vec<_> = metrics()
+            .dead_letters(usize::MAX)
+            .into_iter()
+            .filter(|m| m.topic.starts_with("dlq-test/"))
+            .collect();
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].topic, "dlq-test/routed");
+        assert_eq!(kept[0].reason, "ZMQ endpoint 9 not found");
+        assert_eq!(kept[1].topic, "dlq-test/unmatched");
+        assert_eq!(kept[1].reason, "no matching mapping");
+        assert_eq!(kept[1].source, EndpointType::Mqtt);
+        assert_eq!(kept[1].payload, b"lost");
+    }
+
+    #[tokio::test]
+    async fn test_dedup_key_recorded_after_delivery() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "orders/#".to_string(),
+            target_topic: "out/orders".to_string(),
+            target_endpoint_id: 1,
+            dedup_key: Some("json:id".to_string()),
+            ..Default::default()
+        };
+        let ctx = ForwardContext {
+            repo: Repository::new(crate::db::init_memory_db().await.unwrap()),
+            json_limits: JsonLimits { max_depth: 32, max_bytes: 64 },
+            ..test_context(vec![mapping], HashMap::new(), HashMap::new())
+        };
+        let msg = |payload: &[u8]| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "orders/1".to_string(),
+            payload: payload.to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // The target is not running yet, so the key is not recorded
+        forward_message(&ctx, msg(br#"{"id":"a"}"#)).await;
+        ctx.zmq_cmd_txs.write().insert(1, zmq_tx);
+        forward_message(&ctx, msg(br#"{"id":"a"}"#)).await;
+        assert!(zmq_rx.try_recv().is_ok());
+        forward_message(&ctx, msg(br#"{"id":"a"}"#)).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        // The key path is parsed only within the JSON limits
+        let oversized = format!(r#"{{"id":"b","pad":"{}"}}"#, "x".repeat(64));
+        forward_message(&ctx, msg(oversized.as_bytes())).await;
+        assert!(zmq_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_has_no_side_effects() {
+        let dir = std::env::temp_dir().join(format!("zeromqtt-dry-run-wal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "orders/#".to_string(),
+            target_topic: "out/orders".to_string(),
+            target_endpoint_id: 1,
+            durable: true,
+            dedup_key: Some("hash".to_string()),
+            transform_command: Some("cat".to_string()),
+            ..Default::default()
+        };
+        let repo = Repository::new(crate::db::init_memory_db().await.unwrap());
+        let wal = Arc::new(WriteAheadLog::new(&dir, 1024 * 1024));
+        // Transforms stay disabled, which would drop the message if the command ran
+        let ctx = ForwardContext {
+            repo: repo.clone(),
+            dry_run: Arc::new(DryRun::new(true)),
+            wal: Some(wal.clone()),
+            ..test_context(vec![mapping.clone()], HashMap::new(), HashMap::new())
+        };
+        let msg = ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "orders/1".to_string(),
+            payload: b"1".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg.clone()).await;
+        forward_message(&ctx, msg.clone()).await;
+        assert_eq!(ctx.dry_run.report().would_forward, 2);
+        assert_eq!(wal.pending_count(), 0);
+        assert!(!dir.exists());
+        let key = DedupKey::Hash.extract(&msg.topic, &msg.payload).unwrap();
+        assert!(!repo.seen_since(1, &key, 0).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_durable_mapping_logs_until_delivered() {
+        let dir = std::env::temp_dir().join(format!("zeromqtt-worker-wal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "orders/#".to_string(),
+            target_topic: "out/orders".to_string(),
+            target_endpoint_id: 1,
+            durable: true,
+            ..Default::default()
+        };
+        let stopped = TopicMapping {
+            id: 2,
+            source_topic: "audit/#".to_string(),
+            target_topic: "out/audit".to_string(),
+            target_endpoint_id: 9,
+            durable: true,
+            ..Default::default()
+        };
+        let wal = Arc::new(WriteAheadLog::new(&dir, 1024 * 1024));
+        let ctx = ForwardContext {
+            wal: Some(wal.clone()),
+            ..test_context(vec![mapping, stopped], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = |topic: &str| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: b"1".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // Queued for the endpoint, the entry waits until its thread publishes
+        forward_message(&ctx, msg("orders/1")).await;
+        let Ok(ZmqCommand::Publish(_, _, _, _, Some(receipt))) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(wal.pending_count(), 1);
+        receipt.published();
+        drop(receipt);
+        assert_eq!(wal.pending_count(), 0);
+
+        // A target that is not running leaves the entry for the next replay
+        forward_message(&ctx, msg("audit/1")).await;
+        assert_eq!(wal.pending_count(), 1);
+
+        // An entry left behind by a crash is delivered on replay
+        wal.append(&msg("orders/2")).unwrap();
+        let restarted = ForwardContext {
+            wal: Some(Arc::new(WriteAheadLog::new(&dir, 1024 * 1024))),
+            ..ctx
+        };
+        replay_wal(&restarted).await;
+        let Ok(ZmqCommand::Publish(topic, _, _, _, Some(receipt))) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/orders");
+        receipt.published();
+        drop(receipt);
+        assert_eq!(restarted.wal.as_ref().unwrap().pending_count(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_payload_conversion_drops_invalid_input() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "in".to_string(),
+            target_topic: "out".to_string(),
+            target_endpoint_id: 1,
+            payload_transform: Some(PayloadTransform::JsonToMsgpack),
+            ..Default::default()
+        };
+        let ctx = ForwardContext {
+            json_limits: JsonLimits { max_depth: 2, max_bytes: 1024 * 1024 },
+            ..test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]))
+        };
+        let msg = |payload: &[u8]| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "in".to_string(),
+            payload: payload.to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg(br#"{"n":1}"#)).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(payload, vec![vec![0x81, 0xa1, b'n', 0x01]]);
+
+        forward_message(&ctx, msg(b"not json")).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        // Nested deeper than json_max_depth, so it is not parsed
+        forward_message(&ctx, msg(br#"{"a":{"b":{"c":1}}}"#)).await;
+        assert!(zmq_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_truncate_bytes() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = TopicMapping {
+            id: 1,
+            source_topic: "in".to_string(),
+            target_topic: "out".to_string(),
+            target_endpoint_id: 1,
+            truncate_bytes: Some(4),
+            ..Default::default()
+        };
+        let ctx = test_context(vec![mapping], HashMap::new(), HashMap::from([(1, zmq_tx)]));
+        let msg = |payload: &[u8]| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "in".to_string(),
+            payload: payload.to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg(b"HDR1 and a long tail")).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(payload, vec![b"HDR1".to_vec()]);
+
+        forward_message(&ctx, msg(b"HD")).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(payload, vec![b"HD".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_max_payload_bytes() {
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let mapping = |id: u32, max_payload_bytes: Option<u32>| TopicMapping {
+            id,
+            source_topic: format!("oversize/{}", id),
+            target_topic: "out".to_string(),
+            target_endpoint_id: 1,
+            max_payload_bytes,
+            ..Default::default()
+        };
+        let ctx = ForwardContext {
+            max_payload_bytes: Some(8),
+            ..test_context(
+                vec![mapping(1, None), mapping(2, Some(4)), mapping(3, Some(64))],
+                HashMap::new(),
+                HashMap::from([(1, zmq_tx)]),
+            )
+        };
+        let msg = |topic: &str, payload: &[u8]| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: payload.to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg("oversize/1", b"12345678")).await;
+        let Ok(ZmqCommand::Publish(_, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(payload, vec![b"12345678".to_vec()]);
+
+        let dropped = metrics().oversize_dropped();
+        forward_message(&ctx, msg("oversize/1", b"123456789")).await;
+        assert!(zmq_rx.try_recv().is_err());
+        assert!(metrics().oversize_dropped() > dropped);
+        assert!(metrics()
+            .dead_letters(usize::MAX)
+            .iter()
+            .any(|m| m.topic == "oversize/1" && m.reason.contains("max_payload_bytes")));
+
+        // A mapping's own limit only lowers the bridge-wide one
+        forward_message(&ctx, msg("oversize/2", b"12345")).await;
+        assert!(zmq_rx.try_recv().is_err());
+        forward_message(&ctx, msg("oversize/3", b"123456789")).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        // Too large for several mappings, it still counts once
+        let shared = |id: u32| TopicMapping { source_topic: "oversize/shared".to_string(), ..mapping(id, Some(4)) };
+        *ctx.mappings_cache.write().await = vec![shared(4), shared(5)].into();
+        let before = metrics().dead_letters(usize::MAX).iter().filter(|m| m.topic == "oversize/shared").count();
+        forward_message(&ctx, msg("oversize/shared", b"12345")).await;
+        let after = metrics().dead_letters(usize::MAX).iter().filter(|m| m.topic == "oversize/shared").count();
+        assert_eq!(after - before, 1);
+    }
+
+    #[tokio::test]
+    async fn test_transform_error_policies() {
+        use crate::models::TransformErrorPolicy;
+
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        // Each mapping converts payloads that are not JSON, so every conversion fails
+        let mapping = |id: u32, policy: TransformErrorPolicy| TopicMapping {
+            id,
+            source_topic: format!("in/{}", id),
+            target_topic: format!("out/{}", id),
+            target_endpoint_id: 1,
+            payload_transform: Some(PayloadTransform::JsonToMsgpack),
+            transform_error_policy: policy,
+            ..Default::default()
+        };
+        let repo = Repository::new(crate::db::init_memory_db().await.unwrap());
+        let ctx = ForwardContext {
+            repo: repo.clone(),
+            ..test_context(
+                vec![
+                    mapping(1, TransformErrorPolicy::Drop),
+                    mapping(2, TransformErrorPolicy::PassThrough),
+                    mapping(3, TransformErrorPolicy::DeadLetter),
+                ],
+                HashMap::new(),
+                HashMap::from([(1, zmq_tx)]),
+            )
+        };
+        let msg = |topic: &str| ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: b"not json".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        forward_message(&ctx, msg("in/1")).await;
+        assert!(zmq_rx.try_recv().is_err());
+
+        // Forwarded as received
+        forward_message(&ctx, msg("in/2")).await;
+        let Ok(ZmqCommand::Publish(topic, payload, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/2");
+        assert_eq!(payload, vec![b"not json".to_vec()]);
+
+        forward_message(&ctx, msg("in/3")).await;
+        assert!(zmq_rx.try_recv().is_err());
+        let dead = repo.get_dead_letters(10).await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].mapping_id, 3);
+        assert_eq!((dead[0].source, dead[0].source_id), (EndpointType::Mqtt, 1));
+        assert_eq!(dead[0].topic, "in/3");
+        assert_eq!(dead[0].payload, b"not json");
+        assert!(dead[0].reason.contains("not valid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_skips_out_of_window_mappings() {
+        use chrono::Datelike;
+
+        let (zmq_tx, zmq_rx) = std::sync::mpsc::channel();
+        let elsewhen = chrono::Utc::now().weekday().succ().succ().succ();
+        let mapping = |id: u32, schedule: String| TopicMapping {
+            id,
+            source_topic: "in".to_string(),
+            target_topic: format!("out/{}", id),
+            target_endpoint_id: 1,
+            schedule: Some(schedule),
+            ..Default::default()
+        };
+        let ctx = test_context(
+            vec![
+                mapping(1, format!("{} 00:00-24:00", elsewhen)),
+                mapping(2, "00:00-24:00".to_string()),
+            ],
+            HashMap::new(),
+            HashMap::from([(1, zmq_tx)]),
+        );
+
+        forward_message(
+            &ctx,
+            ForwardMessage {
+                source: MessageSource::Mqtt,
+                source_id: 1,
+                topic: "in".to_string(),
+                payload: b"1".to_vec(),
+                identity: None,
+                user_properties: Vec::new(),
+                frames: Vec::new(),
+            },
+        )
+        .await;
+
+        let Ok(ZmqCommand::Publish(topic, _, _, _, _)) = zmq_rx.try_recv() else { panic!("zmq") };
+        assert_eq!(topic, "out/2");
+        assert!(zmq_rx.try_recv().is_err());
+
+        // Held back by its schedule, not unrouted
+        *ctx.mappings_cache.write().await = vec![TopicMapping {
+            source_topic: "schedule-test/off".to_string(),
+            ..mapping(3, format!("{} 00:00-24:00", elsewhen))
+        }]
+        .into();
+        let msg = ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "schedule-test/off".to_string(),
+            payload: b"1".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+        forward_message(&ctx, msg).await;
+        assert!(zmq_rx.try_recv().is_err());
+        assert!(!metrics().dead_letters(usize::MAX).iter().any(|m| m.topic == "schedule-test/off"));
+    }
+
+    #[test]
+    fn test_subscription_qos_is_highest_per_topic() {
+        let mapping = |id: u32, source_topic: &str, qos: u8| TopicMapping {
+            id,
+            source_topic: source_topic.to_string(),
+            qos,
+            ..Default::default()
+        };
+        let mappings = vec![
+            mapping(1, "telemetry/#", 0),
+            mapping(2, "commands/#", 2),
+            mapping(3, "telemetry/#", 1),
+            TopicMapping { source_endpoint_id: 2, ..mapping(4, "telemetry/#", 2) },
+        ];
+
+        let (topics, qos) = broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Warn);
+        assert_eq!(topics, vec!["telemetry/#", "commands/#"]);
+        assert_eq!(qos["telemetry/#"], 1);
+        assert_eq!(qos["commands/#"], 2);
+
+        let rules = vec![TopicRewrite::Prefix { from: "acme/".to_string(), to: String::new() }];
+        let (topics, qos) = broker_subscriptions(&mappings, 1, &rules, BroadSubscriptions::Warn);
+        assert!(topics.contains(&"acme/telemetry/#".to_string()));
+        assert_eq!(qos["acme/telemetry/#"], 2);
+    }
+
+    #[test]
+    fn test_broad_subscriptions_need_opt_in() {
+        let mapping = |id: u32, source_topic: &str| TopicMapping {
+            id,
+            source_topic: source_topic.to_string(),
+            ..Default::default()
+        };
+        let mappings = vec![
+            mapping(1, "#"),
+            mapping(2, "+/status"),
+            TopicMapping { allow_broad_subscription: true, ..mapping(3, "+/alerts") },
+            mapping(4, "sensors/#"),
+        ];
+
+        let (topics, _) = broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Warn);
+        assert_eq!(topics, vec!["#", "+/status", "+/alerts", "sensors/#"]);
+        let (topics, qos) = broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Reject);
+        assert_eq!(topics, vec!["+/alerts", "sensors/#"]);
+        assert!(!qos.contains_key("#"));
+    }
+
+    #[test]
+    fn test_subscriptions_truncated_to_max() {
+        let mappings: Vec<TopicMapping> = (1..=5)
+            .map(|id| TopicMapping {
+                id,
+                source_topic: format!("sensors/{}/#", id),
+                qos: (id % 3) as u8,
+                ..Default::default()
+            })
+            .collect();
+
+        let (topics, qos) = cap_subscriptions("test", broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Warn), Some(3));
+        assert_eq!(topics, vec!["sensors/1/#", "sensors/2/#", "sensors/3/#"]);
+        assert_eq!(qos.len(), 3);
+        assert!(!qos.contains_key("sensors/4/#"));
+
+        // Unset and not exceeded limits keep every filter
+        assert_eq!(cap_subscriptions("test", broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Warn), None).0.len(), 5);
+        assert_eq!(cap_subscriptions("test", broker_subscriptions(&mappings, 1, &[], BroadSubscriptions::Warn), Some(5)).0.len(), 5);
+    }
+
+    #[test]
+    fn test_invalid_filter_does_not_block_batch() {
+        let topics: Vec<String> = ["sensors/+", "$SYS/#", "bad/#/filter", "", "alerts/#", "a+b/c"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        let (valid, invalid) = split_valid_filters(&topics);
+        assert_eq!(valid, vec!["sensors/+", "$SYS/#", "alerts/#"]);
+        assert_eq!(invalid, vec!["bad/#/filter", "", "a+b/c"]);
+        assert_eq!(subscribe_batches(&valid, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_should_resubscribe() {
+        let mut limits = SubscribeLimits::from_settings(&BridgeConfig::default());
+        assert!(should_resubscribe(&limits, true, false));
+        assert!(should_resubscribe(&limits, false, false));
+        assert!(!should_resubscribe(&limits, false, true));
+
+        limits.resubscribe_on_reconnect = false;
+        assert!(!should_resubscribe(&limits, true, false));
+    }
+
+    #[test]
+    fn test_identity_round_trip() {
+        let mapping = TopicMapping {
+            identity_property: Some("zmq-identity".to_string()),
+            ..Default::default()
+        };
+
+        // ZMQ -> MQTT: identity frame becomes a user property
+        let (identity, data) = split_identity(vec![b"peer-1".to_vec(), Vec::new(), b"req/1 ping".to_vec()]);
+        let from_zmq = ForwardMessage {
+            source: MessageSource::Zmq,
+            source_id: 1,
+            topic: "req/1".to_string(),
+            payload: data,
+            identity,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+        let identity = message_identity(&mapping, &from_zmq).unwrap();
+        let props = identity_properties("zmq-identity", &identity);
+
+        // MQTT -> ZMQ: the reply carries the property back into an identity frame
+        let from_mqtt = ForwardMessage {
+            source: MessageSource::Mqtt,
+            source_id: 1,
+            topic: "rep/1".to_string(),
+            payload: b"pong".to_vec(),
+            identity: None,
+            user_properties: user_properties(&props),
+            frames: Vec::new(),
+        };
+        let frames = with_identity(message_identity(&mapping, &from_mqtt), b"rep/1 pong".to_vec());
+        assert_eq!(frames, vec![b"peer-1".to_vec(), Vec::new(), b"rep/1 pong".to_vec()]);
+
+        // Mappings without the option drop identities
+        assert_eq!(message_identity(&TopicMapping::default(), &from_zmq), None);
+    }
+
+    #[tokio::test]
+    async fn test_source_topic_carried_to_fixed_mqtt_topic() {
+        let (mqtt_tx, mqtt_rx) = std::sync::mpsc::channel();
+        let mapping = |id: u32, carrier: &str| TopicMapping {
+            id,
+            source_endpoint_type: EndpointType::Zmq,
+            source_topic: format!("sensors/{}/#", id),
+            target_endpoint_type: EndpointType::Mqtt,
+            target_endpoint_id: 1,
+            target_topic: "plant/telemetry".to_string(),
+            direction: crate::models::MappingDirection::ZmqToMqtt,
+            source_topic_carrier: Some(carrier.to_string()),
+            ..Default::default()
+        };
+        let ctx = test_context(
+            vec![
+                mapping(1, "property:zmq-topic"),
+                mapping(2, "json:zmq_topic"),
+            ],
+            HashMap::from([(1, mqtt_tx)]),
+            HashMap::new(),
+        );
+        let msg = |topic: &str| ForwardMessage {
+            source: MessageSource::Zmq,
+            source_id: 1,
+            topic: topic.to_string(),
+            payload: br#"{"t":21.5}"#.to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // MQTT v5: the topic travels as a user property and reads back on receipt
+        forward_message(&ctx, msg("sensors/1/hall/temp")).await;
+        let Ok(MqttCommand::Publish(publish)) = mqtt_rx.try_recv() else { panic!("mqtt v5") };
+        assert_eq!(publish.topic, "plant/telemetry");
+        assert_eq!(publish.payload, br#"{"t":21.5}"#);
+        let received = user_properties(&string_properties(&publish.user_properties));
+        assert_eq!(received, vec![("zmq-topic".to_string(), "sensors/1/hall/temp".to_string())]);
+
+        // MQTT 3: the topic travels in a JSON envelope around the payload
+        forward_message(&ctx, msg("sensors/2/roof/wind")).await;
+        let Ok(MqttCommand::Publish(publish)) = mqtt_rx.try_recv() else { panic!("mqtt 3") };
+        assert_eq!(publish.topic, "plant/telemetry");
+        assert!(publish.user_properties.is_empty());
+        let envelope: serde_json::Value = serde_json::from_slice(&publish.payload).unwrap();
+        assert_eq!(envelope["zmq_topic"], "sensors/2/roof/wind");
+        assert_eq!(envelope["payload"]["t"], 21.5);
+    }
+}
@@ -1,14 +1,22 @@
 //! Application configuration module
 
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 /// JWT configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JwtConfig {
     /// Secret key for signing tokens
     pub secret: String,
-    /// Token expiration time in hours
+    /// Refresh token expiration time in hours, i.e. the longest a login lasts
     pub expiration_hours: i64,
+    /// Access token expiration time in minutes
+    #[serde(default = "default_access_expiration_minutes")]
+    pub access_expiration_minutes: i64,
+}
+
+fn default_access_expiration_minutes() -> i64 {
+    15
 }
 
 impl Default for JwtConfig {
@@ -16,6 +24,7 @@ impl Default for JwtConfig {
         Self {
             secret: "zeromqtt-super-secret-key-change-in-production".to_string(),
             expiration_hours: 24,
+            access_expiration_minutes: default_access_expiration_minutes(),
         }
     }
 }
@@ -25,13 +34,25 @@ impl Default for JwtConfig {
 pub struct DefaultCredentials {
     pub username: String,
     pub password: String,
+    /// bcrypt work factor of stored password hashes; weaker hashes are
+    /// upgraded at the user's next login
+    #[serde(default = "default_bcrypt_cost")]
+    pub bcrypt_cost: u32,
+}
+
+fn default_bcrypt_cost() -> u32 {
+    bcrypt::DEFAULT_COST
 }
 
+/// Work factors bcrypt accepts
+const BCRYPT_COSTS: std::ops::RangeInclusive<u32> = 4..=31;
+
 impl Default for DefaultCredentials {
     fn default() -> Self {
         Self {
             username: "zeromqtt".to_string(),
             password: "zeromqtt".to_string(),
+            bcrypt_cost: default_bcrypt_cost(),
         }
     }
 }
@@ -52,12 +73,489 @@ impl Default for ServerConfig {
     }
 }
 
+/// Database configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    /// Optional separate SQLite file for message statistics.
+    /// When unset, stats share the primary database.
+    #[serde(default)]
+    pub stats_path: Option<String>,
+    /// Count and time repository queries in the `db_*` metrics
+    #[serde(default)]
+    pub query_stats: bool,
+}
+
+/// Retention windows for append-only tables, in days; 0 keeps rows forever
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    #[serde(default = "default_history_days")]
+    pub history_days: u32,
+    #[serde(default = "default_audit_days")]
+    pub audit_days: u32,
+    #[serde(default = "default_deadletter_days")]
+    pub deadletter_days: u32,
+    /// How often the pruning task runs, in seconds
+    #[serde(default = "default_prune_interval_secs")]
+    pub prune_interval_secs: u64,
+}
+
+fn default_history_days() -> u32 {
+    7
+}
+
+fn default_audit_days() -> u32 {
+    90
+}
+
+fn default_deadletter_days() -> u32 {
+    14
+}
+
+fn default_prune_interval_secs() -> u64 {
+    3600
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            history_days: default_history_days(),
+            audit_days: default_audit_days(),
+            deadletter_days: default_deadletter_days(),
+            prune_interval_secs: default_prune_interval_secs(),
+        }
+    }
+}
+
+/// How often message counters are written to `message_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsConfig {
+    /// Longest counters are kept in memory before being written; 0 writes
+    /// every update as it happens
+    #[serde(default = "default_stats_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Also write once this many messages are counted but unwritten; 0 only
+    /// writes on the interval
+    #[serde(default)]
+    pub flush_on_count: u64,
+}
+
+fn default_stats_flush_interval_ms() -> u64 {
+    1000
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval_ms: default_stats_flush_interval_ms(),
+            flush_on_count: 0,
+        }
+    }
+}
+
+/// Access to and naming of the Prometheus endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Require a login token or `scrape_token` to read `/api/metrics`
+    #[serde(default)]
+    pub require_auth: bool,
+    /// Static bearer token for scrapers, accepted instead of a login token
+    #[serde(default)]
+    pub scrape_token: Option<String>,
+    /// Prefix of every Prometheus metric name; empty leaves names unprefixed
+    #[serde(default = "default_metrics_namespace")]
+    pub namespace: String,
+    /// Gzip the response for scrapers that send `Accept-Encoding: gzip`
+    #[serde(default = "default_metrics_gzip")]
+    pub gzip: bool,
+}
+
+fn default_metrics_namespace() -> String {
+    "zeromqtt".to_string()
+}
+
+fn default_metrics_gzip() -> bool {
+    true
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            require_auth: false,
+            scrape_token: None,
+            namespace: default_metrics_namespace(),
+            gzip: default_metrics_gzip(),
+        }
+    }
+}
+
+/// ZeroMQ endpoint policy and shared context tuning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZmqSettings {
+    /// Transports endpoints may use, e.g. `["tcp"]`; empty allows all
+    #[serde(default)]
+    pub allowed_transports: Vec<String>,
+    /// I/O threads of the context shared by all ZMQ endpoints
+    #[serde(default = "default_zmq_io_threads")]
+    pub io_threads: i32,
+}
+
+fn default_zmq_io_threads() -> i32 {
+    1
+}
+
+impl Default for ZmqSettings {
+    fn default() -> Self {
+        Self {
+            allowed_transports: Vec::new(),
+            io_threads: default_zmq_io_threads(),
+        }
+    }
+}
+
+/// Order in which mappings are evaluated for each message
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationStrategy {
+    /// Database (id) order
+    #[default]
+    DbOrder,
+    /// Highest `priority` first
+    Priority,
+    /// Fewest wildcards and longest literal prefix first
+    MostSpecific,
+}
+
+/// What to do with a ZMQ forward whose topic contains the frame separator
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeparatorConflict {
+    /// Drop the message and count it
+    #[default]
+    Reject,
+    /// Send it anyway; receivers will split the topic at the separator
+    Forward,
+}
+
+/// What to do with frames of a multipart ZMQ message after the payload frame
+/// when the target cannot carry them as frames
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraFrames {
+    /// Forward only the first payload frame
+    #[default]
+    Drop,
+    /// Append them to the payload in order
+    Concatenate,
+}
+
+/// What to do when a saved mapping duplicates an existing one
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateMappings {
+    /// Save it without checking
+    Allow,
+    /// Save it and return a `Warning` header
+    #[default]
+    Warn,
+    /// Refuse it with 400 Bad Request
+    Reject,
+}
+
+/// What to do with mappings whose MQTT source topic starts with a wildcard
+/// level, e.g. `#` or `+/status`, unless they set `allow_broad_subscription`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadSubscriptions {
+    /// Subscribe and log a warning; saving returns a `Warning` header
+    #[default]
+    Warn,
+    /// Refuse to save them with 400 Bad Request and skip their subscription
+    Reject,
+}
+
+/// What endpoint workers do with a received message while the forwarding
+/// channel is full
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardOverflow {
+    /// Wait for room, stalling the endpoint's receive loop
+    #[default]
+    Block,
+    /// Drop the message just received
+    DropNewest,
+    /// Drop the longest-queued message to make room
+    DropOldest,
+}
+
+/// Bridge runtime configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    /// Start the bridge at boot; when false it stays stopped until started via the API
+    #[serde(default = "default_autostart")]
+    pub autostart: bool,
+    /// Number of order-preserving forwarding shards; 1 keeps a single consumer
+    #[serde(default = "default_forward_shards")]
+    pub forward_shards: usize,
+    /// Sort applied to the mappings cache
+    #[serde(default)]
+    pub evaluation_strategy: EvaluationStrategy,
+    /// Maximum topics per MQTT subscribe call; longer lists are chunked
+    #[serde(default = "default_subscribe_batch_size")]
+    pub subscribe_batch_size: usize,
+    /// Pause between subscribe batches in milliseconds
+    #[serde(default = "default_subscribe_batch_delay_ms")]
+    pub subscribe_batch_delay_ms: u64,
+    /// Maximum concurrent subscribe operations across all brokers; 0 is unlimited
+    #[serde(default)]
+    pub subscribe_concurrency: usize,
+    /// Quiet period before coalesced mapping reloads run; 0 reloads immediately
+    #[serde(default = "default_reload_debounce_ms")]
+    pub reload_debounce_ms: u64,
+    /// Re-issue MQTT subscriptions after a reconnect that lost the session
+    #[serde(default = "default_resubscribe_on_reconnect")]
+    pub resubscribe_on_reconnect: bool,
+    /// Handling of ZMQ target topics that contain the frame separator
+    #[serde(default)]
+    pub separator_conflict: SeparatorConflict,
+    /// Handling of extra multipart ZMQ frames sent to MQTT or single-frame ZMQ targets
+    #[serde(default)]
+    pub zmq_extra_frames: ExtraFrames,
+    /// Sliding window for counting MQTT reconnect attempts
+    #[serde(default = "default_reconnect_window_secs")]
+    pub reconnect_window_secs: u64,
+    /// Reconnect attempts allowed per broker within the window; 0 is unlimited
+    #[serde(default = "default_reconnect_max_per_endpoint")]
+    pub reconnect_max_per_endpoint: usize,
+    /// Reconnect attempts allowed across all brokers within the window; 0 is unlimited
+    #[serde(default = "default_reconnect_max_global")]
+    pub reconnect_max_global: usize,
+    /// Initial hold-off once a cap is hit, doubled on each consecutive throttle
+    #[serde(default = "default_reconnect_throttle_secs")]
+    pub reconnect_throttle_secs: u64,
+    /// Maximum nesting of payloads parsed as JSON; 0 is unlimited
+    #[serde(default = "default_json_max_depth")]
+    pub json_max_depth: usize,
+    /// Maximum size in bytes of payloads parsed as JSON; 0 is unlimited
+    #[serde(default = "default_json_max_bytes")]
+    pub json_max_bytes: usize,
+    /// Handling of mappings that duplicate an existing route
+    #[serde(default)]
+    pub duplicate_mappings: DuplicateMappings,
+    /// Handling of mappings that would subscribe to a broker's whole namespace
+    #[serde(default)]
+    pub broad_subscriptions: BroadSubscriptions,
+    /// Drop messages whose topic has more `/`-separated levels; unset is unlimited
+    #[serde(default)]
+    pub max_topic_levels: Option<usize>,
+    /// Drop and dead-letter messages whose payload is larger, checked as they
+    /// are received; mappings may set a lower `max_payload_bytes`. Unset is
+    /// unlimited
+    #[serde(default)]
+    pub max_payload_bytes: Option<usize>,
+    /// Allow mappings to pipe payloads through external commands (`transform_command`)
+    #[serde(default)]
+    pub exec_transforms: bool,
+    /// Time a transform command may take per payload before it is killed
+    #[serde(default = "default_exec_timeout_ms")]
+    pub exec_timeout_ms: u64,
+    /// Transform processes kept running per command
+    #[serde(default = "default_exec_pool_size")]
+    pub exec_pool_size: usize,
+    /// Directory of the write-ahead log for durable mappings; unset is `~/.zeromqtt/wal`
+    #[serde(default)]
+    pub wal_dir: Option<String>,
+    /// Size at which the active write-ahead log segment is closed
+    #[serde(default = "default_wal_segment_bytes")]
+    pub wal_segment_bytes: u64,
+    /// Interval between write-ahead log compactions
+    #[serde(default = "default_wal_compact_interval_secs")]
+    pub wal_compact_interval_secs: u64,
+    /// Forwarding channel depth above which forwarding counts as falling behind
+    #[serde(default = "default_slow_consumer_depth")]
+    pub slow_consumer_depth: usize,
+    /// How long the depth must stay above `slow_consumer_depth` before it is flagged
+    #[serde(default = "default_slow_consumer_secs")]
+    pub slow_consumer_secs: u64,
+    /// Handling of received messages while the forwarding channel is full
+    #[serde(default)]
+    pub forward_overflow: ForwardOverflow,
+    /// Dead-letter messages dropped by `forward_overflow` instead of only counting them
+    #[serde(default)]
+    pub dead_letter_overflow: bool,
+    /// Interval between liveness checks of each endpoint
+    #[serde(default = "default_health_check_interval_secs")]
+    pub health_check_interval_secs: u64,
+    /// Keep message counters across starts and restarts from the API unless
+    /// the request says otherwise; startup always resets them
+    #[serde(default)]
+    pub preserve_stats_on_restart: bool,
+    /// Stage config mutations for `/api/config/apply` instead of writing them
+    #[serde(default)]
+    pub stage_config_changes: bool,
+    /// Add the message total and rate of `/api/status/stats` to `/api/status`
+    #[serde(default)]
+    pub status_throughput: bool,
+    /// Start in dry-run mode: route messages without publishing them; see
+    /// `/api/bridge/dryrun`
+    #[serde(default)]
+    pub dry_run: bool,
+    /// How long a mapping's `dedup_key` drops repeats of a message key
+    #[serde(default = "default_dedup_ttl_secs")]
+    pub dedup_ttl_secs: u64,
+    /// Most message keys remembered for deduplication; the oldest are pruned first
+    #[serde(default = "default_dedup_max_keys")]
+    pub dedup_max_keys: u64,
+}
+
+fn default_autostart() -> bool {
+    true
+}
+
+fn default_forward_shards() -> usize {
+    1
+}
+
+fn default_subscribe_batch_size() -> usize {
+    100
+}
+
+fn default_subscribe_batch_delay_ms() -> u64 {
+    50
+}
+
+fn default_reload_debounce_ms() -> u64 {
+    250
+}
+
+fn default_resubscribe_on_reconnect() -> bool {
+    true
+}
+
+fn default_reconnect_window_secs() -> u64 {
+    60
+}
+
+fn default_reconnect_max_per_endpoint() -> usize {
+    10
+}
+
+fn default_reconnect_max_global() -> usize {
+    50
+}
+
+fn default_reconnect_throttle_secs() -> u64 {
+    30
+}
+
+fn default_json_max_depth() -> usize {
+    32
+}
+
+fn default_json_max_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_exec_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_exec_pool_size() -> usize {
+    2
+}
+
+fn default_wal_segment_bytes() -> u64 {
+    4 * 1024 * 1024
+}
+
+fn default_wal_compact_interval_secs() -> u64 {
+    60
+}
+
+fn default_slow_consumer_depth() -> usize {
+    800
+}
+
+fn default_slow_consumer_secs() -> u64 {
+    10
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    10
+}
+
+fn default_dedup_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_dedup_max_keys() -> u64 {
+    100_000
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            autostart: default_autostart(),
+            forward_shards: default_forward_shards(),
+            evaluation_strategy: EvaluationStrategy::default(),
+            subscribe_batch_size: default_subscribe_batch_size(),
+            subscribe_batch_delay_ms: default_subscribe_batch_delay_ms(),
+            subscribe_concurrency: 0,
+            reload_debounce_ms: default_reload_debounce_ms(),
+            resubscribe_on_reconnect: default_resubscribe_on_reconnect(),
+            separator_conflict: SeparatorConflict::default(),
+            zmq_extra_frames: ExtraFrames::default(),
+            reconnect_window_secs: default_reconnect_window_secs(),
+            reconnect_max_per_endpoint: default_reconnect_max_per_endpoint(),
+            reconnect_max_global: default_reconnect_max_global(),
+            reconnect_throttle_secs: default_reconnect_throttle_secs(),
+            json_max_depth: default_json_max_depth(),
+            json_max_bytes: default_json_max_bytes(),
+            duplicate_mappings: DuplicateMappings::default(),
+            broad_subscriptions: BroadSubscriptions::default(),
+            max_topic_levels: None,
+            max_payload_bytes: None,
+            exec_transforms: false,
+            exec_timeout_ms: default_exec_timeout_ms(),
+            exec_pool_size: default_exec_pool_size(),
+            wal_dir: None,
+            wal_segment_bytes: default_wal_segment_bytes(),
+            wal_compact_interval_secs: default_wal_compact_interval_secs(),
+            slow_consumer_depth: default_slow_consumer_depth(),
+            slow_consumer_secs: default_slow_consumer_secs(),
+            forward_overflow: ForwardOverflow::default(),
+            dead_letter_overflow: false,
+            health_check_interval_secs: default_health_check_interval_secs(),
+            preserve_stats_on_restart: false,
+            stage_config_changes: false,
+            status_throughput: false,
+            dry_run: false,
+            dedup_ttl_secs: default_dedup_ttl_secs(),
+            dedup_max_keys: default_dedup_max_keys(),
+        }
+    }
+}
+
 /// Application configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppConfig {
+    #[serde(default)]
     pub server: ServerConfig,
+    #[serde(default)]
     pub jwt: JwtConfig,
+    #[serde(default)]
     pub credentials: DefaultCredentials,
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    #[serde(default)]
+    pub bridge: BridgeConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
+    #[serde(default)]
+    pub zmq: ZmqSettings,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 impl AppConfig {
@@ -65,4 +563,161 @@ impl AppConfig {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Load configuration from ~/.zeromqtt/config.json, or the defaults if
+    /// there is none. A file that cannot be read, parsed or validated is an
+    /// error rather than silently replaced by the defaults, whose JWT secret
+    /// and credentials are public.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = config_path() else {
+            return Ok(Self::default());
+        };
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| serde_json::from_str::<AppConfig>(&s).map_err(|e| e.to_string()))
+            .and_then(|config| config.validate().map(|()| config))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl AppConfig {
+    /// Reject settings that deserialize but cannot be used
+    pub fn validate(&self) -> Result<(), String> {
+        if !BCRYPT_COSTS.contains(&self.credentials.bcrypt_cost) {
+            return Err(format!(
+                "credentials.bcrypt_cost must be between {} and {}",
+                BCRYPT_COSTS.start(),
+                BCRYPT_COSTS.end()
+            ));
+        }
+        if !is_metric_namespace(&self.metrics.namespace) {
+            return Err(format!(
+                "metrics.namespace '{}' must start with a letter or '_' and contain only letters, digits and '_'",
+                self.metrics.namespace
+            ));
+        }
+        if self.bridge.max_topic_levels == Some(0) {
+            return Err("bridge.max_topic_levels must be at least 1; leave it unset for no limit".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Whether `namespace` is empty or a valid Prometheus metric name prefix
+fn is_metric_namespace(namespace: &str) -> bool {
+    let mut chars = namespace.chars();
+    chars.next().is_none_or(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Placeholder for redacted secret values
+pub const REDACTED: &str = "***";
+
+/// Key fragments marking a value as secret
+const SECRET_KEY_FRAGMENTS: [&str; 4] = ["secret", "password", "token", "key"];
+
+impl AppConfig {
+    /// The configuration as JSON with every secret replaced by `***`, for
+    /// logs and the admin API. Secrets are matched by key name so new
+    /// settings named like `*_token` or `*_password` are covered too.
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        redact_secrets(&mut value);
+        value
+    }
+}
+
+fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if SECRET_KEY_FRAGMENTS.iter().any(|f| key.contains(f)) {
+                    if !field.is_null() {
+                        *field = serde_json::Value::String(REDACTED.to_string());
+                    }
+                } else {
+                    redact_secrets(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+/// Get the configuration file path: ~/.zeromqtt/config.json
+pub fn config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".zeromqtt").join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacted_hides_secrets() {
+        let mut config = AppConfig::default();
+        config.jwt.secret = "jwt-secret-value".to_string();
+        config.credentials.password = "admin-password-value".to_string();
+        config.metrics.scrape_token = Some("scrape-token-value".to_string());
+
+        let redacted = config.redacted();
+        let dump = redacted.to_string();
+        for secret in ["jwt-secret-value", "admin-password-value", "scrape-token-value"] {
+            assert!(!dump.contains(secret), "{secret} leaked");
+        }
+        assert_eq!(redacted["jwt"]["secret"], REDACTED);
+        assert_eq!(redacted["credentials"]["password"], REDACTED);
+        assert_eq!(redacted["metrics"]["scrape_token"], REDACTED);
+
+        // Non-secret settings stay readable
+        assert_eq!(redacted["server"]["port"], 3000);
+        assert_eq!(redacted["credentials"]["username"], config.credentials.username);
+        assert_eq!(redacted["jwt"]["expiration_hours"], 24);
+    }
+
+    #[test]
+    fn test_validate_bcrypt_cost() {
+        let mut config = AppConfig::default();
+        assert!(config.validate().is_ok());
+        for cost in [3, 32] {
+            config.credentials.bcrypt_cost = cost;
+            assert!(config.validate().unwrap_err().contains("bcrypt_cost"));
+        }
+        config.credentials.bcrypt_cost = 4;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_max_topic_levels() {
+        let mut config = AppConfig::default();
+        config.bridge.max_topic_levels = Some(0);
+        assert!(config.validate().unwrap_err().contains("max_topic_levels"));
+        config.bridge.max_topic_levels = Some(1);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_metrics_namespace() {
+        let mut config = AppConfig::default();
+        for namespace in ["", "bridge", "edge_01", "bridge_"] {
+            config.metrics.namespace = namespace.to_string();
+            assert!(config.validate().is_ok(), "{namespace}");
+        }
+        for namespace in ["1bridge", "my-bridge", "bridge.eu", "zero mqtt"] {
+            config.metrics.namespace = namespace.to_string();
+            assert!(config.validate().unwrap_err().contains("metrics.namespace"), "{namespace}");
+        }
+    }
+
+    #[test]
+    fn test_unset_secrets_stay_null() {
+        let redacted = AppConfig::default().redacted();
+        assert!(redacted["metrics"]["scrape_token"].is_null());
+    }
 }
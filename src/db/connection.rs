@@ -45,8 +45,24 @@ pub async fn init_db() -> Result<SqlitePool, sqlx::Error> {
     Ok(pool)
 }
 
+/// Open a private in-memory database with the full schema but no default data
+pub async fn init_memory_db() -> Result<SqlitePool, sqlx::Error> {
+    // Every connection to `:memory:` is its own database, so keep exactly one alive
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .min_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await?;
+
+    run_migrations(&pool).await?;
+    init_stats_row(&pool).await?;
+    Ok(pool)
+}
+
 /// Run database migrations - CREATE NEW SCHEMA
-async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+pub(crate) async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     // Create mqtt_configs table (plural, supports multiple brokers)
     sqlx::query(
         r#"
@@ -107,21 +123,7 @@ async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .await?;
 
     // Create message_stats table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS message_stats (
-            id INTEGER PRIMARY KEY DEFAULT 1,
-            mqtt_received INTEGER NOT NULL DEFAULT 0,
-            mqtt_sent INTEGER NOT NULL DEFAULT 0,
-            zmq_received INTEGER NOT NULL DEFAULT 0,
-            zmq_sent INTEGER NOT NULL DEFAULT 0,
-            error_count INTEGER NOT NULL DEFAULT 0,
-            start_time INTEGER NOT NULL DEFAULT 0
-        )
-        "#,
-    )
-    .execute(pool)
-    .await?;
+    create_stats_tables(pool).await?;
 
     // Create users table for user management
     sqlx::query(
@@ -139,9 +141,180 @@ async fn run_migrations(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Messages mappings could not transform, pruned by retention
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS dead_letters (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            mapping_id INTEGER NOT NULL,
+            topic TEXT NOT NULL,
+            payload BLOB NOT NULL,
+            reason TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Message keys recently forwarded by mappings with a dedup_key
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS seen_keys (
+            mapping_id INTEGER NOT NULL,
+            key TEXT NOT NULL,
+            seen_at INTEGER NOT NULL,
+            PRIMARY KEY (mapping_id, key)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_seen_keys_seen_at ON seen_keys (seen_at)")
+        .execute(pool)
+        .await?;
+
+    // Refresh tokens that have not been revoked, by `jti`
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS refresh_tokens (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            expires_at INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Access tokens revoked at logout, kept until they would have expired
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS revoked_tokens (
+            jti TEXT PRIMARY KEY,
+            expires_at INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Config mutations staged for one atomic apply
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS pending_changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target_id INTEGER,
+            body TEXT,
+            created_at INTEGER NOT NULL
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     // Migrate old tables if they exist
     migrate_old_tables(pool).await?;
 
+    // Add columns introduced after the initial schema
+    migrate_columns(pool).await?;
+
+    Ok(())
+}
+
+/// Add newer columns to tables created by older versions
+async fn migrate_columns(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    add_column_if_missing(pool, "topic_mappings", "ordering_key", "TEXT").await?;
+    add_column_if_missing(pool, "zmq_configs", "hmac_key", "TEXT").await?;
+    add_column_if_missing(pool, "zmq_configs", "curve_server_key", "TEXT").await?;
+    add_column_if_missing(pool, "zmq_configs", "curve_public_key", "TEXT").await?;
+    add_column_if_missing(pool, "zmq_configs", "curve_secret_key", "TEXT").await?;
+    add_column_if_missing(pool, "zmq_configs", "role", "TEXT NOT NULL DEFAULT 'bind'").await?;
+    add_column_if_missing(pool, "zmq_configs", "multicast_rate_kbps", "INTEGER").await?;
+    add_column_if_missing(pool, "zmq_configs", "multicast_hops", "INTEGER").await?;
+    add_column_if_missing(pool, "zmq_configs", "multicast_recovery_ms", "INTEGER").await?;
+    add_column_if_missing(pool, "zmq_configs", "resubscribe_on_reconnect", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "zmq_configs", "rejoin_warmup_ms", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "zmq_configs", "batch_max_messages", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "zmq_configs", "batch_max_delay_ms", "INTEGER NOT NULL DEFAULT 5").await?;
+    add_column_if_missing(pool, "zmq_configs", "endpoint_role", "TEXT NOT NULL DEFAULT 'both'").await?;
+    add_column_if_missing(pool, "topic_mappings", "priority", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "topic_mappings", "stop_on_match", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "topic_mappings", "target_fallback", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "sample_every_n", "INTEGER").await?;
+    add_column_if_missing(pool, "topic_mappings", "sample_interval_ms", "INTEGER").await?;
+    add_column_if_missing(pool, "topic_mappings", "identity_property", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "additional_targets", "TEXT NOT NULL DEFAULT '[]'").await?;
+    add_column_if_missing(pool, "topic_mappings", "publish_qos", "INTEGER").await?;
+    add_column_if_missing(pool, "topic_mappings", "fallback_topic", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "timestamp_source", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "schedule", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "source_topic_carrier", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "transform_command", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "durable", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "topic_mappings", "payload_transform", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "qos", "INTEGER NOT NULL DEFAULT 1").await?;
+    add_column_if_missing(pool, "topic_mappings", "retain", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "topic_mappings", "transform_error_policy", "TEXT NOT NULL DEFAULT 'drop'").await?;
+    add_column_if_missing(pool, "topic_mappings", "dedup_key", "TEXT").await?;
+    add_column_if_missing(pool, "topic_mappings", "truncate_bytes", "INTEGER").await?;
+    add_column_if_missing(pool, "topic_mappings", "max_payload_bytes", "INTEGER").await?;
+    add_column_if_missing(pool, "topic_mappings", "allow_broad_subscription", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "mqtt_configs", "will_topic", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "will_payload", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "birth_topic", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "birth_payload", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "connect_timeout_secs", "INTEGER NOT NULL DEFAULT 10").await?;
+    add_column_if_missing(pool, "mqtt_configs", "topic_rewrites", "TEXT NOT NULL DEFAULT '[]'").await?;
+    add_column_if_missing(pool, "mqtt_configs", "topic_aliases", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "mqtt_configs", "max_subscriptions", "INTEGER").await?;
+    add_column_if_missing(pool, "mqtt_configs", "protocol_version", "TEXT NOT NULL DEFAULT 'v311'").await?;
+    add_column_if_missing(pool, "mqtt_configs", "clean_start", "INTEGER NOT NULL DEFAULT 1").await?;
+    add_column_if_missing(pool, "mqtt_configs", "session_expiry_secs", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "mqtt_configs", "ca_cert_path", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "client_cert_path", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "client_key_path", "TEXT").await?;
+    add_column_if_missing(pool, "mqtt_configs", "tls_insecure_skip_verify", "INTEGER NOT NULL DEFAULT 0").await?;
+    add_column_if_missing(pool, "mqtt_configs", "endpoint_role", "TEXT NOT NULL DEFAULT 'both'").await?;
+    add_column_if_missing(pool, "zmq_configs", "topic_rewrites", "TEXT NOT NULL DEFAULT '[]'").await?;
+    add_column_if_missing(pool, "mqtt_configs", "rewrite_stage", "TEXT NOT NULL DEFAULT 'ingress'").await?;
+    add_column_if_missing(pool, "zmq_configs", "rewrite_stage", "TEXT NOT NULL DEFAULT 'ingress'").await?;
+    // Users from before roles keep the full access they had
+    add_column_if_missing(pool, "users", "role", "TEXT NOT NULL DEFAULT 'admin'").await?;
+    add_column_if_missing(
+        pool,
+        "zmq_configs",
+        "topic_separator",
+        r#"TEXT NOT NULL DEFAULT '{"type":"delimiter","byte":32}'"#,
+    )
+    .await?;
+    // Dead letters from before these columns have source id 0
+    add_column_if_missing(pool, "dead_letters", "source_type", "TEXT NOT NULL DEFAULT 'mqtt'").await?;
+    add_column_if_missing(pool, "dead_letters", "source_id", "INTEGER NOT NULL DEFAULT 0").await?;
+
+    Ok(())
+}
+
+/// Add a column to an existing table unless it is already present
+async fn add_column_if_missing(
+    pool: &SqlitePool,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), sqlx::Error> {
+    let columns: Vec<(String,)> = sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+        .fetch_all(pool)
+        .await?;
+
+    if !columns.iter().any(|(name,)| name == column) {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+        info!("Added column {}.{}", table, column);
+    }
+
     Ok(())
 }
 
@@ -274,22 +447,7 @@ async fn init_default_data(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     }
 
     // Check if message_stats exists
-    let stats_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM message_stats")
-        .fetch_one(pool)
-        .await?;
-    
-    if stats_count.0 == 0 {
-        let now = chrono::Utc::now().timestamp();
-        sqlx::query(
-            r#"
-            INSERT INTO message_stats (id, mqtt_received, mqtt_sent, zmq_received, zmq_sent, error_count, start_time)
-            VALUES (1, 0, 0, 0, 0, 0, ?)
-            "#,
-        )
-        .bind(now)
-        .execute(pool)
-        .await?;
-    }
+    init_stats_row(pool).await?;
 
     // Check if default user exists
     let user_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
@@ -318,3 +476,66 @@ async fn init_default_data(pool: &SqlitePool) -> Result<(), sqlx::Error> {
 
     Ok(())
 }
+
+/// Initialize a dedicated stats database, used when `database.stats_path` is set
+pub async fn init_stats_db(path: &str) -> Result<SqlitePool, sqlx::Error> {
+    info!("Initializing stats database at: {}", path);
+
+    let options = SqliteConnectOptions::from_str(&format!("sqlite:{}?mode=rwc", path))?
+        .create_if_missing(true)
+        .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+        .synchronous(sqlx::sqlite::SqliteSynchronous::Normal);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await?;
+
+    create_stats_tables(&pool).await?;
+    init_stats_row(&pool).await?;
+
+    Ok(pool)
+}
+
+/// Create the high-write telemetry tables
+async fn create_stats_tables(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_stats (
+            id INTEGER PRIMARY KEY DEFAULT 1,
+            mqtt_received INTEGER NOT NULL DEFAULT 0,
+            mqtt_sent INTEGER NOT NULL DEFAULT 0,
+            zmq_received INTEGER NOT NULL DEFAULT 0,
+            zmq_sent INTEGER NOT NULL DEFAULT 0,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            start_time INTEGER NOT NULL DEFAULT 0
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Insert the single message_stats row if missing
+async fn init_stats_row(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    let stats_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM message_stats")
+        .fetch_one(pool)
+        .await?;
+
+    if stats_count.0 == 0 {
+        let now = chrono::Utc::now().timestamp();
+        sqlx::query(
+            r#"
+            INSERT INTO message_stats (id, mqtt_received, mqtt_sent, zmq_received, zmq_sent, error_count, start_time)
+            VALUES (1, 0, 0, 0, 0, 0, ?)
+            "#,
+        )
+        .bind(now)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Detection of persistent database write failures
+//!
+//! Forwarding does not need the database, so when writes keep failing (read-only
+//! filesystem, disk full) the bridge carries on in a degraded mode: stats
+//! accumulate in memory and are flushed once a write succeeds again.
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use tracing::{info, warn};
+
+/// Consecutive failed writes before the repository reports itself degraded
+pub const DEGRADED_AFTER_FAILURES: u32 = 3;
+
+/// Stats deltas not yet persisted:
+/// mqtt_received, mqtt_sent, zmq_received, zmq_sent, errors
+pub type StatsDelta = [i64; 5];
+
+/// Write failure tracking shared by all clones of a repository
+#[derive(Debug, Default)]
+pub struct WriteHealth {
+    consecutive_failures: AtomicU32,
+    degraded: AtomicBool,
+    last_error: Mutex<Option<String>>,
+    pending_stats: Mutex<StatsDelta>,
+}
+
+impl WriteHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a successful write, leaving degraded mode if it was active
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        if self.degraded.swap(false, Ordering::Relaxed) {
+            *self.last_error.lock() = None;
+            info!("Database writes succeed again; leaving degraded mode");
+        }
+    }
+
+    /// Record a failed write, entering degraded mode after repeated failures
+    pub fn record_failure(&self, error: &sqlx::Error) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        *self.last_error.lock() = Some(error.to_string());
+        if failures >= DEGRADED_AFTER_FAILURES && !self.degraded.swap(true, Ordering::Relaxed) {
+            warn!(
+                "Database writes failing ({}); forwarding continues with in-memory stats only",
+                error
+            );
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Explanation for the status endpoint while degraded
+    pub fn degraded_reason(&self) -> Option<String> {
+        if !self.is_degraded() {
+            return None;
+        }
+        let error = self.last_error.lock().clone().unwrap_or_default();
+        Some(format!(
+            "Database writes are failing ({}); stats are kept in memory until writes recover",
+            error
+        ))
+    }
+
+    /// Take the unpersisted stats plus `delta` for writing
+    pub fn take_pending(&self, delta: StatsDelta) -> StatsDelta {
+        let mut pending = self.pending_stats.lock();
+        let mut total = std::mem::take(&mut *pending);
+        for (t, d) in total.iter_mut().zip(delta) {
+            *t += d;
+        }
+        total
+    }
+
+    /// Add stats not yet persisted: new ones, or ones whose write failed
+    pub fn add_pending(&self, unwritten: StatsDelta) {
+        let mut pending = self.pending_stats.lock();
+        for (total, d) in pending.iter_mut().zip(unwritten) {
+            *total += d;
+        }
+    }
+
+    /// Remove stats that were written from the pending ones
+    pub fn settle(&self, written: StatsDelta) {
+        let mut pending = self.pending_stats.lock();
+        for (total, w) in pending.iter_mut().zip(written) {
+            *total -= w;
+        }
+    }
+
+    pub fn pending(&self) -> StatsDelta {
+        *self.pending_stats.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degrades_after_repeated_failures_and_recovers() {
+        let health = WriteHealth::new();
+        let error = sqlx::Error::PoolClosed;
+
+        for _ in 0..DEGRADED_AFTER_FAILURES - 1 {
+            health.record_failure(&error);
+        }
+        assert!(!health.is_degraded());
+        health.record_failure(&error);
+        assert!(health.is_degraded());
+        assert!(health.degraded_reason().is_some());
+
+        health.record_success();
+        assert!(!health.is_degraded());
+        assert_eq!(health.degraded_reason(), None);
+    }
+
+    #[test]
+    fn test_pending_stats_accumulate_until_flushed() {
+        let health = WriteHealth::new();
+        let failed = health.take_pending([1, 0, 0, 0, 0]);
+        health.add_pending(failed);
+        let failed = health.take_pending([1, 1, 0, 0, 0]);
+        health.add_pending(failed);
+        assert_eq!(health.pending(), [2, 1, 0, 0, 0]);
+
+        // Stats added while a write is in flight stay pending after it
+        health.add_pending([1, 0, 0, 0, 0]);
+        health.settle([2, 1, 0, 0, 0]);
+        assert_eq!(health.pending(), [1, 0, 0, 0, 0]);
+
+        assert_eq!(health.take_pending([0, 0, 1, 0, 0]), [1, 0, 1, 0, 0]);
+        assert_eq!(health.pending(), [0; 5]);
+    }
+}
@@ -1,7 +1,13 @@
 //! Database module for SQLite persistence
 
 pub mod connection;
+pub mod health;
 pub mod repository;
+pub mod retention;
+pub mod stats_flush;
 
 pub use connection::*;
+pub use health::*;
 pub use repository::*;
+pub use retention::*;
+pub use stats_flush::*;
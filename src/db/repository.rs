@@ -2,12 +2,20 @@
 
 use crate::models::{
     CreateMappingRequest, CreateMqttConfigRequest, CreateZmqConfigRequest,
-    CreateUserRequest, ChangePasswordRequest, UpdateUserRequest, UserRecord,
-    EndpointType, MappingDirection, MessageStats, MqttConfig, TopicMapping,
-    ZmqConfig, ZmqSocketType,
+    CreateUserRequest, ChangePasswordRequest, UpdateUserRequest, UserRecord, UserRole, DeadLetter,
+    EndpointRole, EndpointType, ImportItemResult, ImportMode, MappingDirection, MessageStats, MqttConfig, MqttProtocolVersion, PayloadTransform, PendingAction,
+    PendingChange, PendingEntity, RewriteStage, TopicMapping, TableRowCount, TransformErrorPolicy, ZmqConfig, ZmqRole, ZmqSocketType,
 };
-use sqlx::sqlite::SqlitePool;
-use sqlx::FromRow;
+use crate::auth::middleware::constant_time_eq;
+use crate::config::StatsConfig;
+use crate::db::{StatsDelta, StatsFlush, WriteHealth};
+use crate::telemetry::metrics;
+use sqlx::sqlite::{Sqlite, SqliteExecutor, SqlitePool};
+use sqlx::{Connection, FromRow, Transaction};
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
 // ============ Row Types for SQLite ============
 
@@ -25,6 +33,23 @@ struct MqttConfigRow {
     use_tls: i64,
     keep_alive_seconds: i64,
     clean_session: i64,
+    will_topic: Option<String>,
+    will_payload: Option<String>,
+    birth_topic: Option<String>,
+    birth_payload: Option<String>,
+    connect_timeout_secs: i64,
+    topic_rewrites: String,
+    topic_aliases: i64,
+    max_subscriptions: Option<i64>,
+    protocol_version: String,
+    ca_cert_path: Option<String>,
+    client_cert_path: Option<String>,
+    client_key_path: Option<String>,
+    tls_insecure_skip_verify: i64,
+    rewrite_stage: String,
+    clean_start: i64,
+    session_expiry_secs: i64,
+    endpoint_role: String,
 }
 
 impl From<MqttConfigRow> for MqttConfig {
@@ -41,10 +66,69 @@ impl From<MqttConfigRow> for MqttConfig {
             use_tls: row.use_tls != 0,
             keep_alive_seconds: row.keep_alive_seconds as u16,
             clean_session: row.clean_session != 0,
+            clean_start: row.clean_start != 0,
+            session_expiry_secs: row.session_expiry_secs as u32,
+            connect_timeout_secs: row.connect_timeout_secs as u16,
+            topic_rewrites: serde_json::from_str(&row.topic_rewrites).unwrap_or_default(),
+            will_topic: row.will_topic,
+            will_payload: row.will_payload,
+            birth_topic: row.birth_topic,
+            birth_payload: row.birth_payload,
+            topic_aliases: row.topic_aliases != 0,
+            max_subscriptions: row.max_subscriptions.map(|max| max as u32),
+            protocol_version: match row.protocol_version.as_str() {
+                "v5" => MqttProtocolVersion::V5,
+                _ => MqttProtocolVersion::V311,
+            },
+            ca_cert_path: row.ca_cert_path,
+            client_cert_path: row.client_cert_path,
+            client_key_path: row.client_key_path,
+            tls_insecure_skip_verify: row.tls_insecure_skip_verify != 0,
+            rewrite_stage: parse_rewrite_stage(&row.rewrite_stage),
+            endpoint_role: parse_endpoint_role(&row.endpoint_role),
         }
     }
 }
 
+fn protocol_version_str(version: MqttProtocolVersion) -> &'static str {
+    match version {
+        MqttProtocolVersion::V311 => "v311",
+        MqttProtocolVersion::V5 => "v5",
+    }
+}
+
+fn rewrite_stage_str(stage: RewriteStage) -> &'static str {
+    match stage {
+        RewriteStage::Ingress => "ingress",
+        RewriteStage::Egress => "egress",
+        RewriteStage::Both => "both",
+    }
+}
+
+fn parse_rewrite_stage(stage: &str) -> RewriteStage {
+    match stage {
+        "egress" => RewriteStage::Egress,
+        "both" => RewriteStage::Both,
+        _ => RewriteStage::Ingress,
+    }
+}
+
+fn endpoint_role_str(role: EndpointRole) -> &'static str {
+    match role {
+        EndpointRole::SourceOnly => "source_only",
+        EndpointRole::SinkOnly => "sink_only",
+        EndpointRole::Both => "both",
+    }
+}
+
+fn parse_endpoint_role(role: &str) -> EndpointRole {
+    match role {
+        "source_only" => EndpointRole::SourceOnly,
+        "sink_only" => EndpointRole::SinkOnly,
+        _ => EndpointRole::Both,
+    }
+}
+
 #[derive(FromRow)]
 #[allow(dead_code)]
 struct ZmqConfigRow {
@@ -56,6 +140,22 @@ struct ZmqConfigRow {
     connect_endpoints: Option<String>,
     high_water_mark: i64,
     reconnect_interval_ms: i64,
+    hmac_key: Option<String>,
+    topic_rewrites: String,
+    topic_separator: String,
+    curve_server_key: Option<String>,
+    curve_public_key: Option<String>,
+    curve_secret_key: Option<String>,
+    role: String,
+    multicast_rate_kbps: Option<i64>,
+    multicast_hops: Option<i64>,
+    multicast_recovery_ms: Option<i64>,
+    resubscribe_on_reconnect: i64,
+    rejoin_warmup_ms: i64,
+    rewrite_stage: String,
+    batch_max_messages: i64,
+    batch_max_delay_ms: i64,
+    endpoint_role: String,
 }
 
 impl From<ZmqConfigRow> for ZmqConfig {
@@ -65,6 +165,8 @@ impl From<ZmqConfigRow> for ZmqConfig {
             "xsub" => ZmqSocketType::XSub,
             "pub" => ZmqSocketType::Pub,
             "sub" => ZmqSocketType::Sub,
+            "push" => ZmqSocketType::Push,
+            "pull" => ZmqSocketType::Pull,
             _ => ZmqSocketType::XPub,
         };
         
@@ -81,6 +183,57 @@ impl From<ZmqConfigRow> for ZmqConfig {
             connect_endpoints,
             high_water_mark: row.high_water_mark as u32,
             reconnect_interval_ms: row.reconnect_interval_ms as u32,
+            hmac_key: row.hmac_key,
+            topic_rewrites: serde_json::from_str(&row.topic_rewrites).unwrap_or_default(),
+            topic_separator: serde_json::from_str(&row.topic_separator).unwrap_or_default(),
+            curve_server_key: row.curve_server_key,
+            curve_public_key: row.curve_public_key,
+            curve_secret_key: row.curve_secret_key,
+            role: match row.role.as_str() {
+                "connect" => ZmqRole::Connect,
+                _ => ZmqRole::Bind,
+            },
+            multicast_rate_kbps: row.multicast_rate_kbps.map(|v| v as u32),
+            multicast_hops: row.multicast_hops.map(|v| v as u32),
+            multicast_recovery_ms: row.multicast_recovery_ms.map(|v| v as u32),
+            resubscribe_on_reconnect: row.resubscribe_on_reconnect != 0,
+            rejoin_warmup_ms: row.rejoin_warmup_ms as u32,
+            rewrite_stage: parse_rewrite_stage(&row.rewrite_stage),
+            batch_max_messages: row.batch_max_messages as u32,
+            batch_max_delay_ms: row.batch_max_delay_ms as u32,
+            endpoint_role: parse_endpoint_role(&row.endpoint_role),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct PendingChangeRow {
+    id: i64,
+    entity: String,
+    action: String,
+    target_id: Option<i64>,
+    body: Option<String>,
+    created_at: i64,
+}
+
+impl From<PendingChangeRow> for PendingChange {
+    fn from(row: PendingChangeRow) -> Self {
+        PendingChange {
+            id: row.id as u32,
+            entity: match row.entity.as_str() {
+                "mqtt" => PendingEntity::Mqtt,
+                "zmq" => PendingEntity::Zmq,
+                _ => PendingEntity::Mapping,
+            },
+            action: match row.action.as_str() {
+                "create" => PendingAction::Create,
+                "update" => PendingAction::Update,
+                _ => PendingAction::Delete,
+            },
+            target_id: row.target_id.map(|id| id as u32),
+            current: None,
+            proposed: row.body.and_then(|body| serde_json::from_str(&body).ok()),
+            created_at: row.created_at,
         }
     }
 }
@@ -98,6 +251,29 @@ struct TopicMappingRow {
     direction: String,
     enabled: i64,
     description: Option<String>,
+    ordering_key: Option<String>,
+    priority: i64,
+    stop_on_match: i64,
+    target_fallback: Option<String>,
+    sample_every_n: Option<i64>,
+    sample_interval_ms: Option<i64>,
+    identity_property: Option<String>,
+    additional_targets: String,
+    publish_qos: Option<i64>,
+    fallback_topic: Option<String>,
+    timestamp_source: Option<String>,
+    schedule: Option<String>,
+    source_topic_carrier: Option<String>,
+    transform_command: Option<String>,
+    durable: i64,
+    payload_transform: Option<String>,
+    qos: i64,
+    retain: i64,
+    transform_error_policy: String,
+    dedup_key: Option<String>,
+    truncate_bytes: Option<i64>,
+    max_payload_bytes: Option<i64>,
+    allow_broad_subscription: i64,
 }
 
 impl From<TopicMappingRow> for TopicMapping {
@@ -109,6 +285,18 @@ impl From<TopicMappingRow> for TopicMapping {
             "bidirectional" => MappingDirection::Bidirectional,
             _ => MappingDirection::MqttToZmq,
         };
+
+        let payload_transform = row.payload_transform.as_deref().and_then(|t| match t {
+            "json_to_msgpack" => Some(PayloadTransform::JsonToMsgpack),
+            "msgpack_to_json" => Some(PayloadTransform::MsgpackToJson),
+            _ => None,
+        });
+
+        let transform_error_policy = match row.transform_error_policy.as_str() {
+            "pass_through" => TransformErrorPolicy::PassThrough,
+            "dead_letter" => TransformErrorPolicy::DeadLetter,
+            _ => TransformErrorPolicy::Drop,
+        };
         
         let source_endpoint_type = match row.source_endpoint_type.as_str() {
             "zmq" => EndpointType::Zmq,
@@ -131,6 +319,67 @@ impl From<TopicMappingRow> for TopicMapping {
             direction,
             enabled: row.enabled != 0,
             description: row.description,
+            ordering_key: row.ordering_key,
+            priority: row.priority as i32,
+            stop_on_match: row.stop_on_match != 0,
+            target_fallback: row.target_fallback,
+            sample_every_n: row.sample_every_n.map(|n| n as u32),
+            sample_interval_ms: row.sample_interval_ms.map(|ms| ms as u64),
+            identity_property: row.identity_property,
+            additional_targets: serde_json::from_str(&row.additional_targets).unwrap_or_default(),
+            publish_qos: row.publish_qos.map(|q| q as i32),
+            fallback_topic: row.fallback_topic,
+            timestamp_source: row.timestamp_source,
+            schedule: row.schedule,
+            source_topic_carrier: row.source_topic_carrier,
+            transform_command: row.transform_command,
+            durable: row.durable != 0,
+            payload_transform,
+            qos: row.qos as u8,
+            retain: row.retain != 0,
+            transform_error_policy,
+            dedup_key: row.dedup_key,
+            truncate_bytes: row.truncate_bytes.map(|n| n as u32),
+            max_payload_bytes: row.max_payload_bytes.map(|n| n as u32),
+            allow_broad_subscription: row.allow_broad_subscription != 0,
+        }
+    }
+}
+
+fn transform_error_policy_str(policy: TransformErrorPolicy) -> &'static str {
+    match policy {
+        TransformErrorPolicy::Drop => "drop",
+        TransformErrorPolicy::PassThrough => "pass_through",
+        TransformErrorPolicy::DeadLetter => "dead_letter",
+    }
+}
+
+#[derive(FromRow)]
+struct DeadLetterRow {
+    id: i64,
+    mapping_id: i64,
+    source_type: String,
+    source_id: i64,
+    topic: String,
+    payload: Vec<u8>,
+    reason: String,
+    created_at: i64,
+}
+
+impl From<DeadLetterRow> for DeadLetter {
+    fn from(row: DeadLetterRow) -> Self {
+        DeadLetter {
+            id: row.id as u32,
+            mapping_id: row.mapping_id as u32,
+            source: match row.source_type.as_str() {
+                "zmq" => EndpointType::Zmq,
+                _ => EndpointType::Mqtt,
+            },
+            source_id: row.source_id as u32,
+            topic: row.topic,
+            payload: row.payload,
+            reason: row.reason,
+            created_at: row.created_at,
         }
     }
 }
@@ -155,6 +404,7 @@ struct UserRow {
     is_default: i64,
     created_at: i64,
     updated_at: i64,
+    role: String,
 }
 
 impl From<UserRow> for UserRecord {
@@ -163,6 +413,7 @@ impl From<UserRow> for UserRecord {
             id: row.id as u32,
             username: row.username,
             password_hash: row.password_hash,
+            role: parse_user_role(&row.role),
             is_default: row.is_default != 0,
             created_at: row.created_at,
             updated_at: row.updated_at,
@@ -170,17 +421,136 @@ impl From<UserRow> for UserRecord {
     }
 }
 
+fn user_role_str(role: UserRole) -> &'static str {
+    match role {
+        UserRole::Admin => "admin",
+        UserRole::Viewer => "viewer",
+    }
+}
+
+fn parse_user_role(role: &str) -> UserRole {
+    match role {
+        "admin" => UserRole::Admin,
+        _ => UserRole::Viewer,
+    }
+}
+
+fn hash_password(password: &str, cost: u32) -> Result<String, sqlx::Error> {
+    bcrypt::hash(password, cost).map_err(|e| sqlx::Error::Protocol(format!("Failed to hash password: {}", e)))
+}
+
+/// Work factor of a bcrypt hash (`$2b$12$...`); `None` if `stored` is not one
+fn bcrypt_cost(stored: &str) -> Option<u32> {
+    let mut parts = stored.split('$');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(""), Some(version), Some(cost)) if version.starts_with('2') => cost.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Check a password against its stored bcrypt hash, or against the plaintext
+/// stored by versions before passwords were hashed
+fn password_matches(password: &str, stored: &str) -> bool {
+    match bcrypt_cost(stored) {
+        Some(_) => bcrypt::verify(password, stored).unwrap_or(false),
+        None => constant_time_eq(password.as_bytes(), stored.as_bytes()),
+    }
+}
+
 // ============ Repository ============
 
 /// Database repository for all data access
 #[derive(Clone)]
 pub struct Repository {
     pool: SqlitePool,
+    /// Pool for high-write telemetry tables; same as `pool` unless offloaded
+    stats_pool: SqlitePool,
+    /// Whether `stats_pool` is a separate database (`database.stats_path`)
+    stats_offloaded: bool,
+    /// Tracks persistent write failures for degraded mode
+    health: Arc<WriteHealth>,
+    /// When counter updates collected in `health` are written
+    stats_flush: Arc<StatsFlush>,
+    /// Held while the stats row and the pending counters are read or
+    /// written together, so a flush in progress is never counted twice or
+    /// not at all
+    stats_write: Arc<tokio::sync::Mutex<()>>,
+    /// Count and time queries in the `db_*` metrics (`database.query_stats`)
+    query_stats: bool,
+    /// bcrypt work factor of new password hashes (`credentials.bcrypt_cost`)
+    password_cost: u32,
+    /// Hash checked for unknown usernames, so logins take as long for them
+    unknown_user_hash: Arc<OnceLock<String>>,
 }
 
+/// Query futures that can be recorded in the `db_*` metrics
+trait Timed<T>: Future<Output = Result<T, sqlx::Error>> + Sized {
+    /// Await the query, recording its duration and outcome if `enabled`
+    async fn timed(self, enabled: bool) -> Result<T, sqlx::Error> {
+        if !enabled {
+            return self.await;
+        }
+        let start = Instant::now();
+        let result = self.await;
+        metrics().record_db_query(start.elapsed().as_secs_f64() * 1000.0, result.is_err());
+        result
+    }
+}
+
+impl<T, F: Future<Output = Result<T, sqlx::Error>>> Timed<T> for F {}
+
 impl Repository {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            stats_pool: pool.clone(),
+            stats_offloaded: false,
+            pool,
+            health: Arc::new(WriteHealth::new()),
+            stats_flush: Arc::new(StatsFlush::immediate()),
+            stats_write: Arc::default(),
+            query_stats: false,
+            password_cost: bcrypt::DEFAULT_COST,
+            unknown_user_hash: Arc::default(),
+        }
+    }
+
+    /// Create a repository that writes message stats to a separate database
+    pub fn with_stats_pool(pool: SqlitePool, stats_pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            stats_pool,
+            stats_offloaded: true,
+            health: Arc::new(WriteHealth::new()),
+            stats_flush: Arc::new(StatsFlush::immediate()),
+            stats_write: Arc::default(),
+            query_stats: false,
+            password_cost: bcrypt::DEFAULT_COST,
+            unknown_user_hash: Arc::default(),
+        }
+    }
+
+    /// Record every query in the `db_*` metrics
+    pub fn with_query_stats(mut self, enabled: bool) -> Self {
+        self.query_stats = enabled;
+        self
+    }
+
+    /// Collect counter updates in memory and write them as `config` says,
+    /// instead of once per update
+    pub fn with_stats_flush(mut self, config: &StatsConfig) -> Self {
+        self.stats_flush = Arc::new(StatsFlush::from_config(config));
+        self
+    }
+
+    /// Hash new and upgraded passwords with this bcrypt work factor
+    pub fn with_password_cost(mut self, cost: u32) -> Self {
+        self.password_cost = cost;
+        self
+    }
+
+    /// Write failure tracking; degraded while writes keep failing
+    pub fn write_health(&self) -> &WriteHealth {
+        &self.health
     }
 
     // ============ MQTT Configs (Multiple Brokers) ============
@@ -188,6 +558,7 @@ impl Repository {
     pub async fn get_mqtt_configs(&self) -> Result<Vec<MqttConfig>, sqlx::Error> {
         let rows: Vec<MqttConfigRow> = sqlx::query_as("SELECT * FROM mqtt_configs ORDER BY id")
             .fetch_all(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
@@ -196,70 +567,20 @@ impl Repository {
         let row: Option<MqttConfigRow> = sqlx::query_as("SELECT * FROM mqtt_configs WHERE id = ?")
             .bind(id as i64)
             .fetch_optional(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(row.map(|r| r.into()))
     }
 
     pub async fn add_mqtt_config(&self, req: &CreateMqttConfigRequest) -> Result<MqttConfig, sqlx::Error> {
-        let result = sqlx::query(
-            r#"
-            INSERT INTO mqtt_configs (name, enabled, broker_url, port, client_id, username, password, use_tls, keep_alive_seconds, clean_session)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&req.name)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(&req.broker_url)
-        .bind(req.port as i64)
-        .bind(&req.client_id)
-        .bind(&req.username)
-        .bind(&req.password)
-        .bind(if req.use_tls { 1i64 } else { 0i64 })
-        .bind(req.keep_alive_seconds as i64)
-        .bind(if req.clean_session { 1i64 } else { 0i64 })
-        .execute(&self.pool)
-        .await?;
-
-        let id = result.last_insert_rowid() as u32;
-        Ok(MqttConfig {
-            id: Some(id),
-            name: req.name.clone(),
-            enabled: req.enabled,
-            broker_url: req.broker_url.clone(),
-            port: req.port,
-            client_id: req.client_id.clone(),
-            username: req.username.clone(),
-            password: req.password.clone(),
-            use_tls: req.use_tls,
-            keep_alive_seconds: req.keep_alive_seconds,
-            clean_session: req.clean_session,
-        })
+        let id = insert_mqtt_config(&self.pool, req).timed(self.query_stats).await?;
+        self.get_mqtt_config(id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
     }
 
     pub async fn update_mqtt_config(&self, id: u32, req: &CreateMqttConfigRequest) -> Result<Option<MqttConfig>, sqlx::Error> {
-        let result = sqlx::query(
-            r#"
-            UPDATE mqtt_configs SET
-                name = ?, enabled = ?, broker_url = ?, port = ?, client_id = ?,
-                username = ?, password = ?, use_tls = ?, keep_alive_seconds = ?, clean_session = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&req.name)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(&req.broker_url)
-        .bind(req.port as i64)
-        .bind(&req.client_id)
-        .bind(&req.username)
-        .bind(&req.password)
-        .bind(if req.use_tls { 1i64 } else { 0i64 })
-        .bind(req.keep_alive_seconds as i64)
-        .bind(if req.clean_session { 1i64 } else { 0i64 })
-        .bind(id as i64)
-        .execute(&self.pool)
-        .await?;
-
-        if result.rows_affected() > 0 {
+        if write_mqtt_config(&self.pool, id, req).timed(self.query_stats).await? {
             self.get_mqtt_config(id).await
         } else {
             Ok(None)
@@ -267,11 +588,7 @@ impl Repository {
     }
 
     pub async fn delete_mqtt_config(&self, id: u32) -> Result<bool, sqlx::Error> {
-        let result = sqlx::query("DELETE FROM mqtt_configs WHERE id = ?")
-            .bind(id as i64)
-            .execute(&self.pool)
-            .await?;
-        Ok(result.rows_affected() > 0)
+        delete_row(&self.pool, "mqtt_configs", id).timed(self.query_stats).await
     }
 
     // ============ ZMQ Configs (XPUB/XSUB) ============
@@ -279,6 +596,7 @@ impl Repository {
     pub async fn get_zmq_configs(&self) -> Result<Vec<ZmqConfig>, sqlx::Error> {
         let rows: Vec<ZmqConfigRow> = sqlx::query_as("SELECT * FROM zmq_configs ORDER BY id")
             .fetch_all(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
@@ -287,79 +605,20 @@ impl Repository {
         let row: Option<ZmqConfigRow> = sqlx::query_as("SELECT * FROM zmq_configs WHERE id = ?")
             .bind(id as i64)
             .fetch_optional(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(row.map(|r| r.into()))
     }
 
     pub async fn add_zmq_config(&self, req: &CreateZmqConfigRequest) -> Result<ZmqConfig, sqlx::Error> {
-        let socket_type = match req.socket_type {
-            ZmqSocketType::XPub => "xpub",
-            ZmqSocketType::XSub => "xsub",
-            ZmqSocketType::Pub => "pub",
-            ZmqSocketType::Sub => "sub",
-        };
-        
-        let connect_endpoints = req.connect_endpoints.join(",");
-
-        let result = sqlx::query(
-            r#"
-            INSERT INTO zmq_configs (name, enabled, socket_type, bind_endpoint, connect_endpoints, high_water_mark, reconnect_interval_ms)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&req.name)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(socket_type)
-        .bind(&req.bind_endpoint)
-        .bind(&connect_endpoints)
-        .bind(req.high_water_mark as i64)
-        .bind(req.reconnect_interval_ms as i64)
-        .execute(&self.pool)
-        .await?;
-
-        let id = result.last_insert_rowid() as u32;
-        Ok(ZmqConfig {
-            id: Some(id),
-            name: req.name.clone(),
-            enabled: req.enabled,
-            socket_type: req.socket_type.clone(),
-            bind_endpoint: req.bind_endpoint.clone(),
-            connect_endpoints: req.connect_endpoints.clone(),
-            high_water_mark: req.high_water_mark,
-            reconnect_interval_ms: req.reconnect_interval_ms,
-        })
+        let id = insert_zmq_config(&self.pool, req).timed(self.query_stats).await?;
+        self.get_zmq_config(id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
     }
 
     pub async fn update_zmq_config(&self, id: u32, req: &CreateZmqConfigRequest) -> Result<Option<ZmqConfig>, sqlx::Error> {
-        let socket_type = match req.socket_type {
-            ZmqSocketType::XPub => "xpub",
-            ZmqSocketType::XSub => "xsub",
-            ZmqSocketType::Pub => "pub",
-            ZmqSocketType::Sub => "sub",
-        };
-        
-        let connect_endpoints = req.connect_endpoints.join(",");
-
-        let result = sqlx::query(
-            r#"
-            UPDATE zmq_configs SET
-                name = ?, enabled = ?, socket_type = ?, bind_endpoint = ?,
-                connect_endpoints = ?, high_water_mark = ?, reconnect_interval_ms = ?
-            WHERE id = ?
-            "#,
-        )
-        .bind(&req.name)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(socket_type)
-        .bind(&req.bind_endpoint)
-        .bind(&connect_endpoints)
-        .bind(req.high_water_mark as i64)
-        .bind(req.reconnect_interval_ms as i64)
-        .bind(id as i64)
-        .execute(&self.pool)
-        .await?;
-
-        if result.rows_affected() > 0 {
+        if write_zmq_config(&self.pool, id, req).timed(self.query_stats).await? {
             self.get_zmq_config(id).await
         } else {
             Ok(None)
@@ -367,11 +626,7 @@ impl Repository {
     }
 
     pub async fn delete_zmq_config(&self, id: u32) -> Result<bool, sqlx::Error> {
-        let result = sqlx::query("DELETE FROM zmq_configs WHERE id = ?")
-            .bind(id as i64)
-            .execute(&self.pool)
-            .await?;
-        Ok(result.rows_affected() > 0)
+        delete_row(&self.pool, "zmq_configs", id).timed(self.query_stats).await
     }
 
     // ============ Topic Mappings ============
@@ -380,146 +635,400 @@ impl Repository {
         let rows: Vec<TopicMappingRow> =
             sqlx::query_as("SELECT * FROM topic_mappings ORDER BY id")
                 .fetch_all(&self.pool)
+                .timed(self.query_stats)
                 .await?;
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    pub async fn get_mapping(&self, id: u32) -> Result<Option<TopicMapping>, sqlx::Error> {
+        let row: Option<TopicMappingRow> = sqlx::query_as("SELECT * FROM topic_mappings WHERE id = ?")
+            .bind(id as i64)
+            .fetch_optional(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(row.map(|r| r.into()))
+    }
+
     pub async fn add_mapping(&self, req: &CreateMappingRequest) -> Result<TopicMapping, sqlx::Error> {
-        let direction = match req.direction {
-            MappingDirection::MqttToZmq => "mqtt_to_zmq",
-            MappingDirection::ZmqToMqtt => "zmq_to_mqtt",
-            MappingDirection::MqttToMqtt => "mqtt_to_mqtt",
-            MappingDirection::ZmqToZmq => "zmq_to_zmq",
-            MappingDirection::Bidirectional => "bidirectional",
-        };
-        
-        let source_type = match req.source_endpoint_type {
-            EndpointType::Mqtt => "mqtt",
-            EndpointType::Zmq => "zmq",
-        };
-        
-        let target_type = match req.target_endpoint_type {
-            EndpointType::Mqtt => "mqtt",
-            EndpointType::Zmq => "zmq",
+        let id = insert_mapping(&self.pool, req).timed(self.query_stats).await?;
+        self.get_mapping(id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    pub async fn update_mapping(&self, id: u32, req: &CreateMappingRequest) -> Result<Option<TopicMapping>, sqlx::Error> {
+        if write_mapping(&self.pool, id, req).timed(self.query_stats).await? {
+            self.get_mapping(id).await
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn delete_mapping(&self, id: u32) -> Result<bool, sqlx::Error> {
+        delete_row(&self.pool, "topic_mappings", id).timed(self.query_stats).await
+    }
+
+    /// Mappings reading from or delivering to an endpoint, including as an additional target
+    pub async fn get_mappings_referencing(
+        &self,
+        endpoint_type: &EndpointType,
+        id: u32,
+    ) -> Result<Vec<TopicMapping>, sqlx::Error> {
+        let mut mappings = self.get_mappings().await?;
+        mappings.retain(|m| references_endpoint(m, endpoint_type, id));
+        Ok(mappings)
+    }
+
+    /// Delete an endpoint config and every mapping reading from or delivering
+    /// to it in one transaction. Mappings using it only as an additional target
+    /// just lose that target. Returns the ids of the deleted and of the trimmed
+    /// mappings, or None if the config does not exist.
+    pub async fn delete_endpoint_cascade(
+        &self,
+        endpoint_type: &EndpointType,
+        id: u32,
+    ) -> Result<Option<(Vec<u32>, Vec<u32>)>, sqlx::Error> {
+        let table = match endpoint_type {
+            EndpointType::Mqtt => "mqtt_configs",
+            EndpointType::Zmq => "zmq_configs",
         };
+        let mut tx = self.pool.begin().await?;
+        if !delete_row(&mut *tx, table, id).timed(self.query_stats).await? {
+            return Ok(None);
+        }
+
+        let rows: Vec<TopicMappingRow> = sqlx::query_as("SELECT * FROM topic_mappings ORDER BY id")
+            .fetch_all(&mut *tx)
+            .timed(self.query_stats)
+            .await?;
+        let endpoint = (endpoint_type, id);
+        let (mut deleted, mut trimmed) = (Vec::new(), Vec::new());
+        for mut mapping in rows.into_iter().map(TopicMapping::from) {
+            if !references_endpoint(&mapping, endpoint_type, id) {
+                continue;
+            }
+            if (&mapping.source_endpoint_type, mapping.source_endpoint_id) == endpoint
+                || (&mapping.target_endpoint_type, mapping.target_endpoint_id) == endpoint
+            {
+                delete_row(&mut *tx, "topic_mappings", mapping.id).timed(self.query_stats).await?;
+                deleted.push(mapping.id);
+                continue;
+            }
+            mapping.additional_targets.retain(|t| (&t.endpoint_type, t.endpoint_id) != endpoint);
+            sqlx::query("UPDATE topic_mappings SET additional_targets = ? WHERE id = ?")
+                .bind(serde_json::to_string(&mapping.additional_targets).unwrap_or_else(|_| "[]".to_string()))
+                .bind(mapping.id as i64)
+                .execute(&mut *tx)
+                .timed(self.query_stats)
+                .await?;
+            trimmed.push(mapping.id);
+        }
+        tx.commit().timed(self.query_stats).await?;
+        Ok(Some((deleted, trimmed)))
+    }
+
+    // ============ Deduplication ============
+
+    /// Whether `mapping_id` saw `key` at or after `cutoff`
+    pub async fn seen_since(&self, mapping_id: u32, key: &str, cutoff: i64) -> Result<bool, sqlx::Error> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT 1 FROM seen_keys WHERE mapping_id = ? AND key = ? AND seen_at >= ?")
+            .bind(mapping_id as i64)
+            .bind(key)
+            .bind(cutoff)
+            .fetch_optional(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(row.is_some())
+    }
 
+    /// Record that `mapping_id` saw `key` at `now`. Returns false if it had
+    /// already seen the key after `cutoff`, in which case nothing changes.
+    pub async fn mark_seen(&self, mapping_id: u32, key: &str, now: i64, cutoff: i64) -> Result<bool, sqlx::Error> {
         let result = sqlx::query(
             r#"
-            INSERT INTO topic_mappings (source_endpoint_type, source_endpoint_id, target_endpoint_type, target_endpoint_id, source_topic, target_topic, direction, enabled, description)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO seen_keys (mapping_id, key, seen_at) VALUES (?, ?, ?)
+            ON CONFLICT (mapping_id, key) DO UPDATE SET seen_at = excluded.seen_at
+            WHERE seen_keys.seen_at < ?
             "#,
         )
-        .bind(source_type)
-        .bind(req.source_endpoint_id as i64)
-        .bind(target_type)
-        .bind(req.target_endpoint_id as i64)
-        .bind(&req.source_topic)
-        .bind(&req.target_topic)
-        .bind(direction)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(&req.description)
+        .bind(mapping_id as i64)
+        .bind(key)
+        .bind(now)
+        .bind(cutoff)
         .execute(&self.pool)
+        .timed(self.query_stats)
         .await?;
-
-        let id = result.last_insert_rowid() as u32;
-        Ok(TopicMapping {
-            id,
-            source_endpoint_type: req.source_endpoint_type.clone(),
-            source_endpoint_id: req.source_endpoint_id,
-            target_endpoint_type: req.target_endpoint_type.clone(),
-            target_endpoint_id: req.target_endpoint_id,
-            source_topic: req.source_topic.clone(),
-            target_topic: req.target_topic.clone(),
-            direction: req.direction.clone(),
-            enabled: req.enabled,
-            description: req.description.clone(),
-        })
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn update_mapping(&self, id: u32, req: &CreateMappingRequest) -> Result<Option<TopicMapping>, sqlx::Error> {
-        let direction = match req.direction {
-            MappingDirection::MqttToZmq => "mqtt_to_zmq",
-            MappingDirection::ZmqToMqtt => "zmq_to_mqtt",
-            MappingDirection::MqttToMqtt => "mqtt_to_mqtt",
-            MappingDirection::ZmqToZmq => "zmq_to_zmq",
-            MappingDirection::Bidirectional => "bidirectional",
-        };
-        
-        let source_type = match req.source_endpoint_type {
-            EndpointType::Mqtt => "mqtt",
-            EndpointType::Zmq => "zmq",
-        };
-        
-        let target_type = match req.target_endpoint_type {
-            EndpointType::Mqtt => "mqtt",
-            EndpointType::Zmq => "zmq",
-        };
-
-        let result = sqlx::query(
+    /// Delete keys seen before `cutoff`, then the oldest beyond `max_keys`
+    pub async fn prune_seen_keys(&self, cutoff: i64, max_keys: u64) -> Result<u64, sqlx::Error> {
+        let expired = sqlx::query("DELETE FROM seen_keys WHERE seen_at < ?")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        let excess = sqlx::query(
             r#"
-            UPDATE topic_mappings SET
-                source_endpoint_type = ?, source_endpoint_id = ?,
-                target_endpoint_type = ?, target_endpoint_id = ?,
-                source_topic = ?, target_topic = ?, direction = ?,
-                enabled = ?, description = ?
-            WHERE id = ?
+            DELETE FROM seen_keys WHERE rowid IN (
+                SELECT rowid FROM seen_keys ORDER BY seen_at
+                LIMIT MAX((SELECT COUNT(*) FROM seen_keys) - ?, 0)
+            )
             "#,
         )
-        .bind(source_type)
-        .bind(req.source_endpoint_id as i64)
-        .bind(target_type)
-        .bind(req.target_endpoint_id as i64)
-        .bind(&req.source_topic)
-        .bind(&req.target_topic)
-        .bind(direction)
-        .bind(if req.enabled { 1i64 } else { 0i64 })
-        .bind(&req.description)
-        .bind(id as i64)
+        .bind(max_keys as i64)
         .execute(&self.pool)
+        .timed(self.query_stats)
         .await?;
+        Ok(expired.rows_affected() + excess.rows_affected())
+    }
 
-        if result.rows_affected() > 0 {
-            Ok(Some(TopicMapping {
-                id,
-                source_endpoint_type: req.source_endpoint_type.clone(),
-                source_endpoint_id: req.source_endpoint_id,
-                target_endpoint_type: req.target_endpoint_type.clone(),
-                target_endpoint_id: req.target_endpoint_id,
-                source_topic: req.source_topic.clone(),
-                target_topic: req.target_topic.clone(),
-                direction: req.direction.clone(),
-                enabled: req.enabled,
-                description: req.description.clone(),
-            }))
-        } else {
-            Ok(None)
+    // ============ Staged Config Changes ============
+
+    pub async fn add_pending_change(
+        &self,
+        entity: PendingEntity,
+        action: PendingAction,
+        target_id: Option<u32>,
+        body: Option<&serde_json::Value>,
+    ) -> Result<PendingChange, sqlx::Error> {
+        let id = insert_pending_change(&self.pool, entity, action, target_id, body).timed(self.query_stats).await?;
+        let row: PendingChangeRow = sqlx::query_as("SELECT * FROM pending_changes WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(row.into())
+    }
+
+    /// Stage several changes at once, e.g. those of an import; returns how many
+    pub async fn add_pending_changes(
+        &self,
+        changes: &[(PendingEntity, PendingAction, Option<u32>, Option<serde_json::Value>)],
+    ) -> Result<usize, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for (entity, action, target_id, body) in changes {
+            insert_pending_change(&mut *tx, *entity, *action, *target_id, body.as_ref())
+                .timed(self.query_stats)
+                .await?;
         }
+        tx.commit().timed(self.query_stats).await?;
+        Ok(changes.len())
     }
 
-    pub async fn delete_mapping(&self, id: u32) -> Result<bool, sqlx::Error> {
-        let result = sqlx::query("DELETE FROM topic_mappings WHERE id = ?")
-            .bind(id as i64)
-            .execute(&self.pool)
+    /// Staged changes in the order they were made
+    pub async fn get_pending_changes(&self) -> Result<Vec<PendingChange>, sqlx::Error> {
+        let rows: Vec<PendingChangeRow> = sqlx::query_as("SELECT * FROM pending_changes ORDER BY id")
+            .fetch_all(&self.pool)
+            .timed(self.query_stats)
             .await?;
-        Ok(result.rows_affected() > 0)
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Drop every staged change; returns how many there were
+    pub async fn discard_pending_changes(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM pending_changes").execute(&self.pool).timed(self.query_stats).await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Write every staged change in order and clear them, all in one
+    /// transaction. A create with a target id, staged by an import, takes
+    /// that id. An update or delete whose target no longer exists fails
+    /// with `RowNotFound` and leaves the configs and staged changes untouched.
+    pub async fn apply_pending_changes(&self) -> Result<usize, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let rows: Vec<PendingChangeRow> = sqlx::query_as("SELECT * FROM pending_changes ORDER BY id")
+            .fetch_all(&mut *tx)
+            .timed(self.query_stats)
+            .await?;
+        let count = rows.len();
+
+        for row in rows {
+            let change = PendingChange::from(row);
+            let id = change.target_id.unwrap_or(0);
+            let body = change.proposed.unwrap_or_default();
+            let written = match (change.entity, change.action) {
+                (PendingEntity::Mqtt, PendingAction::Create) => {
+                    let new_id = insert_mqtt_config(&mut *tx, &pending_body(body)?).timed(self.query_stats).await?;
+                    renumber_row(&mut *tx, "mqtt_configs", new_id, change.target_id).timed(self.query_stats).await.map(|_| true)
+                }
+                (PendingEntity::Mqtt, PendingAction::Update) => {
+                    write_mqtt_config(&mut *tx, id, &pending_body(body)?).timed(self.query_stats).await
+                }
+                (PendingEntity::Mqtt, PendingAction::Delete) => delete_row(&mut *tx, "mqtt_configs", id).timed(self.query_stats).await,
+                (PendingEntity::Zmq, PendingAction::Create) => {
+                    let new_id = insert_zmq_config(&mut *tx, &pending_body(body)?).timed(self.query_stats).await?;
+                    renumber_row(&mut *tx, "zmq_configs", new_id, change.target_id).timed(self.query_stats).await.map(|_| true)
+                }
+                (PendingEntity::Zmq, PendingAction::Update) => {
+                    write_zmq_config(&mut *tx, id, &pending_body(body)?).timed(self.query_stats).await
+                }
+                (PendingEntity::Zmq, PendingAction::Delete) => delete_row(&mut *tx, "zmq_configs", id).timed(self.query_stats).await,
+                (PendingEntity::Mapping, PendingAction::Create) => {
+                    let new_id = insert_mapping(&mut *tx, &pending_body(body)?).timed(self.query_stats).await?;
+                    renumber_row(&mut *tx, "topic_mappings", new_id, change.target_id).timed(self.query_stats).await.map(|_| true)
+                }
+                (PendingEntity::Mapping, PendingAction::Update) => {
+                    write_mapping(&mut *tx, id, &pending_body(body)?).timed(self.query_stats).await
+                }
+                (PendingEntity::Mapping, PendingAction::Delete) => delete_row(&mut *tx, "topic_mappings", id).timed(self.query_stats).await,
+            }?;
+            if !written {
+                // Dropping the transaction rolls everything back
+                return Err(sqlx::Error::RowNotFound);
+            }
+        }
+
+        sqlx::query("DELETE FROM pending_changes").execute(&mut *tx).timed(self.query_stats).await?;
+        tx.commit().timed(self.query_stats).await?;
+        Ok(count)
+    }
+
+    // ============ Bulk Import ============
+
+    /// Write imported records in one transaction, keeping their ids so
+    /// mappings still reference the endpoints they were exported with.
+    /// Endpoints are written before mappings. `Replace` first deletes every
+    /// config and mapping; `Merge` never overwrites a record, so an imported
+    /// id already in use is rejected. With `best_effort`, records the
+    /// database rejects, e.g. for a duplicate name or id, and mappings whose
+    /// endpoints do not exist afterwards are skipped and reported instead of
+    /// failing the import.
+    pub async fn import_config(
+        &self,
+        mode: ImportMode,
+        best_effort: bool,
+        mqtt: &[(Option<u32>, CreateMqttConfigRequest)],
+        zmq: &[(Option<u32>, CreateZmqConfigRequest)],
+        mappings: &[(Option<u32>, CreateMappingRequest)],
+    ) -> Result<Vec<ImportItemResult>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        if mode == ImportMode::Replace {
+            for table in ["topic_mappings", "zmq_configs", "mqtt_configs"] {
+                sqlx::query(&format!("DELETE FROM {}", table)).execute(&mut *tx).timed(self.query_stats).await?;
+            }
+        }
+
+        // Endpoints kept by a merge
+        let ids = |table: &'static str| format!("SELECT id FROM {}", table);
+        let existing_mqtt: HashSet<u32> = sqlx::query_scalar::<_, i64>(&ids("mqtt_configs"))
+            .fetch_all(&mut *tx)
+            .timed(self.query_stats)
+            .await?
+            .into_iter()
+            .map(|id| id as u32)
+            .collect();
+        let existing_zmq: HashSet<u32> = sqlx::query_scalar::<_, i64>(&ids("zmq_configs"))
+            .fetch_all(&mut *tx)
+            .timed(self.query_stats)
+            .await?
+            .into_iter()
+            .map(|id| id as u32)
+            .collect();
+
+        let mut results = Vec::with_capacity(mqtt.len() + zmq.len() + mappings.len());
+        // Each record gets a savepoint, so a rejected one can be undone alone
+        for (id, req) in mqtt {
+            let mut item = tx.begin().await?;
+            let written = async {
+                let new_id = insert_mqtt_config(&mut *item, req).timed(self.query_stats).await?;
+                renumber_row(&mut *item, "mqtt_configs", new_id, *id).timed(self.query_stats).await?;
+                Ok::<_, sqlx::Error>(id.unwrap_or(new_id))
+            }
+            .await;
+            let name = Some(req.name.clone());
+            results.push(finish_import_item(item, written, best_effort, PendingEntity::Mqtt, *id, name).await?);
+        }
+        for (id, req) in zmq {
+            let mut item = tx.begin().await?;
+            let written = async {
+                let new_id = insert_zmq_config(&mut *item, req).timed(self.query_stats).await?;
+                renumber_row(&mut *item, "zmq_configs", new_id, *id).timed(self.query_stats).await?;
+                Ok::<_, sqlx::Error>(id.unwrap_or(new_id))
+            }
+            .await;
+            let name = Some(req.name.clone());
+            results.push(finish_import_item(item, written, best_effort, PendingEntity::Zmq, *id, name).await?);
+        }
+
+        // Mappings bind to the endpoints written here and to kept ones no
+        // imported record claims: an endpoint the import failed to write
+        // must not be replaced by an unrelated one with the same id
+        let mqtt_ids = import_endpoint_ids(existing_mqtt, mqtt.iter().filter_map(|(id, _)| *id), &results, PendingEntity::Mqtt);
+        let zmq_ids = import_endpoint_ids(existing_zmq, zmq.iter().filter_map(|(id, _)| *id), &results, PendingEntity::Zmq);
+        for (id, req) in mappings {
+            if let Some(missing) = req.missing_endpoint(&mqtt_ids, &zmq_ids) {
+                if !best_effort {
+                    return Err(sqlx::Error::RowNotFound);
+                }
+                results.push(ImportItemResult::failed(PendingEntity::Mapping, *id, None, missing));
+                continue;
+            }
+            let mut item = tx.begin().await?;
+            let written = async {
+                let new_id = insert_mapping(&mut *item, req).timed(self.query_stats).await?;
+                renumber_row(&mut *item, "topic_mappings", new_id, *id).timed(self.query_stats).await?;
+                Ok::<_, sqlx::Error>(id.unwrap_or(new_id))
+            }
+            .await;
+            results.push(finish_import_item(item, written, best_effort, PendingEntity::Mapping, *id, None).await?);
+        }
+
+        tx.commit().timed(self.query_stats).await?;
+        Ok(results)
     }
 
     // ============ Message Stats ============
 
+    /// Recreate the single stats row with zeros if it went missing, e.g.
+    /// after a manual edit of the database
+    async fn restore_stats_row(&self) -> Result<(), sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO message_stats (id, mqtt_received, mqtt_sent, zmq_received, zmq_sent, error_count, start_time)
+            VALUES (1, 0, 0, 0, 0, 0, ?)
+            "#,
+        )
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&self.stats_pool)
+        .timed(self.query_stats)
+        .await?;
+        if result.rows_affected() > 0 {
+            tracing::warn!("message_stats row was missing; recreated it with zeroed counters");
+        }
+        Ok(())
+    }
+
     pub async fn get_stats(&self) -> Result<MessageStats, sqlx::Error> {
-        let row: MessageStatsRow = sqlx::query_as("SELECT * FROM message_stats WHERE id = 1")
-            .fetch_one(&self.pool)
+        let _guard = self.stats_write.lock().await;
+        let row: Option<MessageStatsRow> = sqlx::query_as("SELECT * FROM message_stats WHERE id = 1")
+            .fetch_optional(&self.stats_pool)
+            .timed(self.query_stats)
             .await?;
+        let row = match row {
+            Some(row) => row,
+            None => {
+                self.restore_stats_row().await?;
+                sqlx::query_as("SELECT * FROM message_stats WHERE id = 1")
+                    .fetch_one(&self.stats_pool)
+                    .timed(self.query_stats)
+                    .await?
+            }
+        };
 
+        // Include stats not written yet or whose write failed
+        let [mqtt_received, mqtt_sent, zmq_received, zmq_sent, errors] = self.health.pending();
         Ok(MessageStats {
-            mqtt_received: row.mqtt_received as u64,
-            mqtt_sent: row.mqtt_sent as u64,
-            zmq_received: row.zmq_received as u64,
-            zmq_sent: row.zmq_sent as u64,
+            mqtt_received: (row.mqtt_received + mqtt_received) as u64,
+            mqtt_sent: (row.mqtt_sent + mqtt_sent) as u64,
+            zmq_received: (row.zmq_received + zmq_received) as u64,
+            zmq_sent: (row.zmq_sent + zmq_sent) as u64,
             messages_per_second: 0.0,
             avg_latency_ms: 0.0,
-            error_count: row.error_count as u64,
+            error_count: (row.error_count + errors) as u64,
             queue_depth: 0,
+            oversize_dropped: 0,
+            overflow_dropped: 0,
         })
     }
 
@@ -531,7 +1040,52 @@ impl Repository {
         zmq_sent: i64,
         errors: i64,
     ) -> Result<(), sqlx::Error> {
-        sqlx::query(
+        self.health
+            .add_pending([mqtt_received, mqtt_sent, zmq_received, zmq_sent, errors]);
+        let messages: i64 = self.health.pending()[..4].iter().sum();
+        if !self.stats_flush.is_due(messages as u64, Instant::now()) {
+            return Ok(());
+        }
+        self.flush_stats().await
+    }
+
+    /// Write the counter updates collected so far
+    pub async fn flush_stats(&self) -> Result<(), sqlx::Error> {
+        let _guard = self.stats_write.lock().await;
+        // Stats from failed writes are retried along with the new ones, and
+        // leave the pending counters only once written
+        let delta = self.health.pending();
+        if delta == [0; 5] {
+            return Ok(());
+        }
+        self.stats_flush.flushed(Instant::now());
+        let mut result = self.add_to_stats_row(delta).await;
+        if let Ok(0) = result {
+            // The row went missing: recreate it rather than drop the counts
+            result = match self.restore_stats_row().await {
+                Ok(()) => self.add_to_stats_row(delta).await,
+                Err(e) => Err(e),
+            };
+        }
+
+        match result {
+            Ok(written) => {
+                if written > 0 {
+                    self.health.settle(delta);
+                }
+                self.health.record_success();
+                Ok(())
+            }
+            Err(e) => {
+                self.health.record_failure(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Add `delta` to the stats row, returning the number of rows updated
+    async fn add_to_stats_row(&self, delta: StatsDelta) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
             r#"
             UPDATE message_stats SET
                 mqtt_received = mqtt_received + ?,
@@ -542,24 +1096,35 @@ impl Repository {
             WHERE id = 1
             "#,
         )
-        .bind(mqtt_received)
-        .bind(mqtt_sent)
-        .bind(zmq_received)
-        .bind(zmq_sent)
-        .bind(errors)
-        .execute(&self.pool)
+        .bind(delta[0])
+        .bind(delta[1])
+        .bind(delta[2])
+        .bind(delta[3])
+        .bind(delta[4])
+        .execute(&self.stats_pool)
+        .timed(self.query_stats)
         .await?;
-        Ok(())
+        Ok(result.rows_affected())
     }
 
     pub async fn get_start_time(&self) -> Result<i64, sqlx::Error> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT start_time FROM message_stats WHERE id = 1")
+            .fetch_optional(&self.stats_pool)
+            .timed(self.query_stats)
+            .await?;
+        if let Some((start_time,)) = row {
+            return Ok(start_time);
+        }
+        self.restore_stats_row().await?;
         let row: (i64,) = sqlx::query_as("SELECT start_time FROM message_stats WHERE id = 1")
-            .fetch_one(&self.pool)
+            .fetch_one(&self.stats_pool)
+            .timed(self.query_stats)
             .await?;
         Ok(row.0)
     }
 
     pub async fn reset_stats(&self) -> Result<(), sqlx::Error> {
+        let _guard = self.stats_write.lock().await;
         let now = chrono::Utc::now().timestamp();
         sqlx::query(
             r#"
@@ -574,8 +1139,11 @@ impl Repository {
             "#,
         )
         .bind(now)
-        .execute(&self.pool)
+        .execute(&self.stats_pool)
+        .timed(self.query_stats)
         .await?;
+        // Unpersisted stats belong to the previous run
+        self.health.take_pending([0; 5]);
         Ok(())
     }
 
@@ -584,6 +1152,7 @@ impl Repository {
     pub async fn get_users(&self) -> Result<Vec<UserRecord>, sqlx::Error> {
         let rows: Vec<UserRow> = sqlx::query_as("SELECT * FROM users ORDER BY id")
             .fetch_all(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
@@ -592,6 +1161,7 @@ impl Repository {
         let row: Option<UserRow> = sqlx::query_as("SELECT * FROM users WHERE id = ?")
             .bind(id as i64)
             .fetch_optional(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(row.map(|r| r.into()))
     }
@@ -600,26 +1170,28 @@ impl Repository {
         let row: Option<UserRow> = sqlx::query_as("SELECT * FROM users WHERE username = ?")
             .bind(username)
             .fetch_optional(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(row.map(|r| r.into()))
     }
 
     pub async fn create_user(&self, req: &CreateUserRequest) -> Result<UserRecord, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
-        let password_hash = bcrypt::hash(&req.password, bcrypt::DEFAULT_COST)
-            .map_err(|e| sqlx::Error::Protocol(format!("Failed to hash password: {}", e)))?;
+        let password_hash = hash_password(&req.password, self.password_cost)?;
         
         let result = sqlx::query(
             r#"
-            INSERT INTO users (username, password_hash, is_default, created_at, updated_at)
-            VALUES (?, ?, 0, ?, ?)
+            INSERT INTO users (username, password_hash, role, is_default, created_at, updated_at)
+            VALUES (?, ?, ?, 0, ?, ?)
             "#,
         )
         .bind(&req.username)
         .bind(&password_hash)
+        .bind(user_role_str(req.role))
         .bind(now)
         .bind(now)
         .execute(&self.pool)
+        .timed(self.query_stats)
         .await?;
 
         let id = result.last_insert_rowid() as u32;
@@ -627,6 +1199,7 @@ impl Repository {
             id,
             username: req.username.clone(),
             password_hash,
+            role: req.role,
             is_default: false,
             created_at: now,
             updated_at: now,
@@ -637,14 +1210,16 @@ impl Repository {
         let now = chrono::Utc::now().timestamp();
         let result = sqlx::query(
             r#"
-            UPDATE users SET username = ?, updated_at = ?
+            UPDATE users SET username = ?, role = COALESCE(?, role), updated_at = ?
             WHERE id = ?
             "#,
         )
         .bind(&req.username)
+        .bind(req.role.map(user_role_str))
         .bind(now)
         .bind(id as i64)
         .execute(&self.pool)
+        .timed(self.query_stats)
         .await?;
 
         if result.rows_affected() > 0 {
@@ -664,14 +1239,13 @@ impl Repository {
 
         // Verify current password if provided
         if let Some(ref current_password) = req.current_password
-            && !bcrypt::verify(current_password, &user.password_hash).unwrap_or(false)
+            && !password_matches(current_password, &user.password_hash)
         {
             return Ok(false);
         }
 
         let now = chrono::Utc::now().timestamp();
-        let new_hash = bcrypt::hash(&req.new_password, bcrypt::DEFAULT_COST)
-            .map_err(|e| sqlx::Error::Protocol(format!("Failed to hash password: {}", e)))?;
+        let new_hash = hash_password(&req.new_password, self.password_cost)?;
         
         let result = sqlx::query(
             r#"
@@ -683,6 +1257,7 @@ impl Repository {
         .bind(now)
         .bind(id as i64)
         .execute(&self.pool)
+        .timed(self.query_stats)
         .await?;
 
         Ok(result.rows_affected() > 0)
@@ -700,17 +1275,1018 @@ impl Repository {
         let result = sqlx::query("DELETE FROM users WHERE id = ? AND is_default = 0")
             .bind(id as i64)
             .execute(&self.pool)
+            .timed(self.query_stats)
             .await?;
         Ok(result.rows_affected() > 0)
     }
 
+    /// The user if `password` is theirs. Passwords hashed below
+    /// `password_cost` are rehashed on success.
     pub async fn verify_credentials(&self, username: &str, password: &str) -> Result<Option<UserRecord>, sqlx::Error> {
-        let user = self.get_user_by_username(username).await?;
-        if let Some(ref u) = user
-            && bcrypt::verify(password, &u.password_hash).unwrap_or(false)
-        {
-            return Ok(user);
+        let Some(mut user) = self.get_user_by_username(username).await? else {
+            let hash = self
+                .unknown_user_hash
+                .get_or_init(|| bcrypt::hash("", self.password_cost).unwrap_or_default());
+            let _ = bcrypt::verify(password, hash);
+            return Ok(None);
+        };
+        if !password_matches(password, &user.password_hash) {
+            return Ok(None);
         }
-        Ok(None)
+
+        // Weaker hashes and plaintext left by older versions are replaced
+        if bcrypt_cost(&user.password_hash).is_none_or(|cost| cost < self.password_cost) {
+            user.password_hash = hash_password(password, self.password_cost)?;
+            sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+                .bind(&user.password_hash)
+                .bind(user.id as i64)
+                .execute(&self.pool)
+                .timed(self.query_stats)
+                .await?;
+            tracing::info!("Rehashed password of user {} with cost {}", user.username, self.password_cost);
+        }
+        Ok(Some(user))
+    }
+
+    // ============ Refresh Tokens ============
+
+    /// Remember an issued refresh token, forgetting expired ones
+    pub async fn add_refresh_token(&self, id: &str, username: &str, expires_at: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE expires_at < ?")
+            .bind(chrono::Utc::now().timestamp())
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        sqlx::query("INSERT INTO refresh_tokens (id, username, expires_at) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(username)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether a refresh token was issued and has not been revoked
+    pub async fn refresh_token_active(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT id FROM refresh_tokens WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Revoke a refresh token; false if it was unknown or already revoked
+    pub async fn revoke_refresh_token(&self, id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM refresh_tokens WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revoke an access token until its original expiry
+    pub async fn revoke_access_token(&self, jti: &str, expires_at: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO revoked_tokens (jti, expires_at) VALUES (?, ?)")
+            .bind(jti)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(())
+    }
+
+    /// Whether an access token was revoked
+    pub async fn access_token_revoked(&self, jti: &str) -> Result<bool, sqlx::Error> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT jti FROM revoked_tokens WHERE jti = ?")
+            .bind(jti)
+            .fetch_optional(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Forget revoked access tokens that expired before `now`, as they are
+    /// rejected anyway
+    pub async fn prune_revoked_tokens(&self, now: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < ?")
+            .bind(now)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // ============ Dead Letters ============
+
+    /// Set aside a message from `source` that `mapping_id` could not transform
+    pub async fn add_dead_letter(
+        &self,
+        mapping_id: u32,
+        source: (&EndpointType, u32),
+        topic: &str,
+        payload: &[u8],
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
+        let source_type = match source.0 {
+            EndpointType::Mqtt => "mqtt",
+            EndpointType::Zmq => "zmq",
+        };
+        sqlx::query(
+            "INSERT INTO dead_letters (mapping_id, source_type, source_id, topic, payload, reason, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(mapping_id as i64)
+        .bind(source_type)
+        .bind(source.1 as i64)
+        .bind(topic)
+        .bind(payload)
+        .bind(reason)
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&self.pool)
+        .timed(self.query_stats)
+        .await?;
+        Ok(())
+    }
+
+    /// The newest `limit` dead letters, newest first
+    pub async fn get_dead_letters(&self, limit: u32) -> Result<Vec<DeadLetter>, sqlx::Error> {
+        let rows: Vec<DeadLetterRow> = sqlx::query_as("SELECT * FROM dead_letters ORDER BY id DESC LIMIT ?")
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    // ============ Maintenance ============
+
+    /// Rebuild the database file and truncate the WAL, and the same for the
+    /// stats database if offloaded
+    pub async fn vacuum(&self) -> Result<(), sqlx::Error> {
+        let pools = if self.stats_offloaded {
+            vec![&self.pool, &self.stats_pool]
+        } else {
+            vec![&self.pool]
+        };
+        for pool in pools {
+            sqlx::query("VACUUM").execute(pool).timed(self.query_stats).await?;
+            sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .execute(pool)
+                .timed(self.query_stats)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Whether a table exists in the primary database
+    pub async fn table_exists(&self, table: &str) -> Result<bool, sqlx::Error> {
+        let row: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?")
+                .bind(table)
+                .fetch_one(&self.pool)
+                .timed(self.query_stats)
+                .await?;
+        Ok(row.0 > 0)
+    }
+
+    /// Delete rows whose `created_at` (unix seconds) is older than `cutoff`.
+    /// `table` must be a trusted identifier, never user input.
+    pub async fn prune_table(&self, table: &str, cutoff: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(&format!("DELETE FROM {} WHERE created_at < ?", table))
+            .bind(cutoff)
+            .execute(&self.pool)
+            .timed(self.query_stats)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Row counts of every table in the primary database
+    pub async fn table_row_counts(&self) -> Result<Vec<TableRowCount>, sqlx::Error> {
+        let tables: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )
+        .fetch_all(&self.pool)
+        .timed(self.query_stats)
+        .await?;
+
+        let mut counts = Vec::with_capacity(tables.len());
+        for (table,) in tables {
+            let row: (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM \"{}\"", table))
+                .fetch_one(&self.pool)
+                .timed(self.query_stats)
+                .await?;
+            counts.push(TableRowCount { table, rows: row.0 as u64 });
+        }
+        Ok(counts)
+    }
+}
+
+// ============ Config Writes ============
+//
+// Shared by the endpoints that write one config and by applying staged
+// changes, which writes many in one transaction.
+
+async fn insert_mqtt_config<'e>(executor: impl SqliteExecutor<'e>, req: &CreateMqttConfigRequest) -> Result<u32, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO mqtt_configs (name, enabled, broker_url, port, client_id, username, password, use_tls, keep_alive_seconds, clean_session,
+            will_topic, will_payload, birth_topic, birth_payload, connect_timeout_secs,
+            topic_rewrites, topic_aliases, max_subscriptions, protocol_version,
+            ca_cert_path, client_cert_path, client_key_path, tls_insecure_skip_verify, rewrite_stage,
+            clean_start, session_expiry_secs, endpoint_role)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&req.name)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(&req.broker_url)
+    .bind(req.port as i64)
+    .bind(&req.client_id)
+    .bind(&req.username)
+    .bind(&req.password)
+    .bind(if req.use_tls { 1i64 } else { 0i64 })
+    .bind(req.keep_alive_seconds as i64)
+    .bind(if req.clean_session { 1i64 } else { 0i64 })
+    .bind(&req.will_topic)
+    .bind(&req.will_payload)
+    .bind(&req.birth_topic)
+    .bind(&req.birth_payload)
+    .bind(req.connect_timeout_secs as i64)
+    .bind(serde_json::to_string(&req.topic_rewrites).unwrap_or_else(|_| "[]".to_string()))
+    .bind(if req.topic_aliases { 1i64 } else { 0i64 })
+    .bind(req.max_subscriptions.map(|max| max as i64))
+    .bind(protocol_version_str(req.protocol_version))
+    .bind(&req.ca_cert_path)
+    .bind(&req.client_cert_path)
+    .bind(&req.client_key_path)
+    .bind(if req.tls_insecure_skip_verify { 1i64 } else { 0i64 })
+    .bind(rewrite_stage_str(req.rewrite_stage))
+    .bind(if req.clean_start { 1i64 } else { 0i64 })
+    .bind(req.session_expiry_secs as i64)
+    .bind(endpoint_role_str(req.endpoint_role))
+    .execute(executor)
+    .await?;
+
+    Ok(result.last_insert_rowid() as u32)
+}
+
+async fn write_mqtt_config<'e>(executor: impl SqliteExecutor<'e>, id: u32, req: &CreateMqttConfigRequest) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE mqtt_configs SET
+            name = ?, enabled = ?, broker_url = ?, port = ?, client_id = ?,
+            username = ?, password = ?, use_tls = ?, keep_alive_seconds = ?, clean_session = ?,
+            will_topic = ?, will_payload = ?, birth_topic = ?, birth_payload = ?,
+            connect_timeout_secs = ?, topic_rewrites = ?, topic_aliases = ?,
+            max_subscriptions = ?, protocol_version = ?,
+            ca_cert_path = ?, client_cert_path = ?, client_key_path = ?, tls_insecure_skip_verify = ?,
+            rewrite_stage = ?, clean_start = ?, session_expiry_secs = ?, endpoint_role = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(&req.name)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(&req.broker_url)
+    .bind(req.port as i64)
+    .bind(&req.client_id)
+    .bind(&req.username)
+    .bind(&req.password)
+    .bind(if req.use_tls { 1i64 } else { 0i64 })
+    .bind(req.keep_alive_seconds as i64)
+    .bind(if req.clean_session { 1i64 } else { 0i64 })
+    .bind(&req.will_topic)
+    .bind(&req.will_payload)
+    .bind(&req.birth_topic)
+    .bind(&req.birth_payload)
+    .bind(req.connect_timeout_secs as i64)
+    .bind(serde_json::to_string(&req.topic_rewrites).unwrap_or_else(|_| "[]".to_string()))
+    .bind(if req.topic_aliases { 1i64 } else { 0i64 })
+    .bind(req.max_subscriptions.map(|max| max as i64))
+    .bind(protocol_version_str(req.protocol_version))
+    .bind(&req.ca_cert_path)
+    .bind(&req.client_cert_path)
+    .bind(&req.client_key_path)
+    .bind(if req.tls_insecure_skip_verify { 1i64 } else { 0i64 })
+    .bind(rewrite_stage_str(req.rewrite_stage))
+    .bind(if req.clean_start { 1i64 } else { 0i64 })
+    .bind(req.session_expiry_secs as i64)
+    .bind(endpoint_role_str(req.endpoint_role))
+    .bind(id as i64)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn insert_zmq_config<'e>(executor: impl SqliteExecutor<'e>, req: &CreateZmqConfigRequest) -> Result<u32, sqlx::Error> {
+    let socket_type = match req.socket_type {
+        ZmqSocketType::XPub => "xpub",
+        ZmqSocketType::XSub => "xsub",
+        ZmqSocketType::Pub => "pub",
+        ZmqSocketType::Sub => "sub",
+        ZmqSocketType::Push => "push",
+        ZmqSocketType::Pull => "pull",
+    };
+    
+    let connect_endpoints = req.connect_endpoints.join(",");
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO zmq_configs (name, enabled, socket_type, bind_endpoint, connect_endpoints, high_water_mark, reconnect_interval_ms, hmac_key,
+            topic_rewrites, topic_separator, curve_server_key, curve_public_key, curve_secret_key, role,
+            multicast_rate_kbps, multicast_hops, multicast_recovery_ms, resubscribe_on_reconnect, rejoin_warmup_ms,
+            rewrite_stage, batch_max_messages, batch_max_delay_ms, endpoint_role)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(&req.name)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(socket_type)
+    .bind(&req.bind_endpoint)
+    .bind(&connect_endpoints)
+    .bind(req.high_water_mark as i64)
+    .bind(req.reconnect_interval_ms as i64)
+    .bind(&req.hmac_key)
+    .bind(serde_json::to_string(&req.topic_rewrites).unwrap_or_else(|_| "[]".to_string()))
+    .bind(serde_json::to_string(&req.topic_separator).unwrap_or_default())
+    .bind(&req.curve_server_key)
+    .bind(&req.curve_public_key)
+    .bind(&req.curve_secret_key)
+    .bind(match req.role {
+        ZmqRole::Bind => "bind",
+        ZmqRole::Connect => "connect",
+    })
+    .bind(req.multicast_rate_kbps.map(|v| v as i64))
+    .bind(req.multicast_hops.map(|v| v as i64))
+    .bind(req.multicast_recovery_ms.map(|v| v as i64))
+    .bind(if req.resubscribe_on_reconnect { 1i64 } else { 0i64 })
+    .bind(req.rejoin_warmup_ms as i64)
+    .bind(rewrite_stage_str(req.rewrite_stage))
+    .bind(req.batch_max_messages as i64)
+    .bind(req.batch_max_delay_ms as i64)
+    .bind(endpoint_role_str(req.endpoint_role))
+    .execute(executor)
+    .await?;
+
+    Ok(result.last_insert_rowid() as u32)
+}
+
+async fn write_zmq_config<'e>(executor: impl SqliteExecutor<'e>, id: u32, req: &CreateZmqConfigRequest) -> Result<bool, sqlx::Error> {
+    let socket_type = match req.socket_type {
+        ZmqSocketType::XPub => "xpub",
+        ZmqSocketType::XSub => "xsub",
+        ZmqSocketType::Pub => "pub",
+        ZmqSocketType::Sub => "sub",
+        ZmqSocketType::Push => "push",
+        ZmqSocketType::Pull => "pull",
+    };
+    
+    let connect_endpoints = req.connect_endpoints.join(",");
+
+    let result = sqlx::query(
+        r#"
+        UPDATE zmq_configs SET
+            name = ?, enabled = ?, socket_type = ?, bind_endpoint = ?,
+            connect_endpoints = ?, high_water_mark = ?, reconnect_interval_ms = ?,
+            hmac_key = ?, topic_rewrites = ?, topic_separator = ?,
+            curve_server_key = ?, curve_public_key = ?, curve_secret_key = ?, role = ?,
+            multicast_rate_kbps = ?, multicast_hops = ?, multicast_recovery_ms = ?,
+            resubscribe_on_reconnect = ?, rejoin_warmup_ms = ?, rewrite_stage = ?,
+            batch_max_messages = ?, batch_max_delay_ms = ?, endpoint_role = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(&req.name)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(socket_type)
+    .bind(&req.bind_endpoint)
+    .bind(&connect_endpoints)
+    .bind(req.high_water_mark as i64)
+    .bind(req.reconnect_interval_ms as i64)
+    .bind(&req.hmac_key)
+    .bind(serde_json::to_string(&req.topic_rewrites).unwrap_or_else(|_| "[]".to_string()))
+    .bind(serde_json::to_string(&req.topic_separator).unwrap_or_default())
+    .bind(&req.curve_server_key)
+    .bind(&req.curve_public_key)
+    .bind(&req.curve_secret_key)
+    .bind(match req.role {
+        ZmqRole::Bind => "bind",
+        ZmqRole::Connect => "connect",
+    })
+    .bind(req.multicast_rate_kbps.map(|v| v as i64))
+    .bind(req.multicast_hops.map(|v| v as i64))
+    .bind(req.multicast_recovery_ms.map(|v| v as i64))
+    .bind(if req.resubscribe_on_reconnect { 1i64 } else { 0i64 })
+    .bind(req.rejoin_warmup_ms as i64)
+    .bind(rewrite_stage_str(req.rewrite_stage))
+    .bind(req.batch_max_messages as i64)
+    .bind(req.batch_max_delay_ms as i64)
+    .bind(endpoint_role_str(req.endpoint_role))
+    .bind(id as i64)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+async fn insert_mapping<'e>(executor: impl SqliteExecutor<'e>, req: &CreateMappingRequest) -> Result<u32, sqlx::Error> {
+    let direction = match req.direction {
+        MappingDirection::MqttToZmq => "mqtt_to_zmq",
+        MappingDirection::ZmqToMqtt => "zmq_to_mqtt",
+        MappingDirection::MqttToMqtt => "mqtt_to_mqtt",
+        MappingDirection::ZmqToZmq => "zmq_to_zmq",
+        MappingDirection::Bidirectional => "bidirectional",
+    };
+
+    let payload_transform = req.payload_transform.map(|t| match t {
+        PayloadTransform::JsonToMsgpack => "json_to_msgpack",
+        PayloadTransform::MsgpackToJson => "msgpack_to_json",
+    });
+
+    let transform_error_policy = transform_error_policy_str(req.transform_error_policy);
+    
+    let source_type = match req.source_endpoint_type {
+        EndpointType::Mqtt => "mqtt",
+        EndpointType::Zmq => "zmq",
+    };
+    
+    let target_type = match req.target_endpoint_type {
+        EndpointType::Mqtt => "mqtt",
+        EndpointType::Zmq => "zmq",
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO topic_mappings (source_endpoint_type, source_endpoint_id, target_endpoint_type, target_endpoint_id, source_topic, target_topic, direction, enabled, description, ordering_key, priority, stop_on_match, target_fallback,
+            sample_every_n, sample_interval_ms, identity_property, additional_targets,
+            publish_qos, fallback_topic, timestamp_source, schedule, source_topic_carrier, transform_command, durable,
+            payload_transform, qos, retain, transform_error_policy, dedup_key, truncate_bytes,
+            max_payload_bytes, allow_broad_subscription)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(source_type)
+    .bind(req.source_endpoint_id as i64)
+    .bind(target_type)
+    .bind(req.target_endpoint_id as i64)
+    .bind(&req.source_topic)
+    .bind(&req.target_topic)
+    .bind(direction)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(&req.description)
+    .bind(&req.ordering_key)
+    .bind(req.priority as i64)
+    .bind(if req.stop_on_match { 1i64 } else { 0i64 })
+    .bind(&req.target_fallback)
+    .bind(req.sample_every_n.map(|n| n as i64))
+    .bind(req.sample_interval_ms.map(|ms| ms as i64))
+    .bind(&req.identity_property)
+    .bind(serde_json::to_string(&req.additional_targets).unwrap_or_else(|_| "[]".to_string()))
+    .bind(req.publish_qos.map(|q| q as i64))
+    .bind(&req.fallback_topic)
+    .bind(&req.timestamp_source)
+    .bind(&req.schedule)
+    .bind(&req.source_topic_carrier)
+    .bind(&req.transform_command)
+    .bind(if req.durable { 1i64 } else { 0i64 })
+    .bind(payload_transform)
+    .bind(req.qos as i64)
+    .bind(if req.retain { 1i64 } else { 0i64 })
+    .bind(transform_error_policy)
+    .bind(&req.dedup_key)
+    .bind(req.truncate_bytes.map(|n| n as i64))
+    .bind(req.max_payload_bytes.map(|n| n as i64))
+    .bind(if req.allow_broad_subscription { 1i64 } else { 0i64 })
+    .execute(executor)
+    .await?;
+
+    Ok(result.last_insert_rowid() as u32)
+}
+
+async fn write_mapping<'e>(executor: impl SqliteExecutor<'e>, id: u32, req: &CreateMappingRequest) -> Result<bool, sqlx::Error> {
+    let direction = match req.direction {
+        MappingDirection::MqttToZmq => "mqtt_to_zmq",
+        MappingDirection::ZmqToMqtt => "zmq_to_mqtt",
+        MappingDirection::MqttToMqtt => "mqtt_to_mqtt",
+        MappingDirection::ZmqToZmq => "zmq_to_zmq",
+        MappingDirection::Bidirectional => "bidirectional",
+    };
+
+    let payload_transform = req.payload_transform.map(|t| match t {
+        PayloadTransform::JsonToMsgpack => "json_to_msgpack",
+        PayloadTransform::MsgpackToJson => "msgpack_to_json",
+    });
+
+    let transform_error_policy = transform_error_policy_str(req.transform_error_policy);
+    
+    let source_type = match req.source_endpoint_type {
+        EndpointType::Mqtt => "mqtt",
+        EndpointType::Zmq => "zmq",
+    };
+    
+    let target_type = match req.target_endpoint_type {
+        EndpointType::Mqtt => "mqtt",
+        EndpointType::Zmq => "zmq",
+    };
+
+    let result = sqlx::query(
+        r#"
+        UPDATE topic_mappings SET
+            source_endpoint_type = ?, source_endpoint_id = ?,
+            target_endpoint_type = ?, target_endpoint_id = ?,
+            source_topic = ?, target_topic = ?, direction = ?,
+            enabled = ?, description = ?, ordering_key = ?,
+            priority = ?, stop_on_match = ?, target_fallback = ?,
+            sample_every_n = ?, sample_interval_ms = ?, identity_property = ?,
+            additional_targets = ?, publish_qos = ?, fallback_topic = ?,
+            timestamp_source = ?, schedule = ?, source_topic_carrier = ?,
+            transform_command = ?, durable = ?, payload_transform = ?, qos = ?,
+            retain = ?, transform_error_policy = ?, dedup_key = ?, truncate_bytes = ?,
+            max_payload_bytes = ?, allow_broad_subscription = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(source_type)
+    .bind(req.source_endpoint_id as i64)
+    .bind(target_type)
+    .bind(req.target_endpoint_id as i64)
+    .bind(&req.source_topic)
+    .bind(&req.target_topic)
+    .bind(direction)
+    .bind(if req.enabled { 1i64 } else { 0i64 })
+    .bind(&req.description)
+    .bind(&req.ordering_key)
+    .bind(req.priority as i64)
+    .bind(if req.stop_on_match { 1i64 } else { 0i64 })
+    .bind(&req.target_fallback)
+    .bind(req.sample_every_n.map(|n| n as i64))
+    .bind(req.sample_interval_ms.map(|ms| ms as i64))
+    .bind(&req.identity_property)
+    .bind(serde_json::to_string(&req.additional_targets).unwrap_or_else(|_| "[]".to_string()))
+    .bind(req.publish_qos.map(|q| q as i64))
+    .bind(&req.fallback_topic)
+    .bind(&req.timestamp_source)
+    .bind(&req.schedule)
+    .bind(&req.source_topic_carrier)
+    .bind(&req.transform_command)
+    .bind(if req.durable { 1i64 } else { 0i64 })
+    .bind(payload_transform)
+    .bind(req.qos as i64)
+    .bind(if req.retain { 1i64 } else { 0i64 })
+    .bind(transform_error_policy)
+    .bind(&req.dedup_key)
+    .bind(req.truncate_bytes.map(|n| n as i64))
+    .bind(req.max_payload_bytes.map(|n| n as i64))
+    .bind(if req.allow_broad_subscription { 1i64 } else { 0i64 })
+    .bind(id as i64)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Request body of a staged change
+fn pending_body<T: serde::de::DeserializeOwned>(body: serde_json::Value) -> Result<T, sqlx::Error> {
+    serde_json::from_value(body).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Stage one change; returns its id
+async fn insert_pending_change<'e>(
+    executor: impl SqliteExecutor<'e>,
+    entity: PendingEntity,
+    action: PendingAction,
+    target_id: Option<u32>,
+    body: Option<&serde_json::Value>,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query(
+        "INSERT INTO pending_changes (entity, action, target_id, body, created_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(match entity {
+        PendingEntity::Mqtt => "mqtt",
+        PendingEntity::Zmq => "zmq",
+        PendingEntity::Mapping => "mapping",
+    })
+    .bind(match action {
+        PendingAction::Create => "create",
+        PendingAction::Update => "update",
+        PendingAction::Delete => "delete",
+    })
+    .bind(target_id.map(|id| id as i64))
+    .bind(body.map(|body| body.to_string()))
+    .bind(chrono::Utc::now().timestamp())
+    .execute(executor)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Give a freshly inserted row the id it was imported with, if any
+async fn renumber_row<'e>(
+    executor: impl SqliteExecutor<'e>,
+    table: &str,
+    new_id: u32,
+    id: Option<u32>,
+) -> Result<(), sqlx::Error> {
+    if let Some(id) = id
+        && id != new_id
+    {
+        sqlx::query(&format!("UPDATE {} SET id = ? WHERE id = ?", table))
+            .bind(id as i64)
+            .bind(new_id as i64)
+            .execute(executor)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Keep an imported record's writes, or with `best_effort` undo those of a
+/// record the database rejected and report why
+async fn finish_import_item(
+    item: Transaction<'_, Sqlite>,
+    written: Result<u32, sqlx::Error>,
+    best_effort: bool,
+    entity: PendingEntity,
+    id: Option<u32>,
+    name: Option<String>,
+) -> Result<ImportItemResult, sqlx::Error> {
+    match written {
+        Ok(stored_id) => {
+            item.commit().await?;
+            Ok(ImportItemResult::imported(entity, id, name, stored_id))
+        }
+        Err(sqlx::Error::Database(e)) if best_effort => {
+            item.rollback().await?;
+            Ok(ImportItemResult::failed(entity, id, name, e.message().to_string()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Endpoint ids an import's mappings may reference: `existing` ones no
+/// imported record claims, plus those of `entity` the import wrote
+fn import_endpoint_ids(
+    existing: HashSet<u32>,
+    claimed: impl Iterator<Item = u32>,
+    results: &[ImportItemResult],
+    entity: PendingEntity,
+) -> HashSet<u32> {
+    let claimed: HashSet<u32> = claimed.collect();
+    let mut ids: HashSet<u32> = existing.into_iter().filter(|id| !claimed.contains(id)).collect();
+    ids.extend(results.iter().filter(|r| r.entity == entity).filter_map(|r| r.stored_id));
+    ids
+}
+
+/// Whether `mapping` reads from or delivers to the endpoint
+fn references_endpoint(mapping: &TopicMapping, endpoint_type: &EndpointType, id: u32) -> bool {
+    let endpoint = (endpoint_type, id);
+    (&mapping.source_endpoint_type, mapping.source_endpoint_id) == endpoint
+        || (&mapping.target_endpoint_type, mapping.target_endpoint_id) == endpoint
+        || mapping
+            .additional_targets
+            .iter()
+            .any(|t| (&t.endpoint_type, t.endpoint_id) == endpoint)
+}
+
+async fn delete_row<'e>(executor: impl SqliteExecutor<'e>, table: &str, id: u32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&format!("DELETE FROM {} WHERE id = ?", table))
+        .bind(id as i64)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_memory_db;
+
+    #[tokio::test]
+    async fn test_missing_stats_row_is_recreated() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        repo.increment_stats(3, 0, 0, 0, 0).await.unwrap();
+        sqlx::query("DELETE FROM message_stats").execute(&repo.stats_pool).await.unwrap();
+
+        let stats = repo.get_stats().await.unwrap();
+        assert_eq!(stats.mqtt_received, 0);
+        assert!(repo.get_start_time().await.unwrap() > 0);
+
+        // Counting resumes on the recreated row
+        repo.increment_stats(1, 0, 0, 0, 0).await.unwrap();
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_received, 1);
+
+        sqlx::query("DELETE FROM message_stats").execute(&repo.stats_pool).await.unwrap();
+        assert!(repo.get_start_time().await.unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_stats_flush_cadence() {
+        let config = StatsConfig {
+            flush_interval_ms: 60_000,
+            flush_on_count: 5,
+        };
+        let repo = Repository::new(init_memory_db().await.unwrap()).with_stats_flush(&config);
+        let persisted = |column: &'static str| {
+            let query = format!("SELECT {} FROM message_stats WHERE id = 1", column);
+            let pool = repo.stats_pool.clone();
+            async move { sqlx::query_scalar::<_, i64>(&query).fetch_one(&pool).await.unwrap() }
+        };
+
+        for _ in 0..4 {
+            repo.increment_stats(1, 0, 0, 0, 0).await.unwrap();
+        }
+        assert_eq!(persisted("mqtt_received").await, 0, "below flush_on_count and inside the interval");
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_received, 4);
+
+        // The fifth message reaches flush_on_count
+        repo.increment_stats(1, 0, 0, 0, 0).await.unwrap();
+        assert_eq!(persisted("mqtt_received").await, 5);
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_received, 5);
+
+        repo.increment_stats(0, 1, 0, 0, 0).await.unwrap();
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_sent, 1);
+        // What the periodic task writes when traffic stops
+        repo.flush_stats().await.unwrap();
+        assert_eq!(persisted("mqtt_sent").await, 1);
+        assert_eq!(repo.write_health().pending(), [0; 5]);
+    }
+
+    #[tokio::test]
+    async fn test_stats_read_during_flush_counts_everything_once() {
+        let config = StatsConfig {
+            flush_interval_ms: 60_000,
+            flush_on_count: 1000,
+        };
+        let repo = Repository::new(init_memory_db().await.unwrap()).with_stats_flush(&config);
+        for _ in 0..3 {
+            repo.increment_stats(1, 0, 0, 0, 0).await.unwrap();
+        }
+        let (flushed, during) = tokio::join!(repo.flush_stats(), repo.get_stats());
+        flushed.unwrap();
+        assert_eq!(during.unwrap().mqtt_received, 3);
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_received, 3);
+
+        // A flush recreates a missing row instead of dropping its counts
+        sqlx::query("DELETE FROM message_stats").execute(&repo.stats_pool).await.unwrap();
+        repo.increment_stats(0, 2, 0, 0, 0).await.unwrap();
+        repo.flush_stats().await.unwrap();
+        assert_eq!(repo.write_health().pending(), [0; 5]);
+        assert_eq!(repo.get_stats().await.unwrap().mqtt_sent, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pending_changes_apply_atomically() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let zmq = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": "pub",
+                "bind_endpoint": "tcp://*:5555",
+                "connect_endpoints": [],
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 1000,
+            })
+        };
+        let existing = repo.add_zmq_config(&serde_json::from_value(zmq("existing")).unwrap()).await.unwrap();
+        let existing_id = existing.id.unwrap();
+
+        repo.add_pending_change(PendingEntity::Zmq, PendingAction::Create, None, Some(&zmq("staged")))
+            .await
+            .unwrap();
+        repo.add_pending_change(PendingEntity::Zmq, PendingAction::Update, Some(existing_id), Some(&zmq("renamed")))
+            .await
+            .unwrap();
+        // Nothing is written until the changes are applied
+        assert_eq!(repo.get_zmq_configs().await.unwrap().len(), 1);
+        assert_eq!(repo.get_pending_changes().await.unwrap().len(), 2);
+
+        // A change whose target is gone rolls back the whole batch
+        repo.add_pending_change(PendingEntity::Mapping, PendingAction::Delete, Some(999), None)
+            .await
+            .unwrap();
+        assert!(matches!(repo.apply_pending_changes().await, Err(sqlx::Error::RowNotFound)));
+        assert_eq!(repo.get_zmq_configs().await.unwrap(), vec![existing]);
+        assert_eq!(repo.get_pending_changes().await.unwrap().len(), 3);
+
+        assert_eq!(repo.discard_pending_changes().await.unwrap(), 3);
+        repo.add_pending_change(PendingEntity::Zmq, PendingAction::Create, None, Some(&zmq("staged")))
+            .await
+            .unwrap();
+        repo.add_pending_change(PendingEntity::Zmq, PendingAction::Update, Some(existing_id), Some(&zmq("renamed")))
+            .await
+            .unwrap();
+        assert_eq!(repo.apply_pending_changes().await.unwrap(), 2);
+        let names: Vec<String> = repo.get_zmq_configs().await.unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, ["renamed", "staged"]);
+        assert!(repo.get_pending_changes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_password_hashing() {
+        let repo = Repository::new(init_memory_db().await.unwrap()).with_password_cost(5);
+        let req = CreateUserRequest {
+            username: "alice".to_string(),
+            password: "s3cret-pass".to_string(),
+            role: UserRole::Viewer,
+        };
+        let user = repo.create_user(&req).await.unwrap();
+        assert_ne!(user.password_hash, "s3cret-pass");
+        assert_eq!(bcrypt_cost(&user.password_hash), Some(5));
+
+        assert!(repo.verify_credentials("alice", "s3cret-pass").await.unwrap().is_some());
+        assert!(repo.verify_credentials("alice", "wrong-pass").await.unwrap().is_none());
+        assert!(repo.verify_credentials("bob", "s3cret-pass").await.unwrap().is_none());
+
+        // Weaker hashes are upgraded at the next login
+        let set_stored = |stored: String| {
+            sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+                .bind(stored)
+                .bind(user.id as i64)
+                .execute(&repo.pool)
+        };
+        set_stored(bcrypt::hash("s3cret-pass", 4).unwrap()).await.unwrap();
+        assert!(repo.verify_credentials("alice", "wrong-pass").await.unwrap().is_none());
+        let upgraded = repo.verify_credentials("alice", "s3cret-pass").await.unwrap().unwrap();
+        assert_eq!(bcrypt_cost(&upgraded.password_hash), Some(5));
+        let stored = repo.get_user_by_username("alice").await.unwrap().unwrap();
+        assert_eq!(stored.password_hash, upgraded.password_hash);
+
+        // A plaintext password from an older version still logs in, once
+        set_stored("s3cret-pass".to_string()).await.unwrap();
+        assert!(repo.verify_credentials("alice", "s3cret-pas").await.unwrap().is_none());
+        let rehashed = repo.verify_credentials("alice", "s3cret-pass").await.unwrap().unwrap();
+        assert_eq!(bcrypt_cost(&rehashed.password_hash), Some(5));
+        let stored = repo.get_user_by_username("alice").await.unwrap().unwrap();
+        assert_eq!(stored.password_hash, rehashed.password_hash);
+    }
+
+    #[tokio::test]
+    async fn test_delete_endpoint_cascade() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let mqtt: CreateMqttConfigRequest = serde_json::from_value(serde_json::json!({
+            "name": "broker",
+            "enabled": true,
+            "broker_url": "localhost",
+            "port": 1883,
+            "client_id": "zeromqtt",
+            "use_tls": false,
+            "keep_alive_seconds": 60,
+            "clean_session": true,
+        }))
+        .unwrap();
+        let zmq: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+            "name": "pub",
+            "enabled": true,
+            "socket_type": "pub",
+            "bind_endpoint": "tcp://*:5555",
+            "connect_endpoints": [],
+            "high_water_mark": 1000,
+            "reconnect_interval_ms": 1000,
+        }))
+        .unwrap();
+        let mqtt_id = repo.add_mqtt_config(&mqtt).await.unwrap().id.unwrap();
+        let zmq_id = repo.add_zmq_config(&zmq).await.unwrap().id.unwrap();
+        let other_zmq_id = repo
+            .add_zmq_config(&CreateZmqConfigRequest { name: "other".to_string(), ..zmq })
+            .await
+            .unwrap()
+            .id
+            .unwrap();
+        let mapping = |target_id: u32, additional: serde_json::Value| -> CreateMappingRequest {
+            serde_json::from_value(serde_json::json!({
+                "source_endpoint_type": "mqtt",
+                "source_endpoint_id": mqtt_id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": target_id,
+                "source_topic": "sensors/#",
+                "target_topic": "sensors",
+                "direction": "mqtt_to_zmq",
+                "enabled": true,
+                "additional_targets": additional,
+            }))
+            .unwrap()
+        };
+        let direct = repo.add_mapping(&mapping(zmq_id, serde_json::json!([]))).await.unwrap();
+        let fan_out = repo
+            .add_mapping(&mapping(
+                other_zmq_id,
+                serde_json::json!([{"endpoint_type": "zmq", "endpoint_id": zmq_id, "target_topic": "copy"}]),
+            ))
+            .await
+            .unwrap();
+        let unrelated = repo.add_mapping(&mapping(other_zmq_id, serde_json::json!([]))).await.unwrap();
+
+        let referencing = repo.get_mappings_referencing(&EndpointType::Zmq, zmq_id).await.unwrap();
+        let ids: Vec<u32> = referencing.iter().map(|m| m.id).collect();
+        assert_eq!(ids, [direct.id, fan_out.id]);
+        // Looking up references changes nothing
+        assert_eq!(repo.get_mappings().await.unwrap().len(), 3);
+        assert!(repo.get_zmq_config(zmq_id).await.unwrap().is_some());
+
+        let deleted = repo.delete_endpoint_cascade(&EndpointType::Zmq, zmq_id).await.unwrap();
+        assert_eq!(deleted, Some((vec![direct.id], vec![fan_out.id])));
+        assert!(repo.get_zmq_config(zmq_id).await.unwrap().is_none());
+        let remaining: Vec<u32> = repo.get_mappings().await.unwrap().iter().map(|m| m.id).collect();
+        assert_eq!(remaining, [fan_out.id, unrelated.id]);
+        // The fan-out mapping keeps delivering to its primary target
+        assert!(repo.get_mapping(fan_out.id).await.unwrap().unwrap().additional_targets.is_empty());
+
+        assert_eq!(repo.delete_endpoint_cascade(&EndpointType::Zmq, zmq_id).await.unwrap(), None);
+        let deleted = repo.delete_endpoint_cascade(&EndpointType::Mqtt, mqtt_id).await.unwrap();
+        assert_eq!(deleted, Some((vec![fan_out.id, unrelated.id], vec![])));
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_ids() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let zmq = |name: &str| -> CreateZmqConfigRequest {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": "pub",
+                "bind_endpoint": "tcp://*:5555",
+                "connect_endpoints": [],
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 1000,
+            }))
+            .unwrap()
+        };
+        repo.add_zmq_config(&zmq("kept")).await.unwrap();
+
+        repo.import_config(ImportMode::Merge, false, &[], &[(Some(7), zmq("imported")), (Some(1), zmq("renamed"))], &[])
+            .await
+            .unwrap();
+        let configs = repo.get_zmq_configs().await.unwrap();
+        let ids: Vec<_> = configs.iter().map(|c| (c.id.unwrap(), c.name.as_str())).collect();
+        assert_eq!(ids, [(1, "renamed"), (7, "imported")]);
+
+        repo.import_config(ImportMode::Replace, false, &[], &[(Some(3), zmq("only"))], &[]).await.unwrap();
+        let configs = repo.get_zmq_configs().await.unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].id, Some(3));
+        // Later inserts do not collide with imported ids
+        assert!(repo.add_zmq_config(&zmq("next")).await.unwrap().id > Some(3));
+
+        // A failed import leaves everything as it was
+        assert!(
+            repo.import_config(ImportMode::Replace, false, &[], &[(None, zmq("dup")), (None, zmq("dup"))], &[])
+                .await
+                .is_err()
+        );
+        assert_eq!(repo.get_zmq_configs().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_best_effort_import_skips_rejected_records() {
+        let repo = Repository::new(init_memory_db().await.unwrap());
+        let zmq = |name: &str| -> CreateZmqConfigRequest {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": "pub",
+                "bind_endpoint": "tcp://*:5555",
+                "connect_endpoints": [],
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 1000,
+            }))
+            .unwrap()
+        };
+        let mapping = |source_id: u32, target_id: u32| -> CreateMappingRequest {
+            serde_json::from_value(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": source_id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": target_id,
+                "source_topic": "in/#",
+                "target_topic": "out",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .unwrap()
+        };
+        // The second "dup" clashes with the first; the mapping to it has nowhere to go
+        let endpoints = [(Some(1), zmq("a")), (Some(2), zmq("dup")), (Some(3), zmq("dup"))];
+        let mappings = [(Some(1), mapping(1, 2)), (Some(2), mapping(1, 3))];
+
+        assert!(repo.import_config(ImportMode::Replace, false, &[], &endpoints, &mappings).await.is_err());
+        assert!(repo.get_zmq_configs().await.unwrap().is_empty());
+
+        let results = repo
+            .import_config(ImportMode::Replace, true, &[], &endpoints, &mappings)
+            .await
+            .unwrap();
+        let outcome: Vec<_> = results.iter().map(|r| (r.entity, r.id, r.stored_id, r.error.is_some())).collect();
+        assert_eq!(
+            outcome,
+            [
+                (PendingEntity::Zmq, Some(1), Some(1), false),
+                (PendingEntity::Zmq, Some(2), Some(2), false),
+                (PendingEntity::Zmq, Some(3), None, true),
+                (PendingEntity::Mapping, Some(1), Some(1), false),
+                (PendingEntity::Mapping, Some(2), None, true),
+            ]
+        );
+        assert_eq!(results[4].error.as_deref(), Some("The target ZMQ endpoint 3 does not exist"));
+        assert_eq!(repo.get_zmq_configs().await.unwrap().len(), 2);
+        assert_eq!(repo.get_mappings().await.unwrap().len(), 1);
     }
 }
@@ -0,0 +1,97 @@
+//! Periodic pruning of append-only tables
+
+use crate::config::RetentionConfig;
+use crate::db::Repository;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Forwarded message history
+pub const HISTORY_TABLE: &str = "message_history";
+/// Configuration change audit log
+pub const AUDIT_TABLE: &str = "audit_log";
+/// Messages that could not be delivered
+pub const DEADLETTER_TABLE: &str = "dead_letters";
+
+/// Tables paired with their retention window in days
+fn retention_windows(config: &RetentionConfig) -> [(&'static str, u32); 3] {
+    [
+        (HISTORY_TABLE, config.history_days),
+        (AUDIT_TABLE, config.audit_days),
+        (DEADLETTER_TABLE, config.deadletter_days),
+    ]
+}
+
+/// Delete expired rows, returning the number removed per table
+pub async fn prune_expired(
+    repo: &Repository,
+    config: &RetentionConfig,
+    now: i64,
+) -> Result<Vec<(&'static str, u64)>, sqlx::Error> {
+    let mut pruned = Vec::new();
+
+    for (table, days) in retention_windows(config) {
+        if days == 0 || !repo.table_exists(table).await? {
+            continue;
+        }
+        let cutoff = now - i64::from(days) * 86_400;
+        pruned.push((table, repo.prune_table(table, cutoff).await?));
+    }
+
+    Ok(pruned)
+}
+
+/// Run `prune_expired` every `prune_interval_secs`
+pub fn spawn_retention_task(repo: Repository, config: RetentionConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.prune_interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            match prune_expired(&repo, &config, chrono::Utc::now().timestamp()).await {
+                Ok(pruned) => {
+                    for (table, rows) in pruned.into_iter().filter(|(_, rows)| *rows > 0) {
+                        info!("Pruned {} expired rows from {}", rows, table);
+                    }
+                }
+                Err(e) => warn!("Retention pruning failed: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn test_prune_expired() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE message_history (id INTEGER PRIMARY KEY, created_at INTEGER NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let now = 1_700_000_000;
+        for age_days in [1, 6, 8, 30] {
+            sqlx::query("INSERT INTO message_history (created_at) VALUES (?)")
+                .bind(now - age_days * 86_400)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let repo = Repository::new(pool);
+        let pruned = prune_expired(&repo, &RetentionConfig::default(), now).await.unwrap();
+
+        // Only the history table exists; the others are skipped
+        assert_eq!(pruned, vec![(HISTORY_TABLE, 2)]);
+        let counts = repo.table_row_counts().await.unwrap();
+        assert_eq!(counts[0].table, HISTORY_TABLE);
+        assert_eq!(counts[0].rows, 2);
+    }
+}
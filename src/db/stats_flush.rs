@@ -0,0 +1,90 @@
+//! Batched writes of the message counters
+//!
+//! Every forwarded message bumps the counters in `message_stats`, which costs
+//! a database write per message at high rates. Updates are collected in
+//! memory instead and written once `stats.flush_interval_ms` has passed since
+//! the last write or `stats.flush_on_count` messages are waiting, whichever
+//! comes first. Reads add the unwritten counts, so they stay exact between
+//! writes.
+
+use crate::config::StatsConfig;
+use crate::db::Repository;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// When collected counter updates are written
+#[derive(Debug)]
+pub struct StatsFlush {
+    interval: Duration,
+    on_count: u64,
+    last_flush: Mutex<Instant>,
+}
+
+impl StatsFlush {
+    pub fn new(interval: Duration, on_count: u64) -> Self {
+        Self {
+            interval,
+            on_count,
+            last_flush: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Write every update as it happens
+    pub fn immediate() -> Self {
+        Self::new(Duration::ZERO, 0)
+    }
+
+    pub fn from_config(config: &StatsConfig) -> Self {
+        Self::new(Duration::from_millis(config.flush_interval_ms), config.flush_on_count)
+    }
+
+    /// Whether `pending` counted but unwritten messages should be written at `now`
+    pub fn is_due(&self, pending: u64, now: Instant) -> bool {
+        now.duration_since(*self.last_flush.lock()) >= self.interval
+            || (self.on_count > 0 && pending >= self.on_count)
+    }
+
+    /// Record a write at `now`, restarting the interval
+    pub fn flushed(&self, now: Instant) {
+        *self.last_flush.lock() = now;
+    }
+}
+
+/// Write collected counters every `flush_interval_ms`, so they reach the
+/// database when traffic stops; `None` when every update is written at once
+pub fn spawn_stats_flush_task(repo: Repository, config: &StatsConfig) -> Option<JoinHandle<()>> {
+    if config.flush_interval_ms == 0 {
+        return None;
+    }
+    let period = Duration::from_millis(config.flush_interval_ms);
+    Some(tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = repo.flush_stats().await {
+                warn!("Writing message stats failed: {}", e);
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_due_on_interval_or_count() {
+        let start = Instant::now();
+        let flush = StatsFlush::new(Duration::from_secs(1), 100);
+        flush.flushed(start);
+        assert!(!flush.is_due(99, start + Duration::from_millis(999)));
+        assert!(flush.is_due(100, start + Duration::from_millis(1)));
+        assert!(flush.is_due(1, start + Duration::from_secs(1)));
+
+        flush.flushed(start + Duration::from_secs(1));
+        assert!(!flush.is_due(1, start + Duration::from_millis(1500)));
+        assert!(StatsFlush::immediate().is_due(1, Instant::now()));
+    }
+}
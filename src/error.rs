@@ -23,6 +23,12 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Database error: {0}")]
     DbError(String),
 
@@ -44,6 +50,8 @@ impl IntoResponse for AppError {
             AppError::TokenError(msg) => (StatusCode::UNAUTHORIZED, "token_error", msg.clone()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "bad_request", msg.clone()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, "forbidden", msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg.clone()),
             AppError::DbError(msg) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "db_error", msg.clone())
             }
@@ -10,9 +10,10 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use vite_rs_axum_0_8::ViteServe;
 
 use zeromqtt::api::api_routes;
-use zeromqtt::bridge::BridgeCore;
+use zeromqtt::auth::spawn_revocation_prune_task;
+use zeromqtt::bridge::{spawn_dedup_prune_task, BridgeCore};
 use zeromqtt::config::AppConfig;
-use zeromqtt::db::{init_db, Repository};
+use zeromqtt::db::{init_db, init_stats_db, spawn_retention_task, spawn_stats_flush_task, Repository};
 use zeromqtt::state::AppState;
 
 #[derive(vite_rs::Embed)]
@@ -35,8 +36,14 @@ async fn main() {
     info!("===================================");
 
     // Initialize configuration
-    let config = AppConfig::new();
-    info!("Configuration loaded");
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Invalid configuration {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Configuration loaded: {}", config.redacted());
 
     // Initialize database
     let pool = match init_db().await {
@@ -50,21 +57,48 @@ async fn main() {
         }
     };
 
-    // Create repository
-    let repo = Repository::new(pool);
+    // Create repository, optionally offloading stats writes to a second database
+    let repo = match config.database.stats_path {
+        Some(ref stats_path) => match init_stats_db(stats_path).await {
+            Ok(stats_pool) => {
+                info!("Stats database initialized at {}", stats_path);
+                Repository::with_stats_pool(pool, stats_pool)
+            }
+            Err(e) => {
+                tracing::error!("Failed to initialize stats database: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => Repository::new(pool),
+    }
+    .with_query_stats(config.database.query_stats)
+    .with_stats_flush(&config.stats)
+    .with_password_cost(config.credentials.bcrypt_cost);
+
+    // Keep history/audit/dead-letter tables bounded
+    spawn_retention_task(repo.clone(), config.retention.clone());
+    spawn_dedup_prune_task(repo.clone(), &config.bridge);
+    spawn_revocation_prune_task(repo.clone());
+    // Write counters collected between flushes once traffic stops
+    spawn_stats_flush_task(repo.clone(), &config.stats);
 
     // Create bridge core
-    let bridge = BridgeCore::new(repo.clone());
+    let bridge = BridgeCore::new(repo.clone(), config.bridge.clone()).with_zmq_settings(config.zmq.clone());
     info!("Bridge core created");
 
     // Auto-start the bridge
-    match bridge.start().await {
-        Ok(()) => info!("🔗 Bridge started successfully"),
-        Err(e) => tracing::warn!("Failed to auto-start bridge: {} (can be started manually)", e),
+    if config.bridge.autostart {
+        // Each process starts counting afresh
+        match bridge.start(false).await {
+            Ok(()) => info!("🔗 Bridge started successfully"),
+            Err(e) => tracing::warn!("Failed to auto-start bridge: {} (can be started manually)", e),
+        }
+    } else {
+        info!("Bridge autostart disabled; start it via POST /api/bridge/start");
     }
 
     // Create application state
-    let state = AppState::new(config.clone(), repo, bridge);
+    let state = AppState::new(config.clone(), repo, bridge.clone());
 
     // Start Vite dev server in development mode
     #[cfg(debug_assertions)]
@@ -88,8 +122,9 @@ async fn main() {
         .route_service("/{*path}", ViteServe::new(Assets::boxed()))
         // Add CORS middleware
         .layer(cors)
-        // Add config to request extensions for auth middleware
+        // Add config and repository to request extensions for auth middleware
         .layer(axum::Extension(state.config.clone()))
+        .layer(axum::Extension(state.repo.clone()))
         // Add application state
         .with_state(state);
 
@@ -101,6 +136,42 @@ async fn main() {
 
     let listener = TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+
+    // Stopping also writes the counters collected since the last flush
+    info!("Shutting down");
+    if let Err(e) = bridge.stop().await {
+        tracing::warn!("Failed to stop bridge: {}", e);
+    }
+}
+
+/// Resolve on Ctrl+C or SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
\ No newline at end of file
@@ -49,6 +49,7 @@ impl MockBridgeStore {
                 direction: MappingDirection::MqttToZmq,
                 enabled: true,
                 description: Some("Forward all sensor data to ZeroMQ".to_string()),
+                ..Default::default()
             },
             TopicMapping {
                 id: 2,
@@ -61,6 +62,7 @@ impl MockBridgeStore {
                 direction: MappingDirection::ZmqToMqtt,
                 enabled: true,
                 description: Some("Forward commands from ZeroMQ to MQTT".to_string()),
+                ..Default::default()
             },
             TopicMapping {
                 id: 3,
@@ -73,6 +75,7 @@ impl MockBridgeStore {
                 direction: MappingDirection::Bidirectional,
                 enabled: false,
                 description: Some("Bidirectional telemetry sync".to_string()),
+                ..Default::default()
             },
         ]
     }
@@ -89,6 +92,12 @@ impl MockBridgeStore {
             mqtt_status: ConnectionStatus::Connected,
             zmq_status: ConnectionStatus::Connected,
             version: env!("CARGO_PKG_VERSION").to_string(),
+            last_error: None,
+            degraded: false,
+            degraded_reason: None,
+            slow_consumer: false,
+            total_messages: None,
+            messages_per_second: None,
         }
     }
 
@@ -150,6 +159,29 @@ impl MockBridgeStore {
             direction: req.direction,
             enabled: req.enabled,
             description: req.description,
+            ordering_key: req.ordering_key,
+            priority: req.priority,
+            stop_on_match: req.stop_on_match,
+            target_fallback: req.target_fallback.clone(),
+            sample_every_n: req.sample_every_n,
+            sample_interval_ms: req.sample_interval_ms,
+            identity_property: req.identity_property.clone(),
+            additional_targets: req.additional_targets.clone(),
+            publish_qos: req.publish_qos,
+            fallback_topic: req.fallback_topic.clone(),
+            timestamp_source: req.timestamp_source.clone(),
+            schedule: req.schedule.clone(),
+            source_topic_carrier: req.source_topic_carrier.clone(),
+            transform_command: req.transform_command.clone(),
+            durable: req.durable,
+            payload_transform: req.payload_transform,
+            transform_error_policy: req.transform_error_policy,
+            dedup_key: req.dedup_key.clone(),
+            truncate_bytes: req.truncate_bytes,
+            max_payload_bytes: req.max_payload_bytes,
+            allow_broad_subscription: req.allow_broad_subscription,
+            qos: req.qos,
+            retain: req.retain,
         };
 
         self.mappings.write().push(mapping.clone());
@@ -180,6 +212,29 @@ impl MockBridgeStore {
             mapping.direction = req.direction;
             mapping.enabled = req.enabled;
             mapping.description = req.description;
+            mapping.ordering_key = req.ordering_key;
+            mapping.priority = req.priority;
+            mapping.stop_on_match = req.stop_on_match;
+            mapping.target_fallback = req.target_fallback.clone();
+            mapping.sample_every_n = req.sample_every_n;
+            mapping.sample_interval_ms = req.sample_interval_ms;
+            mapping.identity_property = req.identity_property.clone();
+            mapping.additional_targets = req.additional_targets.clone();
+            mapping.publish_qos = req.publish_qos;
+            mapping.fallback_topic = req.fallback_topic.clone();
+            mapping.timestamp_source = req.timestamp_source.clone();
+            mapping.schedule = req.schedule.clone();
+            mapping.source_topic_carrier = req.source_topic_carrier.clone();
+            mapping.transform_command = req.transform_command.clone();
+            mapping.durable = req.durable;
+            mapping.payload_transform = req.payload_transform;
+            mapping.transform_error_policy = req.transform_error_policy;
+            mapping.dedup_key = req.dedup_key.clone();
+            mapping.truncate_bytes = req.truncate_bytes;
+            mapping.max_payload_bytes = req.max_payload_bytes;
+            mapping.allow_broad_subscription = req.allow_broad_subscription;
+            mapping.qos = req.qos;
+            mapping.retain = req.retain;
             Some(mapping.clone())
         } else {
             None
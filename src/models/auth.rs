@@ -9,12 +9,50 @@ pub struct LoginRequest {
     pub password: String,
 }
 
-/// Login response with JWT token
+/// Login response with an access token and the refresh token that renews it
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,
+    pub refresh_expires_in: i64,
+}
+
+/// Refresh or logout request carrying a refresh token
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Access token minted from a refresh token
+#[derive(Debug, Serialize)]
+pub struct AccessTokenResponse {
+    pub token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+}
+
+/// Kind of JWT
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    /// Short-lived token for API requests
+    #[default]
+    Access,
+    /// Long-lived token that only mints access tokens
+    Refresh,
+}
+
+/// What a user may do through the API
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserRole {
+    /// Read and change configuration, control the bridge and manage users
+    Admin,
+    /// Read-only access
+    #[default]
+    Viewer,
 }
 
 /// JWT Claims structure
@@ -26,18 +64,29 @@ pub struct Claims {
     pub exp: i64,
     /// Issued at timestamp
     pub iat: i64,
+    /// Token type; tokens without one are access tokens
+    #[serde(default)]
+    pub typ: TokenType,
+    /// Token id, recorded when the token is revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// Role of the user when the token was issued; tokens without one are viewers
+    #[serde(default)]
+    pub role: UserRole,
 }
 
 /// User information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub username: String,
+    pub role: UserRole,
 }
 
 /// Current user response
 #[derive(Debug, Serialize)]
 pub struct MeResponse {
     pub username: String,
+    pub role: UserRole,
 }
 
 // ============ User Management Types ============
@@ -49,6 +98,7 @@ pub struct UserRecord {
     pub username: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
+    pub role: UserRole,
     pub is_default: bool,
     pub created_at: i64,
     pub updated_at: i64,
@@ -59,6 +109,7 @@ pub struct UserRecord {
 pub struct UserResponse {
     pub id: u32,
     pub username: String,
+    pub role: UserRole,
     pub is_default: bool,
     pub created_at: i64,
     pub updated_at: i64,
@@ -69,6 +120,7 @@ impl From<UserRecord> for UserResponse {
         UserResponse {
             id: user.id,
             username: user.username,
+            role: user.role,
             is_default: user.is_default,
             created_at: user.created_at,
             updated_at: user.updated_at,
@@ -81,12 +133,17 @@ impl From<UserRecord> for UserResponse {
 pub struct CreateUserRequest {
     pub username: String,
     pub password: String,
+    /// Defaults to `viewer`
+    #[serde(default)]
+    pub role: UserRole,
 }
 
-/// Update user request (username only)
+/// Update user request: username and, optionally, role
 #[derive(Debug, Deserialize)]
 pub struct UpdateUserRequest {
     pub username: String,
+    #[serde(default)]
+    pub role: Option<UserRole>,
 }
 
 /// Change password request
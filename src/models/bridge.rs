@@ -1,5 +1,6 @@
 //! Bridge related models
 
+use crate::config::REDACTED;
 use serde::{Deserialize, Serialize};
 
 /// Bridge running status
@@ -10,6 +11,8 @@ pub enum BridgeState {
     Stopped,
     Error,
     Connecting,
+    /// Running, but recording what would be published instead of publishing it
+    DryRun,
 }
 
 /// Connection status for MQTT or ZeroMQ
@@ -20,6 +23,8 @@ pub enum ConnectionStatus {
     Disconnected,
     Connecting,
     Error,
+    /// Reconnects held off by the reconnect storm limiter
+    Throttled,
 }
 
 /// Overall bridge status
@@ -30,10 +35,27 @@ pub struct BridgeStatus {
     pub mqtt_status: ConnectionStatus,
     pub zmq_status: ConnectionStatus,
     pub version: String,
+    /// Why the last start attempt failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Database writes are failing; forwarding continues with in-memory stats
+    #[serde(default)]
+    pub degraded: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub degraded_reason: Option<String>,
+    /// Forwarding has stayed behind its input beyond `bridge.slow_consumer_secs`
+    #[serde(default)]
+    pub slow_consumer: bool,
+    /// Messages received and sent, as in `/api/status/stats`, with
+    /// `bridge.status_throughput`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_messages: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages_per_second: Option<f64>,
 }
 
 /// MQTT connection configuration - supports multiple brokers
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MqttConfig {
     pub id: Option<u32>,
     pub name: String,              // Broker name: "Primary", "Backup", etc.
@@ -45,7 +67,70 @@ pub struct MqttConfig {
     pub password: Option<String>,
     pub use_tls: bool,
     pub keep_alive_seconds: u16,
+    /// MQTT 3.1.1 session handling; v5 connections use `clean_start` and
+    /// `session_expiry_secs` instead
     pub clean_session: bool,
+    /// MQTT v5: discard any session the broker kept for this client on connect
+    #[serde(default = "default_clean_start")]
+    pub clean_start: bool,
+    /// MQTT v5: how long the broker keeps the session after a disconnect;
+    /// 0 ends it with the connection
+    #[serde(default)]
+    pub session_expiry_secs: u32,
+    /// Give up a connect attempt after this many seconds
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u16,
+    /// Rules normalizing received topics before mappings are matched
+    #[serde(default)]
+    pub topic_rewrites: Vec<TopicRewrite>,
+    /// Whether `topic_rewrites` apply to received topics, target topics or both
+    #[serde(default)]
+    pub rewrite_stage: RewriteStage,
+    /// Last-will topic published by the broker if the bridge drops
+    #[serde(default)]
+    pub will_topic: Option<String>,
+    #[serde(default)]
+    pub will_payload: Option<String>,
+    /// Birth topic published on every successful (re)connect
+    #[serde(default)]
+    pub birth_topic: Option<String>,
+    #[serde(default)]
+    pub birth_payload: Option<String>,
+    /// Replace long topics with MQTT v5 topic aliases on outbound publishes
+    #[serde(default)]
+    pub topic_aliases: bool,
+    /// Most topic filters subscribed on this broker; unset is no limit
+    #[serde(default)]
+    pub max_subscriptions: Option<u32>,
+    /// MQTT protocol version to connect with
+    #[serde(default)]
+    pub protocol_version: MqttProtocolVersion,
+    /// CA bundle (PEM) to verify the broker against instead of the system store
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// Client certificate (PEM) for brokers requiring mutual TLS
+    #[serde(default)]
+    pub client_cert_path: Option<String>,
+    /// Private key (PEM) of the client certificate
+    #[serde(default)]
+    pub client_key_path: Option<String>,
+    /// Accept any broker certificate; only for self-signed development brokers
+    #[serde(default)]
+    pub tls_insecure_skip_verify: bool,
+    /// Which side of mappings the broker may be on
+    #[serde(default)]
+    pub endpoint_role: EndpointRole,
+}
+
+/// MQTT protocol version of a broker connection
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttProtocolVersion {
+    /// MQTT 3.1.1; features needing v5 still switch the connection to v5
+    #[default]
+    V311,
+    /// MQTT 5, falling back to 3.1.1 if the broker rejects it
+    V5,
 }
 
 impl Default for MqttConfig {
@@ -62,12 +147,91 @@ impl Default for MqttConfig {
             use_tls: false,
             keep_alive_seconds: 60,
             clean_session: true,
+            clean_start: true,
+            session_expiry_secs: 0,
+            connect_timeout_secs: default_connect_timeout_secs(),
+            topic_rewrites: Vec::new(),
+            rewrite_stage: RewriteStage::default(),
+            will_topic: None,
+            will_payload: None,
+            birth_topic: None,
+            birth_payload: None,
+            topic_aliases: false,
+            max_subscriptions: None,
+            protocol_version: MqttProtocolVersion::default(),
+            ca_cert_path: None,
+            client_cert_path: None,
+            client_key_path: None,
+            tls_insecure_skip_verify: false,
+            endpoint_role: EndpointRole::Both,
         }
     }
 }
 
+/// Rewrite of a topic received from an endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TopicRewrite {
+    /// Replace a leading `from` with `to`
+    Prefix { from: String, to: String },
+    /// Rewrite topics matching the filter `from`; `+` and `#` in `to` take the
+    /// levels matched by the wildcards of `from`
+    Pattern { from: String, to: String },
+}
+
+/// Where an endpoint's `topic_rewrites` apply in the forwarding pipeline:
+/// ingress rewrites, mapping match, mapping transforms, egress rewrites
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RewriteStage {
+    /// Topics received from the endpoint, before mappings are matched
+    #[default]
+    Ingress,
+    /// Target topics of messages sent to the endpoint, after mapping transforms
+    Egress,
+    /// Both received and target topics
+    Both,
+}
+
+impl RewriteStage {
+    pub fn ingress(self) -> bool {
+        matches!(self, Self::Ingress | Self::Both)
+    }
+
+    pub fn egress(self) -> bool {
+        matches!(self, Self::Egress | Self::Both)
+    }
+}
+
+impl MqttConfig {
+    /// Whether reconnecting resumes the session the broker kept, with its
+    /// subscriptions and the messages queued meanwhile
+    pub fn resumes_session(&self, v5: bool) -> bool {
+        if v5 {
+            !self.clean_start && self.session_expiry_secs > 0
+        } else {
+            !self.clean_session
+        }
+    }
+
+    /// Replace the broker password with `***`, for users who may not read it
+    pub fn redact_secrets(&mut self) {
+        if self.password.is_some() {
+            self.password = Some(REDACTED.to_string());
+        }
+    }
+}
+
+fn default_connect_timeout_secs() -> u16 {
+    10
+}
+
+fn default_clean_start() -> bool {
+    true
+}
+
 /// Request to create/update MQTT config
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateMqttConfigRequest {
     pub name: String,
     pub enabled: bool,
@@ -79,6 +243,40 @@ pub struct CreateMqttConfigRequest {
     pub use_tls: bool,
     pub keep_alive_seconds: u16,
     pub clean_session: bool,
+    #[serde(default = "default_clean_start")]
+    pub clean_start: bool,
+    #[serde(default)]
+    pub session_expiry_secs: u32,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u16,
+    #[serde(default)]
+    pub topic_rewrites: Vec<TopicRewrite>,
+    #[serde(default)]
+    pub rewrite_stage: RewriteStage,
+    #[serde(default)]
+    pub will_topic: Option<String>,
+    #[serde(default)]
+    pub will_payload: Option<String>,
+    #[serde(default)]
+    pub birth_topic: Option<String>,
+    #[serde(default)]
+    pub birth_payload: Option<String>,
+    #[serde(default)]
+    pub topic_aliases: bool,
+    #[serde(default)]
+    pub max_subscriptions: Option<u32>,
+    #[serde(default)]
+    pub protocol_version: MqttProtocolVersion,
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    #[serde(default)]
+    pub client_cert_path: Option<String>,
+    #[serde(default)]
+    pub client_key_path: Option<String>,
+    #[serde(default)]
+    pub tls_insecure_skip_verify: bool,
+    #[serde(default)]
+    pub endpoint_role: EndpointRole,
 }
 
 /// ZeroMQ socket type for XPUB/XSUB proxy pattern
@@ -94,10 +292,14 @@ pub enum ZmqSocketType {
     Pub,
     /// Standard SUB socket - connects to XPUB
     Sub,
+    /// PUSH socket - sends payloads round-robin to connected PULLs, without topics
+    Push,
+    /// PULL socket - receives payloads from PUSHes; messages have an empty topic
+    Pull,
 }
 
 /// ZeroMQ connection configuration - supports XPUB/XSUB proxy pattern
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ZmqConfig {
     pub id: Option<u32>,
     pub name: String,                       // Config name: "Proxy", "Publisher", etc.
@@ -107,6 +309,89 @@ pub struct ZmqConfig {
     pub connect_endpoints: Vec<String>,     // For PUB/SUB: connect addresses
     pub high_water_mark: u32,
     pub reconnect_interval_ms: u32,
+    /// Shared key for HMAC-SHA256 frame signing; unset disables signing
+    #[serde(default)]
+    pub hmac_key: Option<String>,
+    /// Rules normalizing received topics before mappings are matched
+    #[serde(default)]
+    pub topic_rewrites: Vec<TopicRewrite>,
+    /// Whether `topic_rewrites` apply to received topics, target topics or both
+    #[serde(default)]
+    pub rewrite_stage: RewriteStage,
+    /// Layout of topic and payload in messages
+    #[serde(default)]
+    pub topic_separator: TopicSeparator,
+    /// Z85 public key of the CURVE server; setting it enables CURVE
+    #[serde(default)]
+    pub curve_server_key: Option<String>,
+    /// Z85 public key of this endpoint, used when connecting
+    #[serde(default)]
+    pub curve_public_key: Option<String>,
+    /// Z85 secret key of this endpoint
+    #[serde(default)]
+    pub curve_secret_key: Option<String>,
+    /// Whether a PUB/XPUB endpoint binds or connects to a peer that owns the bind
+    #[serde(default)]
+    pub role: ZmqRole,
+    /// Send rate limit of PGM endpoints in kilobits per second; unset keeps the libzmq default
+    #[serde(default)]
+    pub multicast_rate_kbps: Option<u32>,
+    /// Router hops multicast packets may cross; unset keeps the libzmq default of 1
+    #[serde(default)]
+    pub multicast_hops: Option<u32>,
+    /// How long PGM keeps sent data for receivers to recover lost packets
+    #[serde(default)]
+    pub multicast_recovery_ms: Option<u32>,
+    /// Re-send the subscription of a SUB endpoint when it reconnects to a publisher
+    #[serde(default)]
+    pub resubscribe_on_reconnect: bool,
+    /// After a SUB endpoint reconnects, how long it is reported as rejoining,
+    /// the window in which a restarted publisher's messages may be missed
+    #[serde(default)]
+    pub rejoin_warmup_ms: u32,
+    /// Messages a publishing endpoint collects before sending them in one
+    /// burst; 0 or 1 sends each message as it arrives
+    #[serde(default)]
+    pub batch_max_messages: u32,
+    /// Longest a message waits for its batch to fill
+    #[serde(default = "default_batch_max_delay_ms")]
+    pub batch_max_delay_ms: u32,
+    /// Which side of mappings the endpoint may be on
+    #[serde(default)]
+    pub endpoint_role: EndpointRole,
+}
+
+fn default_batch_max_delay_ms() -> u32 {
+    5
+}
+
+/// How a publishing ZMQ endpoint attaches to its peers
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ZmqRole {
+    /// Bind `bind_endpoint` for subscribers to connect to
+    #[default]
+    Bind,
+    /// Connect to `connect_endpoints`, e.g. a proxy or subscriber that binds
+    Connect,
+}
+
+/// How topic and payload are laid out in a ZeroMQ message
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TopicSeparator {
+    /// One frame: topic, the delimiter byte, payload
+    Delimiter { byte: u8 },
+    /// One frame: topic length as a 4-byte big-endian integer, topic, payload
+    LengthPrefixed,
+    /// Topic and payload in two frames
+    Multipart,
+}
+
+impl Default for TopicSeparator {
+    fn default() -> Self {
+        Self::Delimiter { byte: b' ' }
+    }
 }
 
 impl Default for ZmqConfig {
@@ -120,12 +405,38 @@ impl Default for ZmqConfig {
             connect_endpoints: vec![],
             high_water_mark: 1000,
             reconnect_interval_ms: 1000,
+            hmac_key: None,
+            topic_rewrites: Vec::new(),
+            rewrite_stage: RewriteStage::default(),
+            topic_separator: TopicSeparator::default(),
+            curve_server_key: None,
+            curve_public_key: None,
+            curve_secret_key: None,
+            role: ZmqRole::Bind,
+            multicast_rate_kbps: None,
+            multicast_hops: None,
+            multicast_recovery_ms: None,
+            resubscribe_on_reconnect: false,
+            rejoin_warmup_ms: 0,
+            batch_max_messages: 0,
+            batch_max_delay_ms: default_batch_max_delay_ms(),
+            endpoint_role: EndpointRole::Both,
+        }
+    }
+}
+
+impl ZmqConfig {
+    /// Replace the HMAC key and CURVE secret key with `***`, for users who
+    /// may not read them
+    pub fn redact_secrets(&mut self) {
+        for secret in [&mut self.hmac_key, &mut self.curve_secret_key].into_iter().flatten() {
+            *secret = REDACTED.to_string();
         }
     }
 }
 
 /// Request to create/update ZMQ config
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateZmqConfigRequest {
     pub name: String,
     pub enabled: bool,
@@ -134,16 +445,91 @@ pub struct CreateZmqConfigRequest {
     pub connect_endpoints: Vec<String>,
     pub high_water_mark: u32,
     pub reconnect_interval_ms: u32,
+    #[serde(default)]
+    pub hmac_key: Option<String>,
+    #[serde(default)]
+    pub topic_rewrites: Vec<TopicRewrite>,
+    #[serde(default)]
+    pub rewrite_stage: RewriteStage,
+    #[serde(default)]
+    pub topic_separator: TopicSeparator,
+    #[serde(default)]
+    pub curve_server_key: Option<String>,
+    #[serde(default)]
+    pub curve_public_key: Option<String>,
+    #[serde(default)]
+    pub curve_secret_key: Option<String>,
+    #[serde(default)]
+    pub role: ZmqRole,
+    #[serde(default)]
+    pub multicast_rate_kbps: Option<u32>,
+    #[serde(default)]
+    pub multicast_hops: Option<u32>,
+    #[serde(default)]
+    pub multicast_recovery_ms: Option<u32>,
+    #[serde(default)]
+    pub resubscribe_on_reconnect: bool,
+    #[serde(default)]
+    pub rejoin_warmup_ms: u32,
+    #[serde(default)]
+    pub batch_max_messages: u32,
+    #[serde(default = "default_batch_max_delay_ms")]
+    pub batch_max_delay_ms: u32,
+    #[serde(default)]
+    pub endpoint_role: EndpointRole,
+}
+
+/// MQTT config as resolved for the worker, with internally fixed settings
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveMqttConfig {
+    #[serde(flatten)]
+    pub config: MqttConfig,
+    pub server_uri: String,
+    pub qos: i32,
+    pub reconnect_min_seconds: u64,
+    pub reconnect_max_seconds: u64,
+}
+
+/// ZMQ config as resolved for the worker
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveZmqConfig {
+    #[serde(flatten)]
+    pub config: ZmqConfig,
+    pub signing_enabled: bool,
+    pub curve_enabled: bool,
 }
 
 /// Endpoint type for topic mapping
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum EndpointType {
     Mqtt,
     Zmq,
 }
 
+/// Which side of mappings an endpoint may be on, guarding e.g. a publish-only
+/// broker against being subscribed to
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointRole {
+    /// Only mapping sources: messages are received, never delivered
+    SourceOnly,
+    /// Only mapping targets: messages are delivered, never received
+    SinkOnly,
+    #[default]
+    Both,
+}
+
+impl EndpointRole {
+    pub fn allows_source(self) -> bool {
+        self != Self::SinkOnly
+    }
+
+    pub fn allows_sink(self) -> bool {
+        self != Self::SourceOnly
+    }
+}
+
 /// Topic mapping direction - now supports intra-protocol forwarding
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -156,7 +542,7 @@ pub enum MappingDirection {
 }
 
 /// Topic mapping rule - enhanced with endpoint references
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TopicMapping {
     pub id: u32,
     pub source_endpoint_type: EndpointType,
@@ -168,10 +554,155 @@ pub struct TopicMapping {
     pub direction: MappingDirection,
     pub enabled: bool,
     pub description: Option<String>,
+    /// Ordering key spec (`topic:N` or `json:path`); messages sharing a key stay ordered
+    #[serde(default)]
+    pub ordering_key: Option<String>,
+    /// Evaluation priority (higher first) under the `priority` strategy
+    #[serde(default)]
+    pub priority: i32,
+    /// Skip remaining mappings once this one matches
+    #[serde(default)]
+    pub stop_on_match: bool,
+    /// Value substituted for missing `${...}` target topic fields; drop the message if unset
+    #[serde(default)]
+    pub target_fallback: Option<String>,
+    /// Forward only every Nth matching message
+    #[serde(default)]
+    pub sample_every_n: Option<u32>,
+    /// Forward only the first matching message per interval
+    #[serde(default)]
+    pub sample_interval_ms: Option<u64>,
+    /// MQTT v5 user property that carries the ZMQ routing identity; unset drops identities
+    #[serde(default)]
+    pub identity_property: Option<String>,
+    /// Extra targets each matched message is also delivered to
+    #[serde(default)]
+    pub additional_targets: Vec<MappingTarget>,
+    /// QoS for publishes to MQTT targets, overriding `qos`
+    #[serde(default)]
+    pub publish_qos: Option<i32>,
+    /// Topic used when the computed target topic is empty or invalid
+    #[serde(default)]
+    pub fallback_topic: Option<String>,
+    /// Producer timestamp for end-to-end latency: `json:path` or `property:name`
+    #[serde(default)]
+    pub timestamp_source: Option<String>,
+    /// Time windows outside which the mapping does not forward, e.g. `Mon-Fri 09:00-17:00 +01:00`
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Carries the source ZMQ topic into MQTT publishes: `property:name` or `json:field`
+    #[serde(default)]
+    pub source_topic_carrier: Option<String>,
+    /// External command the payload is piped through (`bridge.exec_transforms`)
+    #[serde(default)]
+    pub transform_command: Option<String>,
+    /// Log matched messages to the write-ahead log until they are delivered
+    #[serde(default)]
+    pub durable: bool,
+    /// Re-encode payloads between JSON and MessagePack
+    #[serde(default)]
+    pub payload_transform: Option<PayloadTransform>,
+    /// MQTT QoS for subscribing to the source topic and publishing to targets
+    #[serde(default = "default_mapping_qos")]
+    pub qos: u8,
+    /// Publish to MQTT targets as retained, so late subscribers get the last value
+    #[serde(default)]
+    pub retain: bool,
+    /// What happens to a message whose transform or payload conversion fails
+    #[serde(default)]
+    pub transform_error_policy: TransformErrorPolicy,
+    /// Drop messages whose key this mapping already forwarded within
+    /// `bridge.dedup_ttl_secs`, remembered across restarts: `hash` of topic
+    /// and payload, or `json:path` for an id in the payload
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    /// Forward at most this many leading payload bytes; the rest is discarded
+    #[serde(default)]
+    pub truncate_bytes: Option<u32>,
+    /// Drop messages with a larger payload, below `bridge.max_payload_bytes`
+    #[serde(default)]
+    pub max_payload_bytes: Option<u32>,
+    /// Confirm that a source topic starting with a wildcard level, which
+    /// subscribes to everything on the broker, is intended
+    #[serde(default)]
+    pub allow_broad_subscription: bool,
+}
+
+fn default_mapping_qos() -> u8 {
+    1
+}
+
+/// Conversion of a payload's representation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadTransform {
+    JsonToMsgpack,
+    MsgpackToJson,
+}
+
+/// Handling of a message whose transform step failed
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformErrorPolicy {
+    /// Drop the message
+    #[default]
+    Drop,
+    /// Forward the payload as it was before the failed step
+    PassThrough,
+    /// Store the original message in the dead-letter table
+    DeadLetter,
+}
+
+/// A fan-out target of a topic mapping
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MappingTarget {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub target_topic: String,
+}
+
+impl Default for TopicMapping {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            source_endpoint_type: EndpointType::Mqtt,
+            source_endpoint_id: 1,
+            target_endpoint_type: EndpointType::Zmq,
+            target_endpoint_id: 1,
+            source_topic: String::new(),
+            target_topic: String::new(),
+            direction: MappingDirection::MqttToZmq,
+            enabled: true,
+            description: None,
+            ordering_key: None,
+            priority: 0,
+            stop_on_match: false,
+            target_fallback: None,
+            sample_every_n: None,
+            sample_interval_ms: None,
+            identity_property: None,
+            additional_targets: Vec::new(),
+            publish_qos: None,
+            fallback_topic: None,
+            timestamp_source: None,
+            schedule: None,
+            source_topic_carrier: None,
+            transform_command: None,
+            durable: false,
+            payload_transform: None,
+            qos: default_mapping_qos(),
+            retain: false,
+            transform_error_policy: TransformErrorPolicy::Drop,
+            dedup_key: None,
+            truncate_bytes: None,
+            max_payload_bytes: None,
+            allow_broad_subscription: false,
+        }
+    }
 }
 
 /// Request to create a new topic mapping
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CreateMappingRequest {
     pub source_endpoint_type: EndpointType,
     pub source_endpoint_id: u32,
@@ -182,6 +713,241 @@ pub struct CreateMappingRequest {
     pub direction: MappingDirection,
     pub enabled: bool,
     pub description: Option<String>,
+    #[serde(default)]
+    pub ordering_key: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub stop_on_match: bool,
+    #[serde(default)]
+    pub target_fallback: Option<String>,
+    #[serde(default)]
+    pub sample_every_n: Option<u32>,
+    #[serde(default)]
+    pub sample_interval_ms: Option<u64>,
+    #[serde(default)]
+    pub identity_property: Option<String>,
+    #[serde(default)]
+    pub additional_targets: Vec<MappingTarget>,
+    #[serde(default)]
+    pub publish_qos: Option<i32>,
+    #[serde(default)]
+    pub fallback_topic: Option<String>,
+    #[serde(default)]
+    pub timestamp_source: Option<String>,
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default)]
+    pub source_topic_carrier: Option<String>,
+    #[serde(default)]
+    pub transform_command: Option<String>,
+    #[serde(default)]
+    pub durable: bool,
+    #[serde(default)]
+    pub payload_transform: Option<PayloadTransform>,
+    #[serde(default = "default_mapping_qos")]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub transform_error_policy: TransformErrorPolicy,
+    #[serde(default)]
+    pub dedup_key: Option<String>,
+    #[serde(default)]
+    pub truncate_bytes: Option<u32>,
+    #[serde(default)]
+    pub max_payload_bytes: Option<u32>,
+    #[serde(default)]
+    pub allow_broad_subscription: bool,
+}
+
+impl CreateMappingRequest {
+    /// The first endpoint the mapping reads from or delivers to that is not
+    /// among `mqtt_ids` or `zmq_ids`, described for an error message
+    pub fn missing_endpoint(
+        &self,
+        mqtt_ids: &std::collections::HashSet<u32>,
+        zmq_ids: &std::collections::HashSet<u32>,
+    ) -> Option<String> {
+        let endpoints = [
+            ("source", &self.source_endpoint_type, self.source_endpoint_id),
+            ("target", &self.target_endpoint_type, self.target_endpoint_id),
+        ]
+        .into_iter()
+        .chain(
+            self.additional_targets
+                .iter()
+                .map(|t| ("additional target", &t.endpoint_type, t.endpoint_id)),
+        );
+        for (role, endpoint_type, id) in endpoints {
+            let (known, kind) = match endpoint_type {
+                EndpointType::Mqtt => (mqtt_ids.contains(&id), "MQTT"),
+                EndpointType::Zmq => (zmq_ids.contains(&id), "ZMQ"),
+            };
+            if !known {
+                return Some(format!("The {} {} endpoint {} does not exist", role, kind, id));
+            }
+        }
+        None
+    }
+
+    /// The first endpoint the mapping uses against its `endpoint_role`,
+    /// described for an error message. A bidirectional mapping reads from and
+    /// delivers to both of its endpoints; endpoints missing from the maps are
+    /// left to `missing_endpoint`.
+    pub fn misused_endpoint(
+        &self,
+        mqtt_roles: &std::collections::HashMap<u32, EndpointRole>,
+        zmq_roles: &std::collections::HashMap<u32, EndpointRole>,
+    ) -> Option<String> {
+        let both_ways = self.direction == MappingDirection::Bidirectional;
+        // (use, type, id, read from, delivered to)
+        let endpoints = [
+            ("source", &self.source_endpoint_type, self.source_endpoint_id, true, both_ways),
+            ("target", &self.target_endpoint_type, self.target_endpoint_id, both_ways, true),
+        ]
+        .into_iter()
+        .chain(
+            self.additional_targets
+                .iter()
+                .map(|t| ("additional target", &t.endpoint_type, t.endpoint_id, false, true)),
+        );
+        for (usage, endpoint_type, id, read, delivered) in endpoints {
+            let (role, kind) = match endpoint_type {
+                EndpointType::Mqtt => (mqtt_roles.get(&id), "MQTT"),
+                EndpointType::Zmq => (zmq_roles.get(&id), "ZMQ"),
+            };
+            let Some(&role) = role else { continue };
+            if read && !role.allows_source() {
+                return Some(format!(
+                    "The {} {} endpoint {} is sink_only and cannot be a mapping source",
+                    usage, kind, id
+                ));
+            }
+            if delivered && !role.allows_sink() {
+                return Some(format!(
+                    "The {} {} endpoint {} is source_only and cannot be a mapping target",
+                    usage, kind, id
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Ids that differ between the running bridge and the database
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EntityDiff {
+    /// Present in the database but not running
+    pub added: Vec<u32>,
+    /// Running but no longer in the database
+    pub removed: Vec<u32>,
+    /// Running with different settings than the database
+    pub changed: Vec<u32>,
+}
+
+impl EntityDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Running vs persisted configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigDiff {
+    pub bridge_running: bool,
+    pub in_sync: bool,
+    pub mqtt: EntityDiff,
+    pub zmq: EntityDiff,
+    pub mappings: EntityDiff,
+}
+
+/// Kind of config a staged change writes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingEntity {
+    Mqtt,
+    Zmq,
+    Mapping,
+}
+
+/// What a staged change does to its config
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// A config mutation staged for `/api/config/apply`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PendingChange {
+    pub id: u32,
+    pub entity: PendingEntity,
+    pub action: PendingAction,
+    /// Config updated or deleted, or the id an imported create keeps; unset
+    /// for other creates
+    pub target_id: Option<u32>,
+    /// Current value of the target, filled in when pending changes are listed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<serde_json::Value>,
+    /// Request body the change applies; unset for deletes
+    pub proposed: Option<serde_json::Value>,
+    /// Unix seconds when the change was staged
+    pub created_at: i64,
+}
+
+/// Every config record, as served by `/api/config/export` and accepted by
+/// `/api/config/import`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigExport {
+    /// Reminder that the export holds credentials; ignored on import
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(default)]
+    pub mqtt: Vec<MqttConfig>,
+    #[serde(default)]
+    pub zmq: Vec<ZmqConfig>,
+    #[serde(default)]
+    pub mappings: Vec<TopicMapping>,
+}
+
+/// How `/api/config/import` treats records missing from the import
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Keep them; imported records with an id already in use are rejected
+    #[default]
+    Merge,
+    /// Delete them, leaving exactly the imported records
+    Replace,
+}
+
+/// Outcome of one record of `/api/config/import`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ImportItemResult {
+    pub entity: PendingEntity,
+    /// Id of the record in the imported document
+    pub id: Option<u32>,
+    /// Name of an endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Id the record is stored under; unset if it was not imported
+    pub stored_id: Option<u32>,
+    /// Why the record was not imported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportItemResult {
+    pub fn imported(entity: PendingEntity, id: Option<u32>, name: Option<String>, stored_id: u32) -> Self {
+        Self { entity, id, name, stored_id: Some(stored_id), error: None }
+    }
+
+    pub fn failed(entity: PendingEntity, id: Option<u32>, name: Option<String>, error: String) -> Self {
+        Self { entity, id, name, stored_id: None, error: Some(error) }
+    }
 }
 
 /// Message statistics
@@ -201,8 +967,21 @@ pub struct MessageStats {
     pub avg_latency_ms: f64,
     /// Error count
     pub error_count: u64,
-    /// Queue depth
+    /// Messages waiting in the forwarding channel
     pub queue_depth: u32,
+    /// Messages dropped for exceeding their payload size limit since startup
+    #[serde(default)]
+    pub oversize_dropped: u64,
+    /// Messages dropped by `bridge.forward_overflow` since startup
+    #[serde(default)]
+    pub overflow_dropped: u64,
+}
+
+impl MessageStats {
+    /// Messages received and sent on both sides
+    pub fn total_messages(&self) -> u64 {
+        self.mqtt_received + self.mqtt_sent + self.zmq_received + self.zmq_sent
+    }
 }
 
 impl Default for MessageStats {
@@ -216,6 +995,8 @@ impl Default for MessageStats {
             avg_latency_ms: 0.0,
             error_count: 0,
             queue_depth: 0,
+            oversize_dropped: 0,
+            overflow_dropped: 0,
         }
     }
 }
@@ -233,3 +1014,188 @@ pub struct ChartData {
     pub label: String,
     pub data: Vec<TimeSeriesPoint>,
 }
+
+/// Number of rows in a database table
+#[derive(Debug, Clone, Serialize)]
+pub struct TableRowCount {
+    pub table: String,
+    pub rows: u64,
+}
+
+/// Publish QoS outcome for one mapping
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct QosStatus {
+    pub requested_qos: i32,
+    pub effective_qos: i32,
+    pub downgrades: u64,
+}
+
+/// A mapping loaded in the running bridge with its runtime state
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveMapping {
+    #[serde(flatten)]
+    pub mapping: TopicMapping,
+    /// Requested vs effective publish QoS, once something was published to MQTT
+    pub publish_qos_status: Option<QosStatus>,
+    /// Whether the mapping's schedule currently allows forwarding
+    pub schedule_active: bool,
+}
+
+/// Payload bytes an endpoint received and was sent since startup
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointBandwidth {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+
+/// Messages dry-run mode held back from one target endpoint
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WouldForwardTarget {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub messages: u64,
+}
+
+/// A message dry-run mode held back, kept as a sample of what would be published
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct WouldForward {
+    /// Unix seconds when the message was routed
+    pub timestamp: i64,
+    pub mapping_id: u32,
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    /// Topic as received, after ingress rewrites
+    pub source_topic: String,
+    pub target_topic: String,
+    pub payload_bytes: usize,
+}
+
+/// What the bridge would have published since dry-run mode was last enabled
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DryRunReport {
+    pub enabled: bool,
+    /// Unix seconds when dry-run mode was last enabled
+    pub since: Option<i64>,
+    pub would_forward: u64,
+    pub targets: Vec<WouldForwardTarget>,
+    /// Most recent held back messages, newest first
+    pub samples: Vec<WouldForward>,
+}
+
+/// A forwarding error kept for `/api/status/errors`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ForwardError {
+    /// Unix seconds when the error occurred
+    pub timestamp: i64,
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub topic: String,
+    pub reason: String,
+}
+
+/// A message set aside because a mapping could not transform it
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DeadLetter {
+    pub id: u32,
+    pub mapping_id: u32,
+    /// Endpoint the message was received from
+    pub source: EndpointType,
+    pub source_id: u32,
+    pub topic: String,
+    /// Payload as received, before any transform
+    pub payload: Vec<u8>,
+    pub reason: String,
+    /// Unix seconds when the message was set aside
+    pub created_at: i64,
+}
+
+/// A message kept for `/api/status/deadletter` because no mapping matched it
+/// or its target endpoint was missing
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetterMessage {
+    /// Unix seconds when the message was set aside
+    pub timestamp: i64,
+    pub source: EndpointType,
+    pub source_id: u32,
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub reason: String,
+}
+
+/// A `DeadLetterMessage` or stored `DeadLetter` as served by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterEntry {
+    pub timestamp: i64,
+    pub source: EndpointType,
+    pub source_id: u32,
+    /// Mapping that could not transform the message, for stored dead letters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapping_id: Option<u32>,
+    pub topic: String,
+    /// Payload bytes, base64-encoded
+    pub payload: String,
+    pub reason: String,
+}
+
+/// Outcome of the latest liveness check of an endpoint
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointHealth {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub healthy: bool,
+    /// Unix seconds of the check
+    pub checked_at: i64,
+}
+
+/// Live connection state of one endpoint of the running bridge
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EndpointConnection {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub name: String,
+    pub status: ConnectionStatus,
+    /// Unix seconds the endpoint entered `status`
+    pub since: i64,
+}
+
+/// Runtime state of one endpoint worker thread
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerThreadInfo {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    pub name: String,
+    /// False once the thread has exited, e.g. after a failed connect
+    pub alive: bool,
+    pub started_at: i64,
+    pub messages_received: u64,
+    pub messages_sent: u64,
+    /// Unix seconds of the last message handled, if any
+    pub last_activity: Option<i64>,
+    /// Resolved client id of MQTT threads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Reconnects are held off by the reconnect storm limiter
+    pub throttled: bool,
+}
+
+/// A mapping that matches a probed topic, with the targets it would produce
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingMatch {
+    pub mapping_id: u32,
+    pub source_topic: String,
+    pub targets: Vec<MatchedTarget>,
+    pub stop_on_match: bool,
+    /// An earlier match has `stop_on_match`, so this mapping would not run
+    pub shadowed: bool,
+}
+
+/// One computed target of a matching mapping
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchedTarget {
+    pub endpoint_type: EndpointType,
+    pub endpoint_id: u32,
+    /// None when the message would be dropped (unresolved template or empty topic)
+    pub target_topic: Option<String>,
+}
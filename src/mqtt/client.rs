@@ -1,8 +1,9 @@
 //! MQTT client wrapper using paho-mqtt
 
 use crate::models::MqttConfig;
+use crate::mqtt::{expand_client_id, ssl_options};
 use paho_mqtt::{
-    AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message, SslOptionsBuilder,
+    AsyncClient, ConnectOptions, ConnectOptionsBuilder, CreateOptionsBuilder, Message, Properties, PropertyCode, SslOptions,
 };
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -15,6 +16,58 @@ pub struct MqttMessage {
     pub payload: Vec<u8>,
 }
 
+/// Build the last-will message for a broker config, if one is configured
+pub fn will_message(config: &MqttConfig) -> Option<Message> {
+    let topic = config.will_topic.as_deref().filter(|t| !t.is_empty())?;
+    let payload = config.will_payload.as_deref().unwrap_or("offline");
+    Some(Message::new_retained(topic, payload, 1))
+}
+
+/// Build the birth message published on every successful (re)connect
+pub fn birth_message(config: &MqttConfig) -> Option<Message> {
+    let topic = config.birth_topic.as_deref().filter(|t| !t.is_empty())?;
+    let payload = config.birth_payload.as_deref().unwrap_or("online");
+    Some(Message::new_retained(topic, payload, 1))
+}
+
+/// Options of a bridge connection to a broker. Sessions follow `clean_session`
+/// on MQTT 3.1.1 and `clean_start` with `session_expiry_secs` on v5.
+pub fn connect_options(config: &MqttConfig, v5: bool, ssl: Option<&SslOptions>) -> Result<ConnectOptions, paho_mqtt::Error> {
+    let mut conn_opts = if v5 {
+        ConnectOptionsBuilder::new_v5()
+    } else {
+        ConnectOptionsBuilder::new()
+    };
+    conn_opts
+        .keep_alive_interval(Duration::from_secs(config.keep_alive_seconds as u64))
+        .connect_timeout(Duration::from_secs(config.connect_timeout_secs as u64));
+    if v5 {
+        conn_opts.clean_start(config.clean_start);
+        if config.session_expiry_secs > 0 {
+            let mut props = Properties::new();
+            // The expiry is a four byte integer; u32::MAX never expires
+            props.push_int(PropertyCode::SessionExpiryInterval, config.session_expiry_secs as i32)?;
+            conn_opts.properties(props);
+        }
+    } else {
+        conn_opts.clean_session(config.clean_session);
+    }
+
+    if let Some(ref username) = config.username {
+        conn_opts.user_name(username);
+    }
+    if let Some(ref password) = config.password {
+        conn_opts.password(password);
+    }
+    if let Some(will) = will_message(config) {
+        conn_opts.will_message(will);
+    }
+    if let Some(ssl) = ssl {
+        conn_opts.ssl_options(ssl.clone());
+    }
+    Ok(conn_opts.finalize())
+}
+
 /// MQTT client wrapper
 pub struct MqttClient {
     client: AsyncClient,
@@ -33,11 +86,18 @@ impl MqttClient {
 
         let create_opts = CreateOptionsBuilder::new()
             .server_uri(&server_uri)
-            .client_id(&config.client_id)
+            .client_id(expand_client_id(&config.client_id, &config.name))
             .finalize();
 
         let client = AsyncClient::new(create_opts)?;
 
+        // Fires on the initial connect and after every automatic reconnect
+        if let Some(birth) = birth_message(&config) {
+            client.set_connected_callback(move |cli| {
+                let _ = cli.publish(birth.clone());
+            });
+        }
+
         Ok(Self {
             client,
             config,
@@ -51,6 +111,7 @@ impl MqttClient {
         conn_opts
             .keep_alive_interval(Duration::from_secs(self.config.keep_alive_seconds as u64))
             .clean_session(self.config.clean_session)
+            .connect_timeout(Duration::from_secs(self.config.connect_timeout_secs as u64))
             .automatic_reconnect(Duration::from_secs(1), Duration::from_secs(30));
 
         if let Some(ref username) = self.config.username {
@@ -60,9 +121,12 @@ impl MqttClient {
             conn_opts.password(password);
         }
 
+        if let Some(will) = will_message(&self.config) {
+            conn_opts.will_message(will);
+        }
+
         if self.config.use_tls {
-            let ssl_opts = SslOptionsBuilder::new().finalize();
-            conn_opts.ssl_options(ssl_opts);
+            conn_opts.ssl_options(ssl_options(&self.config)?);
         }
 
         let conn_opts = conn_opts.finalize();
@@ -164,3 +228,28 @@ impl StreamExt for paho_mqtt::AsyncReceiver<Option<Message>> {
         self.recv().await.ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_will_and_birth_messages() {
+        let mut config = MqttConfig::default();
+        assert!(will_message(&config).is_none());
+        assert!(birth_message(&config).is_none());
+
+        config.will_topic = Some("bridge/status".to_string());
+        config.birth_topic = Some("bridge/status".to_string());
+        config.birth_payload = Some("up".to_string());
+
+        let will = will_message(&config).unwrap();
+        assert_eq!(will.topic(), "bridge/status");
+        assert_eq!(will.payload_str(), "offline");
+        assert!(will.retained());
+
+        let birth = birth_message(&config).unwrap();
+        assert_eq!(birth.payload_str(), "up");
+        assert!(birth.retained());
+    }
+}
@@ -0,0 +1,115 @@
+//! Placeholder expansion for MQTT client ids
+//!
+//! Supported placeholders: `{hostname}`, `{pid}`, `{name}` (broker config
+//! name) and `{random}` (8 hex characters, fresh on every connect).
+
+use rand::Rng;
+
+const PLACEHOLDERS: [&str; 4] = ["hostname", "pid", "name", "random"];
+
+/// Reject `{...}` placeholders that are not supported
+pub fn validate_client_id(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(format!("Unclosed placeholder in client_id '{}'", template));
+        };
+        let name = &rest[start + 1..start + len];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "Unknown client_id placeholder '{{{}}}': expected one of {{hostname}}, {{pid}}, {{name}}, {{random}}",
+                name
+            ));
+        }
+        rest = &rest[start + len + 1..];
+    }
+    Ok(())
+}
+
+/// Persistent sessions are keyed by client id, so an id that changes between
+/// connects (`{random}`) or restarts (`{pid}`) would orphan the queued session
+pub fn validate_persistent_client_id(template: &str, clean_session: bool) -> Result<(), String> {
+    if clean_session {
+        return Ok(());
+    }
+    if template.trim().is_empty() {
+        return Err("a persistent session requires a client_id".to_string());
+    }
+    if template.contains("{random}") || template.contains("{pid}") {
+        return Err(format!(
+            "client_id '{}' changes across restarts; a persistent session needs a stable id without {{random}} or {{pid}}",
+            template
+        ));
+    }
+    Ok(())
+}
+
+/// Expand the placeholders of a client id for the broker config `name`
+pub fn expand_client_id(template: &str, name: &str) -> String {
+    if !template.contains('{') {
+        return template.to_string();
+    }
+    let random = format!("{:08x}", rand::thread_rng().gen_range(0..=u32::MAX));
+    expand_with(template, &hostname(), std::process::id(), name, &random)
+}
+
+fn expand_with(template: &str, hostname: &str, pid: u32, name: &str, random: &str) -> String {
+    template
+        .replace("{hostname}", hostname)
+        .replace("{pid}", &pid.to_string())
+        .replace("{name}", name)
+        .replace("{random}", random)
+}
+
+/// Host name from the environment, falling back to /etc/hostname
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        assert_eq!(
+            expand_with("bridge-{hostname}-{pid}-{name}", "edge1", 42, "plant", "0"),
+            "bridge-edge1-42-plant"
+        );
+        assert_eq!(expand_with("{name}-{random}", "h", 1, "b", "deadbeef"), "b-deadbeef");
+    }
+
+    #[test]
+    fn test_persistent_session_needs_stable_id() {
+        assert!(validate_persistent_client_id("bridge-{hostname}-{name}", false).is_ok());
+        assert!(validate_persistent_client_id("bridge-{random}", false).is_err());
+        assert!(validate_persistent_client_id("bridge-{pid}", false).is_err());
+        assert!(validate_persistent_client_id("", false).is_err());
+        assert!(validate_persistent_client_id("bridge-{random}", true).is_ok());
+    }
+
+    #[test]
+    fn test_literal_id_unchanged() {
+        assert_eq!(expand_client_id("zeromqtt-bridge", "plant"), "zeromqtt-bridge");
+    }
+
+    #[test]
+    fn test_random_differs_per_expansion() {
+        let a = expand_client_id("id-{random}", "b");
+        assert_eq!(a.len(), "id-".len() + 8);
+        assert_ne!(a, expand_client_id("id-{random}", "b"));
+    }
+
+    #[test]
+    fn test_validate_client_id() {
+        assert!(validate_client_id("bridge-{hostname}-{pid}").is_ok());
+        assert!(validate_client_id("plain").is_ok());
+        assert!(validate_client_id("bridge-{host}").is_err());
+        assert!(validate_client_id("bridge-{pid").is_err());
+    }
+}
@@ -1,5 +1,13 @@
 //! MQTT client module
 
 pub mod client;
+pub mod client_id;
+pub mod properties;
+pub mod tls;
+pub mod topic_alias;
 
 pub use client::*;
+pub use client_id::*;
+pub use properties::*;
+pub use tls::*;
+pub use topic_alias::*;
@@ -0,0 +1,75 @@
+//! MQTT v5 user properties carrying ZeroMQ routing identities and topics
+//!
+//! User property values are UTF-8 strings, so identities are hex encoded.
+
+use paho_mqtt::{Properties, PropertyCode};
+
+/// Hex encode identity bytes for use as a property value
+pub fn encode_identity(identity: &[u8]) -> String {
+    identity.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode a hex identity property value
+pub fn decode_identity(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 != 0 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Build publish properties holding an identity under `name`
+pub fn identity_properties(name: &str, identity: &[u8]) -> Properties {
+    let mut props = Properties::new();
+    let _ = props.push_string_pair(PropertyCode::UserProperty, name, &encode_identity(identity));
+    props
+}
+
+/// Build publish properties from name/value pairs
+pub fn string_properties(pairs: &[(String, String)]) -> Properties {
+    let mut props = Properties::new();
+    for (name, value) in pairs {
+        let _ = props.push_string_pair(PropertyCode::UserProperty, name, value);
+    }
+    props
+}
+
+/// Collect all user properties of a received message
+pub fn user_properties(props: &Properties) -> Vec<(String, String)> {
+    props.user_iter().collect()
+}
+
+/// Find and decode the identity stored under `name`
+pub fn identity_from_user_properties(props: &[(String, String)], name: &str) -> Option<Vec<u8>> {
+    props
+        .iter()
+        .find(|(key, _)| key == name)
+        .and_then(|(_, value)| decode_identity(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_hex_round_trip() {
+        let identity = vec![0x00, 0x7f, 0xff, b'a'];
+        assert_eq!(encode_identity(&identity), "007fff61");
+        assert_eq!(decode_identity("007fff61"), Some(identity));
+        assert_eq!(decode_identity("abc"), None);
+        assert_eq!(decode_identity("zz"), None);
+    }
+
+    #[test]
+    fn test_identity_properties_round_trip() {
+        let props = identity_properties("zmq-identity", b"peer-1");
+        let user = user_properties(&props);
+        assert_eq!(
+            identity_from_user_properties(&user, "zmq-identity"),
+            Some(b"peer-1".to_vec())
+        );
+        assert_eq!(identity_from_user_properties(&user, "other"), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! TLS options for MQTT broker connections
+//!
+//! A broker config may name a CA bundle to trust instead of the system
+//! store, and a client certificate and key for brokers that require mutual
+//! TLS. Paths are checked when the config is saved, so a typo surfaces as a
+//! rejected request rather than a failed connect later.
+
+use crate::models::MqttConfig;
+use paho_mqtt::{SslOptions, SslOptionsBuilder};
+use std::path::Path;
+
+/// Reject certificate and key paths that do not name a readable file, and a
+/// client key without its certificate
+pub fn validate_tls_files(
+    ca_cert_path: Option<&str>,
+    client_cert_path: Option<&str>,
+    client_key_path: Option<&str>,
+) -> Result<(), String> {
+    for (field, path) in [
+        ("ca_cert_path", ca_cert_path),
+        ("client_cert_path", client_cert_path),
+        ("client_key_path", client_key_path),
+    ] {
+        if let Some(path) = path
+            && !Path::new(path).is_file()
+        {
+            return Err(format!("{} '{}' is not a file", field, path));
+        }
+    }
+    if client_key_path.is_some() && client_cert_path.is_none() {
+        return Err("client_key_path requires client_cert_path".to_string());
+    }
+    Ok(())
+}
+
+/// SSL options of a TLS broker connection
+pub fn ssl_options(config: &MqttConfig) -> Result<SslOptions, paho_mqtt::Error> {
+    let mut builder = SslOptionsBuilder::new();
+    if let Some(ref path) = config.ca_cert_path {
+        builder.trust_store(path)?;
+    }
+    if let Some(ref path) = config.client_cert_path {
+        builder.key_store(path)?;
+    }
+    if let Some(ref path) = config.client_key_path {
+        builder.private_key(path)?;
+    }
+    if config.tls_insecure_skip_verify {
+        builder.enable_server_cert_auth(false).verify(false);
+    }
+    Ok(builder.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_tls_files() {
+        let dir = std::env::temp_dir().join(format!("zeromqtt-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("client.crt");
+        std::fs::write(&cert, "cert").unwrap();
+        let cert = cert.to_str().unwrap();
+
+        assert!(validate_tls_files(None, None, None).is_ok());
+        assert!(validate_tls_files(Some(cert), Some(cert), Some(cert)).is_ok());
+
+        let err = validate_tls_files(Some("/nonexistent/ca.pem"), None, None).unwrap_err();
+        assert!(err.contains("ca_cert_path"));
+        // A directory is not a certificate
+        assert!(validate_tls_files(None, Some(dir.to_str().unwrap()), None).is_err());
+        assert!(validate_tls_files(None, None, Some(cert)).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+//! MQTT v5 topic aliases for outbound publishes
+//!
+//! A v5 broker may let a client replace a topic name with a small number.
+//! The first publish to a topic carries both the name and its alias; later
+//! publishes carry only the alias. Aliases are scoped to one network
+//! connection, so the map is reset whenever the client (re)connects.
+
+use paho_mqtt::{Properties, PropertyCode};
+use std::collections::HashMap;
+
+/// How a publish names its topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasUse {
+    /// Send the topic together with a newly assigned alias
+    Assign(u16),
+    /// Send only the alias, with an empty topic
+    Reuse(u16),
+}
+
+impl AliasUse {
+    /// Topic to put on the wire for `topic`
+    pub fn wire_topic(self, topic: &str) -> &str {
+        match self {
+            AliasUse::Assign(_) => topic,
+            AliasUse::Reuse(_) => "",
+        }
+    }
+
+    /// Add the `Topic Alias` property to `props`
+    pub fn apply(self, props: &mut Properties) {
+        let (AliasUse::Assign(alias) | AliasUse::Reuse(alias)) = self;
+        let _ = props.push_int(PropertyCode::TopicAlias, alias as i32);
+    }
+}
+
+/// Aliases assigned on the current connection
+#[derive(Debug, Default)]
+pub struct TopicAliases {
+    maximum: u16,
+    assigned: HashMap<String, u16>,
+}
+
+impl TopicAliases {
+    /// Aliases `1..=maximum`, the `Topic Alias Maximum` from the broker's
+    /// CONNACK; 0 disables aliasing
+    pub fn new(maximum: u16) -> Self {
+        Self {
+            maximum,
+            assigned: HashMap::new(),
+        }
+    }
+
+    /// Forget every alias, for a new connection and its negotiated maximum
+    pub fn reset(&mut self, maximum: u16) {
+        self.maximum = maximum;
+        self.assigned.clear();
+    }
+
+    /// Alias for `topic`, assigned on first use. Topics seen first keep their
+    /// alias for the connection; once all are taken, new topics go unaliased.
+    pub fn alias(&mut self, topic: &str) -> Option<AliasUse> {
+        if let Some(&alias) = self.assigned.get(topic) {
+            return Some(AliasUse::Reuse(alias));
+        }
+        if topic.is_empty() || self.assigned.len() >= self.maximum as usize {
+            return None;
+        }
+        // Lowest alias not in use; a forgotten alias leaves a gap
+        let alias = (1..=self.maximum).find(|a| !self.assigned.values().any(|v| v == a))?;
+        self.assigned.insert(topic.to_string(), alias);
+        Some(AliasUse::Assign(alias))
+    }
+
+    /// Drop the alias of `topic` if the publish assigning it never reached
+    /// the broker, so the next publish assigns it again
+    pub fn forget(&mut self, topic: &str) {
+        self.assigned.remove(topic);
+    }
+}
+
+/// Topic alias maximum a broker advertised in its CONNACK properties
+pub fn broker_alias_maximum(props: &Properties) -> u16 {
+    props
+        .get_int(PropertyCode::TopicAliasMaximum)
+        .map_or(0, |max| max.clamp(0, u16::MAX as i32) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases_assigned_and_reused() {
+        let mut aliases = TopicAliases::new(2);
+        assert_eq!(aliases.alias("plant/line-1/telemetry"), Some(AliasUse::Assign(1)));
+        assert_eq!(aliases.alias("plant/line-2/telemetry"), Some(AliasUse::Assign(2)));
+        assert_eq!(aliases.alias("plant/line-1/telemetry"), Some(AliasUse::Reuse(1)));
+        // Maximum reached: further topics are sent by name
+        assert_eq!(aliases.alias("plant/line-3/telemetry"), None);
+        assert_eq!(aliases.alias("plant/line-2/telemetry"), Some(AliasUse::Reuse(2)));
+
+        assert_eq!(AliasUse::Reuse(1).wire_topic("plant/line-1/telemetry"), "");
+        assert_eq!(AliasUse::Assign(1).wire_topic("plant/line-1/telemetry"), "plant/line-1/telemetry");
+    }
+
+    #[test]
+    fn test_reset_on_reconnect() {
+        let mut aliases = TopicAliases::new(1);
+        assert_eq!(aliases.alias("a"), Some(AliasUse::Assign(1)));
+        aliases.reset(2);
+        assert_eq!(aliases.alias("b"), Some(AliasUse::Assign(1)));
+        assert_eq!(aliases.alias("a"), Some(AliasUse::Assign(2)));
+
+        // A broker without aliases disables them
+        aliases.reset(0);
+        assert_eq!(aliases.alias("a"), None);
+    }
+
+    #[test]
+    fn test_forget_failed_assignment() {
+        let mut aliases = TopicAliases::new(4);
+        assert_eq!(aliases.alias("a"), Some(AliasUse::Assign(1)));
+        assert_eq!(aliases.alias("b"), Some(AliasUse::Assign(2)));
+        aliases.forget("a");
+        assert_eq!(aliases.alias("c"), Some(AliasUse::Assign(1)));
+        assert_eq!(aliases.alias("a"), Some(AliasUse::Assign(3)));
+    }
+
+    #[test]
+    fn test_broker_alias_maximum() {
+        let mut props = Properties::new();
+        assert_eq!(broker_alias_maximum(&props), 0);
+        props.push_int(PropertyCode::TopicAliasMaximum, 10).unwrap();
+        assert_eq!(broker_alias_maximum(&props), 10);
+    }
+}
@@ -1,9 +1,21 @@
 //! Prometheus-compatible metrics for the bridge
 
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::OnceLock;
 use std::time::Instant;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use crate::models::{DeadLetterMessage, EndpointBandwidth, EndpointHealth, EndpointType, ForwardError};
+
+/// Forwarding errors kept for `/api/status/errors`
+pub const RECENT_ERRORS_CAPACITY: usize = 100;
+/// Unrouted messages kept for `/api/status/deadletter`
+pub const DEAD_LETTER_CAPACITY: usize = 500;
+/// Upper bounds of the latency histogram buckets in milliseconds
+pub const LATENCY_BUCKETS_MS: [f64; 10] = [0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0];
+/// Seconds the forwarding rate is averaged over
+pub const RATE_WINDOW_SECS: usize = 10;
 
 /// Global metrics registry
 static METRICS: OnceLock<Metrics> = OnceLock::new();
@@ -21,10 +33,56 @@ pub struct Metrics {
     zmq_messages_received: AtomicU64,
     zmq_messages_sent: AtomicU64,
     errors_total: AtomicU64,
+    hmac_failures_total: AtomicU64,
+    sampled_dropped_total: AtomicU64,
+    qos_downgrade_total: AtomicU64,
+    empty_target_dropped_total: AtomicU64,
+    separator_conflict_total: AtomicU64,
+    reconnect_throttled_total: AtomicU64,
+    json_limit_exceeded_total: AtomicU64,
+    schedule_skipped_total: AtomicU64,
+    subscribe_failed_total: AtomicU64,
+    subscriptions_truncated_total: AtomicU64,
+    topic_too_deep_total: AtomicU64,
+    exec_transform_failed_total: AtomicU64,
+    payload_conversion_failed_total: AtomicU64,
+    dead_lettered_total: AtomicU64,
+    duplicates_dropped_total: AtomicU64,
+    payload_truncated_total: AtomicU64,
+    payload_oversize_dropped_total: AtomicU64,
+    forward_overflow_dropped_total: AtomicU64,
+    zmq_rejoins_total: AtomicU64,
+
+    // Gauges
+    wal_pending: AtomicU64,
+    slow_consumer: AtomicU64,
     
-    // Latency tracking (simplified histogram using buckets)
-    latency_samples: RwLock<Vec<f64>>,
+    // Forwarding latency
+    latency: LatencyHistogram,
+    // Producer-to-forward latency from message timestamps
+    e2e_latency: LatencyHistogram,
+    // Time messages wait in ZMQ endpoint queues, including batch delay
+    zmq_queue_time: LatencyHistogram,
+    // Messages received and sent per second over the last few seconds
+    message_rate: RateWindow,
+
+    // Repository queries, recorded only with `database.query_stats`
+    db_queries_total: AtomicU64,
+    db_query_errors_total: AtomicU64,
+    db_query_duration: LatencyHistogram,
     
+    // Payload bytes (received, sent) per (endpoint type, endpoint id)
+    endpoint_bytes: RwLock<BTreeMap<(&'static str, u32), (u64, u64)>>,
+    // Messages forwarded per mapping id
+    mapping_forwarded: RwLock<BTreeMap<u32, AtomicU64>>,
+    // Most recent forwarding errors, oldest first
+    recent_errors: Mutex<VecDeque<ForwardError>>,
+    // Most recent messages no mapping or endpoint took, oldest first
+    dead_letter: RwLock<VecDeque<DeadLetterMessage>>,
+
+    // Latest (healthy, checked at) per (endpoint type, endpoint id)
+    endpoint_health: RwLock<BTreeMap<(&'static str, u32), (bool, i64)>>,
+
     // Start time for uptime calculation
     start_time: Instant,
 }
@@ -37,7 +95,39 @@ impl Metrics {
             zmq_messages_received: AtomicU64::new(0),
             zmq_messages_sent: AtomicU64::new(0),
             errors_total: AtomicU64::new(0),
-            latency_samples: RwLock::new(Vec::with_capacity(1000)),
+            hmac_failures_total: AtomicU64::new(0),
+            sampled_dropped_total: AtomicU64::new(0),
+            qos_downgrade_total: AtomicU64::new(0),
+            empty_target_dropped_total: AtomicU64::new(0),
+            separator_conflict_total: AtomicU64::new(0),
+            reconnect_throttled_total: AtomicU64::new(0),
+            json_limit_exceeded_total: AtomicU64::new(0),
+            schedule_skipped_total: AtomicU64::new(0),
+            subscribe_failed_total: AtomicU64::new(0),
+            subscriptions_truncated_total: AtomicU64::new(0),
+            topic_too_deep_total: AtomicU64::new(0),
+            exec_transform_failed_total: AtomicU64::new(0),
+            payload_conversion_failed_total: AtomicU64::new(0),
+            dead_lettered_total: AtomicU64::new(0),
+            duplicates_dropped_total: AtomicU64::new(0),
+            payload_truncated_total: AtomicU64::new(0),
+            payload_oversize_dropped_total: AtomicU64::new(0),
+            forward_overflow_dropped_total: AtomicU64::new(0),
+            zmq_rejoins_total: AtomicU64::new(0),
+            wal_pending: AtomicU64::new(0),
+            slow_consumer: AtomicU64::new(0),
+            latency: LatencyHistogram::default(),
+            e2e_latency: LatencyHistogram::default(),
+            zmq_queue_time: LatencyHistogram::default(),
+            message_rate: RateWindow::default(),
+            db_queries_total: AtomicU64::new(0),
+            db_query_errors_total: AtomicU64::new(0),
+            db_query_duration: LatencyHistogram::default(),
+            endpoint_bytes: RwLock::new(BTreeMap::new()),
+            mapping_forwarded: RwLock::new(BTreeMap::new()),
+            recent_errors: Mutex::new(VecDeque::with_capacity(RECENT_ERRORS_CAPACITY)),
+            dead_letter: RwLock::new(VecDeque::with_capacity(DEAD_LETTER_CAPACITY)),
+            endpoint_health: RwLock::new(BTreeMap::new()),
             start_time: Instant::now(),
         }
     }
@@ -45,21 +135,25 @@ impl Metrics {
     /// Record MQTT message received
     pub fn record_mqtt_received(&self) {
         self.mqtt_messages_received.fetch_add(1, Ordering::Relaxed);
+        self.message_rate.record(self.start_time.elapsed().as_secs());
     }
 
     /// Record MQTT message sent
     pub fn record_mqtt_sent(&self) {
         self.mqtt_messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.message_rate.record(self.start_time.elapsed().as_secs());
     }
 
     /// Record ZMQ message received
     pub fn record_zmq_received(&self) {
         self.zmq_messages_received.fetch_add(1, Ordering::Relaxed);
+        self.message_rate.record(self.start_time.elapsed().as_secs());
     }
 
     /// Record ZMQ message sent
     pub fn record_zmq_sent(&self) {
         self.zmq_messages_sent.fetch_add(1, Ordering::Relaxed);
+        self.message_rate.record(self.start_time.elapsed().as_secs());
     }
 
     /// Record an error
@@ -67,14 +161,249 @@ impl Metrics {
         self.errors_total.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record an error forwarding `topic` to an endpoint, keeping its details
+    /// among the most recent errors
+    pub fn record_forward_error(&self, endpoint_type: &EndpointType, endpoint_id: u32, topic: &str, reason: String) {
+        self.record_error();
+        let event = ForwardError {
+            timestamp: chrono::Utc::now().timestamp(),
+            endpoint_type: endpoint_type.clone(),
+            endpoint_id,
+            topic: topic.to_string(),
+            reason,
+        };
+        let mut recent = self.recent_errors.lock();
+        if recent.len() == RECENT_ERRORS_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(event);
+    }
+
+    /// Up to `count` of the most recent forwarding errors, newest first
+    pub fn recent_errors(&self, count: usize) -> Vec<ForwardError> {
+        self.recent_errors.lock().iter().rev().take(count).cloned().collect()
+    }
+
+    /// Keep a message that could not be routed, evicting the oldest once
+    /// `DEAD_LETTER_CAPACITY` are kept
+    pub fn push_dead_letter(&self, message: DeadLetterMessage) {
+        let mut dead_letter = self.dead_letter.write();
+        if dead_letter.len() == DEAD_LETTER_CAPACITY {
+            dead_letter.pop_front();
+        }
+        dead_letter.push_back(message);
+    }
+
+    /// Up to `count` of the most recent unrouted messages, newest first
+    pub fn dead_letters(&self, count: usize) -> Vec<DeadLetterMessage> {
+        self.dead_letter.read().iter().rev().take(count).cloned().collect()
+    }
+
+    /// Record a ZMQ frame dropped for a missing or invalid HMAC
+    pub fn record_hmac_failure(&self) {
+        self.hmac_failures_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message skipped by mapping sampling
+    pub fn record_sampled_drop(&self) {
+        self.sampled_dropped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an MQTT publish delivered below its requested QoS
+    pub fn record_qos_downgrade(&self) {
+        self.qos_downgrade_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message dropped because its computed target topic was empty or invalid
+    pub fn record_empty_target_drop(&self) {
+        self.empty_target_dropped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a ZMQ forward rejected because its topic contains the frame separator
+    pub fn record_separator_conflict(&self) {
+        self.separator_conflict_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an MQTT reconnect attempt held off by the storm limiter
+    pub fn record_reconnect_throttled(&self) {
+        self.reconnect_throttled_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a payload refused by the JSON size/depth limits
+    pub fn record_json_limit_exceeded(&self) {
+        self.json_limit_exceeded_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message skipped because its mapping is outside its schedule
+    pub fn record_schedule_skip(&self) {
+        self.schedule_skipped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a topic filter that could not be subscribed
+    pub fn record_subscribe_failure(&self) {
+        self.subscribe_failed_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record topic filters left out of a subscribe set by `max_subscriptions`
+    pub fn record_subscriptions_truncated(&self, count: usize) {
+        self.subscriptions_truncated_total.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Record payload bytes received from an endpoint
+    pub fn record_bytes_received(&self, endpoint_type: &EndpointType, endpoint_id: u32, bytes: usize) {
+        let key = (endpoint_label(endpoint_type), endpoint_id);
+        self.endpoint_bytes.write().entry(key).or_default().0 += bytes as u64;
+    }
+
+    /// Record payload bytes sent to an endpoint
+    pub fn record_bytes_sent(&self, endpoint_type: &EndpointType, endpoint_id: u32, bytes: usize) {
+        let key = (endpoint_label(endpoint_type), endpoint_id);
+        self.endpoint_bytes.write().entry(key).or_default().1 += bytes as u64;
+    }
+
+    /// Record a message forwarded to a target by `mapping_id`
+    pub fn record_mapping_forwarded(&self, mapping_id: u32) {
+        // Only a mapping's first message takes the write lock
+        if let Some(forwarded) = self.mapping_forwarded.read().get(&mapping_id) {
+            forwarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.mapping_forwarded.write().entry(mapping_id).or_default().fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drop the counters of mappings not in `mapping_ids`, e.g. deleted ones
+    pub fn retain_mapping_counters(&self, mapping_ids: &[u32]) {
+        self.mapping_forwarded.write().retain(|id, _| mapping_ids.contains(id));
+    }
+
+    /// Byte counters of every endpoint that moved data
+    pub fn endpoint_bandwidth(&self) -> Vec<EndpointBandwidth> {
+        self.endpoint_bytes
+            .read()
+            .iter()
+            .map(|(&(label, endpoint_id), &(bytes_received, bytes_sent))| EndpointBandwidth {
+                endpoint_type: if label == "mqtt" { EndpointType::Mqtt } else { EndpointType::Zmq },
+                endpoint_id,
+                bytes_received,
+                bytes_sent,
+            })
+            .collect()
+    }
+
+    /// Record the outcome of a liveness check of an endpoint
+    pub fn record_endpoint_health(&self, endpoint_type: &EndpointType, endpoint_id: u32, healthy: bool) {
+        let key = (endpoint_label(endpoint_type), endpoint_id);
+        self.endpoint_health
+            .write()
+            .insert(key, (healthy, chrono::Utc::now().timestamp()));
+    }
+
+    /// Forget endpoint health, e.g. once the bridge stops checking
+    pub fn clear_endpoint_health(&self) {
+        self.endpoint_health.write().clear();
+    }
+
+    /// Latest liveness check of every checked endpoint
+    pub fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.endpoint_health
+            .read()
+            .iter()
+            .map(|(&(label, endpoint_id), &(healthy, checked_at))| EndpointHealth {
+                endpoint_type: if label == "mqtt" { EndpointType::Mqtt } else { EndpointType::Zmq },
+                endpoint_id,
+                healthy,
+                checked_at,
+            })
+            .collect()
+    }
+
+    /// Record a message dropped for exceeding the topic level limit
+    pub fn record_topic_too_deep(&self) {
+        self.topic_too_deep_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message whose transform command failed or is disabled
+    pub fn record_exec_transform_failure(&self) {
+        self.exec_transform_failed_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message whose payload could not be converted
+    pub fn record_payload_conversion_failure(&self) {
+        self.payload_conversion_failed_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message stored as a dead letter after a failed transform
+    pub fn record_dead_letter(&self) {
+        self.dead_lettered_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message dropped because its mapping already forwarded its key
+    pub fn record_duplicate_drop(&self) {
+        self.duplicates_dropped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a payload cut to its mapping's `truncate_bytes`
+    pub fn record_payload_truncation(&self) {
+        self.payload_truncated_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a message dropped for a payload over its size limit
+    pub fn record_oversize_drop(&self) {
+        self.payload_oversize_dropped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Messages dropped for a payload over their size limit
+    pub fn oversize_dropped(&self) -> u64 {
+        self.payload_oversize_dropped_total.load(Ordering::Relaxed)
+    }
+
+    /// Record a message dropped because the forwarding channel was full
+    pub fn record_forward_overflow(&self) {
+        self.forward_overflow_dropped_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Messages dropped because the forwarding channel was full
+    pub fn forward_overflow_dropped(&self) -> u64 {
+        self.forward_overflow_dropped_total.load(Ordering::Relaxed)
+    }
+
+    /// Record a SUB endpoint reconnecting to a publisher it was connected to before
+    pub fn record_zmq_rejoin(&self) {
+        self.zmq_rejoins_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Set the number of durable messages logged but not yet delivered
+    pub fn set_wal_pending(&self, pending: usize) {
+        self.wal_pending.store(pending as u64, Ordering::Relaxed);
+    }
+
+    /// Set whether forwarding is persistently falling behind its input
+    pub fn set_slow_consumer(&self, slow: bool) {
+        self.slow_consumer.store(slow as u64, Ordering::Relaxed);
+    }
+
     /// Record message forwarding latency in milliseconds
     pub fn record_latency(&self, latency_ms: f64) {
-        let mut samples = self.latency_samples.write();
-        // Keep last 1000 samples for histogram
-        if samples.len() >= 1000 {
-            samples.remove(0);
+        self.latency.observe(latency_ms);
+    }
+
+    /// Record end-to-end latency from a producer timestamp in milliseconds
+    pub fn record_e2e_latency(&self, latency_ms: f64) {
+        self.e2e_latency.observe(latency_ms);
+    }
+
+    /// Record how long a message waited to be sent by a ZMQ endpoint in milliseconds
+    pub fn record_zmq_queue_time(&self, latency_ms: f64) {
+        self.zmq_queue_time.observe(latency_ms);
+    }
+
+    /// Record a database query and how long it took in milliseconds
+    pub fn record_db_query(&self, duration_ms: f64, failed: bool) {
+        self.db_queries_total.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.db_query_errors_total.fetch_add(1, Ordering::Relaxed);
         }
-        samples.push(latency_ms);
+        self.db_query_duration.observe(duration_ms);
     }
 
     /// Get uptime in seconds
@@ -82,73 +411,305 @@ impl Metrics {
         self.start_time.elapsed().as_secs_f64()
     }
 
+    /// Messages received and sent per second, like `MessageStats::total_messages`,
+    /// averaged over the last `RATE_WINDOW_SECS` complete seconds so bursts
+    /// show instead of being spread over the uptime
+    pub fn messages_per_second(&self) -> f64 {
+        self.message_rate.rate(self.start_time.elapsed().as_secs())
+    }
+
     /// Get total messages forwarded
     pub fn total_forwarded(&self) -> u64 {
         self.mqtt_messages_sent.load(Ordering::Relaxed) + 
         self.zmq_messages_sent.load(Ordering::Relaxed)
     }
 
-    /// Generate Prometheus-compatible metrics output
-    pub fn render_prometheus(&self) -> String {
+    /// Generate Prometheus-compatible metrics output, with every metric
+    /// name prefixed by `namespace` (`metrics.namespace`)
+    pub fn render_prometheus(&self, namespace: &str) -> String {
+        let ns = metric_prefix(namespace);
         let mqtt_rx = self.mqtt_messages_received.load(Ordering::Relaxed);
         let mqtt_tx = self.mqtt_messages_sent.load(Ordering::Relaxed);
         let zmq_rx = self.zmq_messages_received.load(Ordering::Relaxed);
         let zmq_tx = self.zmq_messages_sent.load(Ordering::Relaxed);
         let errors = self.errors_total.load(Ordering::Relaxed);
+        let hmac_failures = self.hmac_failures_total.load(Ordering::Relaxed);
+        let sampled_dropped = self.sampled_dropped_total.load(Ordering::Relaxed);
+        let qos_downgrade = self.qos_downgrade_total.load(Ordering::Relaxed);
+        let empty_target_dropped = self.empty_target_dropped_total.load(Ordering::Relaxed);
+        let separator_conflict = self.separator_conflict_total.load(Ordering::Relaxed);
+        let reconnect_throttled = self.reconnect_throttled_total.load(Ordering::Relaxed);
+        let json_limit_exceeded = self.json_limit_exceeded_total.load(Ordering::Relaxed);
+        let schedule_skipped = self.schedule_skipped_total.load(Ordering::Relaxed);
+        let subscribe_failed = self.subscribe_failed_total.load(Ordering::Relaxed);
+        let subscriptions_truncated = self.subscriptions_truncated_total.load(Ordering::Relaxed);
+        let topic_too_deep = self.topic_too_deep_total.load(Ordering::Relaxed);
+        let exec_transform_failed = self.exec_transform_failed_total.load(Ordering::Relaxed);
+        let payload_conversion_failed = self.payload_conversion_failed_total.load(Ordering::Relaxed);
+        let dead_lettered = self.dead_lettered_total.load(Ordering::Relaxed);
+        let duplicates_dropped = self.duplicates_dropped_total.load(Ordering::Relaxed);
+        let payload_truncated = self.payload_truncated_total.load(Ordering::Relaxed);
+        let payload_oversize_dropped = self.oversize_dropped();
+        let forward_overflow_dropped = self.forward_overflow_dropped();
+        let zmq_rejoins = self.zmq_rejoins_total.load(Ordering::Relaxed);
+        let wal_pending = self.wal_pending.load(Ordering::Relaxed);
+        let slow_consumer = self.slow_consumer.load(Ordering::Relaxed);
         let uptime = self.uptime_seconds();
+        let messages_per_second = self.messages_per_second();
 
-        // Calculate latency percentiles
-        let samples = self.latency_samples.read();
-        let (p50, p95, p99) = if samples.is_empty() {
-            (0.0, 0.0, 0.0)
-        } else {
-            let mut sorted: Vec<f64> = samples.clone();
-            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-            let len = sorted.len();
-            let p50 = sorted[len * 50 / 100];
-            let p95 = sorted[len * 95 / 100];
-            let p99 = sorted.get(len * 99 / 100).copied().unwrap_or(sorted[len - 1]);
-            (p50, p95, p99)
-        };
+        let mut output = format!(
+r#"# HELP {ns}mqtt_messages_received_total Total MQTT messages received
+# TYPE {ns}mqtt_messages_received_total counter
+{ns}mqtt_messages_received_total {}
+
+# HELP {ns}mqtt_messages_sent_total Total MQTT messages sent
+# TYPE {ns}mqtt_messages_sent_total counter
+{ns}mqtt_messages_sent_total {}
+
+# HELP {ns}zmq_messages_received_total Total ZeroMQ messages received
+# TYPE {ns}zmq_messages_received_total counter
+{ns}zmq_messages_received_total {}
+
+# HELP {ns}zmq_messages_sent_total Total ZeroMQ messages sent
+# TYPE {ns}zmq_messages_sent_total counter
+{ns}zmq_messages_sent_total {}
+
+# HELP {ns}errors_total Total errors encountered
+# TYPE {ns}errors_total counter
+{ns}errors_total {}
+
+# HELP {ns}hmac_failures_total ZeroMQ frames dropped for failed HMAC verification
+# TYPE {ns}hmac_failures_total counter
+{ns}hmac_failures_total {}
+
+# HELP {ns}sampled_dropped_total Messages skipped by per-mapping sampling
+# TYPE {ns}sampled_dropped_total counter
+{ns}sampled_dropped_total {}
+
+# HELP {ns}qos_downgrade_total MQTT publishes delivered below the requested QoS
+# TYPE {ns}qos_downgrade_total counter
+{ns}qos_downgrade_total {}
+
+# HELP {ns}empty_target_dropped_total Messages dropped for an empty or invalid computed target topic
+# TYPE {ns}empty_target_dropped_total counter
+{ns}empty_target_dropped_total {}
+
+# HELP {ns}separator_conflict_total ZeroMQ forwards rejected for a topic containing the frame separator
+# TYPE {ns}separator_conflict_total counter
+{ns}separator_conflict_total {}
 
-        format!(
-r#"# HELP zeromqtt_mqtt_messages_received_total Total MQTT messages received
-# TYPE zeromqtt_mqtt_messages_received_total counter
-zeromqtt_mqtt_messages_received_total {}
+# HELP {ns}reconnect_throttled_total MQTT reconnect attempts held off by the reconnect storm limiter
+# TYPE {ns}reconnect_throttled_total counter
+{ns}reconnect_throttled_total {}
 
-# HELP zeromqtt_mqtt_messages_sent_total Total MQTT messages sent
-# TYPE zeromqtt_mqtt_messages_sent_total counter
-zeromqtt_mqtt_messages_sent_total {}
+# HELP {ns}json_limit_exceeded_total Payloads dropped for exceeding the JSON size or depth limits
+# TYPE {ns}json_limit_exceeded_total counter
+{ns}json_limit_exceeded_total {}
 
-# HELP zeromqtt_zmq_messages_received_total Total ZeroMQ messages received
-# TYPE zeromqtt_zmq_messages_received_total counter
-zeromqtt_zmq_messages_received_total {}
+# HELP {ns}schedule_skipped_total Messages skipped by mappings outside their schedule
+# TYPE {ns}schedule_skipped_total counter
+{ns}schedule_skipped_total {}
 
-# HELP zeromqtt_zmq_messages_sent_total Total ZeroMQ messages sent
-# TYPE zeromqtt_zmq_messages_sent_total counter
-zeromqtt_zmq_messages_sent_total {}
+# HELP {ns}subscribe_failed_total Topic filters that were invalid or rejected by the broker
+# TYPE {ns}subscribe_failed_total counter
+{ns}subscribe_failed_total {}
 
-# HELP zeromqtt_errors_total Total errors encountered
-# TYPE zeromqtt_errors_total counter
-zeromqtt_errors_total {}
+# HELP {ns}subscriptions_truncated_total Topic filters not subscribed because the broker's max_subscriptions was reached
+# TYPE {ns}subscriptions_truncated_total counter
+{ns}subscriptions_truncated_total {}
 
-# HELP zeromqtt_uptime_seconds Uptime in seconds
-# TYPE zeromqtt_uptime_seconds gauge
-zeromqtt_uptime_seconds {:.2}
+# HELP {ns}topic_too_deep_total Messages dropped for having more topic levels than bridge.max_topic_levels
+# TYPE {ns}topic_too_deep_total counter
+{ns}topic_too_deep_total {}
 
-# HELP zeromqtt_messages_forwarded_total Total messages forwarded
-# TYPE zeromqtt_messages_forwarded_total counter
-zeromqtt_messages_forwarded_total {}
+# HELP {ns}exec_transform_failed_total Messages whose transform command failed, timed out or is disabled
+# TYPE {ns}exec_transform_failed_total counter
+{ns}exec_transform_failed_total {}
 
-# HELP zeromqtt_latency_milliseconds Message forwarding latency
-# TYPE zeromqtt_latency_milliseconds summary
-zeromqtt_latency_milliseconds{{quantile="0.5"}} {:.3}
-zeromqtt_latency_milliseconds{{quantile="0.95"}} {:.3}
-zeromqtt_latency_milliseconds{{quantile="0.99"}} {:.3}
+# HELP {ns}payload_conversion_failed_total Messages whose payload did not parse for the mapping's payload_transform
+# TYPE {ns}payload_conversion_failed_total counter
+{ns}payload_conversion_failed_total {}
+
+# HELP {ns}dead_lettered_total Messages stored in the dead-letter table after a failed transform
+# TYPE {ns}dead_lettered_total counter
+{ns}dead_lettered_total {}
+
+# HELP {ns}duplicates_dropped_total Messages dropped because their mapping already forwarded their dedup_key
+# TYPE {ns}duplicates_dropped_total counter
+{ns}duplicates_dropped_total {}
+
+# HELP {ns}payload_truncated_total Payloads cut to their mapping's truncate_bytes before forwarding
+# TYPE {ns}payload_truncated_total counter
+{ns}payload_truncated_total {}
+
+# HELP {ns}payload_oversize_dropped_total Messages dropped because their payload exceeded max_payload_bytes
+# TYPE {ns}payload_oversize_dropped_total counter
+{ns}payload_oversize_dropped_total {}
+
+# HELP {ns}forward_overflow_dropped_total Messages dropped by forward_overflow while the forwarding channel was full
+# TYPE {ns}forward_overflow_dropped_total counter
+{ns}forward_overflow_dropped_total {}
+
+# HELP {ns}zmq_rejoins_total Reconnects of ZMQ SUB endpoints to publishers, e.g. after a publisher restart
+# TYPE {ns}zmq_rejoins_total counter
+{ns}zmq_rejoins_total {}
+
+# HELP {ns}wal_pending Messages of durable mappings in the write-ahead log awaiting delivery
+# TYPE {ns}wal_pending gauge
+{ns}wal_pending {}
+
+# HELP {ns}slow_consumer 1 while the forwarding channel stays above its depth threshold
+# TYPE {ns}slow_consumer gauge
+{ns}slow_consumer {}
+
+# HELP {ns}messages_per_second Messages received and sent per second over the last 10 seconds
+# TYPE {ns}messages_per_second gauge
+{ns}messages_per_second {:.2}
+
+# HELP {ns}uptime_seconds Uptime in seconds
+# TYPE {ns}uptime_seconds gauge
+{ns}uptime_seconds {:.2}
+
+# HELP {ns}messages_forwarded_total Total messages forwarded
+# TYPE {ns}messages_forwarded_total counter
+{ns}messages_forwarded_total {}
 "#,
-            mqtt_rx, mqtt_tx, zmq_rx, zmq_tx, errors, uptime, 
-            mqtt_tx + zmq_tx, p50, p95, p99
-        )
+            mqtt_rx, mqtt_tx, zmq_rx, zmq_tx, errors, hmac_failures, sampled_dropped, qos_downgrade, empty_target_dropped, separator_conflict, reconnect_throttled, json_limit_exceeded, schedule_skipped, subscribe_failed, subscriptions_truncated, topic_too_deep, exec_transform_failed, payload_conversion_failed, dead_lettered, duplicates_dropped, payload_truncated, payload_oversize_dropped, forward_overflow_dropped, zmq_rejoins, wal_pending, slow_consumer, messages_per_second, uptime, 
+            mqtt_tx + zmq_tx
+        );
+
+        self.latency.render(&mut output, &ns, "latency_milliseconds", "Message forwarding latency");
+        self.e2e_latency.render(
+            &mut output,
+            &ns,
+            "e2e_latency_milliseconds",
+            "Latency from the producer timestamp to forwarding",
+        );
+        self.zmq_queue_time.render(
+            &mut output,
+            &ns,
+            "zmq_queue_milliseconds",
+            "Time messages wait in a ZMQ endpoint's queue before sending, including batch delay",
+        );
+
+        // Only present while `database.query_stats` records queries
+        let db_queries = self.db_queries_total.load(Ordering::Relaxed);
+        if db_queries > 0 {
+            let _ = writeln!(output, "\n# HELP {ns}db_queries_total Database queries run by the repository");
+            let _ = writeln!(output, "# TYPE {ns}db_queries_total counter");
+            let _ = writeln!(output, "{}db_queries_total {}", ns, db_queries);
+            let _ = writeln!(output, "\n# HELP {ns}db_query_errors_total Database queries that returned an error");
+            let _ = writeln!(output, "# TYPE {ns}db_query_errors_total counter");
+            let _ = writeln!(
+                output,
+                "{}db_query_errors_total {}",
+                ns,
+                self.db_query_errors_total.load(Ordering::Relaxed)
+            );
+            self.db_query_duration.render(
+                &mut output,
+                &ns,
+                "db_query_duration_milliseconds",
+                "Duration of database queries",
+            );
+        }
+
+        let endpoint_bytes = self.endpoint_bytes.read();
+        if !endpoint_bytes.is_empty() {
+            let _ = writeln!(output, "\n# HELP {ns}endpoint_bytes_total Payload bytes received from and sent to each endpoint");
+            let _ = writeln!(output, "# TYPE {ns}endpoint_bytes_total counter");
+            for (&(label, id), &(received, sent)) in endpoint_bytes.iter() {
+                for (direction, bytes) in [("received", received), ("sent", sent)] {
+                    let _ = writeln!(
+                        output,
+                        "{}endpoint_bytes_total{{endpoint=\"{}:{}\",direction=\"{}\"}} {}",
+                        ns, label, id, direction, bytes
+                    );
+                }
+            }
+        }
+        drop(endpoint_bytes);
+
+        let mapping_forwarded = self.mapping_forwarded.read();
+        if !mapping_forwarded.is_empty() {
+            let _ = writeln!(output, "\n# HELP {ns}mapping_forwarded_total Messages forwarded by each topic mapping");
+            let _ = writeln!(output, "# TYPE {ns}mapping_forwarded_total counter");
+            for (id, forwarded) in mapping_forwarded.iter() {
+                let _ = writeln!(
+                    output,
+                    "{}mapping_forwarded_total{{mapping_id=\"{}\"}} {}",
+                    ns,
+                    id,
+                    forwarded.load(Ordering::Relaxed)
+                );
+            }
+        }
+        drop(mapping_forwarded);
+
+        let endpoint_health = self.endpoint_health.read();
+        if !endpoint_health.is_empty() {
+            let _ = writeln!(output, "\n# HELP {ns}endpoint_healthy 1 if the latest liveness check of the endpoint passed");
+            let _ = writeln!(output, "# TYPE {ns}endpoint_healthy gauge");
+            for (&(label, id), &(healthy, _)) in endpoint_health.iter() {
+                let _ = writeln!(output, "{}endpoint_healthy{{endpoint=\"{}:{}\"}} {}", ns, label, id, healthy as u8);
+            }
+        }
+        output
+    }
+}
+
+/// `namespace_` prefix of metric names; nothing for an empty namespace. A
+/// trailing `_` in the namespace is not doubled.
+fn metric_prefix(namespace: &str) -> String {
+    match namespace.trim_end_matches('_') {
+        "" => String::new(),
+        namespace => format!("{}_", namespace),
+    }
+}
+
+/// `endpoint` label prefix of an endpoint type
+fn endpoint_label(endpoint_type: &EndpointType) -> &'static str {
+    match endpoint_type {
+        EndpointType::Mqtt => "mqtt",
+        EndpointType::Zmq => "zmq",
+    }
+}
+
+/// Latency histogram with fixed buckets, so recording and scraping stay
+/// constant-time and Prometheus computes quantiles server-side
+#[derive(Default)]
+struct LatencyHistogram {
+    // Observations per bucket; the last slot holds those above every bound
+    buckets: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    // Sum of observations in microseconds
+    sum_us: AtomicU64,
+    count: AtomicU64,
+}
+
+impl LatencyHistogram {
+    fn observe(&self, latency_ms: f64) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| latency_ms <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add((latency_ms.max(0.0) * 1000.0) as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Append the `_bucket`, `_sum` and `_count` series of `name`
+    fn render(&self, output: &mut String, ns: &str, name: &str, help: &str) {
+        let _ = writeln!(output, "\n# HELP {}{} {}", ns, name, help);
+        let _ = writeln!(output, "# TYPE {}{} histogram", ns, name);
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = LATENCY_BUCKETS_MS.get(i).map_or("+Inf".to_string(), |bound| bound.to_string());
+            let _ = writeln!(output, "{}{}_bucket{{le=\"{}\"}} {}", ns, name, le, cumulative);
+        }
+        let sum_ms = self.sum_us.load(Ordering::Relaxed) as f64 / 1000.0;
+        let _ = writeln!(output, "{}{}_sum {:.3}", ns, name, sum_ms);
+        let _ = writeln!(output, "{}{}_count {}", ns, name, self.count.load(Ordering::Relaxed));
     }
 }
 
@@ -158,10 +719,64 @@ impl Default for Metrics {
     }
 }
 
+/// Per-second message counts of a sliding window, indexed by second modulo
+/// the window; the slot of the current second is still filling. Each slot
+/// packs the second it counts (high 32 bits) with its count (low 32 bits),
+/// so recording is one atomic update and a stale slot restarts at 1.
+#[derive(Default)]
+struct RateWindow {
+    slots: [AtomicU64; RATE_WINDOW_SECS + 1],
+}
+
+impl RateWindow {
+    fn record(&self, second: u64) {
+        let slot = &self.slots[Self::slot(second)];
+        let _ = slot.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |packed| {
+            Some(if packed >> 32 == second { packed + 1 } else { (second << 32) | 1 })
+        });
+    }
+
+    /// Average of the complete seconds in the window
+    fn rate(&self, second: u64) -> f64 {
+        let complete = second.saturating_sub(RATE_WINDOW_SECS as u64)..second;
+        let total: u64 = self
+            .slots
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .filter(|packed| complete.contains(&(packed >> 32)))
+            .map(|packed| packed & u32::MAX as u64)
+            .sum();
+        total as f64 / RATE_WINDOW_SECS as f64
+    }
+
+    fn slot(second: u64) -> usize {
+        (second % (RATE_WINDOW_SECS as u64 + 1)) as usize
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rate_window_follows_bursts() {
+        let window = RateWindow::default();
+        for _ in 0..50 {
+            window.record(100);
+        }
+        // The current second is still filling
+        assert_eq!(window.rate(100), 0.0);
+        assert_eq!(window.rate(101), 5.0);
+        window.record(105);
+        assert_eq!(window.rate(106), 5.1);
+        // The burst leaves the window, the later message a second after it
+        assert_eq!(window.rate(111), 0.1);
+        assert_eq!(window.rate(116), 0.0);
+        // Idle longer than the window
+        window.record(500);
+        assert_eq!(window.rate(501), 0.1);
+    }
+
     #[test]
     fn test_metrics_counters() {
         let m = Metrics::new();
@@ -179,8 +794,151 @@ mod tests {
         m.record_mqtt_sent();
         m.record_zmq_sent();
         
-        let output = m.render_prometheus();
+        let output = m.render_prometheus("zeromqtt");
         assert!(output.contains("zeromqtt_mqtt_messages_sent_total 1"));
         assert!(output.contains("zeromqtt_zmq_messages_sent_total 1"));
     }
+
+    #[test]
+    fn test_latency_histogram() {
+        let m = Metrics::new();
+        m.record_latency(0.2);
+        m.record_latency(1.0);
+        m.record_latency(7.5);
+        m.record_latency(900.0);
+        m.record_e2e_latency(250.0);
+
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("# TYPE zeromqtt_latency_milliseconds histogram"));
+        // Buckets are cumulative, and a bound includes values equal to it
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"1\"} 2\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"5\"} 2\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"10\"} 3\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"500\"} 3\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_bucket{le=\"+Inf\"} 4\n"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_sum 908.700"));
+        assert!(output.contains("zeromqtt_latency_milliseconds_count 4"));
+
+        assert!(output.contains("zeromqtt_e2e_latency_milliseconds_bucket{le=\"100\"} 0\n"));
+        assert!(output.contains("zeromqtt_e2e_latency_milliseconds_bucket{le=\"250\"} 1\n"));
+        assert!(output.contains("zeromqtt_e2e_latency_milliseconds_count 1"));
+    }
+
+    #[test]
+    fn test_db_query_stats() {
+        let m = Metrics::new();
+        assert!(!m.render_prometheus("zeromqtt").contains("db_queries_total"));
+
+        m.record_db_query(0.3, false);
+        m.record_db_query(12.0, true);
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("zeromqtt_db_queries_total 2\n"));
+        assert!(output.contains("zeromqtt_db_query_errors_total 1\n"));
+        assert!(output.contains("zeromqtt_db_query_duration_milliseconds_bucket{le=\"0.5\"} 1\n"));
+        assert!(output.contains("zeromqtt_db_query_duration_milliseconds_count 2"));
+    }
+
+    #[test]
+    fn test_endpoint_bytes() {
+        let m = Metrics::new();
+        m.record_bytes_received(&EndpointType::Mqtt, 1, 100);
+        m.record_bytes_received(&EndpointType::Mqtt, 1, 50);
+        m.record_bytes_sent(&EndpointType::Zmq, 2, 4096);
+
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("zeromqtt_endpoint_bytes_total{endpoint=\"mqtt:1\",direction=\"received\"} 150"));
+        assert!(output.contains("zeromqtt_endpoint_bytes_total{endpoint=\"zmq:2\",direction=\"sent\"} 4096"));
+
+        let bandwidth = m.endpoint_bandwidth();
+        assert_eq!(bandwidth.len(), 2);
+        assert_eq!((bandwidth[0].bytes_received, bandwidth[0].bytes_sent), (150, 0));
+        assert_eq!(bandwidth[1].endpoint_type, EndpointType::Zmq);
+    }
+
+    #[test]
+    fn test_mapping_forwarded() {
+        let m = Metrics::new();
+        m.record_mapping_forwarded(1);
+        m.record_mapping_forwarded(1);
+        m.record_mapping_forwarded(2);
+
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("zeromqtt_mapping_forwarded_total{mapping_id=\"1\"} 2"));
+        assert!(output.contains("zeromqtt_mapping_forwarded_total{mapping_id=\"2\"} 1"));
+
+        // Mapping 2 was deleted
+        m.retain_mapping_counters(&[1]);
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("zeromqtt_mapping_forwarded_total{mapping_id=\"1\"} 2"));
+        assert!(!output.contains("mapping_id=\"2\""));
+    }
+
+    #[test]
+    fn test_recent_errors_bounded() {
+        let m = Metrics::new();
+        for i in 0..RECENT_ERRORS_CAPACITY + 5 {
+            m.record_forward_error(&EndpointType::Mqtt, 1, &format!("t/{}", i), "not found".to_string());
+        }
+
+        assert_eq!(m.errors_total.load(Ordering::Relaxed), RECENT_ERRORS_CAPACITY as u64 + 5);
+        let recent = m.recent_errors(usize::MAX);
+        assert_eq!(recent.len(), RECENT_ERRORS_CAPACITY);
+        assert_eq!(recent[0].topic, format!("t/{}", RECENT_ERRORS_CAPACITY + 4));
+        assert_eq!(recent.last().unwrap().topic, "t/5");
+        assert_eq!(m.recent_errors(2).len(), 2);
+    }
+
+    #[test]
+    fn test_dead_letter_bounded() {
+        let m = Metrics::new();
+        for i in 0..DEAD_LETTER_CAPACITY + 3 {
+            m.push_dead_letter(DeadLetterMessage {
+                timestamp: 0,
+                source: EndpointType::Zmq,
+                source_id: 1,
+                topic: format!("t/{}", i),
+                payload: Vec::new(),
+                reason: "no matching mapping".to_string(),
+            });
+        }
+
+        let kept = m.dead_letters(usize::MAX);
+        assert_eq!(kept.len(), DEAD_LETTER_CAPACITY);
+        assert_eq!(kept[0].topic, format!("t/{}", DEAD_LETTER_CAPACITY + 2));
+        assert_eq!(kept.last().unwrap().topic, "t/3");
+    }
+
+    #[test]
+    fn test_endpoint_health() {
+        let m = Metrics::new();
+        m.record_endpoint_health(&EndpointType::Mqtt, 1, true);
+        m.record_endpoint_health(&EndpointType::Zmq, 2, false);
+
+        let output = m.render_prometheus("zeromqtt");
+        assert!(output.contains("zeromqtt_endpoint_healthy{endpoint=\"mqtt:1\"} 1"));
+        assert!(output.contains("zeromqtt_endpoint_healthy{endpoint=\"zmq:2\"} 0"));
+
+        m.clear_endpoint_health();
+        assert!(m.endpoint_health().is_empty());
+    }
+
+    #[test]
+    fn test_custom_namespace() {
+        let m = Metrics::new();
+        m.record_mqtt_sent();
+        m.record_bytes_sent(&EndpointType::Mqtt, 1, 10);
+
+        let output = m.render_prometheus("bridge");
+        assert!(output.contains("# TYPE bridge_mqtt_messages_sent_total counter"));
+        assert!(output.contains("bridge_mqtt_messages_sent_total 1"));
+        assert!(output.contains("bridge_endpoint_bytes_total{endpoint=\"mqtt:1\",direction=\"sent\"} 10"));
+        assert!(!output.contains("zeromqtt_"));
+
+        let output = m.render_prometheus("");
+        assert!(output.contains("\nmqtt_messages_sent_total 1"));
+
+        let output = m.render_prometheus("bridge_");
+        assert!(output.contains("\nbridge_mqtt_messages_sent_total 1"));
+    }
 }
@@ -1,6 +1,9 @@
 //! ZeroMQ client wrapper - supports XPUB/XSUB proxy pattern
 
-use crate::models::{ZmqConfig, ZmqSocketType};
+use crate::models::{ZmqConfig, ZmqRole, ZmqSocketType};
+use crate::zeromq::{
+    apply_multicast_options, bind_socket, connect_socket, decode_message, encode_message, split_identity_frames,
+};
 use std::sync::Arc;
 use std::thread;
 use tokio::sync::mpsc;
@@ -12,6 +15,8 @@ use zmq::{Context, Socket, SocketType};
 pub struct ZmqMessage {
     pub topic: String,
     pub payload: Vec<u8>,
+    /// Frames of a multipart message after the payload frame
+    pub frames: Vec<Vec<u8>>,
 }
 
 /// ZeroMQ client wrapper with XPUB/XSUB support
@@ -24,12 +29,11 @@ pub struct ZmqClient {
 }
 
 impl ZmqClient {
-    /// Create a new ZeroMQ client
-    pub fn new(config: ZmqConfig, message_tx: mpsc::Sender<ZmqMessage>) -> Result<Self, zmq::Error> {
-        let context = Context::new();
-
+    /// Create a new ZeroMQ client on a shared context; `inproc://` endpoints
+    /// only reach sockets of the same context
+    pub fn new(context: &Context, config: ZmqConfig, message_tx: mpsc::Sender<ZmqMessage>) -> Result<Self, zmq::Error> {
         Ok(Self {
-            context,
+            context: context.clone(),
             config,
             socket: None,
             message_tx,
@@ -44,20 +48,25 @@ impl ZmqClient {
             ZmqSocketType::XSub => SocketType::XSUB,
             ZmqSocketType::Pub => SocketType::PUB,
             ZmqSocketType::Sub => SocketType::SUB,
+            ZmqSocketType::Push => SocketType::PUSH,
+            ZmqSocketType::Pull => SocketType::PULL,
         };
 
         let socket = self.context.socket(socket_type)?;
         socket.set_sndhwm(self.config.high_water_mark as i32)?;
         socket.set_rcvhwm(self.config.high_water_mark as i32)?;
+        apply_multicast_options(&socket, &self.config)?;
 
-        // Bind or connect based on socket type
-        if let Some(ref endpoint) = self.config.bind_endpoint {
-            socket.bind(endpoint)?;
+        // Bind or connect based on socket type; connect-role endpoints only connect
+        if let Some(ref endpoint) = self.config.bind_endpoint
+            && self.config.role == ZmqRole::Bind
+        {
+            bind_socket(&socket, &self.config, endpoint)?;
             info!("[ZMQ:{}] Socket bound to: {}", self.config.name, endpoint);
         }
 
         for endpoint in &self.config.connect_endpoints {
-            socket.connect(endpoint)?;
+            connect_socket(&socket, &self.config, endpoint)?;
             info!("[ZMQ:{}] Socket connected to: {}", self.config.name, endpoint);
         }
 
@@ -73,11 +82,13 @@ impl ZmqClient {
     /// Publish a message
     pub fn publish(&self, topic: &str, payload: &[u8]) -> Result<(), zmq::Error> {
         if let Some(ref socket) = self.socket {
-            let mut message = topic.as_bytes().to_vec();
-            message.push(b' '); // Separator
-            message.extend_from_slice(payload);
-            
-            socket.send(&message, 0)?;
+            // PUSH/PULL has no topics: only the payload is sent, without
+            // blocking when no peer is connected
+            let (frames, flags) = match self.config.socket_type {
+                ZmqSocketType::Push => (vec![payload.to_vec()], zmq::DONTWAIT),
+                _ => (encode_message(self.config.topic_separator, topic, &[payload.to_vec()]), 0),
+            };
+            socket.send_multipart(frames, flags)?;
             debug!("[ZMQ:{}] Published to topic: {}", self.config.name, topic);
         } else {
             warn!("[ZMQ:{}] Socket not initialized", self.config.name);
@@ -87,8 +98,8 @@ impl ZmqClient {
 
     /// Start the receiver in a background thread
     pub fn start_receiver(&self) -> Result<(), zmq::Error> {
-        // Only start receiver for SUB/XSUB socket types
-        if !matches!(self.config.socket_type, ZmqSocketType::Sub | ZmqSocketType::XSub) {
+        // Only start receiver for SUB/XSUB/PULL socket types
+        if !matches!(self.config.socket_type, ZmqSocketType::Sub | ZmqSocketType::XSub | ZmqSocketType::Pull) {
             return Ok(());
         }
 
@@ -102,6 +113,7 @@ impl ZmqClient {
         thread::spawn(move || {
             let socket_type = match config.socket_type {
                 ZmqSocketType::XSub => SocketType::XSUB,
+                ZmqSocketType::Pull => SocketType::PULL,
                 _ => SocketType::SUB,
             };
 
@@ -112,34 +124,42 @@ impl ZmqClient {
                     return;
                 }
             };
+            if let Err(e) = apply_multicast_options(&socket, &config) {
+                warn!("[ZMQ:{}] Failed to set multicast options: {}", config.name, e);
+            }
 
             // Bind or connect
             if let Some(ref endpoint) = config.bind_endpoint
-                && socket.bind(endpoint).is_err()
+                && bind_socket(&socket, &config, endpoint).is_err()
             {
                 error!("[ZMQ:{}] Failed to bind", config.name);
                 return;
             }
 
             for endpoint in &config.connect_endpoints {
-                if let Err(e) = socket.connect(endpoint) {
+                if let Err(e) = connect_socket(&socket, &config, endpoint) {
                     warn!("[ZMQ:{}] Failed to connect to {}: {}", config.name, endpoint, e);
                 }
             }
 
-            let _ = socket.set_subscribe(b"");
+            if config.socket_type != ZmqSocketType::Pull {
+                let _ = socket.set_subscribe(b"");
+            }
             let _ = socket.set_rcvtimeo(1000);
 
             info!("[ZMQ:{}] Receiver started", config.name);
 
             while *running.read() {
-                match socket.recv_bytes(0) {
-                    Ok(data) => {
-                        if let Some(sep_pos) = data.iter().position(|&b| b == b' ') {
-                            let topic = String::from_utf8_lossy(&data[..sep_pos]).to_string();
-                            let payload = data[sep_pos + 1..].to_vec();
-
-                            let msg = ZmqMessage { topic, payload };
+                match socket.recv_multipart(0) {
+                    Ok(frames) => {
+                        let decoded = match config.socket_type {
+                            ZmqSocketType::Pull => (!frames.is_empty()).then(|| (String::new(), frames)),
+                            _ => decode_message(config.topic_separator, split_identity_frames(frames).1),
+                        };
+                        if let Some((topic, payload_frames)) = decoded {
+                            let mut payload_frames = payload_frames.into_iter();
+                            let payload = payload_frames.next().unwrap_or_default();
+                            let msg = ZmqMessage { topic, payload, frames: payload_frames.collect() };
                             
                             let tx_clone = tx.clone();
                             let _ = tokio::runtime::Handle::try_current()
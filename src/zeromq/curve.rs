@@ -0,0 +1,147 @@
+//! CURVE encryption of ZeroMQ links
+//!
+//! Keys are configured in Z85, the 40-character text form printed by
+//! `curve_keygen`. An endpoint with a server key binds as a CURVE server,
+//! decrypting with its secret key, and connects as a CURVE client that
+//! authenticates the server by that key and encrypts with its own key pair.
+//! Socket options only affect later binds and connects, so each bind and
+//! connect sets the role it needs first.
+
+use crate::models::ZmqConfig;
+
+/// Length of a CURVE key in Z85
+pub const CURVE_KEY_Z85_LEN: usize = 40;
+
+/// The 32 bytes of a Z85 CURVE key
+pub fn decode_curve_key(key: &str) -> Option<Vec<u8>> {
+    if key.len() != CURVE_KEY_Z85_LEN {
+        return None;
+    }
+    zmq::z85_decode(key).ok()
+}
+
+/// Reject keys that are not Z85 CURVE keys, and key sets CURVE cannot use:
+/// a server key needs the secret key, plus the public key if the socket
+/// connects anywhere
+pub fn validate_curve_keys(
+    server_key: Option<&str>,
+    public_key: Option<&str>,
+    secret_key: Option<&str>,
+    connects: bool,
+) -> Result<(), String> {
+    for (field, key) in [
+        ("curve_server_key", server_key),
+        ("curve_public_key", public_key),
+        ("curve_secret_key", secret_key),
+    ] {
+        if let Some(key) = key
+            && decode_curve_key(key).is_none()
+        {
+            return Err(format!("{} must be a {}-character Z85 key", field, CURVE_KEY_Z85_LEN));
+        }
+    }
+    if server_key.is_none() {
+        if public_key.is_some() || secret_key.is_some() {
+            return Err("curve_public_key and curve_secret_key require curve_server_key".to_string());
+        }
+        return Ok(());
+    }
+    if secret_key.is_none() {
+        return Err("curve_server_key requires curve_secret_key".to_string());
+    }
+    if connects && public_key.is_none() {
+        return Err("curve_server_key on a connecting socket requires curve_public_key".to_string());
+    }
+    Ok(())
+}
+
+fn curve_key(key: &Option<String>) -> Result<Vec<u8>, zmq::Error> {
+    key.as_deref().and_then(decode_curve_key).ok_or(zmq::Error::EINVAL)
+}
+
+/// Bind `socket` to `endpoint`, as a CURVE server if `config` has a server key
+pub fn bind_socket(socket: &zmq::Socket, config: &ZmqConfig, endpoint: &str) -> Result<(), zmq::Error> {
+    if config.curve_server_key.is_some() {
+        socket.set_curve_server(true)?;
+        socket.set_curve_secretkey(&curve_key(&config.curve_secret_key)?)?;
+    }
+    socket.bind(endpoint)
+}
+
+/// Connect `socket` to `endpoint`, as a CURVE client if `config` has a server key
+pub fn connect_socket(socket: &zmq::Socket, config: &ZmqConfig, endpoint: &str) -> Result<(), zmq::Error> {
+    if config.curve_server_key.is_some() {
+        socket.set_curve_server(false)?;
+        socket.set_curve_serverkey(&curve_key(&config.curve_server_key)?)?;
+        socket.set_curve_publickey(&curve_key(&config.curve_public_key)?)?;
+        socket.set_curve_secretkey(&curve_key(&config.curve_secret_key)?)?;
+    }
+    socket.connect(endpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_curve_keys() {
+        let server = zmq::CurveKeyPair::new().unwrap();
+        let client = zmq::CurveKeyPair::new().unwrap();
+        let server_public = zmq::z85_encode(&server.public_key).unwrap();
+        let server_secret = zmq::z85_encode(&server.secret_key).unwrap();
+        let client_public = zmq::z85_encode(&client.public_key).unwrap();
+        let client_secret = zmq::z85_encode(&client.secret_key).unwrap();
+
+        assert!(validate_curve_keys(None, None, None, true).is_ok());
+        // A server only needs its secret key
+        assert!(validate_curve_keys(Some(&server_public), None, Some(&server_secret), false).is_ok());
+        assert!(validate_curve_keys(Some(&server_public), Some(&client_public), Some(&client_secret), true).is_ok());
+
+        assert!(validate_curve_keys(Some(&server_public), Some(&client_public), None, true).is_err());
+        assert!(validate_curve_keys(Some(&server_public), None, Some(&client_secret), true).is_err());
+        assert!(validate_curve_keys(None, Some(&client_public), Some(&client_secret), true).is_err());
+
+        let err = validate_curve_keys(Some("not-z85"), None, Some(&server_secret), false).unwrap_err();
+        assert!(err.contains("curve_server_key"));
+        // Right length, but '~' is not in the Z85 alphabet
+        assert!(validate_curve_keys(Some(&"~".repeat(40)), None, Some(&server_secret), false).is_err());
+    }
+
+    #[test]
+    fn test_curve_link() {
+        if zmq::has("curve") != Some(true) {
+            return;
+        }
+        let server_keys = zmq::CurveKeyPair::new().unwrap();
+        let client_keys = zmq::CurveKeyPair::new().unwrap();
+        let server_config = ZmqConfig {
+            curve_server_key: Some(zmq::z85_encode(&server_keys.public_key).unwrap()),
+            curve_secret_key: Some(zmq::z85_encode(&server_keys.secret_key).unwrap()),
+            ..Default::default()
+        };
+        let client_config = ZmqConfig {
+            curve_server_key: server_config.curve_server_key.clone(),
+            curve_public_key: Some(zmq::z85_encode(&client_keys.public_key).unwrap()),
+            curve_secret_key: Some(zmq::z85_encode(&client_keys.secret_key).unwrap()),
+            ..Default::default()
+        };
+
+        let context = zmq::Context::new();
+        let pull = context.socket(zmq::PULL).unwrap();
+        bind_socket(&pull, &server_config, "tcp://127.0.0.1:*").unwrap();
+        let endpoint = pull.get_last_endpoint().unwrap().unwrap();
+        let push = context.socket(zmq::PUSH).unwrap();
+        connect_socket(&push, &client_config, &endpoint).unwrap();
+
+        push.send("sealed", 0).unwrap();
+        pull.set_rcvtimeo(2000).unwrap();
+        assert_eq!(pull.recv_bytes(0).unwrap(), b"sealed");
+
+        // A client without the keys never completes the handshake
+        let plain = context.socket(zmq::PUSH).unwrap();
+        plain.connect(&endpoint).unwrap();
+        plain.send("plain", zmq::DONTWAIT).ok();
+        pull.set_rcvtimeo(200).unwrap();
+        assert!(pull.recv_bytes(0).is_err());
+    }
+}
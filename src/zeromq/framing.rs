@@ -0,0 +1,126 @@
+//! Topic/payload framing of ZeroMQ messages
+
+use crate::models::TopicSeparator;
+
+/// Frames carrying `topic` and the payload frames in the given layout.
+/// Single-frame layouts join the payload frames in order.
+pub fn encode_message(separator: TopicSeparator, topic: &str, payload: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    match separator {
+        TopicSeparator::Delimiter { byte } => {
+            let mut frame = topic.as_bytes().to_vec();
+            frame.push(byte);
+            frame.extend(payload.concat());
+            vec![frame]
+        }
+        TopicSeparator::LengthPrefixed => {
+            let mut frame = (topic.len() as u32).to_be_bytes().to_vec();
+            frame.extend_from_slice(topic.as_bytes());
+            frame.extend(payload.concat());
+            vec![frame]
+        }
+        TopicSeparator::Multipart => {
+            let mut frames = Vec::with_capacity(1 + payload.len());
+            frames.push(topic.as_bytes().to_vec());
+            frames.extend_from_slice(payload);
+            frames
+        }
+    }
+}
+
+/// Topic and payload frames of received data frames; `None` if they are not
+/// laid out as `separator` describes. Multipart messages keep every frame
+/// after the topic, single-frame layouts yield one payload frame.
+pub fn decode_message(separator: TopicSeparator, frames: Vec<Vec<u8>>) -> Option<(String, Vec<Vec<u8>>)> {
+    match separator {
+        TopicSeparator::Delimiter { byte } => {
+            let [frame] = <[Vec<u8>; 1]>::try_from(frames).ok()?;
+            let pos = frame.iter().position(|&b| b == byte)?;
+            Some((String::from_utf8_lossy(&frame[..pos]).into_owned(), vec![frame[pos + 1..].to_vec()]))
+        }
+        TopicSeparator::LengthPrefixed => {
+            let [frame] = <[Vec<u8>; 1]>::try_from(frames).ok()?;
+            let len = u32::from_be_bytes(frame.get(..4)?.try_into().ok()?) as usize;
+            let topic = frame.get(4..4 + len)?;
+            Some((String::from_utf8_lossy(topic).into_owned(), vec![frame[4 + len..].to_vec()]))
+        }
+        TopicSeparator::Multipart => {
+            if frames.len() < 2 {
+                return None;
+            }
+            let mut frames = frames.into_iter();
+            let topic = frames.next()?;
+            Some((String::from_utf8_lossy(&topic).into_owned(), frames.collect()))
+        }
+    }
+}
+
+/// Whether a topic contains the delimiter byte, which would corrupt the
+/// topic/payload split on the receiving side
+pub fn has_separator(separator: TopicSeparator, topic: &str) -> bool {
+    match separator {
+        TopicSeparator::Delimiter { byte } => topic.as_bytes().contains(&byte),
+        TopicSeparator::LengthPrefixed | TopicSeparator::Multipart => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zeromq::{split_identity_frames, with_identity_frames};
+
+    const SPACE: TopicSeparator = TopicSeparator::Delimiter { byte: b' ' };
+
+    fn frames(parts: &[&[u8]]) -> Vec<Vec<u8>> {
+        parts.iter().map(|p| p.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_has_separator() {
+        assert!(has_separator(SPACE, "a b/c"));
+        assert!(!has_separator(SPACE, "a_b/c"));
+        assert!(has_separator(TopicSeparator::Delimiter { byte: b'|' }, "a|b"));
+        assert!(!has_separator(TopicSeparator::Multipart, "a b/c"));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Framed layouts keep spaces in topics and leading binary payload bytes
+        for separator in [TopicSeparator::LengthPrefixed, TopicSeparator::Multipart] {
+            let encoded = encode_message(separator, "sensors/a b", &frames(&[b" \x00binary payload"]));
+            assert_eq!(
+                decode_message(separator, encoded),
+                Some(("sensors/a b".to_string(), frames(&[b" \x00binary payload"])))
+            );
+        }
+        assert_eq!(encode_message(SPACE, "t", &frames(&[b"p"])), frames(&[b"t p"]));
+        assert_eq!(decode_message(SPACE, frames(&[b"t p q"])), Some(("t".to_string(), frames(&[b"p q"]))));
+    }
+
+    #[test]
+    fn test_three_frame_round_trip() {
+        let payload = frames(&[b"header", b"body"]);
+        let encoded = encode_message(TopicSeparator::Multipart, "orders/1", &payload);
+        assert_eq!(encoded, frames(&[b"orders/1", b"header", b"body"]));
+        assert_eq!(
+            decode_message(TopicSeparator::Multipart, encoded.clone()),
+            Some(("orders/1".to_string(), payload.clone()))
+        );
+
+        // Through a routing envelope and back
+        let sent = with_identity_frames(Some(b"peer".to_vec()), encoded.clone());
+        assert_eq!(sent.len(), 5);
+        assert_eq!(split_identity_frames(sent), (Some(b"peer".to_vec()), encoded.clone()));
+        assert_eq!(split_identity_frames(encoded.clone()), (None, encoded));
+
+        // Single-frame layouts join the frames
+        assert_eq!(encode_message(SPACE, "t", &payload), frames(&[b"t headerbody"]));
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        assert_eq!(decode_message(SPACE, frames(&[b"no-separator"])), None);
+        assert_eq!(decode_message(TopicSeparator::LengthPrefixed, vec![vec![0, 0, 0, 9, b't']]), None);
+        assert_eq!(decode_message(TopicSeparator::LengthPrefixed, vec![vec![0, 0]]), None);
+        assert_eq!(decode_message(TopicSeparator::Multipart, frames(&[b"t p"])), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Routing identity frames on multipart ZeroMQ messages
+//!
+//! Only a ROUTER envelope carries an identity: the identity frame, an empty
+//! delimiter frame, then the data frames. Any other multipart message is data
+//! throughout, so extra frames of a publisher are never taken for an identity.
+
+/// Split received frames into an optional identity and the data frames
+pub fn split_identity_frames(mut frames: Vec<Vec<u8>>) -> (Option<Vec<u8>>, Vec<Vec<u8>>) {
+    if frames.len() > 2 && frames[1].is_empty() {
+        let data = frames.split_off(2);
+        return (frames.into_iter().next(), data);
+    }
+    (None, frames)
+}
+
+/// Split received frames into an optional identity and a single data frame
+pub fn split_identity(frames: Vec<Vec<u8>>) -> (Option<Vec<u8>>, Vec<u8>) {
+    let (identity, mut data) = split_identity_frames(frames);
+    (identity, data.pop().unwrap_or_default())
+}
+
+/// Build the frames to send for data frames and an optional identity
+pub fn with_identity_frames(identity: Option<Vec<u8>>, data: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    match identity {
+        Some(identity) => [identity, Vec::new()].into_iter().chain(data).collect(),
+        None => data,
+    }
+}
+
+/// Build the frames to send for a data frame and optional identity
+pub fn with_identity(identity: Option<Vec<u8>>, data: Vec<u8>) -> Vec<Vec<u8>> {
+    with_identity_frames(identity, vec![data])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_identity() {
+        assert_eq!(split_identity(vec![b"t p".to_vec()]), (None, b"t p".to_vec()));
+        assert_eq!(
+            split_identity(vec![b"peer-1".to_vec(), Vec::new(), b"t p".to_vec()]),
+            (Some(b"peer-1".to_vec()), b"t p".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_identity_frames_round_trip() {
+        let frames = with_identity(Some(vec![0, 1, 0xff]), b"t p".to_vec());
+        assert_eq!(split_identity(frames), (Some(vec![0, 1, 0xff]), b"t p".to_vec()));
+        assert_eq!(with_identity(None, b"t p".to_vec()), vec![b"t p".to_vec()]);
+    }
+
+    #[test]
+    fn test_multipart_identity_frames() {
+        let data = vec![b"t".to_vec(), b"p".to_vec()];
+        assert_eq!(split_identity_frames(data.clone()), (None, data.clone()));
+
+        let frames = with_identity_frames(Some(b"peer".to_vec()), data.clone());
+        assert_eq!(frames.len(), 4);
+        assert_eq!(split_identity_frames(frames), (Some(b"peer".to_vec()), data));
+    }
+
+    #[test]
+    fn test_extra_frames_are_not_an_identity() {
+        // A publisher's [topic, payload] has no ROUTER envelope
+        let frames = vec![b"t".to_vec(), b"p".to_vec(), b"q".to_vec()];
+        assert_eq!(split_identity_frames(frames.clone()), (None, frames));
+    }
+}
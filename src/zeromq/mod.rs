@@ -1,5 +1,19 @@
 //! ZeroMQ client module
 
 pub mod client;
+pub mod curve;
+pub mod framing;
+pub mod identity;
+pub mod monitor;
+pub mod multicast;
+pub mod signing;
+pub mod transport;
 
 pub use client::*;
+pub use curve::*;
+pub use framing::*;
+pub use identity::*;
+pub use monitor::*;
+pub use multicast::*;
+pub use signing::*;
+pub use transport::*;
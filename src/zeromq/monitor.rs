@@ -0,0 +1,190 @@
+//! Socket liveness from ZeroMQ monitor events
+//!
+//! ZeroMQ hides connection state behind its sockets: a SUB connected to a
+//! dead publisher looks just like one whose publisher is quiet. A monitor
+//! reports binds, connects and disconnects, from which the bridge derives
+//! whether an endpoint is actually reachable, and when a connection came
+//! back, e.g. after a publisher restart.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zmq::SocketEvent;
+
+/// Distinguishes monitor endpoints of sockets sharing a context
+static NEXT_MONITOR: AtomicU64 = AtomicU64::new(0);
+
+/// Events `Liveness` is built from
+const MONITORED: [SocketEvent; 5] = [
+    SocketEvent::LISTENING,
+    SocketEvent::BIND_FAILED,
+    SocketEvent::CONNECTED,
+    SocketEvent::DISCONNECTED,
+    SocketEvent::CLOSED,
+];
+
+/// Bind and connection state of a socket, built from monitor events
+#[derive(Debug, Default)]
+pub struct Liveness {
+    expects_bind: bool,
+    expects_peers: bool,
+    bound: bool,
+    connected: HashSet<String>,
+    /// Endpoints connected at least once
+    seen: HashSet<String>,
+    /// Endpoints connected again since the last `take_rejoined`
+    rejoined: Vec<String>,
+}
+
+impl Liveness {
+    /// A socket that binds to `bind`, if set, and connects to `connect`.
+    /// `inproc://` binds and connections emit no events, so they are not
+    /// waited for: such a bind is live once it succeeds.
+    pub fn new(bind: Option<&str>, connect: &[String]) -> Self {
+        Self {
+            expects_bind: bind.is_some_and(|e| !e.starts_with("inproc://")),
+            expects_peers: connect.iter().any(|e| !e.starts_with("inproc://")),
+            ..Default::default()
+        }
+    }
+
+    /// Apply one monitor event (its raw id) for `endpoint`
+    pub fn apply(&mut self, event: u16, endpoint: &str) {
+        let is = |e: SocketEvent| event == e.to_raw();
+        if is(SocketEvent::LISTENING) {
+            self.bound = true;
+        } else if is(SocketEvent::BIND_FAILED) {
+            self.bound = false;
+        } else if is(SocketEvent::CONNECTED) {
+            self.connected.insert(endpoint.to_string());
+            if !self.seen.insert(endpoint.to_string()) {
+                self.rejoined.push(endpoint.to_string());
+            }
+        } else if is(SocketEvent::DISCONNECTED) || is(SocketEvent::CLOSED) {
+            self.connected.remove(endpoint);
+        }
+    }
+
+    /// Bound if it binds, and connected to a peer if it connects
+    pub fn healthy(&self) -> bool {
+        (!self.expects_bind || self.bound) && (!self.expects_peers || !self.connected.is_empty())
+    }
+
+    /// Endpoints that reconnected since the last call
+    pub fn take_rejoined(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.rejoined)
+    }
+}
+
+/// A monitor attached to a socket, feeding its `Liveness`
+pub struct SocketMonitor {
+    events: zmq::Socket,
+    liveness: Liveness,
+}
+
+impl SocketMonitor {
+    /// Monitor `socket`. Attach before binding or connecting, or the events
+    /// of those calls are missed.
+    pub fn attach(context: &zmq::Context, socket: &zmq::Socket, liveness: Liveness) -> Result<Self, zmq::Error> {
+        let endpoint = format!("inproc://zeromqtt-monitor-{}", NEXT_MONITOR.fetch_add(1, Ordering::Relaxed));
+        let mask = MONITORED.iter().fold(0, |mask, e| mask | e.to_raw() as i32);
+        socket.monitor(&endpoint, mask)?;
+        let events = context.socket(zmq::PAIR)?;
+        events.connect(&endpoint)?;
+        Ok(Self { events, liveness })
+    }
+
+    /// Drain pending events and report whether the socket is live
+    pub fn healthy(&mut self) -> bool {
+        self.drain();
+        self.liveness.healthy()
+    }
+
+    /// Drain pending events and return the endpoints that reconnected
+    pub fn rejoined(&mut self) -> Vec<String> {
+        self.drain();
+        self.liveness.take_rejoined()
+    }
+
+    fn drain(&mut self) {
+        while let Ok(frames) = self.events.recv_multipart(zmq::DONTWAIT) {
+            if let Some((event, endpoint)) = parse_event(&frames) {
+                self.liveness.apply(event, &endpoint);
+            }
+        }
+    }
+}
+
+/// Event id and endpoint of a monitor message: a frame holding the event id
+/// (u16) and value (u32), then a frame holding the endpoint address
+fn parse_event(frames: &[Vec<u8>]) -> Option<(u16, String)> {
+    let header = frames.first()?;
+    let id = u16::from_le_bytes([*header.first()?, *header.get(1)?]);
+    let endpoint = String::from_utf8_lossy(frames.get(1)?).into_owned();
+    Some((id, endpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_liveness() {
+        let mut liveness = Liveness::new(None, &["tcp://10.0.0.1:5556".to_string()]);
+        assert!(!liveness.healthy());
+        liveness.apply(SocketEvent::CONNECTED.to_raw(), "tcp://10.0.0.1:5556");
+        assert!(liveness.healthy());
+        liveness.apply(SocketEvent::DISCONNECTED.to_raw(), "tcp://10.0.0.1:5556");
+        assert!(!liveness.healthy());
+
+        // Nothing to wait for on in-process connections
+        assert!(Liveness::new(None, &["inproc://relay".to_string()]).healthy());
+    }
+
+    #[test]
+    fn test_rejoin_detected() {
+        let endpoint = "tcp://10.0.0.1:5556";
+        let mut liveness = Liveness::new(None, &[endpoint.to_string()]);
+        liveness.apply(SocketEvent::CONNECTED.to_raw(), endpoint);
+        assert!(liveness.take_rejoined().is_empty());
+
+        liveness.apply(SocketEvent::DISCONNECTED.to_raw(), endpoint);
+        liveness.apply(SocketEvent::CONNECTED.to_raw(), endpoint);
+        assert_eq!(liveness.take_rejoined(), vec![endpoint.to_string()]);
+        assert!(liveness.take_rejoined().is_empty());
+    }
+
+    #[test]
+    fn test_bind_liveness() {
+        let mut liveness = Liveness::new(Some("tcp://0.0.0.0:5555"), &[]);
+        assert!(!liveness.healthy());
+        liveness.apply(SocketEvent::LISTENING.to_raw(), "tcp://0.0.0.0:5555");
+        assert!(liveness.healthy());
+
+        assert!(Liveness::new(Some("inproc://relay"), &[]).healthy());
+    }
+
+    #[test]
+    fn test_monitor_reports_bind() {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::PUB).unwrap();
+        let mut monitor = SocketMonitor::attach(&context, &socket, Liveness::new(Some("tcp://127.0.0.1:*"), &[])).unwrap();
+        socket.bind("tcp://127.0.0.1:*").unwrap();
+
+        let mut healthy = false;
+        for _ in 0..50 {
+            healthy = monitor.healthy();
+            if healthy {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(healthy);
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let frames = vec![vec![0x01, 0x00, 7, 0, 0, 0], b"tcp://a:1".to_vec()];
+        assert_eq!(parse_event(&frames), Some((SocketEvent::CONNECTED.to_raw(), "tcp://a:1".to_string())));
+        assert_eq!(parse_event(&[vec![0x01]]), None);
+    }
+}
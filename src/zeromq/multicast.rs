@@ -0,0 +1,100 @@
+//! PGM/EPGM multicast endpoints
+//!
+//! `pgm://` (raw PGM) and `epgm://` (PGM over UDP) deliver one stream to
+//! every subscriber on a LAN segment, e.g. `epgm://eth0;239.192.1.1:5555`.
+//! libzmq only speaks them when built with OpenPGM (`--with-pgm`), and only
+//! for publish/subscribe sockets. Rate, hops and recovery window must be set
+//! before the socket binds or connects.
+
+use crate::models::{ZmqConfig, ZmqSocketType};
+use crate::zeromq::endpoint_transport;
+
+/// Whether `endpoint` uses a PGM transport
+pub fn is_multicast(endpoint: &str) -> bool {
+    matches!(endpoint_transport(endpoint), Some("pgm" | "epgm"))
+}
+
+/// Reject PGM endpoints on sockets without publish/subscribe semantics, and
+/// addresses not of the form `interface;group:port`
+pub fn validate_multicast_endpoint(endpoint: &str, socket_type: &ZmqSocketType) -> Result<(), String> {
+    if !is_multicast(endpoint) {
+        return Ok(());
+    }
+    if matches!(socket_type, ZmqSocketType::Push | ZmqSocketType::Pull) {
+        return Err(format!("multicast endpoint '{}' requires a PUB, XPUB, SUB or XSUB socket", endpoint));
+    }
+    let address = endpoint.split_once("://").map_or("", |(_, address)| address);
+    let valid = address.split_once(';').is_some_and(|(interface, group)| {
+        !interface.is_empty()
+            && group
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0))
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("multicast endpoint '{}' must look like epgm://interface;group:port", endpoint))
+    }
+}
+
+/// Apply the configured multicast options if any endpoint of `config` uses PGM
+pub fn apply_multicast_options(socket: &zmq::Socket, config: &ZmqConfig) -> Result<(), zmq::Error> {
+    let multicast = config
+        .bind_endpoint
+        .iter()
+        .chain(&config.connect_endpoints)
+        .any(|e| is_multicast(e));
+    if !multicast {
+        return Ok(());
+    }
+    if let Some(rate) = config.multicast_rate_kbps {
+        socket.set_rate(rate as i32)?;
+    }
+    if let Some(hops) = config.multicast_hops {
+        socket.set_multicast_hops(hops as i32)?;
+    }
+    if let Some(recovery) = config.multicast_recovery_ms {
+        socket.set_recovery_ivl(recovery as i32)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_multicast_endpoint() {
+        let pub_socket = ZmqSocketType::Pub;
+        assert!(validate_multicast_endpoint("epgm://eth0;239.192.1.1:5555", &pub_socket).is_ok());
+        assert!(validate_multicast_endpoint("pgm://192.168.1.10;239.192.1.1:5555", &ZmqSocketType::Sub).is_ok());
+        // Other transports are not checked here
+        assert!(validate_multicast_endpoint("tcp://*:5555", &ZmqSocketType::Push).is_ok());
+
+        assert!(validate_multicast_endpoint("epgm://239.192.1.1:5555", &pub_socket).is_err());
+        assert!(validate_multicast_endpoint("epgm://eth0;239.192.1.1", &pub_socket).is_err());
+        assert!(validate_multicast_endpoint("epgm://;239.192.1.1:5555", &pub_socket).is_err());
+        let err = validate_multicast_endpoint("epgm://eth0;239.192.1.1:5555", &ZmqSocketType::Push).unwrap_err();
+        assert!(err.contains("PUB"));
+    }
+
+    #[test]
+    fn test_options_only_for_multicast() {
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::PUB).unwrap();
+        let mut config = ZmqConfig {
+            multicast_rate_kbps: Some(1000),
+            multicast_hops: Some(4),
+            multicast_recovery_ms: Some(2000),
+            ..Default::default()
+        };
+        apply_multicast_options(&socket, &config).unwrap();
+        assert_eq!(socket.get_multicast_hops().unwrap(), 1);
+
+        config.bind_endpoint = Some("epgm://eth0;239.192.1.1:5555".to_string());
+        apply_multicast_options(&socket, &config).unwrap();
+        assert_eq!(socket.get_rate().unwrap(), 1000);
+        assert_eq!(socket.get_multicast_hops().unwrap(), 4);
+        assert_eq!(socket.get_recovery_ivl().unwrap(), 2000);
+    }
+}
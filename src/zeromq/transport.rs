@@ -0,0 +1,55 @@
+//! Transport allowlist for ZeroMQ endpoints
+
+/// Transport of an endpoint such as `tcp://host:port`, without `://`
+pub fn endpoint_transport(endpoint: &str) -> Option<&str> {
+    endpoint.split_once("://").map(|(transport, _)| transport)
+}
+
+/// Check an endpoint against allowed transports (`tcp`, `ipc`, ...); an
+/// empty list allows every transport
+pub fn check_transport(endpoint: &str, allowed: &[String]) -> Result<(), String> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    let Some(transport) = endpoint_transport(endpoint) else {
+        return Err(format!("ZMQ endpoint '{}' has no transport prefix", endpoint));
+    };
+    let permitted = allowed
+        .iter()
+        .any(|a| a.trim_end_matches("://").eq_ignore_ascii_case(transport));
+    if permitted {
+        Ok(())
+    } else {
+        Err(format!(
+            "ZMQ transport '{}://' is not allowed (allowed: {})",
+            transport,
+            allowed.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_allowlist_allows_all() {
+        assert_eq!(check_transport("ipc:///tmp/zeromqtt.sock", &[]), Ok(()));
+        assert_eq!(check_transport("inproc://bridge", &[]), Ok(()));
+    }
+
+    #[test]
+    fn test_allowed_transports() {
+        let allowed = vec!["tcp".to_string(), "ipc://".to_string()];
+        assert_eq!(check_transport("tcp://127.0.0.1:5555", &allowed), Ok(()));
+        assert_eq!(check_transport("ipc:///tmp/zeromqtt.sock", &allowed), Ok(()));
+    }
+
+    #[test]
+    fn test_rejected_transports() {
+        let allowed = vec!["tcp".to_string()];
+        assert!(check_transport("inproc://bridge", &allowed).is_err());
+        assert!(check_transport("ipc:///tmp/zeromqtt.sock", &allowed).is_err());
+        assert!(check_transport("127.0.0.1:5555", &allowed).is_err());
+    }
+}
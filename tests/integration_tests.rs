@@ -23,6 +23,7 @@ mod bridge_tests {
             direction,
             enabled,
             description: None,
+            ..Default::default()
         }
     }
 
@@ -123,6 +124,9 @@ mod worker_tests {
             source_id: 1,
             topic: "test/topic".to_string(),
             payload: b"hello".to_vec(),
+            identity: None,
+            user_properties: Vec::new(),
+            frames: Vec::new(),
         };
         
         assert_eq!(msg.source, MessageSource::Mqtt);
@@ -479,4 +483,1407 @@ mod e2e_bridge_tests {
         println!("Start with: cargo run");
         println!("Then use the web interface to configure mappings and start the bridge.");
     }
+
+    /// Test that the bridge announces itself with a birth message on connect
+    #[test]
+    #[ignore]
+    fn test_birth_message_on_connect() {
+        use paho_mqtt::{AsyncClient, CreateOptionsBuilder, ConnectOptionsBuilder};
+        use zeromqtt::models::MqttConfig;
+        use zeromqtt::mqtt::MqttClient;
+
+        let test_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let status_topic = format!("zeromqtt/test/{}/status", test_id);
+
+        println!("\n=== Birth Message Test ===\n");
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // Monitor subscribed to the status topic
+            let create_opts = CreateOptionsBuilder::new()
+                .server_uri("tcp://broker.emqx.io:1883")
+                .client_id(&format!("zeromqtt-test-monitor-{}", test_id))
+                .finalize();
+            let mut monitor = AsyncClient::new(create_opts).expect("Failed to create MQTT client");
+            monitor
+                .connect(ConnectOptionsBuilder::new().clean_session(true).finalize())
+                .await
+                .expect("Failed to connect monitor");
+            let stream = monitor.get_stream(10);
+            monitor.subscribe(&status_topic, 1).await.expect("Failed to subscribe");
+
+            // Bridge-side client with will/birth configured
+            let config = MqttConfig {
+                client_id: format!("zeromqtt-test-bridge-{}", test_id),
+                will_topic: Some(status_topic.clone()),
+                birth_topic: Some(status_topic.clone()),
+                ..Default::default()
+            };
+            let (tx, _rx) = tokio::sync::mpsc::channel(10);
+            let bridge = MqttClient::new(config, tx).expect("Failed to create bridge client");
+            bridge.connect().await.expect("Failed to connect bridge");
+
+            let received = tokio::time::timeout(
+                Duration::from_secs(5),
+                async { stream.recv().await.ok().flatten() }
+            ).await;
+
+            // Clear the retained status so the public broker is left clean
+            let clear = paho_mqtt::Message::new_retained(&status_topic, Vec::new(), 1);
+            let _ = bridge.inner().publish(clear).await;
+            bridge.disconnect().await.ok();
+            monitor.disconnect(None).await.ok();
+
+            let msg = received.ok().flatten().expect("No birth message received");
+            println!("[MQTT] Received: {} {}", msg.topic(), msg.payload_str());
+            assert_eq!(msg.payload_str(), "online");
+        });
+    }
+}
+
+/// In-process tests: the bridge runs against an in-memory database and
+/// local ZMQ sockets, so no server, broker or network access is needed.
+/// MQTT endpoints still need a broker and are covered by `e2e_bridge_tests`.
+mod in_process_tests {
+    use std::time::Duration;
+    use zeromqtt::bridge::BridgeCore;
+    use zeromqtt::config::AppConfig;
+    use zeromqtt::db::{init_memory_db, Repository};
+    use zeromqtt::models::*;
+    use zeromqtt::state::AppState;
+
+    /// Bridge wired exactly as in `main`, backed by an in-memory database
+    struct Harness {
+        state: AppState,
+    }
+
+    impl Harness {
+        async fn new() -> Self {
+            let config = AppConfig::default();
+            let repo = Repository::new(init_memory_db().await.expect("in-memory database"));
+            let bridge = BridgeCore::new(repo.clone(), config.bridge.clone());
+            Self {
+                state: AppState::new(config, repo, bridge),
+            }
+        }
+
+        async fn add_zmq(&self, name: &str, socket_type: &str, bind: Option<&str>, connect: &[&str]) -> ZmqConfig {
+            let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": socket_type,
+                "bind_endpoint": bind,
+                "connect_endpoints": connect,
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 100,
+            }))
+            .unwrap();
+            self.state.repo.add_zmq_config(&req).await.unwrap()
+        }
+
+        async fn add_mqtt(&self, name: &str, broker_url: &str, client_id: &str, clean_session: bool) -> MqttConfig {
+            let req: CreateMqttConfigRequest = serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "broker_url": broker_url,
+                "port": 1883,
+                "client_id": client_id,
+                "username": null,
+                "password": null,
+                "use_tls": false,
+                "keep_alive_seconds": 30,
+                "clean_session": clean_session,
+            }))
+            .unwrap();
+            self.state.repo.add_mqtt_config(&req).await.unwrap()
+        }
+
+        async fn add_mapping(&self, mapping: serde_json::Value) -> TopicMapping {
+            let req: CreateMappingRequest = serde_json::from_value(mapping).unwrap();
+            self.state.repo.add_mapping(&req).await.unwrap()
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_zmq_to_zmq_forwarding_in_process() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25601"]).await;
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25602"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25601").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25602").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        // Keep publishing until both slow-joiner subscriptions are established
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send("in/sensor 42", 0).unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 42"[..]));
+
+        let stats = harness.state.repo.get_stats().await.unwrap();
+        assert!(stats.zmq_received >= 1);
+    }
+
+    /// Endpoints share one ZMQ context, so they can relay to each other over inproc://
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_inproc_relay_between_endpoints() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25605"]).await;
+        let relay_out = harness.add_zmq("relay-out", "pub", Some("inproc://zeromqtt-test-relay"), &[]).await;
+        let relay_in = harness.add_zmq("relay-in", "sub", None, &["inproc://zeromqtt-test-relay"]).await;
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25606"), &[]).await;
+        for (source, target, source_topic, target_topic) in [
+            (&ingress, &relay_out, "in/#", "relay/data"),
+            (&relay_in, &egress, "relay/#", "out/data"),
+        ] {
+            harness
+                .add_mapping(serde_json::json!({
+                    "source_endpoint_type": "zmq",
+                    "source_endpoint_id": source.id,
+                    "target_endpoint_type": "zmq",
+                    "target_endpoint_id": target.id,
+                    "source_topic": source_topic,
+                    "target_topic": target_topic,
+                    "direction": "zmq_to_zmq",
+                    "enabled": true,
+                    "description": null,
+                }))
+                .await;
+        }
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25605").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25606").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send("in/sensor 42", 0).unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 42"[..]));
+    }
+
+    /// Multipart input, with a space in its topic and leading the payload, is
+    /// forwarded to a length-prefixed output
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_zmq_topic_separators() {
+        let harness = Harness::new().await;
+        let mut endpoints = Vec::new();
+        for (name, socket_type, bind, connect, separator) in [
+            ("ingress", "sub", None, vec!["tcp://127.0.0.1:25607"], serde_json::json!({ "type": "multipart" })),
+            ("egress", "pub", Some("tcp://127.0.0.1:25608"), vec![], serde_json::json!({ "type": "length_prefixed" })),
+        ] {
+            let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": socket_type,
+                "bind_endpoint": bind,
+                "connect_endpoints": connect,
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 100,
+                "topic_separator": separator,
+            }))
+            .unwrap();
+            endpoints.push(harness.state.repo.add_zmq_config(&req).await.unwrap());
+        }
+        assert_eq!(endpoints[0].topic_separator, TopicSeparator::Multipart);
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": endpoints[0].id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": endpoints[1].id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25607").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25608").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send_multipart([&b"in/room 1"[..], &b" 42"[..]], 0).unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        let mut expected = 8u32.to_be_bytes().to_vec();
+        expected.extend_from_slice(b"out/data 42");
+        assert_eq!(received, Some(expected));
+    }
+
+    /// Every frame of a `[topic, header, body]` message survives the bridge
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_zmq_multipart_frames_round_trip() {
+        let harness = Harness::new().await;
+        let mut endpoints = Vec::new();
+        for (name, socket_type, bind, connect) in [
+            ("ingress", "sub", None, vec!["tcp://127.0.0.1:25609"]),
+            ("egress", "pub", Some("tcp://127.0.0.1:25610"), vec![]),
+        ] {
+            let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+                "name": name,
+                "enabled": true,
+                "socket_type": socket_type,
+                "bind_endpoint": bind,
+                "connect_endpoints": connect,
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 100,
+                "topic_separator": { "type": "multipart" },
+            }))
+            .unwrap();
+            endpoints.push(harness.state.repo.add_zmq_config(&req).await.unwrap());
+        }
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": endpoints[0].id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": endpoints[1].id,
+                "source_topic": "orders/#",
+                "target_topic": "out/orders",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25609").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25610").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send_multipart([&b"orders/1"[..], &b"v=2"[..], &b"{\"qty\":3}"[..]], 0).unwrap();
+            if let Ok(frames) = consumer.recv_multipart(0) {
+                received = Some(frames);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(
+            received,
+            Some(vec![b"out/orders".to_vec(), b"v=2".to_vec(), b"{\"qty\":3}".to_vec()])
+        );
+    }
+
+    /// Topic-less PULL input reaches a PUSH output frame for frame
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_zmq_push_pull_pipeline() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "pull", Some("tcp://127.0.0.1:25611"), &[]).await;
+        let egress = harness.add_zmq("egress", "push", Some("tcp://127.0.0.1:25612"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "#",
+                "target_topic": "jobs",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUSH).unwrap();
+        producer.connect("tcp://127.0.0.1:25611").unwrap();
+        let consumer = context.socket(zmq::PULL).unwrap();
+        consumer.connect("tcp://127.0.0.1:25612").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send_multipart([&b"job 1"[..], &b"{\"n\":1}"[..]], zmq::DONTWAIT).ok();
+            if let Ok(frames) = consumer.recv_multipart(0) {
+                received = Some(frames);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received, Some(vec![b"job 1".to_vec(), b"{\"n\":1}".to_vec()]));
+    }
+
+    /// A restart keeps the message counters when asked to and zeroes them otherwise
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_restart_preserves_stats() {
+        let harness = Harness::new().await;
+        harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25614"), &[]).await;
+        let bridge = &harness.state.bridge;
+        let repo = &harness.state.repo;
+
+        bridge.start(false).await.expect("bridge start");
+        repo.increment_stats(3, 2, 0, 0, 1).await.unwrap();
+        let start_time = repo.get_start_time().await.unwrap();
+
+        bridge.restart(true).await.expect("restart preserving stats");
+        let stats = repo.get_stats().await.unwrap();
+        assert_eq!((stats.mqtt_received, stats.mqtt_sent, stats.error_count), (3, 2, 1));
+        assert_eq!(repo.get_start_time().await.unwrap(), start_time);
+
+        bridge.restart(false).await.expect("restart resetting stats");
+        let stats = repo.get_stats().await.unwrap();
+        assert_eq!((stats.mqtt_received, stats.mqtt_sent, stats.error_count), (0, 0, 0));
+
+        bridge.stop().await.unwrap();
+    }
+
+    /// Endpoint status follows the peer of a connecting socket, and the
+    /// overall ZMQ status is connected once any endpoint is
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_endpoint_connection_status() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25619"]).await;
+        let bridge = &harness.state.bridge;
+
+        let zmq_status = || async move {
+            let endpoints = bridge.endpoint_connections();
+            let endpoint = endpoints.iter().find(|c| c.endpoint_type == EndpointType::Zmq).cloned();
+            (endpoint.map(|c| (c.endpoint_id, c.status)), bridge.get_status().await.zmq_status)
+        };
+
+        bridge.start(false).await.expect("bridge start");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            zmq_status().await,
+            (Some((ingress.id.unwrap(), ConnectionStatus::Connecting)), ConnectionStatus::Connecting)
+        );
+        assert_eq!(bridge.get_status().await.mqtt_status, ConnectionStatus::Disconnected);
+
+        // The SUB retries every reconnect_interval_ms until a publisher binds
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25619").unwrap();
+        let mut status = zmq_status().await;
+        for _ in 0..50 {
+            if status.1 == ConnectionStatus::Connected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            status = zmq_status().await;
+        }
+        assert_eq!(status, (Some((ingress.id.unwrap(), ConnectionStatus::Connected)), ConnectionStatus::Connected));
+
+        bridge.stop().await.unwrap();
+        assert!(bridge.endpoint_connections().is_empty());
+        assert_eq!(bridge.get_status().await.zmq_status, ConnectionStatus::Disconnected);
+    }
+
+    /// Changing an endpoint while the bridge runs restarts only that
+    /// endpoint's thread, with the new settings
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_endpoint_change_applies_without_restart() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25620"]).await;
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25621"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+        let bridge = &harness.state.bridge;
+        bridge.start(false).await.expect("bridge start");
+
+        // Point the subscriber at another publisher
+        let mut req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!(ingress)).unwrap();
+        req.connect_endpoints = vec!["tcp://127.0.0.1:25622".to_string()];
+        harness.state.repo.update_zmq_config(ingress.id.unwrap(), &req).await.unwrap();
+        bridge.reload_endpoints().await.expect("reload endpoints");
+        assert!(bridge.config_diff().await.unwrap().in_sync);
+        assert_eq!(bridge.worker_threads().len(), 2);
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25622").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25621").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send("in/sensor 7", 0).unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 7"[..]));
+    }
+
+    /// A connect-role PUB attaches to a subscriber that owns the bind
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_connect_role_pub() {
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25615"]).await;
+        let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+            "name": "egress",
+            "enabled": true,
+            "socket_type": "pub",
+            "bind_endpoint": null,
+            "connect_endpoints": ["tcp://127.0.0.1:25616"],
+            "high_water_mark": 1000,
+            "reconnect_interval_ms": 100,
+            "role": "connect",
+        }))
+        .unwrap();
+        let egress = harness.state.repo.add_zmq_config(&req).await.unwrap();
+        assert_eq!(egress.role, ZmqRole::Connect);
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        // Both test sockets bind; the bridge connects to each
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25615").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.bind("tcp://127.0.0.1:25616").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let mut received = None;
+        for _ in 0..50 {
+            producer.send("in/sensor 7", 0).unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 7"[..]));
+    }
+
+    /// A SUB endpoint picks up again after its publisher restarts on the same address
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_sub_rejoins_restarted_pub() {
+        let harness = Harness::new().await;
+        let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+            "name": "ingress",
+            "enabled": true,
+            "socket_type": "sub",
+            "bind_endpoint": null,
+            "connect_endpoints": ["tcp://127.0.0.1:25617"],
+            "high_water_mark": 1000,
+            "reconnect_interval_ms": 100,
+            "resubscribe_on_reconnect": true,
+            "rejoin_warmup_ms": 500,
+        }))
+        .unwrap();
+        let ingress = harness.state.repo.add_zmq_config(&req).await.unwrap();
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25618"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        let context = zmq::Context::new();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25618").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(200).unwrap();
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        // Send until one message makes it through the bridge
+        let relay = |producer: &zmq::Socket| {
+            for _ in 0..50 {
+                producer.send("in/sensor 7", 0).unwrap();
+                if let Ok(data) = consumer.recv_bytes(0) {
+                    return Some(data);
+                }
+            }
+            None
+        };
+
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25617").unwrap();
+        assert_eq!(relay(&producer).as_deref(), Some(&b"out/data 7"[..]));
+
+        // Restart the publisher on the same address
+        producer.set_linger(0).unwrap();
+        drop(producer);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25617").unwrap();
+        let received = relay(&producer);
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 7"[..]));
+    }
+
+    /// A broker-side disconnect must not leave a clean-session bridge deaf:
+    /// the bridge is kicked by a client reusing its id and has to resubscribe.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_resubscribe_after_reconnect() {
+        use paho_mqtt::{AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
+
+        let test_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let bridge_client_id = format!("zeromqtt-test-resub-{}", test_id);
+        let source_topic = format!("zeromqtt/test/{}/in", test_id);
+
+        let harness = Harness::new().await;
+        let broker = harness.add_mqtt("broker", "broker.emqx.io", &bridge_client_id, true).await;
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25603"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "mqtt",
+                "source_endpoint_id": broker.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": source_topic,
+                "target_topic": "out/data",
+                "direction": "mqtt_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        // Taking over the client id makes the broker drop the bridge's session
+        let connect = |client_id: String| async move {
+            let client = AsyncClient::new(
+                CreateOptionsBuilder::new()
+                    .server_uri("tcp://broker.emqx.io:1883")
+                    .client_id(&client_id)
+                    .finalize(),
+            )
+            .unwrap();
+            client
+                .connect(ConnectOptionsBuilder::new().clean_session(true).finalize())
+                .await
+                .expect("connect to broker");
+            client
+        };
+        let intruder = connect(bridge_client_id.clone()).await;
+        intruder.disconnect(None).await.ok();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        let context = zmq::Context::new();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25603").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(500).unwrap();
+
+        let publisher = connect(format!("zeromqtt-test-resub-pub-{}", test_id)).await;
+        let mut received = None;
+        for _ in 0..20 {
+            publisher.publish(Message::new(&source_topic, "42", 1)).await.unwrap();
+            if let Ok(data) = consumer.recv_bytes(0) {
+                received = Some(data);
+                break;
+            }
+        }
+
+        publisher.disconnect(None).await.ok();
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(received.as_deref(), Some(&b"out/data 42"[..]));
+    }
+
+    /// A broker that never answers fails the connect within
+    /// `connect_timeout_secs` instead of hanging the caller.
+    #[tokio::test]
+    async fn test_mqtt_connect_times_out() {
+        use zeromqtt::mqtt::MqttClient;
+
+        // TEST-NET-1 is reserved for documentation and never routed
+        let config = MqttConfig {
+            broker_url: "192.0.2.1".to_string(),
+            client_id: "zeromqtt-test-timeout".to_string(),
+            connect_timeout_secs: 1,
+            ..Default::default()
+        };
+        let (tx, _rx) = tokio::sync::mpsc::channel(10);
+        let client = MqttClient::new(config, tx).expect("Failed to create client");
+
+        let result = tokio::time::timeout(Duration::from_secs(10), client.connect())
+            .await
+            .expect("connect hung past its timeout");
+        assert!(result.is_err());
+    }
+
+    /// With a persistent session and a stable client id the broker queues
+    /// messages while the bridge is stopped and delivers them on restart.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_persistent_session_survives_restart() {
+        use paho_mqtt::{AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
+
+        let test_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let bridge_client_id = format!("zeromqtt-test-persist-{}", test_id);
+        let source_topic = format!("zeromqtt/test/{}/queued", test_id);
+
+        let harness = Harness::new().await;
+        let broker = harness.add_mqtt("broker", "broker.emqx.io", &bridge_client_id, false).await;
+        let egress = harness.add_zmq("egress", "pub", Some("tcp://127.0.0.1:25604"), &[]).await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "mqtt",
+                "source_endpoint_id": broker.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": source_topic,
+                "target_topic": "out/queued",
+                "direction": "mqtt_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+
+        let connect = |client_id: String, clean_session: bool| async move {
+            let client = AsyncClient::new(
+                CreateOptionsBuilder::new()
+                    .server_uri("tcp://broker.emqx.io:1883")
+                    .client_id(&client_id)
+                    .finalize(),
+            )
+            .unwrap();
+            client
+                .connect(ConnectOptionsBuilder::new().clean_session(clean_session).finalize())
+                .await
+                .expect("connect to broker");
+            client
+        };
+
+        // First run creates the session and its subscription
+        harness.state.bridge.start(false).await.expect("bridge start");
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        harness.state.bridge.stop().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // Published while the bridge is down
+        let publisher = connect(format!("zeromqtt-test-persist-pub-{}", test_id), true).await;
+        for i in 0..3 {
+            publisher
+                .publish(Message::new(&source_topic, format!("m{}", i), 1))
+                .await
+                .unwrap();
+        }
+        publisher.disconnect(None).await.ok();
+
+        let context = zmq::Context::new();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25604").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        consumer.set_rcvtimeo(10_000).unwrap();
+
+        harness.state.bridge.start(false).await.expect("bridge restart");
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            match consumer.recv_bytes(0) {
+                Ok(data) => received.push(String::from_utf8_lossy(&data).into_owned()),
+                Err(_) => break,
+            }
+        }
+        harness.state.bridge.stop().await.unwrap();
+
+        // Drop the broker-side session again
+        let cleanup = connect(bridge_client_id, true).await;
+        cleanup.disconnect(None).await.ok();
+
+        assert_eq!(received, vec!["out/queued m0", "out/queued m1", "out/queued m2"]);
+    }
+
+    /// On MQTT v5 a session opened with `clean_start=false` and an expiry is
+    /// still present when the bridge client reconnects.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_v5_session_expiry_resumes_session() {
+        use paho_mqtt::{AsyncClient, CreateOptionsBuilder, MQTT_VERSION_5};
+        use zeromqtt::mqtt::connect_options;
+
+        let test_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let mut config = MqttConfig {
+            broker_url: "broker.emqx.io".to_string(),
+            client_id: format!("zeromqtt-test-v5-session-{}", test_id),
+            clean_start: false,
+            session_expiry_secs: 300,
+            ..Default::default()
+        };
+        let client = AsyncClient::new(
+            CreateOptionsBuilder::new()
+                .server_uri("tcp://broker.emqx.io:1883")
+                .client_id(&config.client_id)
+                .mqtt_version(MQTT_VERSION_5)
+                .finalize(),
+        )
+        .unwrap();
+
+        let session_present = |rsp: paho_mqtt::ServerResponse| {
+            rsp.connect_response().map(|c| c.session_present).unwrap_or(false)
+        };
+
+        client.connect(connect_options(&config, true, None).unwrap()).await.expect("connect to broker");
+        client.disconnect(None).await.unwrap();
+        let rsp = client.connect(connect_options(&config, true, None).unwrap()).await.expect("reconnect to broker");
+        assert!(session_present(rsp));
+        client.disconnect(None).await.unwrap();
+
+        // Drop the broker-side session again
+        config.clean_start = true;
+        config.session_expiry_secs = 0;
+        let rsp = client.connect(connect_options(&config, true, None).unwrap()).await.expect("clean connect");
+        assert!(!session_present(rsp));
+        client.disconnect(None).await.ok();
+    }
+
+    /// Changing a broker's URL while the bridge runs reconnects it to the new broker
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_broker_url_change_reconnects() {
+        let harness = Harness::new().await;
+        // `.invalid` never resolves, so the first connect fails
+        let broker = harness.add_mqtt("broker", "broker.invalid", "zeromqtt-test-reload-{random}", true).await;
+        let bridge = &harness.state.bridge;
+        let broker_status = || {
+            bridge
+                .endpoint_connections()
+                .into_iter()
+                .find(|c| c.endpoint_type == EndpointType::Mqtt)
+                .map(|c| c.status)
+        };
+
+        bridge.start(false).await.expect("bridge start");
+        let mut status = broker_status();
+        for _ in 0..50 {
+            if status == Some(ConnectionStatus::Error) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            status = broker_status();
+        }
+        assert_eq!(status, Some(ConnectionStatus::Error));
+
+        let mut req: CreateMqttConfigRequest = serde_json::from_value(serde_json::json!(broker)).unwrap();
+        req.broker_url = "broker.emqx.io".to_string();
+        harness.state.repo.update_mqtt_config(broker.id.unwrap(), &req).await.unwrap();
+        bridge.reload_endpoints().await.expect("reload endpoints");
+
+        for _ in 0..100 {
+            status = broker_status();
+            if status == Some(ConnectionStatus::Connected) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        bridge.stop().await.unwrap();
+        assert_eq!(status, Some(ConnectionStatus::Connected));
+    }
+
+    /// A `retain` mapping publishes retained messages, so a subscriber that
+    /// joins after the publish still receives the last value.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn test_retained_publish_seen_by_late_subscriber() {
+        use paho_mqtt::{AsyncClient, ConnectOptionsBuilder, CreateOptionsBuilder, Message};
+
+        let test_id = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let target_topic = format!("zeromqtt/test/{}/retained", test_id);
+
+        let harness = Harness::new().await;
+        let ingress = harness.add_zmq("ingress", "sub", None, &["tcp://127.0.0.1:25613"]).await;
+        let broker = harness
+            .add_mqtt("broker", "broker.emqx.io", &format!("zeromqtt-test-retain-{}", test_id), true)
+            .await;
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "mqtt",
+                "target_endpoint_id": broker.id,
+                "source_topic": "in/#",
+                "target_topic": target_topic,
+                "direction": "zmq_to_mqtt",
+                "enabled": true,
+                "description": null,
+                "retain": true,
+            }))
+            .await;
+
+        harness.state.bridge.start(false).await.expect("bridge start");
+        tokio::time::sleep(Duration::from_secs(3)).await;
+
+        // Publish before anyone subscribes to the target topic
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25613").unwrap();
+        for _ in 0..10 {
+            producer.send("in/sensor 42", 0).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        harness.state.bridge.stop().await.unwrap();
+
+        let mut subscriber = AsyncClient::new(
+            CreateOptionsBuilder::new()
+                .server_uri("tcp://broker.emqx.io:1883")
+                .client_id(format!("zeromqtt-test-retain-sub-{}", test_id))
+                .finalize(),
+        )
+        .unwrap();
+        let stream = subscriber.get_stream(10);
+        subscriber
+            .connect(ConnectOptionsBuilder::new().clean_session(true).finalize())
+            .await
+            .expect("connect to broker");
+        subscriber.subscribe(&target_topic, 1).await.unwrap();
+
+        let received = tokio::time::timeout(Duration::from_secs(10), stream.recv())
+            .await
+            .ok()
+            .and_then(|msg| msg.ok().flatten());
+
+        // Clear the retained value so the public broker is left clean
+        let clear = Message::new_retained(&target_topic, Vec::new(), 1);
+        subscriber.publish(clear).await.ok();
+        subscriber.disconnect(None).await.ok();
+
+        let received = received.expect("retained message");
+        assert!(received.retained());
+        assert_eq!(received.payload(), b"42");
+    }
+
+    /// ZMQ-to-ZMQ route whose egress endpoint batches up to `batch_max_messages`
+    async fn batching_route(ingress_port: u16, egress_port: u16, batch_max_messages: u32) -> Harness {
+        let harness = Harness::new().await;
+        let ingress = harness
+            .add_zmq("ingress", "sub", None, &[&format!("tcp://127.0.0.1:{}", ingress_port)])
+            .await;
+        let req: CreateZmqConfigRequest = serde_json::from_value(serde_json::json!({
+            "name": "egress",
+            "enabled": true,
+            "socket_type": "pub",
+            "bind_endpoint": format!("tcp://127.0.0.1:{}", egress_port),
+            "connect_endpoints": [],
+            "high_water_mark": 100000,
+            "reconnect_interval_ms": 100,
+            "batch_max_messages": batch_max_messages,
+            "batch_max_delay_ms": 20,
+        }))
+        .unwrap();
+        let egress = harness.state.repo.add_zmq_config(&req).await.unwrap();
+        harness
+            .add_mapping(serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": ingress.id,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": egress.id,
+                "source_topic": "in/#",
+                "target_topic": "out/data",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            }))
+            .await;
+        harness
+    }
+
+    /// Publish until a message makes it through the bridge, so both slow-joiner
+    /// subscriptions are established
+    async fn await_route(producer: &zmq::Socket, consumer: &zmq::Socket) {
+        consumer.set_rcvtimeo(200).unwrap();
+        for _ in 0..50 {
+            producer.send("in/warmup 0", 0).unwrap();
+            if consumer.recv_bytes(0).is_ok() {
+                // Drain warmup messages still in flight
+                while consumer.recv_bytes(0).is_ok() {}
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("route never came up");
+    }
+
+    /// A batching endpoint still delivers every message on its own, in order
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_zmq_batching_delivers_each_message() {
+        let harness = batching_route(25623, 25624, 10).await;
+        harness.state.bridge.start(false).await.expect("bridge start");
+
+        let context = zmq::Context::new();
+        let producer = context.socket(zmq::PUB).unwrap();
+        producer.bind("tcp://127.0.0.1:25623").unwrap();
+        let consumer = context.socket(zmq::SUB).unwrap();
+        consumer.connect("tcp://127.0.0.1:25624").unwrap();
+        consumer.set_subscribe(b"").unwrap();
+        await_route(&producer, &consumer).await;
+
+        // Fewer than a batch, so they go out once the delay passes
+        for i in 1..=3 {
+            producer.send(format!("in/sensor {}", i).as_bytes(), 0).unwrap();
+        }
+        consumer.set_rcvtimeo(2000).unwrap();
+        let received: Vec<Vec<u8>> = (0..3).filter_map(|_| consumer.recv_bytes(0).ok()).collect();
+
+        harness.state.bridge.stop().await.unwrap();
+        assert_eq!(
+            received,
+            vec![b"out/data 1".to_vec(), b"out/data 2".to_vec(), b"out/data 3".to_vec()]
+        );
+    }
+
+    /// Messages per second through a ZMQ-to-ZMQ route with and without
+    /// batching on the egress endpoint. Prints both rates; run with
+    /// `cargo test --test integration_tests bench_zmq_batching -- --ignored --nocapture`
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore]
+    async fn bench_zmq_batching_throughput() {
+        const MESSAGES: usize = 20_000;
+
+        let mut rates = Vec::new();
+        for (batch_max_messages, ingress_port, egress_port) in [(0, 25625, 25626), (256, 25627, 25628)] {
+            let harness = batching_route(ingress_port, egress_port, batch_max_messages).await;
+            harness.state.bridge.start(false).await.expect("bridge start");
+
+            let context = zmq::Context::new();
+            let producer = context.socket(zmq::PUB).unwrap();
+            producer.set_sndhwm(0).unwrap();
+            producer.bind(&format!("tcp://127.0.0.1:{}", ingress_port)).unwrap();
+            let consumer = context.socket(zmq::SUB).unwrap();
+            consumer.set_rcvhwm(0).unwrap();
+            consumer.connect(&format!("tcp://127.0.0.1:{}", egress_port)).unwrap();
+            consumer.set_subscribe(b"").unwrap();
+            await_route(&producer, &consumer).await;
+
+            let start = std::time::Instant::now();
+            for i in 0..MESSAGES {
+                producer.send(format!("in/sensor {}", i).as_bytes(), 0).unwrap();
+            }
+            consumer.set_rcvtimeo(1000).unwrap();
+            let mut received = 0;
+            while received < MESSAGES && consumer.recv_bytes(0).is_ok() {
+                received += 1;
+            }
+            let elapsed = start.elapsed();
+            harness.state.bridge.stop().await.unwrap();
+
+            let rate = received as f64 / elapsed.as_secs_f64();
+            println!(
+                "batch_max_messages={}: {} of {} messages in {:?}, {:.0} msg/s",
+                batch_max_messages, received, MESSAGES, elapsed, rate
+            );
+            rates.push(rate);
+        }
+        println!("batching speedup: {:.2}x", rates[1] / rates[0]);
+    }
+}
+
+/// API routing as served by `main`, exercised in-process without a listener
+mod api_tests {
+    use axum::body::Body;
+    use axum::http::{header, Request, StatusCode};
+    use axum::Router;
+    use tower::ServiceExt;
+    use zeromqtt::api::api_routes;
+    use zeromqtt::bridge::BridgeCore;
+    use zeromqtt::config::AppConfig;
+    use zeromqtt::db::{init_memory_db, Repository};
+    use zeromqtt::state::AppState;
+
+    async fn app() -> Router {
+        app_with(AppConfig::default()).await
+    }
+
+    async fn app_with(config: AppConfig) -> Router {
+        let repo = Repository::new(init_memory_db().await.expect("in-memory database"));
+        let bridge = BridgeCore::new(repo.clone(), config.bridge.clone());
+        let state = AppState::new(config, repo, bridge);
+        api_routes()
+            .layer(axum::Extension(state.config.clone()))
+            .layer(axum::Extension(state.repo.clone()))
+            .with_state(state)
+    }
+
+    async fn status(app: &Router, method: &str, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn test_protected_routes_require_token() {
+        let app = app().await;
+        for (method, uri) in [
+            ("GET", "/config/mappings"),
+            ("GET", "/status"),
+            ("GET", "/users"),
+            ("POST", "/bridge/stop"),
+        ] {
+            assert_eq!(status(&app, method, uri, None).await, StatusCode::UNAUTHORIZED, "{} {}", method, uri);
+        }
+        assert_eq!(status(&app, "GET", "/config/mappings", Some("not-a-token")).await, StatusCode::UNAUTHORIZED);
+        // Public unless metrics.require_auth is set
+        assert_eq!(status(&app, "GET", "/metrics", None).await, StatusCode::OK);
+
+        let token = login(&app).await;
+        assert_eq!(status(&app, "GET", "/config/mappings", Some(&token)).await, StatusCode::OK);
+    }
+
+    async fn login(app: &Router) -> String {
+        login_as(app, "zeromqtt", "zeromqtt").await
+    }
+
+    async fn login_as(app: &Router, username: &str, password: &str) -> String {
+        let login = Request::builder()
+            .method("POST")
+            .uri("/auth/login")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::json!({"username": username, "password": password}).to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(login).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let login: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        login["token"].as_str().unwrap().to_string()
+    }
+
+    async fn import(app: &Router, token: &str, query: &str, document: &serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri(format!("/config/import?{}", query))
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(document.to_string()))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_import_atomic_and_best_effort() {
+        let app = app().await;
+        let token = login(&app).await;
+        let zmq = |id: u32, name: &str| {
+            serde_json::json!({
+                "id": id,
+                "name": name,
+                "enabled": true,
+                "socket_type": "pub",
+                "bind_endpoint": format!("tcp://*:{}", 5555 + id),
+                "connect_endpoints": [],
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 1000,
+            })
+        };
+        let mapping = |id: u32, target_id: u32| {
+            serde_json::json!({
+                "id": id,
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": 1,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": target_id,
+                "source_topic": "in/#",
+                "target_topic": "out",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            })
+        };
+        // Endpoint 3 repeats the name of endpoint 2, so mapping 2 loses its target
+        let document = serde_json::json!({
+            "zmq": [zmq(1, "a"), zmq(2, "dup"), zmq(3, "dup")],
+            "mappings": [mapping(1, 2), mapping(2, 3)],
+        });
+
+        let (status, _) = import(&app, &token, "mode=replace", &document).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let request = Request::get("/config/zmq")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let configs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(configs, serde_json::json!([]), "a failed atomic import writes nothing");
+
+        let (status, report) = import(&app, &token, "mode=replace&best_effort=true", &document).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((report["zmq"].as_u64(), report["mappings"].as_u64(), report["failed"].as_u64()), (Some(2), Some(1), Some(2)));
+        let failed: Vec<_> = report["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|r| r.get("error").is_some())
+            .map(|r| (r["entity"].as_str().unwrap().to_string(), r["id"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(failed, [("zmq".to_string(), 3), ("mapping".to_string(), 2)]);
+
+        // A rejected endpoint must not leave its mappings bound to the existing record with that id
+        let document = serde_json::json!({ "zmq": [zmq(2, "a")], "mappings": [mapping(7, 2)] });
+        let (status, report) = import(&app, &token, "mode=merge&best_effort=true", &document).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((report["zmq"].as_u64(), report["mappings"].as_u64(), report["failed"].as_u64()), (Some(0), Some(0), Some(2)));
+
+        // A merge never overwrites the record with an imported id
+        let document = serde_json::json!({ "zmq": [zmq(1, "renamed")] });
+        assert_eq!(import(&app, &token, "mode=merge", &document).await.0, StatusCode::BAD_REQUEST);
+    }
+
+    async fn post_json(app: &Router, token: &str, uri: &str, body: &serde_json::Value) -> StatusCode {
+        let request = Request::post(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    async fn get_json(app: &Router, token: &str, uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::get(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_viewers_cannot_read_secrets() {
+        let app = app().await;
+        let admin = login(&app).await;
+        let zmq = serde_json::json!({
+            "name": "signed",
+            "enabled": true,
+            "socket_type": "pub",
+            "bind_endpoint": "tcp://*:5560",
+            "connect_endpoints": [],
+            "high_water_mark": 1000,
+            "reconnect_interval_ms": 1000,
+            "hmac_key": "shared-secret",
+        });
+        assert_eq!(post_json(&app, &admin, "/config/zmq", &zmq).await, StatusCode::OK);
+        let user = serde_json::json!({"username": "watcher", "password": "watcher-pass", "role": "viewer"});
+        assert_eq!(post_json(&app, &admin, "/users", &user).await, StatusCode::OK);
+        let viewer = login_as(&app, "watcher", "watcher-pass").await;
+
+        let (_, configs) = get_json(&app, &viewer, "/config/zmq").await;
+        assert_eq!(configs[0]["hmac_key"], "***");
+        let (_, configs) = get_json(&app, &admin, "/config/zmq").await;
+        assert_eq!(configs[0]["hmac_key"], "shared-secret");
+        assert_eq!(get_json(&app, &viewer, "/config/export").await.0, StatusCode::FORBIDDEN);
+        assert_eq!(get_json(&app, &viewer, "/config/pending").await.0, StatusCode::FORBIDDEN);
+
+        // A viewer proves who they are before changing their password
+        let (_, users) = get_json(&app, &admin, "/users").await;
+        let id = users.as_array().unwrap().iter().find(|u| u["username"] == "watcher").unwrap()["id"].clone();
+        let uri = format!("/users/{}/password", id);
+        let change = serde_json::json!({"new_password": "stolen-token"});
+        assert_eq!(post_json(&app, &viewer, &uri, &change).await, StatusCode::BAD_REQUEST);
+        let change = serde_json::json!({"current_password": "watcher-pass", "new_password": "renewed-pass"});
+        assert_eq!(post_json(&app, &viewer, &uri, &change).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_mapping_respects_endpoint_roles() {
+        let app = app().await;
+        let token = login(&app).await;
+        let zmq = |id: u32, role: &str| {
+            serde_json::json!({
+                "id": id,
+                "name": role,
+                "enabled": true,
+                "socket_type": "pub",
+                "bind_endpoint": format!("tcp://*:{}", 5555 + id),
+                "connect_endpoints": [],
+                "high_water_mark": 1000,
+                "reconnect_interval_ms": 1000,
+                "endpoint_role": role,
+            })
+        };
+        let document = serde_json::json!({"zmq": [zmq(1, "source_only"), zmq(2, "sink_only")]});
+        assert_eq!(import(&app, &token, "mode=replace", &document).await.0, StatusCode::OK);
+
+        let mapping = |source: u32, target: u32| {
+            serde_json::json!({
+                "source_endpoint_type": "zmq",
+                "source_endpoint_id": source,
+                "target_endpoint_type": "zmq",
+                "target_endpoint_id": target,
+                "source_topic": "in/#",
+                "target_topic": "out",
+                "direction": "zmq_to_zmq",
+                "enabled": true,
+                "description": null,
+            })
+        };
+        assert_eq!(post_json(&app, &token, "/config/mappings", &mapping(2, 1)).await, StatusCode::BAD_REQUEST);
+        assert_eq!(post_json(&app, &token, "/config/mappings", &mapping(1, 2)).await, StatusCode::OK);
+
+        // Endpoint 1 is now a mapping source, so it cannot become sink_only
+        let mut update = zmq(1, "sink_only");
+        update["name"] = serde_json::json!("renamed");
+        let request = Request::put("/config/zmq/1")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(update.to_string()))
+            .unwrap();
+        assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn scrape(app: &Router, accept_encoding: Option<&str>) -> (Option<String>, Vec<u8>) {
+        let mut request = Request::builder().uri("/metrics");
+        if let Some(encoding) = accept_encoding {
+            request = request.header(header::ACCEPT_ENCODING, encoding);
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let encoding = response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (encoding, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_metrics_gzip_negotiation() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let app = app().await;
+        let (encoding, plain) = scrape(&app, None).await;
+        assert_eq!(encoding, None);
+        let plain = String::from_utf8(plain).unwrap();
+
+        let (encoding, body) = scrape(&app, Some("deflate, gzip;q=0.8")).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+        let mut text = String::new();
+        GzDecoder::new(body.as_slice()).read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().next(), plain.lines().next());
+
+        let (encoding, _) = scrape(&app, Some("gzip;q=0, identity")).await;
+        assert_eq!(encoding, None);
+
+        let mut config = AppConfig::default();
+        config.metrics.gzip = false;
+        let (encoding, _) = scrape(&app_with(config).await, Some("gzip")).await;
+        assert_eq!(encoding, None);
+    }
 }
@@ -97,13 +97,26 @@ struct MessageStats {
 // ============================================================================
 
 struct ApiClient {
+    /// Sends the access token of the default user, an admin, with every request
     client: Client,
     base_url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    token: String,
+}
+
 impl ApiClient {
-    fn new() -> Self {
-        Self { client: Client::new(), base_url: API_BASE.to_string() }
+    async fn login() -> Result<Self, reqwest::Error> {
+        let credentials = serde_json::json!({ "username": "zeromqtt", "password": "zeromqtt" });
+        let login: LoginResponse =
+            Client::new().post(format!("{}/auth/login", API_BASE)).json(&credentials).send().await?.json().await?;
+        let mut headers = reqwest::header::HeaderMap::new();
+        let bearer = format!("Bearer {}", login.token);
+        headers.insert(reqwest::header::AUTHORIZATION, bearer.parse().expect("token is a valid header value"));
+        let client = Client::builder().default_headers(headers).build()?;
+        Ok(Self { client, base_url: API_BASE.to_string() })
     }
 
     async fn get_status(&self) -> Result<BridgeStatus, reqwest::Error> {
@@ -276,7 +289,7 @@ fn test_zmq_to_mqtt_message(topic: &str, payload: &str, timeout_ms: u64) -> bool
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let api = ApiClient::new();
+    let api = ApiClient::login().await?;
     let results = Results::new();
     let test_id = chrono::Utc::now().timestamp_millis();
     